@@ -0,0 +1,234 @@
+// benches/hot_path.rs
+//! Per-request costs on the proxy's hot path: `cargo bench`. The
+//! `contended` cases split their iterations across one task per core on a
+//! multi-threaded runtime, to show what the shared locks cost under load.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use hyper::{Body, Request};
+use rust_load_balancer::circuit_breaker::CircuitBreaker;
+use rust_load_balancer::config::{BackendConfig, CircuitBreakerConfig, HeaderFilterConfig, LoadBalancerConfig};
+use rust_load_balancer::load_balancer::create_load_balancer;
+use rust_load_balancer::metrics::clock::CoarseClock;
+use rust_load_balancer::middleware::{Filter, FilterContext, HeaderFilter};
+use rust_load_balancer::proxy::{Backend, BackendPool};
+use rust_load_balancer::testing::TestHarness;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::runtime::Runtime;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap()
+}
+
+/// Run `op` `iters` times spread over one task per core, and time the lot.
+fn contended<F, Fut>(rt: &Runtime, iters: u64, op: F) -> Duration
+where
+    F: Fn() -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let tasks = std::thread::available_parallelism().map_or(4, |n| n.get()) as u64;
+    rt.block_on(async {
+        let start = Instant::now();
+        let handles: Vec<_> = (0..tasks)
+            .map(|i| {
+                let op = op.clone();
+                // The first tasks take the remainder
+                let share = iters / tasks + u64::from(i < iters % tasks);
+                tokio::spawn(async move {
+                    for _ in 0..share {
+                        op().await;
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        start.elapsed()
+    })
+}
+
+fn backends(n: usize) -> Arc<[Arc<Backend>]> {
+    (0..n)
+        .map(|i| {
+            let config: BackendConfig =
+                serde_yaml::from_str(&format!("url: 'http://10.0.0.{}:8080'", i + 1)).unwrap();
+            Arc::new(Backend::new(&config))
+        })
+        .collect()
+}
+
+fn balancer_selection(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("balancer_selection");
+    for n in [3, 100] {
+        let config: LoadBalancerConfig = serde_yaml::from_str("algorithm: round_robin").unwrap();
+        let balancer = create_load_balancer(&config);
+        let backends = backends(n);
+        group.bench_with_input(BenchmarkId::new("round_robin", n), &n, |b, _| {
+            b.to_async(&rt).iter(|| balancer.select_backend(&backends, None))
+        });
+        group.bench_with_input(BenchmarkId::new("round_robin_contended", n), &n, |b, _| {
+            b.iter_custom(|iters| {
+                let (balancer, backends) = (balancer.clone(), backends.clone());
+                contended(&rt, iters, move || {
+                    let (balancer, backends) = (balancer.clone(), backends.clone());
+                    async move {
+                        balancer.select_backend(&backends, None).await;
+                    }
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+fn healthy_backends(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("healthy_backends");
+    let configs = (0..20)
+        .map(|i| serde_yaml::from_str(&format!("url: 'http://10.0.0.{}:8080'", i + 1)).unwrap())
+        .collect();
+    let pool = BackendPool::new(configs);
+    group.bench_function("snapshot", |b| b.iter(|| pool.get_healthy_backends()));
+    group.bench_function("snapshot_contended", |b| {
+        b.iter_custom(|iters| {
+            let pool = pool.clone();
+            contended(&rt, iters, move || {
+                let pool = pool.clone();
+                async move {
+                    pool.get_healthy_backends();
+                }
+            })
+        })
+    });
+    group.finish();
+}
+
+fn circuit_breaker(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("circuit_breaker");
+    let config: CircuitBreakerConfig = serde_yaml::from_str("{}").unwrap();
+    let breaker = Arc::new(CircuitBreaker::new(config));
+    group.bench_function("permit_and_record", |b| {
+        b.to_async(&rt).iter(|| async {
+            if breaker.call_permitted().await {
+                breaker.record_success().await;
+            }
+        })
+    });
+    group.bench_function("permit_and_record_contended", |b| {
+        b.iter_custom(|iters| {
+            let breaker = breaker.clone();
+            contended(&rt, iters, move || {
+                let breaker = breaker.clone();
+                async move {
+                    if breaker.call_permitted().await {
+                        breaker.record_success().await;
+                    }
+                }
+            })
+        })
+    });
+    group.finish();
+}
+
+fn header_rewrite(c: &mut Criterion) {
+    let rt = runtime();
+    let config: HeaderFilterConfig = serde_yaml::from_str(
+        "{ request_set: { x-forwarded-proto: https, x-env: prod, x-team: edge }, \
+           request_remove: [cookie, x-debug] }",
+    )
+    .unwrap();
+    let filter = HeaderFilter::new(&config);
+    let request = || {
+        Request::get("/api/users?page=2")
+            .header("host", "example.com")
+            .header("user-agent", "bench/1.0")
+            .header("accept", "application/json")
+            .header("cookie", "session=abc123")
+            .header("x-debug", "1")
+            .body(Body::empty())
+            .unwrap()
+    };
+    c.bench_function("header_rewrite", |b| {
+        b.to_async(&rt).iter_batched(
+            request,
+            |mut req| {
+                let filter = &filter;
+                async move {
+                    let mut ctx = FilterContext::new(String::new(), &req, None);
+                    filter.on_request(&mut req, &mut ctx).await;
+                    req
+                }
+            },
+            criterion::BatchSize::SmallInput,
+        )
+    });
+}
+
+fn clock(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clock");
+    group.bench_function("instant_now", |b| b.iter(Instant::now));
+    let coarse = CoarseClock::start(Duration::from_millis(1));
+    group.bench_function("coarse_now", |b| b.iter(|| coarse.now()));
+    group.finish();
+}
+
+fn full_request(c: &mut Criterion) {
+    let rt = runtime();
+    let harness = rt.block_on(TestHarness::start(1)).unwrap();
+    c.bench_function("full_request_loopback", |b| {
+        b.to_async(&rt).iter(|| async {
+            let response = harness.get("/").await.unwrap();
+            assert!(response.status().is_success());
+        })
+    });
+    rt.block_on(harness.shutdown());
+}
+
+/// A request on a connection of its own, so each one costs an accept, by
+/// acceptor setup: `runtime.acceptors` and `runtime.reuse_port`.
+fn fresh_connections(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("fresh_connections");
+    for (acceptors, reuse_port) in [(1, false), (4, false), (4, true)] {
+        let harness = rt
+            .block_on(TestHarness::start_with(1, |config| {
+                config.runtime.acceptors = acceptors;
+                config.runtime.reuse_port = reuse_port;
+            }))
+            .unwrap();
+        let addr = harness.addr();
+        let id = BenchmarkId::new(format!("acceptors_{}_reuse_port_{}", acceptors, reuse_port), "contended");
+        group.bench_function(id, |b| {
+            b.iter_custom(|iters| {
+                contended(&rt, iters, move || async move {
+                    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+                    stream
+                        .write_all(b"GET / HTTP/1.1\r\nHost: lb\r\nConnection: close\r\n\r\n")
+                        .await
+                        .unwrap();
+                    let mut response = Vec::new();
+                    stream.read_to_end(&mut response).await.unwrap();
+                    assert!(response.starts_with(b"HTTP/1.1 200"));
+                })
+            })
+        });
+        rt.block_on(harness.shutdown());
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    balancer_selection,
+    healthy_backends,
+    circuit_breaker,
+    header_rewrite,
+    clock,
+    full_request,
+    fresh_connections
+);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+#!/usr/bin/env bash
+# benchmarks/acceptors.sh – connection-rate comparison across acceptor setups
+#
+# Starts the release LB once per runtime configuration and drives it with
+# non-keep-alive requests, so every request costs a fresh accept().
+# Requires: wrk, mock backends running (scripts/start_backends.sh).
+source "$(dirname "$0")/common.sh"
+
+BIN="${BIN:-target/release/rust-load-balancer}"
+BASE_CONFIG="${BASE_CONFIG:-config.yaml}"
+DUR="${DUR:-20s}"
+CONNS="${CONNS:-256}"
+URL="http://${LB_HOST}:${LB_PORT}/echo"
+# Each entry is "acceptors:reuse_port"
+SETUPS=(${SETUPS:-1:false 4:false 4:true 8:true})
+
+for setup in "${SETUPS[@]}"; do
+  [[ "$setup" =~ ^[1-9][0-9]*:(true|false)$ ]] || { echo "Bad setup '$setup', want acceptors:reuse_port"; exit 1; }
+done
+[[ -x "$BIN" ]] || { echo "Build first: cargo build --release"; exit 1; }
+
+tmp_config=$(mktemp --suffix=.yaml)
+trap 'rm -f "$tmp_config"; [[ -n "${LB_PID:-}" ]] && kill "$LB_PID" 2>/dev/null || true' EXIT
+
+printf "%-10s %-11s %12s %12s\n" ACCEPTORS REUSE_PORT REQ/SEC P99
+for setup in "${SETUPS[@]}"; do
+  acceptors=${setup%%:*}
+  reuse=${setup##*:}
+
+  # Drop any runtime section from the base config and append ours
+  awk '/^runtime:/{skip=1; next} /^[^ ]/{skip=0} !skip' "$BASE_CONFIG" > "$tmp_config"
+  printf "\nruntime:\n  acceptors: %s\n  reuse_port: %s\n" "$acceptors" "$reuse" >> "$tmp_config"
+
+  "$BIN" "$tmp_config" >/dev/null 2>&1 &
+  LB_PID=$!
+  sleep 2
+
+  out=$(wrk -t4 -c"$CONNS" -d"$DUR" --latency -H "Connection: close" "$URL")
+  rps=$(awk '/Requests\/sec/ {print $2}' <<<"$out")
+  p99=$(awk '$1 == "99%" {print $2}' <<<"$out")
+  printf "%-10s %-11s %12s %12s\n" "$acceptors" "$reuse" "$rps" "$p99"
+
+  kill "$LB_PID"; wait "$LB_PID" 2>/dev/null || true
+  unset LB_PID
+  sleep 1
+done
@@ -0,0 +1,181 @@
+// examples/bench.rs
+//! A tiny wrk: sends GETs at a fixed rate and prints the latency
+//! distribution.
+//!
+//! ```text
+//! cargo run --release --example bench -- [URL] [--rps N] [--duration SECS]
+//!     [--concurrency N] [--backends N] [--latency-ms N]
+//! ```
+//!
+//! Without a URL it benchmarks an in-process load balancer in front of
+//! `--backends` mock backends (`testing::TestHarness`), so nothing else has
+//! to be running. `--rps 0` sends as fast as `--concurrency` connections
+//! allow instead of at a fixed rate.
+use anyhow::{bail, Context, Result};
+use hdrhistogram::Histogram;
+use hyper::{Body, Client, Request, Uri};
+use rust_load_balancer::testing::TestHarness;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::time::MissedTickBehavior;
+
+struct Args {
+    url: Option<String>,
+    rps: u64,
+    duration: Duration,
+    concurrency: usize,
+    backends: usize,
+    latency: Duration,
+}
+
+impl Args {
+    fn parse() -> Result<Self> {
+        let mut args = Args {
+            url: None,
+            rps: 1000,
+            duration: Duration::from_secs(10),
+            concurrency: 64,
+            backends: 3,
+            latency: Duration::ZERO,
+        };
+        let mut argv = std::env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            let mut value = || argv.next().with_context(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--rps" => args.rps = value()?.parse()?,
+                "--duration" => args.duration = Duration::from_secs_f64(value()?.parse()?),
+                "--concurrency" => args.concurrency = value()?.parse()?,
+                "--backends" => args.backends = value()?.parse()?,
+                "--latency-ms" => args.latency = Duration::from_millis(value()?.parse()?),
+                flag if flag.starts_with("--") => bail!("Unknown option {}", flag),
+                _ => args.url = Some(arg),
+            }
+        }
+        if args.concurrency == 0 {
+            bail!("--concurrency must be at least 1");
+        }
+        Ok(args)
+    }
+}
+
+#[derive(Default)]
+struct Results {
+    statuses: BTreeMap<u16, u64>,
+    errors: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse()?;
+
+    let harness = match &args.url {
+        Some(_) => None,
+        None => {
+            let harness = TestHarness::start(args.backends).await?;
+            for backend in harness.backends() {
+                backend.set_latency(args.latency);
+            }
+            Some(harness)
+        }
+    };
+    let uri: Uri = match (&args.url, &harness) {
+        (Some(url), _) => url.parse()?,
+        (None, Some(harness)) => format!("http://{}/", harness.addr()).parse()?,
+        (None, None) => unreachable!(),
+    };
+
+    match args.rps {
+        0 => println!("Sending to {} for {:?} over {} connections", uri, args.duration, args.concurrency),
+        rps => println!("Sending {} req/s to {} for {:?}, at most {} at once", rps, uri, args.duration, args.concurrency),
+    }
+
+    let client = Client::builder().pool_max_idle_per_host(args.concurrency).build_http::<Body>();
+    // Microseconds, up to a minute
+    let histogram = Arc::new(Mutex::new(Histogram::<u64>::new_with_bounds(1, 60_000_000, 3)?));
+    let results = Arc::new(Mutex::new(Results::default()));
+    let in_flight = Arc::new(Semaphore::new(args.concurrency));
+
+    let send = {
+        let (client, histogram, results) = (client.clone(), histogram.clone(), results.clone());
+        move |uri: Uri, scheduled: Instant| {
+            let (client, histogram, results) = (client.clone(), histogram.clone(), results.clone());
+            async move {
+                let request = Request::get(uri).body(Body::empty()).unwrap();
+                let outcome = match client.request(request).await {
+                    Ok(response) => {
+                        let status = response.status().as_u16();
+                        hyper::body::to_bytes(response.into_body()).await.map(|_| status)
+                    }
+                    Err(e) => Err(e),
+                };
+                // From when it was due, so a stalled proxy can't hide the
+                // requests it held up
+                let elapsed = scheduled.elapsed().as_micros() as u64;
+                histogram.lock().unwrap().saturating_record(elapsed.max(1));
+                let mut results = results.lock().unwrap();
+                match outcome {
+                    Ok(status) => *results.statuses.entry(status).or_default() += 1,
+                    Err(_) => results.errors += 1,
+                }
+            }
+        }
+    };
+
+    let start = Instant::now();
+    let deadline = start + args.duration;
+    let mut tasks = tokio::task::JoinSet::new();
+    if args.rps == 0 {
+        for _ in 0..args.concurrency {
+            let (send, uri) = (send.clone(), uri.clone());
+            tasks.spawn(async move {
+                while Instant::now() < deadline {
+                    send(uri.clone(), Instant::now()).await;
+                }
+            });
+        }
+    } else {
+        let mut ticks = tokio::time::interval(Duration::from_secs_f64(1.0 / args.rps as f64));
+        // Requests that fall behind are sent late, not dropped
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Burst);
+        loop {
+            let scheduled = ticks.tick().await.into_std();
+            if scheduled >= deadline {
+                break;
+            }
+            let permit = in_flight.clone().acquire_owned().await?;
+            let request = send(uri.clone(), scheduled);
+            tasks.spawn(async move {
+                request.await;
+                drop(permit);
+            });
+        }
+    }
+    while tasks.join_next().await.is_some() {}
+    let elapsed = start.elapsed();
+
+    report(&histogram.lock().unwrap(), &results.lock().unwrap(), elapsed);
+
+    if let Some(harness) = harness {
+        harness.shutdown().await;
+    }
+    Ok(())
+}
+
+fn report(histogram: &Histogram<u64>, results: &Results, elapsed: Duration) {
+    let total = histogram.len();
+    println!();
+    println!("Requests:   {} in {:.2?} ({:.1} req/s)", total, elapsed, total as f64 / elapsed.as_secs_f64());
+    for (status, count) in &results.statuses {
+        println!("  {}:      {}", status, count);
+    }
+    if results.errors > 0 {
+        println!("  errors:   {}", results.errors);
+    }
+    println!("Latency:");
+    for (label, quantile) in [("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("p99.9", 0.999)] {
+        println!("  {:<8}  {:>10.3}ms", label, histogram.value_at_quantile(quantile) as f64 / 1000.0);
+    }
+    println!("  {:<8}  {:>10.3}ms", "max", histogram.max() as f64 / 1000.0);
+}
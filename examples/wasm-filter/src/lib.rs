@@ -0,0 +1,57 @@
+//! Sample wasm filter: rejects requests whose `x-api-key` header doesn't
+//! match the filter's `config`, and tags responses with `x-filtered-by`.
+//!
+//! Build with `cargo build --release --target wasm32-unknown-unknown` and
+//! point a route's `wasm.module` at
+//! `target/wasm32-unknown-unknown/release/api_key_filter.wasm`.
+
+#[link(wasm_import_module = "lb")]
+extern "C" {
+    fn get_config(ptr: *mut u8, cap: i32) -> i32;
+    fn get_header(name_ptr: *const u8, name_len: i32, ptr: *mut u8, cap: i32) -> i32;
+    fn set_header(name_ptr: *const u8, name_len: i32, value_ptr: *const u8, value_len: i32) -> i32;
+    fn send_response(status: i32, body_ptr: *const u8, body_len: i32) -> i32;
+    fn log(ptr: *const u8, len: i32);
+}
+
+/// Call a getter, growing the buffer until the value fits.
+fn read(get: impl Fn(*mut u8, i32) -> i32) -> Option<Vec<u8>> {
+    let mut buf = vec![0; 64];
+    loop {
+        let len = get(buf.as_mut_ptr(), buf.len() as i32);
+        if len < 0 {
+            return None;
+        }
+        if len as usize <= buf.len() {
+            buf.truncate(len as usize);
+            return Some(buf);
+        }
+        buf.resize(len as usize, 0);
+    }
+}
+
+fn header(name: &str) -> Option<Vec<u8>> {
+    read(|ptr, cap| unsafe { get_header(name.as_ptr(), name.len() as i32, ptr, cap) })
+}
+
+#[no_mangle]
+pub extern "C" fn on_request() {
+    let expected = read(|ptr, cap| unsafe { get_config(ptr, cap) }).unwrap_or_default();
+    if header("x-api-key").as_deref() == Some(expected.as_slice()) {
+        return;
+    }
+    let message = "rejected request without a valid API key";
+    let body = "Invalid API key";
+    unsafe {
+        log(message.as_ptr(), message.len() as i32);
+        send_response(401, body.as_ptr(), body.len() as i32);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn on_response(_status: i32) {
+    let (name, value) = ("x-filtered-by", "api-key-filter");
+    unsafe {
+        set_header(name.as_ptr(), name.len() as i32, value.as_ptr(), value.len() as i32);
+    }
+}
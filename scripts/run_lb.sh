@@ -113,7 +113,7 @@ if ! $NO_BUILD; then
 fi
 
 echo -e "${GREEN}▶ Running load balancer with config:${NC} $CONFIG_PATH"
-echo -e "${YELLOW}  (LB listens on 0.0.0.0:8080 per current src/main.rs)${NC}"
+echo -e "${YELLOW}  (LB listens on the 'listeners:' addresses in the config, default 0.0.0.0:8080)${NC}"
 echo -e "${GREEN}▶ Metrics:${NC} If enabled in config, check http://localhost:<metrics_port><metrics_path>"
 
 # Run in foreground so you see logs; cleanup trap handles backends on Ctrl-C.
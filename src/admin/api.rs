@@ -0,0 +1,257 @@
+// src/admin/api.rs
+use super::AuditLog;
+use crate::config::redacted;
+use crate::health::HealthCheckResult;
+use crate::logging::TapFilter;
+use crate::proxy::{read_body, Backend, HealthStatus, Proxy};
+use crate::server::PeerAddr;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default number of entries per dimension in top-talker listings.
+const DEFAULT_TOP: usize = 10;
+
+/// Largest request body the admin API reads.
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+/// How long `/admin/tap` streams by default, and at most.
+const DEFAULT_TAP_SECS: u64 = 30;
+const MAX_TAP_SECS: u64 = 300;
+
+const MAINTENANCE_ROUTES: &str = "/admin/maintenance/routes/";
+const BACKENDS: &str = "/admin/backends/";
+
+#[derive(Serialize)]
+struct MaintenanceStatus {
+    enabled: bool,
+    /// Routes in maintenance of their own.
+    routes: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct HealthSweep {
+    healthy: usize,
+    unhealthy: usize,
+    results: Vec<HealthCheckResult>,
+}
+
+#[derive(Serialize)]
+struct PoolBackend {
+    id: String,
+    url: String,
+    pool: String,
+    zone: Option<String>,
+    region: Option<String>,
+    weight: u32,
+    /// `weight` as `dynamic_weights` scaled it.
+    effective_weight: u32,
+    health: &'static str,
+    draining: bool,
+    active_connections: usize,
+    total_requests: u64,
+    failed_requests: u64,
+}
+
+impl PoolBackend {
+    fn new(backend: &Backend) -> Self {
+        let metrics = backend.get_metrics();
+        Self {
+            id: backend.id.clone(),
+            url: backend.url.to_string(),
+            pool: backend.pool.clone(),
+            zone: backend.zone.clone(),
+            region: backend.region.clone(),
+            weight: backend.weight(),
+            effective_weight: backend.effective_weight(),
+            health: match backend.health_status() {
+                HealthStatus::Healthy => "healthy",
+                HealthStatus::Unhealthy => "unhealthy",
+                HealthStatus::Unknown => "unknown",
+            },
+            draining: backend.is_draining(),
+            active_connections: metrics.active_connections,
+            total_requests: metrics.total_requests,
+            failed_requests: metrics.failed_requests,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SetMaintenance {
+    enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct SetWeight {
+    weight: u32,
+}
+
+/// Operational endpoints served on the admin port.
+#[derive(Clone)]
+pub struct AdminApi {
+    proxy: Arc<Proxy>,
+    audit: Arc<AuditLog>,
+}
+
+impl AdminApi {
+    pub fn new(proxy: Arc<Proxy>) -> Self {
+        Self { proxy, audit: Arc::new(AuditLog::default()) }
+    }
+
+    pub async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        let client = req.extensions().get::<PeerAddr>().map(|peer| peer.0.ip());
+        match (req.method(), req.uri().path()) {
+            (&Method::GET, "/admin/stats/traffic") => {
+                let top = query_param(&req, "top")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_TOP);
+                json_response(StatusCode::OK, &self.proxy.traffic_stats().snapshot(top))
+            }
+            (&Method::GET, "/admin/stats/latency") => {
+                json_response(StatusCode::OK, &self.proxy.latency_stats().snapshot())
+            }
+            (&Method::GET, "/admin/config") => {
+                json_response(StatusCode::OK, &redacted(&self.proxy.config()))
+            }
+            (&Method::GET, "/admin/config/diff") => match self.proxy.last_reload() {
+                Some(diff) => json_response(StatusCode::OK, &*diff),
+                None => text_response(StatusCode::NOT_FOUND, "Not reloaded since startup"),
+            },
+            (&Method::GET, "/admin/pool") => {
+                let backends: Vec<_> = self.proxy.pool().all_backends().iter().map(|b| PoolBackend::new(b)).collect();
+                json_response(StatusCode::OK, &backends)
+            }
+            (&Method::GET, "/admin/pool/stats") => json_response(StatusCode::OK, &self.proxy.pool().pool_stats()),
+            (&Method::GET, "/admin/maintenance") => {
+                json_response(StatusCode::OK, &self.maintenance_status())
+            }
+            (&Method::GET, "/admin/audit") => json_response(StatusCode::OK, &self.audit.entries()),
+            (&Method::GET, "/admin/tap") => self.tap(&req),
+            (&Method::POST, "/admin/healthcheck/run") => {
+                let results = self.proxy.health_checker().run_now().await;
+                let healthy = results.iter().filter(|result| result.healthy).count();
+                let sweep = HealthSweep { healthy, unhealthy: results.len() - healthy, results };
+                json_response(StatusCode::OK, &sweep)
+            }
+            (&Method::PUT, path) if path.starts_with(BACKENDS) && path.ends_with("/weight") => {
+                let id = path[BACKENDS.len()..path.len() - "/weight".len()].to_string();
+                self.set_weight(req, client, &id).await
+            }
+            (&Method::PUT, "/admin/maintenance") => match read_json::<SetMaintenance>(req).await {
+                Ok(update) => {
+                    let old = self.proxy.maintenance().is_enabled();
+                    self.proxy.maintenance().set_enabled(update.enabled);
+                    self.audit.record(client, "set_maintenance", "*", json!(old), json!(update.enabled));
+                    json_response(StatusCode::OK, &self.maintenance_status())
+                }
+                Err(response) => response,
+            },
+            (&Method::PUT, path) if path.starts_with(MAINTENANCE_ROUTES) => {
+                let route = path[MAINTENANCE_ROUTES.len()..].to_string();
+                let router = self.proxy.router();
+                let Some(matched) = router.routes().find(|r| r.name() == route) else {
+                    return text_response(StatusCode::NOT_FOUND, "Unknown route");
+                };
+                let old = self.proxy.maintenance().route_enabled(matched);
+                match read_json::<SetMaintenance>(req).await {
+                    Ok(update) => {
+                        self.proxy.maintenance().set_route_enabled(&route, update.enabled);
+                        self.audit.record(client, "set_maintenance", &route, json!(old), json!(update.enabled));
+                        json_response(StatusCode::OK, &self.maintenance_status())
+                    }
+                    Err(response) => response,
+                }
+            }
+            _ => text_response(StatusCode::NOT_FOUND, "Not Found"),
+        }
+    }
+}
+
+impl AdminApi {
+    async fn set_weight(&self, req: Request<Body>, client: Option<IpAddr>, id: &str) -> Response<Body> {
+        let Some(backend) = self.proxy.pool().get_backend(id) else {
+            return text_response(StatusCode::NOT_FOUND, "Unknown backend");
+        };
+        let update = match read_json::<SetWeight>(req).await {
+            Ok(update) => update,
+            Err(response) => return response,
+        };
+        if update.weight == 0 {
+            return text_response(StatusCode::BAD_REQUEST, "Weight must be greater than 0; drain the backend instead");
+        }
+        let old = backend.weight();
+        backend.set_weight(update.weight);
+        self.audit.record(client, "set_weight", id, json!(old), json!(update.weight));
+        json_response(StatusCode::OK, &PoolBackend::new(&backend))
+    }
+
+    /// Stream summaries of live requests as NDJSON for a while.
+    fn tap(&self, req: &Request<Body>) -> Response<Body> {
+        let sample = match query_param(req, "sample").map(|v| v.parse::<f64>()) {
+            None => 1.0,
+            Some(Ok(sample)) if sample > 0.0 && sample <= 1.0 => sample,
+            Some(_) => return text_response(StatusCode::BAD_REQUEST, "sample must be in (0, 1]"),
+        };
+        let secs = match query_param(req, "duration_secs").map(|v| v.parse::<u64>()) {
+            None => DEFAULT_TAP_SECS,
+            Some(Ok(secs)) if (1..=MAX_TAP_SECS).contains(&secs) => secs,
+            Some(_) => {
+                return text_response(StatusCode::BAD_REQUEST, &format!("duration_secs must be 1 to {}", MAX_TAP_SECS))
+            }
+        };
+        let filter = TapFilter { route: query_param(req, "route"), sample, duration: Duration::from_secs(secs) };
+        Response::builder()
+            .header("Content-Type", "application/x-ndjson")
+            .header("Cache-Control", "no-cache")
+            .body(self.proxy.tap().stream(filter))
+            .unwrap()
+    }
+
+    fn maintenance_status(&self) -> MaintenanceStatus {
+        let maintenance = self.proxy.maintenance();
+        let router = self.proxy.router();
+        MaintenanceStatus {
+            enabled: maintenance.is_enabled(),
+            routes: router
+                .routes()
+                .filter(|route| maintenance.route_enabled(route))
+                .map(|route| route.name().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// The request's JSON body, or the response explaining why it's unusable.
+async fn read_json<T: serde::de::DeserializeOwned>(req: Request<Body>) -> Result<T, Response<Body>> {
+    let body = read_body(req.into_body(), Some(MAX_BODY_BYTES)).await?;
+    serde_json::from_slice(&body).map_err(|e| text_response(StatusCode::BAD_REQUEST, &e.to_string()))
+}
+
+fn query_param(req: &Request<Body>, name: &str) -> Option<String> {
+    let query = req.uri().query()?;
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn json_response<T: Serialize>(status: StatusCode, value: &T) -> Response<Body> {
+    match serde_json::to_vec(value) {
+        Ok(body) => Response::builder()
+            .status(status)
+            .header("Content-Type", "application/json")
+            .body(Body::from(body))
+            .unwrap(),
+        Err(e) => text_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+fn text_response(status: StatusCode, message: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(message.to_string()))
+        .unwrap()
+}
@@ -0,0 +1,62 @@
+// src/admin/audit.rs
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use tracing::info;
+
+/// Entries kept for `GET /admin/audit`.
+const CAPACITY: usize = 1000;
+
+/// One change made through the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// RFC 3339.
+    pub at: String,
+    pub client: Option<IpAddr>,
+    pub action: String,
+    /// What was changed, e.g. a backend ID.
+    pub target: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// Changes made through the admin API, newest last. Each is also logged
+/// at info under the `audit` target, which outlives the process.
+#[derive(Default)]
+pub struct AuditLog {
+    entries: Mutex<VecDeque<AuditEntry>>,
+}
+
+impl AuditLog {
+    pub fn record(&self, client: Option<IpAddr>, action: &str, target: &str, old: Value, new: Value) {
+        info!(
+            target: "audit",
+            client = ?client,
+            action,
+            target_id = target,
+            old = %old,
+            new = %new,
+            "Admin change"
+        );
+        let entry = AuditEntry {
+            at: chrono::Utc::now().to_rfc3339(),
+            client,
+            action: action.to_string(),
+            target: target.to_string(),
+            old,
+            new,
+        };
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if entries.len() == CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.iter().cloned().collect()
+    }
+}
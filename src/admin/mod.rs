@@ -0,0 +1,6 @@
+// src/admin/mod.rs
+mod api;
+mod audit;
+
+pub use api::AdminApi;
+pub use audit::{AuditEntry, AuditLog};
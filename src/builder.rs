@@ -0,0 +1,321 @@
+// src/builder.rs
+use anyhow::{Context, Result};
+use futures::FutureExt;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::admin::AdminApi;
+use crate::cluster::ClusterNode;
+use crate::config::{Config, ListenerProtocol};
+use crate::discovery::DnsDiscovery;
+use crate::lifecycle::{Lifecycle, LoadBalancerHandle};
+use crate::load_balancer::DynamicWeights;
+use crate::metrics::{clock, MetricsRegistry, MetricsSink};
+use crate::middleware::Filter;
+use crate::proxy::{BackendPool, MaintenanceScheduler, Proxy};
+use crate::routing::RequestClassifier;
+use crate::server::{
+    tls, AccessControl, ConnectionGate, ConnectionLimits, Drain, MetricsServer, RequestHandler, ServerBuilder, SocketHandover,
+};
+use crate::tcp_proxy::TcpProxy;
+
+/// Assembles a load balancer from a `Config`, for `main.rs` or any binary
+/// embedding one.
+///
+/// ```no_run
+/// # async fn run(config: rust_load_balancer::config::Config) -> anyhow::Result<()> {
+/// let lb = rust_load_balancer::Builder::new(config).build().await?;
+/// lb.serve().await
+/// # }
+/// ```
+pub struct Builder {
+    config: Config,
+    handover: Option<Arc<SocketHandover>>,
+    classifiers: Vec<Arc<dyn RequestClassifier>>,
+    filters: Vec<Arc<dyn Filter>>,
+}
+
+impl Builder {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            handover: None,
+            classifiers: Vec::new(),
+            filters: Vec::new(),
+        }
+    }
+
+    /// Listening sockets to take over. By default they're inherited from
+    /// systemd or the previous process at `runtime.handover_socket`.
+    pub fn with_handover(mut self, handover: Arc<SocketHandover>) -> Self {
+        self.handover = Some(handover);
+        self
+    }
+
+    /// See `Proxy::with_classifier`.
+    pub fn with_classifier(mut self, classifier: Arc<dyn RequestClassifier>) -> Self {
+        self.classifiers.push(classifier);
+        self
+    }
+
+    /// See `Proxy::with_filter`.
+    pub fn with_filter(mut self, filter: Arc<dyn Filter>) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Validate the config, start health checks, discovery and the metrics,
+    /// admin and cluster servers. Listeners start with `serve`.
+    pub async fn build(self) -> Result<Instance> {
+        let config = self.config;
+        config.validate().context("Invalid configuration")?;
+        if let Some(ms) = config.runtime.coarse_clock_ms {
+            let running = clock::install(Duration::from_millis(ms));
+            if running != Duration::from_millis(ms) {
+                warn!("A coarse clock ticking every {:?} is already running; keeping it", running);
+            }
+        }
+
+        // Take over listening sockets from systemd or a previous process
+        let handover = match self.handover {
+            Some(handover) => handover,
+            None => Arc::new(SocketHandover::inherit(config.runtime.handover_socket.as_deref())?),
+        };
+        let drain = Drain::new();
+
+        let metrics_registry = Arc::new(MetricsRegistry::with_slo(config.metrics.slo.clone())?);
+        let pool = Arc::new(BackendPool::new(config.backends.clone()));
+
+        let mut proxy = Proxy::new(config.clone(), pool, metrics_registry.collector());
+        #[cfg(feature = "geoip")]
+        if let Some(geoip) = &config.geoip {
+            let classifier = crate::routing::GeoIpClassifier::open(&geoip.database)?;
+            info!("Loaded GeoIP database {}", geoip.database.display());
+            proxy = proxy.with_classifier(Arc::new(classifier));
+        }
+        for classifier in self.classifiers {
+            proxy = proxy.with_classifier(classifier);
+        }
+        for filter in self.filters {
+            proxy = proxy.with_filter(filter);
+        }
+        let proxy = Arc::new(proxy);
+
+        // Share backend failures with other instances
+        if let Some(cluster) = &config.cluster {
+            let listener = handover.bind_tcp("@cluster", cluster.listen).await?;
+            ClusterNode::new(cluster.clone(), proxy.clone())?.start(listener, drain.watcher())?;
+        }
+
+        proxy.start_health_checker();
+        proxy.start_admission_control();
+        start_discovery(&config, &proxy)?;
+        if !config.maintenance_windows.is_empty() {
+            MaintenanceScheduler::new(config.maintenance_windows.clone()).spawn(proxy.pool());
+        }
+        if let Some(dynamic_weights) = &config.load_balancer.dynamic_weights {
+            DynamicWeights::new(dynamic_weights).spawn(proxy.pool());
+        }
+
+        if config.metrics.enabled {
+            let metrics_addr = SocketAddr::new(config.metrics.bind, config.metrics.port);
+            let listener = handover.bind_tcp("@metrics", metrics_addr).await?;
+            let mut server = MetricsServer::new(
+                metrics_registry.clone(),
+                config.metrics.path.clone(),
+                AccessControl::new("metrics", &config.metrics.access),
+            )
+            .with_metrics(proxy.metrics());
+            if let Some(tls) = &config.metrics.tls {
+                server = server.with_tls(tls::acceptor(tls).context("Loading the metrics TLS certificate")?);
+            }
+            server.start(listener, drain.watcher())?;
+        }
+        if config.admin.enabled {
+            let admin = &config.admin;
+            if !admin.bind.is_loopback() && admin.access.allow.is_empty() && !admin.access.requires_credentials() {
+                warn!("Admin API listens on {} with neither an allowlist nor credentials", admin.bind);
+            }
+            let listener = handover.bind_tcp("@admin", SocketAddr::new(admin.bind, admin.port)).await?;
+            let mut server = MetricsServer::admin(AdminApi::new(proxy.clone()), AccessControl::new("admin", &admin.access))
+                .with_metrics(proxy.metrics());
+            if let Some(tls) = &admin.tls {
+                server = server.with_tls(tls::acceptor(tls).context("Loading the admin TLS certificate")?);
+            }
+            server.start(listener, drain.watcher())?;
+        }
+
+        Ok(Instance {
+            handle: LoadBalancerHandle::new(proxy.clone()),
+            proxy,
+            metrics_registry,
+            handover,
+            drain: Mutex::new(Some(drain)),
+        })
+    }
+}
+
+/// A built load balancer. `serve` runs its listeners until shutdown is
+/// asked for, the sockets are handed over to a new process, or a listener
+/// fails, then drains open connections.
+pub struct Instance {
+    proxy: Arc<Proxy>,
+    metrics_registry: Arc<MetricsRegistry>,
+    handover: Arc<SocketHandover>,
+    /// Taken by `serve`.
+    drain: Mutex<Option<Drain>>,
+    handle: LoadBalancerHandle,
+}
+
+impl Instance {
+    /// The configuration in effect, as of the last reload.
+    pub fn config(&self) -> Arc<Config> {
+        self.proxy.config()
+    }
+
+    /// For stopping, reloading and watching this instance from elsewhere.
+    pub fn handle(&self) -> LoadBalancerHandle {
+        self.handle.clone()
+    }
+
+    pub fn proxy(&self) -> Arc<Proxy> {
+        self.proxy.clone()
+    }
+
+    pub fn pool(&self) -> Arc<BackendPool> {
+        self.proxy.pool()
+    }
+
+    pub fn metrics(&self) -> Arc<dyn MetricsSink> {
+        self.proxy.metrics()
+    }
+
+    /// The Prometheus registry served on the metrics port.
+    pub fn metrics_registry(&self) -> Arc<MetricsRegistry> {
+        self.metrics_registry.clone()
+    }
+
+    /// Stop accepting and drain for up to `runtime.drain_timeout_secs`;
+    /// `serve` returns once it's done. See `LoadBalancerHandle::shutdown`
+    /// to wait for that or pick another deadline.
+    pub fn shutdown(&self) {
+        self.handle.request_shutdown(self.config().runtime.drain_timeout());
+    }
+
+    /// Run the configured listeners. Can only be called once.
+    pub async fn serve(&self) -> Result<()> {
+        let drain = self
+            .drain
+            .lock()
+            .unwrap()
+            .take()
+            .context("Instance::serve called twice")?;
+        let config = self.config();
+        let config = &*config;
+        let handler = RequestHandler::new(self.proxy.clone());
+
+        let shared_gate = ConnectionGate::new(config.runtime.max_connections);
+        // Start one server per configured listener
+        let servers = config.listeners.iter().map(|listener| {
+            match &listener.unix_socket {
+                Some(path) => info!("Starting listener '{}' on unix:{}", listener.name, path.display()),
+                None => info!("Starting listener '{}' on {}", listener.name, listener.address),
+            }
+            if listener.protocol == ListenerProtocol::Tcp {
+                return TcpProxy::new(listener, &self.proxy)
+                    .with_handover(self.handover.clone())
+                    .with_drain(drain.watcher())
+                    .serve()
+                    .boxed();
+            }
+            let http = ServerBuilder::new(listener.address)
+                .with_unix_socket(listener.unix_socket.clone())
+                .with_name(listener.name.as_str())
+                .with_handler(handler.clone())
+                .with_metrics(self.proxy.metrics())
+                .with_acceptors(
+                    config.runtime.acceptors,
+                    config.runtime.reuse_port,
+                    config.runtime.listen_backlog,
+                )
+                .with_limits(ConnectionLimits::from(listener))
+                .with_shared_gate(shared_gate.clone(), config.runtime.reject_when_full)
+                .with_http_options(listener.http.clone())
+                .with_handover(self.handover.clone())
+                .with_drain(drain.watcher())
+                .serve();
+            #[cfg(feature = "http3")]
+            if let Some(http3) = &listener.http.http3 {
+                use futures::TryFutureExt;
+                let http3 = crate::server::http3::Http3Server::new(
+                    listener.name.as_str(),
+                    http3.address(listener.address),
+                    http3.clone(),
+                    handler.clone(),
+                )
+                .with_metrics(self.proxy.metrics())
+                .with_drain(drain.watcher())
+                .serve();
+                return futures::future::try_join(http, http3).map_ok(drop).boxed();
+            }
+            http.boxed()
+        });
+        let server = futures::future::try_join_all(servers);
+
+        // A new process taking our sockets over means it's time to drain
+        let handed_over = async {
+            match &config.runtime.handover_socket {
+                Some(path) => self.handover.serve_handover(path).await,
+                None => std::future::pending().await,
+            }
+        };
+        let mut shutdown = self.handle.shutdown.subscribe();
+
+        self.handle.set_state(Lifecycle::Started);
+        let result = tokio::select! {
+            result = server => result.map(drop),
+            _ = shutdown.wait_for(Option::is_some) => Ok(()),
+            result = handed_over => result.map(|()| {
+                info!("Listening sockets handed over to new process");
+            }),
+        };
+
+        self.handle.set_state(Lifecycle::Draining);
+        let grace = (*shutdown.borrow()).unwrap_or(config.runtime.drain_timeout());
+        info!("Draining connections for up to {:?}", grace);
+        if !drain.drain(grace).await {
+            warn!("Drain timeout reached with connections still open");
+        }
+        self.handle.set_state(Lifecycle::Stopped);
+        result
+    }
+}
+
+/// Start watching the configured discovery sources.
+fn start_discovery(config: &Config, proxy: &Arc<Proxy>) -> Result<()> {
+    for dns in &config.discovery.dns {
+        info!("Discovering {} backends from DNS name {}", dns.pool, dns.name);
+        DnsDiscovery::new(dns.clone()).spawn(proxy.clone(), &config.discovery.reconcile);
+    }
+    #[cfg(feature = "consul")]
+    for consul in &config.discovery.consul {
+        info!("Discovering {} backends from Consul service {}", consul.pool, consul.service);
+        crate::discovery::ConsulDiscovery::new(consul.clone())?
+            .spawn(proxy.clone(), &config.discovery.reconcile);
+    }
+    #[cfg(feature = "etcd")]
+    if let Some(etcd) = &config.etcd {
+        info!("Loading backends and routes from etcd at {}", etcd.endpoint);
+        crate::discovery::EtcdStore::new(etcd.clone(), proxy.clone(), &config.discovery.reconcile)
+            .spawn();
+    }
+    #[cfg(feature = "xds")]
+    if let Some(xds) = &config.discovery.xds {
+        info!("Discovering clusters from xDS server {}", xds.server);
+        crate::discovery::XdsDiscovery::new(xds.clone(), proxy.clone(), config.discovery.reconcile.clone())
+            .spawn();
+    }
+    Ok(())
+}
@@ -0,0 +1,375 @@
+// src/cache/disk.rs
+use super::store::{Entry, LruStore};
+use crate::config::DiskCacheConfig;
+use crate::metrics::MetricsSink;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+const MAGIC: &[u8; 4] = b"LBC1";
+
+/// Entries waiting to be written; more are dropped rather than slowing
+/// requests down.
+const WRITE_QUEUE: usize = 64;
+
+/// Cached responses kept in files under `path`, so they survive restarts
+/// and can outgrow memory. Entries are written behind the memory tier by a
+/// background task, and checked against a CRC-32 when read back.
+pub(crate) struct DiskTier {
+    files: Arc<DiskFiles>,
+    writes: mpsc::Sender<(String, Entry)>,
+    max_entry_bytes: usize,
+}
+
+impl DiskTier {
+    /// Create `path` if needed and start the writer, which first indexes
+    /// the files a previous run left behind.
+    pub fn open(config: &DiskCacheConfig, metrics: Arc<dyn MetricsSink>) -> io::Result<Self> {
+        std::fs::create_dir_all(&config.path)?;
+        let files = Arc::new(DiskFiles::new(config.path.clone(), config.max_bytes as usize, metrics));
+        let (writes, queue) = mpsc::channel(WRITE_QUEUE);
+        tokio::spawn(files.clone().run(queue));
+        Ok(Self {
+            files,
+            writes,
+            max_entry_bytes: config.max_entry_bytes as usize,
+        })
+    }
+
+    pub fn max_entry_bytes(&self) -> usize {
+        self.max_entry_bytes
+    }
+
+    /// Queue `entry` to be written.
+    pub fn write_behind(&self, key: &str, entry: &Entry) {
+        if entry.body.len() > self.max_entry_bytes {
+            return;
+        }
+        if self.writes.try_send((key.to_string(), entry.clone())).is_err() {
+            self.files.metrics.record_cache_disk("write_dropped");
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Entry> {
+        self.files.read(key).await
+    }
+
+    pub fn remove(&self, key: &str) {
+        if let Some(path) = self.files.index.remove(key) {
+            self.files.update_size();
+            tokio::spawn(async move {
+                let _ = tokio::fs::remove_file(path).await;
+            });
+        }
+    }
+}
+
+struct DiskFiles {
+    dir: PathBuf,
+    /// Files by cache key, sized by their length.
+    index: LruStore<PathBuf>,
+    metrics: Arc<dyn MetricsSink>,
+}
+
+/// Everything stored about an entry besides its body.
+#[derive(Debug, Deserialize, Serialize)]
+struct Meta {
+    key: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    vary: Vec<(String, Option<String>)>,
+    /// Milliseconds since the Unix epoch.
+    stored_at_ms: u64,
+    initial_age_secs: u64,
+    lifetime_secs: u64,
+    stale_while_revalidate_secs: u64,
+    stale_if_error_secs: u64,
+}
+
+impl DiskFiles {
+    fn new(dir: PathBuf, max_bytes: usize, metrics: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            dir,
+            index: LruStore::new(max_bytes),
+            metrics,
+        }
+    }
+
+    async fn run(self: Arc<Self>, mut queue: mpsc::Receiver<(String, Entry)>) {
+        self.scan().await;
+        while let Some((key, entry)) = queue.recv().await {
+            if let Err(e) = self.write(&key, &entry).await {
+                warn!(key = %key, error = %e, "Failed to write cache entry to disk");
+                self.metrics.record_cache_disk("write_failed");
+            }
+        }
+    }
+
+    /// Index entry files from earlier runs, oldest first so they're evicted
+    /// first, and clean up interrupted writes.
+    async fn scan(&self) {
+        let mut found = Vec::new();
+        let Ok(mut dir) = tokio::fs::read_dir(&self.dir).await else {
+            return;
+        };
+        while let Ok(Some(file)) = dir.next_entry().await {
+            let path = file.path();
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("tmp") => {
+                    let _ = tokio::fs::remove_file(&path).await;
+                }
+                Some("entry") => {
+                    let Ok(metadata) = file.metadata().await else { continue };
+                    match read_key(&path).await {
+                        Ok(key) => found.push((metadata.modified().ok(), key, path, metadata.len())),
+                        Err(e) => {
+                            debug!(path = %path.display(), error = %e, "Removing unreadable cache file");
+                            let _ = tokio::fs::remove_file(&path).await;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        found.sort_by_key(|(modified, ..)| *modified);
+        let count = found.len();
+        for (_, key, path, len) in found {
+            self.index_file(&key, path, len as usize).await;
+        }
+        if count > 0 {
+            info!(dir = %self.dir.display(), entries = count, "Indexed cached responses on disk");
+        }
+        self.update_size();
+    }
+
+    async fn write(&self, key: &str, entry: &Entry) -> io::Result<()> {
+        let bytes = encode(key, entry);
+        let path = self.path_for(key);
+        // Readers only ever see complete files
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, &bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        self.index_file(key, path, bytes.len()).await;
+        self.metrics.record_cache_disk("write");
+        self.update_size();
+        Ok(())
+    }
+
+    /// Track `path` for `key`, deleting files evicted to make room.
+    async fn index_file(&self, key: &str, path: PathBuf, len: usize) {
+        let (stored, evicted) = self.index.insert(key.to_string(), path.clone(), len);
+        if !stored {
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+        for (_, old) in evicted.into_iter().filter(|(_, old)| *old != path) {
+            let _ = tokio::fs::remove_file(old).await;
+            self.metrics.record_cache_disk("evicted");
+        }
+    }
+
+    async fn read(&self, key: &str) -> Option<Entry> {
+        let path = self.index.get(key)?;
+        let decoded = tokio::fs::read(&path)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| decode(&bytes));
+        match decoded {
+            Ok((stored_key, entry)) if stored_key == key => {
+                self.metrics.record_cache_disk("hit");
+                Some(entry)
+            }
+            Ok(_) => {
+                // Another key with the same file name replaced it
+                self.index.remove(key);
+                None
+            }
+            Err(e) => {
+                warn!(key = %key, path = %path.display(), error = %e, "Dropping corrupt cache file");
+                self.metrics.record_cache_disk("corrupt");
+                self.index.remove(key);
+                let _ = tokio::fs::remove_file(&path).await;
+                self.update_size();
+                None
+            }
+        }
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        // Names only need to be stable within one run; the key is stored
+        // in the file and checked on every read
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.dir.join(format!("{:016x}.entry", hasher.finish()))
+    }
+
+    fn update_size(&self) {
+        let (entries, bytes) = self.index.usage();
+        self.metrics.update_cache_disk_size(entries, bytes);
+    }
+}
+
+/// `LBC1`, the metadata's length and JSON, the body, and a CRC-32 of all
+/// of that.
+fn encode(key: &str, entry: &Entry) -> Vec<u8> {
+    let stored_at = SystemTime::now() - entry.stored_at.elapsed();
+    let text = |value: &HeaderValue| value.to_str().ok().map(str::to_string);
+    let meta = Meta {
+        key: key.to_string(),
+        status: entry.status.as_u16(),
+        headers: entry
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), text(value)?)))
+            .collect(),
+        vary: entry
+            .vary
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.as_ref().and_then(text)))
+            .collect(),
+        stored_at_ms: stored_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+        initial_age_secs: entry.initial_age.as_secs(),
+        lifetime_secs: entry.lifetime.as_secs(),
+        stale_while_revalidate_secs: entry.stale_while_revalidate.as_secs(),
+        stale_if_error_secs: entry.stale_if_error.as_secs(),
+    };
+    let meta = serde_json::to_vec(&meta).expect("cache metadata serializes");
+
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 4 + meta.len() + entry.body.len() + 4);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&(meta.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&meta);
+    bytes.extend_from_slice(&entry.body);
+    let checksum = crc32fast::hash(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Result<(String, Entry), String> {
+    let (content, checksum) = bytes
+        .split_last_chunk::<4>()
+        .ok_or("file too short")?;
+    if crc32fast::hash(content) != u32::from_le_bytes(*checksum) {
+        return Err("checksum mismatch".to_string());
+    }
+    let (meta, body) = split_meta(content)?;
+    let meta: Meta = serde_json::from_slice(meta).map_err(|e| e.to_string())?;
+
+    let header_map = |pairs: &[(String, String)]| -> Result<HeaderMap, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                Ok((
+                    HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?,
+                    HeaderValue::from_str(value).map_err(|e| e.to_string())?,
+                ))
+            })
+            .collect()
+    };
+    let vary = meta
+        .vary
+        .iter()
+        .map(|(name, value)| {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?;
+            let value = value.as_deref().map(HeaderValue::from_str).transpose().map_err(|e| e.to_string())?;
+            Ok((name, value))
+        })
+        .collect::<Result<_, String>>()?;
+    let stored_at = UNIX_EPOCH + Duration::from_millis(meta.stored_at_ms);
+    let entry = Entry {
+        status: StatusCode::from_u16(meta.status).map_err(|e| e.to_string())?,
+        headers: header_map(&meta.headers)?,
+        body: body.to_vec().into(),
+        vary,
+        // Time spent on disk counts toward its age
+        stored_at: Instant::now(),
+        initial_age: Duration::from_secs(meta.initial_age_secs) + stored_at.elapsed().unwrap_or_default(),
+        lifetime: Duration::from_secs(meta.lifetime_secs),
+        stale_while_revalidate: Duration::from_secs(meta.stale_while_revalidate_secs),
+        stale_if_error: Duration::from_secs(meta.stale_if_error_secs),
+    };
+    Ok((meta.key, entry))
+}
+
+fn split_meta(content: &[u8]) -> Result<(&[u8], &[u8]), String> {
+    let rest = content.strip_prefix(MAGIC).ok_or("not a cache file")?;
+    let (len, rest) = rest.split_first_chunk::<4>().ok_or("file too short")?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if len > rest.len() {
+        return Err("truncated metadata".to_string());
+    }
+    Ok(rest.split_at(len))
+}
+
+/// The key stored in a file, without reading its body.
+async fn read_key(path: &Path) -> io::Result<String> {
+    use tokio::io::AsyncReadExt;
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut prefix = [0; 8];
+    file.read_exact(&mut prefix).await?;
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+    if &prefix[..4] != MAGIC {
+        return Err(invalid("not a cache file".to_string()));
+    }
+    let len = u32::from_le_bytes(prefix[4..].try_into().unwrap()) as usize;
+    let mut meta = vec![0; len];
+    file.read_exact(&mut meta).await?;
+    let meta: Meta = serde_json::from_slice(&meta).map_err(|e| invalid(e.to_string()))?;
+    Ok(meta.key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::NoopMetrics;
+
+    #[tokio::test]
+    async fn test_disk_entries_survive_restarts_and_corruption_is_caught() {
+        let dir = std::env::temp_dir().join(format!("lb-disk-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = DiskFiles::new(dir.clone(), 1024 * 1024, Arc::new(NoopMetrics));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("text/plain"));
+        let entry = Entry {
+            status: StatusCode::OK,
+            headers,
+            body: "hello".into(),
+            vary: vec![(HeaderName::from_static("accept-language"), None)],
+            stored_at: Instant::now(),
+            initial_age: Duration::from_secs(5),
+            lifetime: Duration::from_secs(60),
+            stale_while_revalidate: Duration::ZERO,
+            stale_if_error: Duration::from_secs(600),
+        };
+        files.write("example.com/a", &entry).await.unwrap();
+        files.write("example.com/b", &entry).await.unwrap();
+
+        // A fresh index finds both after a restart
+        let restarted = DiskFiles::new(dir.clone(), 1024 * 1024, Arc::new(NoopMetrics));
+        restarted.scan().await;
+        let read = restarted.read("example.com/a").await.unwrap();
+        assert_eq!(read.body, "hello");
+        assert_eq!(read.headers["content-type"], "text/plain");
+        assert_eq!(read.vary, entry.vary);
+        assert!(read.initial_age >= Duration::from_secs(5));
+        assert_eq!(read.stale_if_error, Duration::from_secs(600));
+
+        let path = restarted.path_for("example.com/b");
+        let mut bytes = std::fs::read(&path).unwrap();
+        let body_at = bytes.len() - 6;
+        bytes[body_at] ^= 0xff;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(restarted.read("example.com/b").await.is_none());
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+// src/cache/freshness.rs
+use chrono::{DateTime, FixedOffset};
+use hyper::header::{
+    HeaderMap, AGE, AUTHORIZATION, CACHE_CONTROL, DATE, ETAG, EXPIRES, IF_MODIFIED_SINCE,
+    IF_NONE_MATCH, LAST_MODIFIED, PRAGMA, SET_COOKIE, VARY,
+};
+use hyper::{Method, StatusCode};
+use std::time::Duration;
+
+/// The `Cache-Control` directives the cache acts on.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct CacheControl {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub private: bool,
+    pub max_age: Option<u64>,
+    pub s_maxage: Option<u64>,
+    pub must_revalidate: bool,
+    pub stale_while_revalidate: Option<u64>,
+    pub stale_if_error: Option<u64>,
+}
+
+impl CacheControl {
+    pub fn parse(headers: &HeaderMap) -> Self {
+        let mut directives = Self::default();
+        let values = headers.get_all(CACHE_CONTROL).iter().filter_map(|v| v.to_str().ok());
+        for directive in values.flat_map(|value| value.split(',')) {
+            let (name, arg) = match directive.split_once('=') {
+                Some((name, arg)) => (name.trim(), Some(arg.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let seconds = || arg.and_then(|arg| arg.parse().ok());
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => directives.no_store = true,
+                // `no-cache="field"` only restricts those fields; treat it
+                // like plain `no-cache`
+                "no-cache" => directives.no_cache = true,
+                "private" => directives.private = true,
+                "max-age" => directives.max_age = seconds(),
+                "s-maxage" => directives.s_maxage = seconds(),
+                "must-revalidate" | "proxy-revalidate" => directives.must_revalidate = true,
+                "stale-while-revalidate" => directives.stale_while_revalidate = seconds(),
+                "stale-if-error" => directives.stale_if_error = seconds(),
+                _ => {}
+            }
+        }
+        directives
+    }
+}
+
+/// How a request may use the cache.
+#[derive(Debug, PartialEq)]
+pub(crate) enum RequestMode {
+    /// Serve fresh entries, store misses.
+    Normal,
+    /// `no-cache` or `max-age=0`: always check with the backend first.
+    Revalidate,
+    /// Don't touch the cache at all.
+    Bypass,
+}
+
+pub(crate) fn request_mode(method: &Method, headers: &HeaderMap) -> RequestMode {
+    // A shared cache must not hand one user's authorized response to another
+    if !matches!(*method, Method::GET | Method::HEAD) || headers.contains_key(AUTHORIZATION) {
+        return RequestMode::Bypass;
+    }
+    let directives = CacheControl::parse(headers);
+    if directives.no_store {
+        return RequestMode::Bypass;
+    }
+    let pragma_no_cache = headers
+        .get(PRAGMA)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("no-cache"));
+    if directives.no_cache || directives.max_age == Some(0) || pragma_no_cache {
+        return RequestMode::Revalidate;
+    }
+    RequestMode::Normal
+}
+
+/// How long a response may be served without revalidating, or `None` if it
+/// mustn't be stored at all.
+pub(crate) fn storable_lifetime(
+    status: StatusCode,
+    headers: &HeaderMap,
+    default_ttl: Duration,
+) -> Option<Duration> {
+    // Cacheable by default (RFC 9110 section 15.1), minus partial content
+    let cacheable_status = matches!(status.as_u16(), 200 | 203 | 204 | 300 | 301 | 308 | 404 | 410);
+    let vary_all = headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.split(',').any(|name| name.trim() == "*"));
+    let directives = CacheControl::parse(headers);
+    if !cacheable_status
+        || vary_all
+        || directives.no_store
+        || directives.private
+        || headers.contains_key(SET_COOKIE)
+    {
+        return None;
+    }
+
+    let has_validator = headers.contains_key(ETAG) || headers.contains_key(LAST_MODIFIED);
+    let lifetime = if directives.no_cache {
+        Duration::ZERO
+    } else if let Some(seconds) = directives.s_maxage.or(directives.max_age) {
+        Duration::from_secs(seconds)
+    } else if let Some(expires) = http_date(headers, EXPIRES) {
+        let date = http_date(headers, DATE).unwrap_or_else(|| chrono::Utc::now().fixed_offset());
+        (expires - date).to_std().unwrap_or_default()
+    } else {
+        default_ttl
+    };
+    // Without a validator a response that's stale right away is useless
+    (!lifetime.is_zero() || has_validator).then_some(lifetime)
+}
+
+/// How long past its lifetime a response may still be served while it's
+/// revalidated in the background, and when the backend fails.
+pub(crate) fn stale_windows(headers: &HeaderMap) -> (Duration, Duration) {
+    let directives = CacheControl::parse(headers);
+    if directives.must_revalidate {
+        return (Duration::ZERO, Duration::ZERO);
+    }
+    let window = |seconds: Option<u64>| Duration::from_secs(seconds.unwrap_or_default());
+    (window(directives.stale_while_revalidate), window(directives.stale_if_error))
+}
+
+/// The backend's `Age` for a response it sent.
+pub(crate) fn age(headers: &HeaderMap) -> Duration {
+    headers
+        .get(AGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or_default()
+}
+
+/// Whether a client's conditional request is satisfied by a response with
+/// `headers`, so it can be answered with 304.
+pub(crate) fn not_modified(request: &HeaderMap, headers: &HeaderMap) -> bool {
+    if let Some(if_none_match) = request.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        let Some(etag) = headers.get(ETAG).and_then(|v| v.to_str().ok()) else {
+            return false;
+        };
+        // Weak comparison, as required for If-None-Match
+        let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        return if_none_match.trim() == "*"
+            || if_none_match.split(',').any(|tag| opaque(tag) == opaque(etag));
+    }
+    match (http_date(request, IF_MODIFIED_SINCE), http_date(headers, LAST_MODIFIED)) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+fn http_date(headers: &HeaderMap, name: hyper::header::HeaderName) -> Option<DateTime<FixedOffset>> {
+    let value = headers.get(name)?.to_str().ok()?;
+    DateTime::parse_from_rfc2822(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (hyper::header::HeaderName::from_static(name), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_storable_lifetime_follows_cache_headers() {
+        let ok = StatusCode::OK;
+        let default = Duration::from_secs(30);
+        let lifetime = |pairs| storable_lifetime(ok, &headers(pairs), default);
+
+        assert_eq!(lifetime(&[("cache-control", "public, max-age=60, s-maxage=120")]), Some(Duration::from_secs(120)));
+        assert_eq!(lifetime(&[("cache-control", "max-age=60")]), Some(Duration::from_secs(60)));
+        assert_eq!(
+            lifetime(&[("date", "Wed, 14 Oct 2026 10:00:00 GMT"), ("expires", "Wed, 14 Oct 2026 10:05:00 GMT")]),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(lifetime(&[]), Some(default));
+        assert_eq!(storable_lifetime(ok, &HeaderMap::new(), Duration::ZERO), None);
+        // no-cache is stored only when it can be revalidated
+        assert_eq!(lifetime(&[("cache-control", "no-cache")]), None);
+        assert_eq!(lifetime(&[("cache-control", "no-cache"), ("etag", "\"v1\"")]), Some(Duration::ZERO));
+
+        assert_eq!(lifetime(&[("cache-control", "private, max-age=60")]), None);
+        assert_eq!(lifetime(&[("cache-control", "no-store")]), None);
+        assert_eq!(lifetime(&[("set-cookie", "a=b")]), None);
+        assert_eq!(lifetime(&[("vary", "accept, *")]), None);
+        assert_eq!(storable_lifetime(StatusCode::PARTIAL_CONTENT, &HeaderMap::new(), default), None);
+
+        let cached = headers(&[("etag", "W/\"v1\""), ("last-modified", "Wed, 14 Oct 2026 10:00:00 GMT")]);
+        let stale = |pairs| stale_windows(&headers(pairs));
+        assert_eq!(
+            stale(&[("cache-control", "max-age=60, stale-while-revalidate=30, stale-if-error=600")]),
+            (Duration::from_secs(30), Duration::from_secs(600))
+        );
+        assert_eq!(
+            stale(&[("cache-control", "stale-while-revalidate=30, must-revalidate")]),
+            (Duration::ZERO, Duration::ZERO)
+        );
+
+        assert!(not_modified(&headers(&[("if-none-match", "\"v0\", \"v1\"")]), &cached));
+        assert!(!not_modified(&headers(&[("if-none-match", "\"v2\"")]), &cached));
+        assert!(not_modified(&headers(&[("if-modified-since", "Wed, 14 Oct 2026 11:00:00 GMT")]), &cached));
+        assert!(!not_modified(&headers(&[("if-modified-since", "Wed, 14 Oct 2026 09:00:00 GMT")]), &cached));
+    }
+}
@@ -0,0 +1,638 @@
+// src/cache/mod.rs
+mod disk;
+mod freshness;
+mod store;
+
+use crate::config::CacheConfig;
+use crate::metrics::MetricsSink;
+use dashmap::DashMap;
+use disk::DiskTier;
+use freshness::{RequestMode, age, not_modified, request_mode, stale_windows, storable_lifetime};
+use futures::StreamExt;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, AGE, CONNECTION, CONTENT_LENGTH, ETAG, HOST,
+    IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, TRANSFER_ENCODING, VARY,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use store::{Entry, LruStore};
+use tokio::sync::watch;
+use tracing::{debug, error};
+
+const X_CACHE: &str = "x-cache";
+
+/// Headers that describe one transfer rather than the stored response.
+const UNSTORED_HEADERS: &[&str] = &["x-backend-id", X_CACHE];
+
+/// How long a request waits for another one fetching the same key before
+/// going to the backend itself.
+const MAX_COALESCE_WAIT: Duration = Duration::from_secs(10);
+
+/// Where a response came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// Served from the cache without asking a backend.
+    Hit,
+    /// Served what a concurrent request for the same key just fetched.
+    Coalesced,
+    /// A stale entry the backend confirmed with 304 was served.
+    Revalidated,
+    /// A stale entry was served under `stale-while-revalidate` or
+    /// `stale-if-error`.
+    Stale,
+    Miss,
+    /// The request couldn't use the cache, e.g. a POST.
+    Bypass,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "hit",
+            CacheStatus::Coalesced => "coalesced",
+            CacheStatus::Revalidated => "revalidated",
+            CacheStatus::Stale => "stale",
+            CacheStatus::Miss => "miss",
+            CacheStatus::Bypass => "bypass",
+        }
+    }
+
+    /// Whether the response body came from the cache.
+    pub fn is_cached(&self) -> bool {
+        !matches!(self, CacheStatus::Miss | CacheStatus::Bypass)
+    }
+}
+
+/// In-memory HTTP cache for GET and HEAD responses, shared by every route
+/// with `policy.cache`.
+///
+/// Freshness follows `Cache-Control` (`s-maxage`, `max-age`, `no-cache`,
+/// `no-store`, `private`) and `Expires`, falling back to
+/// `default_ttl_secs`. Stale entries with an `ETag` or `Last-Modified` are
+/// revalidated with a conditional request, and clients' own conditional
+/// requests are answered with 304 from the cache. As a shared cache it
+/// skips requests with `Authorization` and responses setting cookies.
+///
+/// Concurrent misses for one key wait for the first to finish instead of
+/// all going to the backend. Within `stale-while-revalidate` a stale entry
+/// is served while it's refreshed in the background, and within
+/// `stale-if-error` it stands in for errors and 5xx responses.
+///
+/// With `cache.disk`, every stored entry is also written to disk in the
+/// background, and entries too large for memory are kept only there.
+pub struct ResponseCache {
+    store: LruStore<Entry>,
+    /// Holds what's in memory and larger entries, when configured.
+    disk: Option<DiskTier>,
+    /// Keys being fetched, and a receiver told when that's done.
+    in_flight: DashMap<String, watch::Receiver<()>>,
+    max_entry_bytes: usize,
+    default_ttl: Duration,
+    metrics: Arc<dyn MetricsSink>,
+}
+
+/// Held while fetching a key; requests for it wait until it's dropped.
+struct Flight<'a> {
+    cache: &'a ResponseCache,
+    key: String,
+    _done: watch::Sender<()>,
+}
+
+impl Drop for Flight<'_> {
+    fn drop(&mut self) {
+        // Waiters wake when `_done` drops, right after this
+        self.cache.in_flight.remove(&self.key);
+    }
+}
+
+impl ResponseCache {
+    pub fn new(config: &CacheConfig, metrics: Arc<dyn MetricsSink>) -> Self {
+        let disk = config.disk.as_ref().and_then(|disk| match DiskTier::open(disk, metrics.clone()) {
+            Ok(tier) => Some(tier),
+            Err(e) => {
+                error!(path = %disk.path.display(), error = %e, "Disk cache unavailable, caching in memory only");
+                None
+            }
+        });
+        Self {
+            store: LruStore::new(config.max_memory_bytes as usize),
+            disk,
+            in_flight: DashMap::new(),
+            max_entry_bytes: config.max_entry_bytes as usize,
+            default_ttl: Duration::from_secs(config.default_ttl_secs),
+            metrics,
+        }
+    }
+
+    /// Answer `req` from the cache, or with `fetch` and store the result.
+    /// Responses that used the cache get an `X-Cache` header of `HIT`,
+    /// `STALE` or `MISS`.
+    ///
+    /// A stale entry served under `stale-while-revalidate` is refreshed by
+    /// handing a conditional request to `revalidate`, which should run
+    /// [`ResponseCache::revalidate`] in the background.
+    pub async fn serve<F, Fut, E, R>(
+        &self,
+        req: Request<Body>,
+        fetch: F,
+        revalidate: R,
+    ) -> Result<(Response<Body>, CacheStatus), E>
+    where
+        F: FnOnce(Request<Body>) -> Fut,
+        Fut: Future<Output = Result<Response<Body>, E>>,
+        R: FnOnce(Request<Body>),
+    {
+        let result = self.lookup_or_fetch(req, fetch, revalidate).await;
+        if let Ok((_, status)) = &result {
+            self.metrics.record_cache_lookup(status.as_str());
+        }
+        result
+    }
+
+    async fn lookup_or_fetch<F, Fut, E, R>(
+        &self,
+        mut req: Request<Body>,
+        fetch: F,
+        revalidate: R,
+    ) -> Result<(Response<Body>, CacheStatus), E>
+    where
+        F: FnOnce(Request<Body>) -> Fut,
+        Fut: Future<Output = Result<Response<Body>, E>>,
+        R: FnOnce(Request<Body>),
+    {
+        let mode = request_mode(req.method(), req.headers());
+        if mode == RequestMode::Bypass {
+            return Ok((fetch(req).await?, CacheStatus::Bypass));
+        }
+
+        let key = cache_key(&req);
+        let head = req.method() == Method::HEAD;
+        let client_headers = req.headers().clone();
+        let mut cached = self.lookup(&key, &client_headers).await;
+        let mut _flight = None;
+        if mode == RequestMode::Normal {
+            if let Some(entry) = &cached {
+                if entry.is_fresh() {
+                    return Ok(self.respond(entry, &client_headers, head, CacheStatus::Hit));
+                }
+                if entry.usable_stale(entry.stale_while_revalidate) {
+                    revalidate(conditional(&req, entry));
+                    return Ok(self.respond(entry, &client_headers, head, CacheStatus::Stale));
+                }
+            }
+            match self.join(&key) {
+                Ok(flight) => _flight = Some(flight),
+                Err(mut done) => {
+                    let since = Instant::now();
+                    let _ = tokio::time::timeout(MAX_COALESCE_WAIT, done.changed()).await;
+                    cached = self.lookup(&key, &client_headers).await;
+                    // Even a response that's stale right away answers the
+                    // requests that were waiting for it
+                    let fetched = |entry: &&Entry| entry.is_fresh() || entry.stored_at >= since;
+                    if let Some(entry) = cached.as_ref().filter(fetched) {
+                        return Ok(self.respond(entry, &client_headers, head, CacheStatus::Coalesced));
+                    }
+                }
+            }
+        }
+
+        // Validate what we have; the client's own conditions are checked
+        // against the result
+        if let Some(entry) = &cached {
+            add_validators(req.headers_mut(), entry);
+        }
+        let result = fetch(req).await;
+        let failed = result.as_ref().map_or(true, |response| response.status().is_server_error());
+        if let Some(entry) = cached.as_ref().filter(|entry| failed && entry.usable_stale(entry.stale_if_error)) {
+            debug!(key = %key, "Serving stale response for failed backend request");
+            return Ok(self.respond(entry, &client_headers, head, CacheStatus::Stale));
+        }
+        Ok(self.update(key, cached, &client_headers, head, result?).await)
+    }
+
+    /// Refresh the entry for `req`, a request handed to the `revalidate`
+    /// callback of [`ResponseCache::serve`], unless that's already underway.
+    pub async fn revalidate<F, Fut, E>(&self, req: Request<Body>, fetch: F)
+    where
+        F: FnOnce(Request<Body>) -> Fut,
+        Fut: Future<Output = Result<Response<Body>, E>>,
+        E: std::fmt::Display,
+    {
+        let key = cache_key(&req);
+        let Ok(_flight) = self.join(&key) else {
+            return;
+        };
+        let client_headers = req.headers().clone();
+        let cached = self.lookup(&key, &client_headers).await;
+        match fetch(req).await {
+            Ok(response) if !response.status().is_server_error() => {
+                let (response, _) = self.update(key, cached, &client_headers, false, response).await;
+                // Drain whatever wasn't buffered so the connection is reused
+                let _ = hyper::body::to_bytes(response.into_body()).await;
+            }
+            Ok(response) => debug!(key = %key, status = %response.status(), "Background revalidation failed"),
+            Err(e) => debug!(key = %key, error = %e, "Background revalidation failed"),
+        }
+    }
+
+    async fn lookup(&self, key: &str, client_headers: &HeaderMap) -> Option<Entry> {
+        if let Some(entry) = self.store.get(key) {
+            return Some(entry).filter(|entry| entry.matches(client_headers));
+        }
+        let entry = self.disk.as_ref()?.get(key).await?;
+        if entry.body.len() <= self.max_entry_bytes {
+            self.insert_in_memory(key.to_string(), entry.clone());
+        }
+        Some(entry).filter(|entry| entry.matches(client_headers))
+    }
+
+    /// Become the request fetching `key`, or get told when that one's done.
+    fn join(&self, key: &str) -> Result<Flight<'_>, watch::Receiver<()>> {
+        match self.in_flight.entry(key.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(flight) => Err(flight.get().clone()),
+            dashmap::mapref::entry::Entry::Vacant(slot) => {
+                let (done, waiters) = watch::channel(());
+                slot.insert(waiters);
+                Ok(Flight {
+                    cache: self,
+                    key: key.to_string(),
+                    _done: done,
+                })
+            }
+        }
+    }
+
+    /// Apply the backend's answer to a cache miss or revalidation.
+    async fn update(
+        &self,
+        key: String,
+        cached: Option<Entry>,
+        client_headers: &HeaderMap,
+        head: bool,
+        response: Response<Body>,
+    ) -> (Response<Body>, CacheStatus) {
+        if let (Some(entry), StatusCode::NOT_MODIFIED) = (cached, response.status()) {
+            let entry = self.refresh(key, entry, response.headers());
+            return self.respond(&entry, client_headers, head, CacheStatus::Revalidated);
+        }
+        let response = if head { response } else { self.store_response(key, client_headers, response).await };
+        (with_x_cache(response, "MISS"), CacheStatus::Miss)
+    }
+
+    fn respond(
+        &self,
+        entry: &Entry,
+        client_headers: &HeaderMap,
+        head: bool,
+        status: CacheStatus,
+    ) -> (Response<Body>, CacheStatus) {
+        let mut response = if not_modified(client_headers, &entry.headers) {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            *response.headers_mut() = entry.headers.clone();
+            response.headers_mut().remove(CONTENT_LENGTH);
+            response
+        } else {
+            let body = if head { Body::empty() } else { Body::from(entry.body.clone()) };
+            let mut response = Response::new(body);
+            *response.status_mut() = entry.status;
+            *response.headers_mut() = entry.headers.clone();
+            response
+        };
+        response
+            .headers_mut()
+            .insert(AGE, HeaderValue::from(entry.age().as_secs()));
+        let x_cache = if status == CacheStatus::Stale { "STALE" } else { "HIT" };
+        (with_x_cache(response, x_cache), status)
+    }
+
+    /// Buffer and store `response` if it's cacheable and small enough,
+    /// handing it back either way.
+    async fn store_response(
+        &self,
+        key: String,
+        client_headers: &HeaderMap,
+        response: Response<Body>,
+    ) -> Response<Body> {
+        let Some(lifetime) = storable_lifetime(response.status(), response.headers(), self.default_ttl)
+        else {
+            return response;
+        };
+        let too_large = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok())
+            .is_some_and(|len| len > self.max_storable_bytes());
+        if too_large {
+            return response;
+        }
+
+        let (parts, body) = response.into_parts();
+        let body = match buffer(body, self.max_storable_bytes()).await {
+            Ok(body) => body,
+            Err(body) => return Response::from_parts(parts, body),
+        };
+        let vary = vary_names(&parts.headers)
+            .map(|name| {
+                let value = client_headers.get(&name).cloned();
+                (name, value)
+            })
+            .collect();
+        let (stale_while_revalidate, stale_if_error) = stale_windows(&parts.headers);
+        let entry = Entry {
+            status: parts.status,
+            headers: stored_headers(&parts.headers),
+            body: body.clone(),
+            vary,
+            stored_at: Instant::now(),
+            initial_age: age(&parts.headers),
+            lifetime,
+            stale_while_revalidate,
+            stale_if_error,
+        };
+        debug!(key = %key, lifetime_secs = lifetime.as_secs(), bytes = body.len(), "Caching response");
+        self.insert(key, entry);
+        Response::from_parts(parts, Body::from(body))
+    }
+
+    /// Update a stale entry with the headers of the backend's 304.
+    fn refresh(&self, key: String, mut entry: Entry, headers: &HeaderMap) -> Entry {
+        for (name, value) in stored_headers(headers).iter() {
+            if name != CONTENT_LENGTH {
+                entry.headers.insert(name, value.clone());
+            }
+        }
+        entry.stored_at = Instant::now();
+        entry.initial_age = age(headers);
+        match storable_lifetime(entry.status, &entry.headers, self.default_ttl) {
+            Some(lifetime) => {
+                entry.lifetime = lifetime;
+                (entry.stale_while_revalidate, entry.stale_if_error) = stale_windows(&entry.headers);
+                self.insert(key, entry.clone());
+            }
+            None => {
+                self.store.remove(&key);
+                if let Some(disk) = &self.disk {
+                    disk.remove(&key);
+                }
+                self.update_size();
+            }
+        }
+        entry
+    }
+
+    fn max_storable_bytes(&self) -> usize {
+        let disk = self.disk.as_ref().map_or(0, |disk| disk.max_entry_bytes());
+        self.max_entry_bytes.max(disk)
+    }
+
+    fn insert(&self, key: String, entry: Entry) {
+        if let Some(disk) = &self.disk {
+            disk.write_behind(&key, &entry);
+        }
+        if entry.body.len() <= self.max_entry_bytes {
+            self.insert_in_memory(key, entry);
+        } else {
+            // Would otherwise shadow the newer copy on disk
+            self.store.remove(&key);
+            self.update_size();
+        }
+    }
+
+    fn insert_in_memory(&self, key: String, entry: Entry) {
+        let size = entry.size(&key);
+        let (_, evicted) = self.store.insert(key.clone(), entry, size);
+        let evicted = evicted.iter().filter(|(evicted, _)| *evicted != key).count();
+        if evicted > 0 {
+            self.metrics.record_cache_evictions(evicted);
+        }
+        self.update_size();
+    }
+
+    fn update_size(&self) {
+        let (entries, bytes) = self.store.usage();
+        self.metrics.update_cache_size(entries, bytes);
+    }
+}
+
+/// GET and HEAD share entries; the request is already normalized.
+fn cache_key(req: &Request<Body>) -> String {
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| req.uri().authority().map(|a| a.as_str()))
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let path = req.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    format!("{}{}", host, path)
+}
+
+/// A GET for what `req` asks for, validating `entry`.
+fn conditional(req: &Request<Body>, entry: &Entry) -> Request<Body> {
+    let mut conditional = Request::get(req.uri().clone()).body(Body::empty()).unwrap();
+    *conditional.headers_mut() = req.headers().clone();
+    add_validators(conditional.headers_mut(), entry);
+    conditional
+}
+
+fn add_validators(headers: &mut HeaderMap, entry: &Entry) {
+    headers.remove(IF_NONE_MATCH);
+    headers.remove(IF_MODIFIED_SINCE);
+    if let Some(etag) = entry.headers.get(ETAG) {
+        headers.insert(IF_NONE_MATCH, etag.clone());
+    }
+    if let Some(modified) = entry.headers.get(LAST_MODIFIED) {
+        headers.insert(IF_MODIFIED_SINCE, modified.clone());
+    }
+}
+
+fn vary_names(headers: &HeaderMap) -> impl Iterator<Item = HeaderName> + '_ {
+    headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+}
+
+fn stored_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut headers = headers.clone();
+    for name in [AGE, CONNECTION, TRANSFER_ENCODING] {
+        headers.remove(name);
+    }
+    for name in UNSTORED_HEADERS {
+        headers.remove(*name);
+    }
+    headers
+}
+
+fn with_x_cache(mut response: Response<Body>, value: &'static str) -> Response<Body> {
+    response
+        .headers_mut()
+        .insert(X_CACHE, HeaderValue::from_static(value));
+    response
+}
+
+/// Read `body` if it's at most `limit` bytes. Otherwise hand back a body that
+/// replays what was read before streaming the rest.
+async fn buffer(mut body: Body, limit: usize) -> Result<Bytes, Body> {
+    let mut chunks = Vec::new();
+    let mut len = 0;
+    while let Some(chunk) = body.data().await {
+        let failed = chunk.is_err();
+        len += chunk.as_ref().map_or(0, |chunk| chunk.len());
+        chunks.push(chunk);
+        if failed || len > limit {
+            return Err(Body::wrap_stream(futures::stream::iter(chunks).chain(body)));
+        }
+    }
+    let mut bytes = Vec::with_capacity(len);
+    for chunk in chunks.into_iter().flatten() {
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn cache(max_memory_bytes: u64) -> ResponseCache {
+        let config = CacheConfig {
+            max_memory_bytes,
+            ..CacheConfig::default()
+        };
+        ResponseCache::new(&config, Arc::new(crate::metrics::NoopMetrics))
+    }
+
+    fn get(path: &str) -> Request<Body> {
+        Request::get(path).header("host", "example.com").body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cache_serves_hits_and_revalidates_stale_entries() {
+        let cache = cache(1024 * 1024);
+        let fetches = AtomicUsize::new(0);
+        let backend = |req: Request<Body>| {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            let revalidating = req.headers().get(IF_NONE_MATCH).is_some_and(|tag| tag == "\"v1\"");
+            let response = Response::builder()
+                .header("cache-control", if req.uri().path() == "/stale" { "max-age=0" } else { "max-age=60" })
+                .header("etag", "\"v1\"")
+                .header("x-backend-id", "b1");
+            let response = if revalidating {
+                response.status(StatusCode::NOT_MODIFIED).body(Body::empty())
+            } else {
+                response.body(Body::from("hello"))
+            };
+            async move { Ok::<_, Infallible>(response.unwrap()) }
+        };
+
+        let (response, status) = cache.serve(get("/fresh"), backend, |_| {}).await.unwrap();
+        assert_eq!(status, CacheStatus::Miss);
+        assert_eq!(response.headers()[X_CACHE], "MISS");
+        assert_eq!(hyper::body::to_bytes(response.into_body()).await.unwrap(), "hello");
+
+        let (response, status) = cache.serve(get("/fresh"), backend, |_| {}).await.unwrap();
+        assert_eq!(status, CacheStatus::Hit);
+        assert_eq!(response.headers()[X_CACHE], "HIT");
+        assert!(response.headers().get("x-backend-id").is_none());
+        assert_eq!(hyper::body::to_bytes(response.into_body()).await.unwrap(), "hello");
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        // The client's own validator is answered from the cache
+        let mut req = get("/fresh");
+        req.headers_mut().insert(IF_NONE_MATCH, HeaderValue::from_static("\"v1\""));
+        let (response, _) = cache.serve(req, backend, |_| {}).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        cache.serve(get("/stale"), backend, |_| {}).await.unwrap();
+        let (response, status) = cache.serve(get("/stale"), backend, |_| {}).await.unwrap();
+        assert_eq!(status, CacheStatus::Revalidated);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(hyper::body::to_bytes(response.into_body()).await.unwrap(), "hello");
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+
+        let post = Request::post("/fresh").body(Body::empty()).unwrap();
+        assert_eq!(cache.serve(post, backend, |_| {}).await.unwrap().1, CacheStatus::Bypass);
+    }
+
+    #[tokio::test]
+    async fn test_cache_evicts_least_recently_used() {
+        // Room for two of the three entries
+        let cache = cache(150);
+        let backend = |_req: Request<Body>| async {
+            Ok::<_, Infallible>(
+                Response::builder()
+                    .header("cache-control", "max-age=60")
+                    .body(Body::from("x".repeat(20)))
+                    .unwrap(),
+            )
+        };
+        for path in ["/a", "/b", "/a", "/c"] {
+            cache.serve(get(path), backend, |_| {}).await.unwrap();
+        }
+        assert_eq!(cache.serve(get("/a"), backend, |_| {}).await.unwrap().1, CacheStatus::Hit);
+        assert_eq!(cache.serve(get("/b"), backend, |_| {}).await.unwrap().1, CacheStatus::Miss);
+    }
+
+    #[tokio::test]
+    async fn test_cache_coalesces_misses_and_serves_stale() {
+        let cache = cache(1024 * 1024);
+        let fetches = AtomicUsize::new(0);
+        let failing = std::sync::atomic::AtomicBool::new(false);
+        let backend = |_req: Request<Body>| {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            let fail = failing.load(Ordering::SeqCst);
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                if fail {
+                    return Err("backend down");
+                }
+                let response = Response::builder()
+                    .header("cache-control", "max-age=0, stale-while-revalidate=60, stale-if-error=60")
+                    .header("etag", "\"v1\"")
+                    .body(Body::from("hello"))
+                    .unwrap();
+                Ok(response)
+            }
+        };
+
+        let (first, second) = tokio::join!(
+            cache.serve(get("/hot"), backend, |_| {}),
+            cache.serve(get("/hot"), backend, |_| {})
+        );
+        let mut statuses = [first.unwrap().1, second.unwrap().1];
+        statuses.sort_by_key(|status| status.as_str());
+        assert_eq!(statuses, [CacheStatus::Coalesced, CacheStatus::Miss]);
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        // Stale entries are served while they're refreshed elsewhere
+        let refresh = std::sync::Mutex::new(None);
+        let (response, status) = cache
+            .serve(get("/hot"), backend, |req| *refresh.lock().unwrap() = Some(req))
+            .await
+            .unwrap();
+        assert_eq!(status, CacheStatus::Stale);
+        assert_eq!(response.headers()[X_CACHE], "STALE");
+        let refresh = refresh.lock().unwrap().take().unwrap();
+        assert_eq!(refresh.headers()[IF_NONE_MATCH], "\"v1\"");
+        cache.revalidate(refresh, backend).await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+        // A no-cache request must reach the backend, which is down
+        failing.store(true, Ordering::SeqCst);
+        let mut req = get("/hot");
+        req.headers_mut().insert("cache-control", HeaderValue::from_static("no-cache"));
+        let (response, status) = cache.serve(req, backend, |_| {}).await.unwrap();
+        assert_eq!(status, CacheStatus::Stale);
+        assert_eq!(hyper::body::to_bytes(response.into_body()).await.unwrap(), "hello");
+    }
+}
@@ -0,0 +1,155 @@
+// src/cache/store.rs
+use hyper::body::Bytes;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::StatusCode;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A stored response.
+#[derive(Debug, Clone)]
+pub(crate) struct Entry {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    /// Request headers named by the response's `Vary`, as they were sent.
+    pub vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    pub stored_at: Instant,
+    /// The backend's `Age` when it was stored.
+    pub initial_age: Duration,
+    pub lifetime: Duration,
+    pub stale_while_revalidate: Duration,
+    pub stale_if_error: Duration,
+}
+
+impl Entry {
+    pub fn age(&self) -> Duration {
+        self.initial_age + self.stored_at.elapsed()
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        self.age() < self.lifetime
+    }
+
+    /// Whether it's stale but still within `window` past its lifetime.
+    pub fn usable_stale(&self, window: Duration) -> bool {
+        !window.is_zero() && self.age() < self.lifetime + window
+    }
+
+    /// Whether a request with `headers` may get this response.
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| headers.get(name) == value.as_ref())
+    }
+
+    /// Roughly what the entry costs in memory.
+    pub fn size(&self, key: &str) -> usize {
+        let headers: usize = self
+            .headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+        key.len() + headers + self.body.len()
+    }
+}
+
+/// Values by key, evicting the least recently used ones to stay within a
+/// size budget.
+pub(crate) struct LruStore<V> {
+    max_bytes: usize,
+    inner: Mutex<Inner<V>>,
+}
+
+struct Inner<V> {
+    entries: HashMap<String, Slot<V>>,
+    /// Keys by when they were last used, oldest first.
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+    bytes: usize,
+}
+
+struct Slot<V> {
+    value: V,
+    size: usize,
+    used: u64,
+}
+
+impl<V> Inner<V> {
+    fn touch(&mut self, key: &str) {
+        self.clock += 1;
+        let clock = self.clock;
+        if let Some(slot) = self.entries.get_mut(key) {
+            self.recency.remove(&slot.used);
+            slot.used = clock;
+            self.recency.insert(clock, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<V> {
+        let slot = self.entries.remove(key)?;
+        self.recency.remove(&slot.used);
+        self.bytes -= slot.size;
+        Some(slot.value)
+    }
+}
+
+impl<V: Clone> LruStore<V> {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                clock: 0,
+                bytes: 0,
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        let mut inner = self.inner.lock().unwrap();
+        let value = inner.entries.get(key)?.value.clone();
+        inner.touch(key);
+        Some(value)
+    }
+
+    /// Store `value` of `size` bytes, replacing any under `key`, as the most
+    /// recently used. Returns what was evicted to make room, and `false` if
+    /// it's too large to store at all.
+    pub fn insert(&self, key: String, value: V, size: usize) -> (bool, Vec<(String, V)>) {
+        if size > self.max_bytes {
+            return (false, Vec::new());
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let mut evicted = Vec::new();
+        if let Some(replaced) = inner.remove(&key) {
+            evicted.push((key.clone(), replaced));
+        }
+        while inner.bytes + size > self.max_bytes {
+            let Some((_, oldest)) = inner.recency.pop_first() else {
+                break;
+            };
+            if let Some(slot) = inner.entries.remove(&oldest) {
+                inner.bytes -= slot.size;
+                evicted.push((oldest, slot.value));
+            }
+        }
+        inner.clock += 1;
+        let used = inner.clock;
+        inner.recency.insert(used, key.clone());
+        inner.entries.insert(key, Slot { value, size, used });
+        inner.bytes += size;
+        (true, evicted)
+    }
+
+    pub fn remove(&self, key: &str) -> Option<V> {
+        self.inner.lock().unwrap().remove(key)
+    }
+
+    /// Entries held and the bytes they take.
+    pub fn usage(&self) -> (usize, usize) {
+        let inner = self.inner.lock().unwrap();
+        (inner.entries.len(), inner.bytes)
+    }
+}
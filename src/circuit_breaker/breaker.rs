@@ -83,7 +83,8 @@ impl CircuitBreaker {
         }
     }
     
-    pub async fn record_failure(&self) {
+    /// Returns `true` when this failure opened the circuit.
+    pub async fn record_failure(&self) -> bool {
         self.total_requests.fetch_add(1, Ordering::Relaxed);
         self.failed_requests.fetch_add(1, Ordering::Relaxed);
         
@@ -96,20 +97,34 @@ impl CircuitBreaker {
                 if failure_count >= self.config.failure_threshold {
                     drop(state);
                     self.transition_to_open().await;
+                    return true;
                 }
+                false
             }
             CircuitBreakerState::HalfOpen => {
                 drop(state);
                 self.transition_to_open().await;
+                true
             }
             CircuitBreakerState::Open => {
                 // Already open, update last failure time
                 let mut last_failure = self.last_failure_time.write().await;
                 *last_failure = Some(Instant::now());
+                false
             }
         }
     }
     
+    /// Open a closed circuit without waiting for failures of our own, e.g.
+    /// because a peer saw the backend fail. Returns whether it was closed.
+    pub async fn trip(&self) -> bool {
+        if *self.state.read().await != CircuitBreakerState::Closed {
+            return false;
+        }
+        self.transition_to_open().await;
+        true
+    }
+    
     async fn transition_to_open(&self) {
         let mut state = self.state.write().await;
         *state = CircuitBreakerState::Open;
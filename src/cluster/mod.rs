@@ -0,0 +1,4 @@
+// src/cluster/mod.rs
+mod peer;
+
+pub use peer::ClusterNode;
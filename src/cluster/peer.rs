@@ -0,0 +1,274 @@
+// src/cluster/peer.rs
+use crate::circuit_breaker::CircuitBreakerManager;
+use crate::config::ClusterConfig;
+use crate::proxy::{BackendEvent, BackendPool, Proxy};
+use crate::server::{access::constant_time_eq, DrainWatcher};
+use anyhow::{bail, Result};
+use hyper::body::HttpBody;
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, warn};
+
+const EVENTS_PATH: &str = "/cluster/events";
+
+/// How long a peer gets to accept an event.
+const PEER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Largest message accepted from a peer.
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Shares what this instance learns about backends with its peers and
+/// applies what they learn.
+///
+/// Events are pushed to every peer as they happen; there is no retry or
+/// catching up, so a peer that misses one finds out on its own as before.
+/// A backend a peer reported unhealthy comes back with our next passing
+/// health check, and a tripped circuit half-opens after its usual timeout.
+pub struct ClusterNode {
+    config: ClusterConfig,
+    instance_id: String,
+    client: reqwest::Client,
+    proxy: Arc<Proxy>,
+}
+
+/// What peers POST to each other.
+#[derive(Debug, Deserialize, Serialize)]
+struct PeerMessage {
+    from: String,
+    events: Vec<BackendEvent>,
+}
+
+impl ClusterNode {
+    pub fn new(config: ClusterConfig, proxy: Arc<Proxy>) -> Result<Self> {
+        let instance_id = config
+            .instance_id
+            .clone()
+            .or_else(|| std::env::var("HOSTNAME").ok())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let client = reqwest::Client::builder().timeout(PEER_TIMEOUT).build()?;
+        Ok(Self {
+            config,
+            instance_id,
+            client,
+            proxy,
+        })
+    }
+
+    /// Forward local events to peers and accept theirs on `listener` until
+    /// draining starts.
+    pub fn start(self, listener: TcpListener, mut drain: DrainWatcher) -> Result<()> {
+        let addr = listener.local_addr()?;
+        let node = Arc::new(self);
+        tokio::spawn(node.clone().forward(node.proxy.pool().subscribe()));
+
+        let service_node = node.clone();
+        let make_service = hyper::service::make_service_fn(move |_| {
+            let node = service_node.clone();
+            async move {
+                Ok::<_, Infallible>(hyper::service::service_fn(move |req: Request<Body>| {
+                    let node = node.clone();
+                    async move { Ok::<_, Infallible>(node.handle(req).await) }
+                }))
+            }
+        });
+        let server = Server::from_tcp(listener.into_std()?)?
+            .serve(make_service)
+            .with_graceful_shutdown(async move { drain.draining().await });
+
+        info!(
+            instance = %node.instance_id,
+            peers = node.config.peers.len(),
+            "Cluster listener on http://{}{}",
+            addr,
+            EVENTS_PATH
+        );
+        tokio::spawn(async move {
+            if let Err(e) = server.await {
+                error!("Cluster server error: {}", e);
+            }
+        });
+        Ok(())
+    }
+
+    async fn forward(self: Arc<Self>, mut events: broadcast::Receiver<BackendEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(event) => self.send(event).await,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!(missed, "Cluster fell behind, some backend events were not shared");
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
+
+    async fn send(&self, event: BackendEvent) {
+        let message = PeerMessage {
+            from: self.instance_id.clone(),
+            events: vec![event.clone()],
+        };
+        let metrics = self.proxy.metrics();
+        let (message, metrics, event) = (&message, &metrics, &event);
+        let sends = self.config.peers.iter().map(|peer| async move {
+            let result = self.post(peer, message).await;
+            match result {
+                Ok(()) => metrics.record_cluster_event(event.kind(), "sent"),
+                Err(e) => {
+                    metrics.record_cluster_event(event.kind(), "failed");
+                    warn!(peer = %peer, backend = %event.backend_id(), error = %e, "Failed to notify peer");
+                }
+            }
+        });
+        futures::future::join_all(sends).await;
+    }
+
+    async fn post(&self, peer: &url::Url, message: &PeerMessage) -> Result<()> {
+        let mut request = self.client.post(peer.join(EVENTS_PATH)?).json(message);
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            bail!("peer returned {}", response.status());
+        }
+        Ok(())
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        if (req.method(), req.uri().path()) != (&Method::POST, EVENTS_PATH) {
+            return text_response(StatusCode::NOT_FOUND, "Not Found");
+        }
+        if !authorized(&req, self.config.token.as_deref()) {
+            return text_response(StatusCode::UNAUTHORIZED, "Unauthorized");
+        }
+        let Some(body) = read_limited(req.into_body(), MAX_MESSAGE_BYTES).await else {
+            return text_response(StatusCode::PAYLOAD_TOO_LARGE, "Message too large");
+        };
+        let message: PeerMessage = match serde_json::from_slice(&body) {
+            Ok(message) => message,
+            Err(e) => return text_response(StatusCode::BAD_REQUEST, &e.to_string()),
+        };
+        if message.from == self.instance_id {
+            // Listed ourselves as a peer
+            return text_response(StatusCode::NO_CONTENT, "");
+        }
+
+        let pool = self.proxy.pool();
+        let breakers = self.proxy.circuit_breakers();
+        let metrics = self.proxy.metrics();
+        for event in &message.events {
+            if !apply(event, &pool, &breakers).await {
+                continue;
+            }
+            info!(peer = %message.from, backend = %event.backend_id(), kind = event.kind(), "Applied event from peer");
+            metrics.record_cluster_event(event.kind(), "received");
+            match event {
+                BackendEvent::CircuitOpened(id) => {
+                    let state = breakers.get_or_create(id).get_state().await;
+                    metrics.update_circuit_breaker_state(id, state);
+                }
+                BackendEvent::Unhealthy(id) => metrics.update_backend_health(id, false),
+            }
+        }
+        text_response(StatusCode::NO_CONTENT, "")
+    }
+}
+
+/// Act on a peer's event. Returns `false` when it changed nothing, e.g.
+/// for backends we don't have or already avoid.
+async fn apply(event: &BackendEvent, pool: &BackendPool, breakers: &CircuitBreakerManager) -> bool {
+    let Some(backend) = pool.get_backend(event.backend_id()) else {
+        debug!(backend = %event.backend_id(), "Ignoring peer event for unknown backend");
+        return false;
+    };
+    match event {
+        BackendEvent::CircuitOpened(_) => breakers.get_or_create(&backend.id).trip().await,
+        BackendEvent::Unhealthy(_) => pool.eject_backend(&backend.id).await,
+    }
+}
+
+fn authorized(req: &Request<Body>, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or("");
+    constant_time_eq(presented.as_bytes(), token.as_bytes())
+}
+
+async fn read_limited(mut body: Body, limit: usize) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk.ok()?);
+        if bytes.len() > limit {
+            return None;
+        }
+    }
+    Some(bytes)
+}
+
+fn text_response(status: StatusCode, message: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(message.to_string()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit_breaker::CircuitBreakerState;
+    use crate::config::BackendConfig;
+
+    #[tokio::test]
+    async fn test_peer_events_take_backends_out_of_rotation() {
+        let backends: Vec<BackendConfig> =
+            serde_yaml::from_str("[{ url: \"http://10.0.0.1\" }, { url: \"http://10.0.0.2\" }]").unwrap();
+        let pool = BackendPool::new(backends);
+        let breakers = CircuitBreakerManager::new(serde_yaml::from_str("{}").unwrap());
+
+        let opened = BackendEvent::CircuitOpened("10.0.0.1:80".to_string());
+        assert!(apply(&opened, &pool, &breakers).await);
+        assert!(!apply(&opened, &pool, &breakers).await);
+        assert_eq!(breakers.get_or_create("10.0.0.1:80").get_state().await, CircuitBreakerState::Open);
+
+        assert!(apply(&BackendEvent::Unhealthy("10.0.0.2:80".to_string()), &pool, &breakers).await);
+        let healthy: Vec<String> = pool.get_healthy_backends().iter().map(|b| b.id.clone()).collect();
+        assert_eq!(healthy, ["10.0.0.1:80"]);
+
+        assert!(!apply(&BackendEvent::Unhealthy("10.0.0.9:80".to_string()), &pool, &breakers).await);
+
+        let message: PeerMessage =
+            serde_json::from_str(r#"{"from": "lb-2", "events": [{"kind": "unhealthy", "backend": "10.0.0.2:80"}]}"#)
+                .unwrap();
+        assert_eq!(message.events, [BackendEvent::Unhealthy("10.0.0.2:80".to_string())]);
+    }
+
+    #[test]
+    fn test_cluster_listener_needs_a_token_off_loopback() {
+        let config = |cluster: &str| -> crate::config::Config {
+            serde_yaml::from_str(&format!(
+                "load_balancer: {{}}\nbackends: [{{ url: 'http://127.0.0.1:8001' }}]\nhealth_check: {{}}\n\
+                 circuit_breaker: {{}}\nretry: {{}}\nmetrics: {{}}\ncluster: {}",
+                cluster
+            ))
+            .unwrap()
+        };
+        assert!(config("{ peers: [http://lb-2:7946] }").validate().is_err());
+        assert!(config("{ peers: [http://lb-2:7946], token: secret }").validate().is_ok());
+        assert!(config("{ listen: 127.0.0.1:7946, peers: [http://127.0.0.1:7947] }").validate().is_ok());
+
+        let req = |auth: &str| Request::post("/cluster/events").header(header::AUTHORIZATION, auth).body(Body::empty()).unwrap();
+        assert!(authorized(&req("Bearer secret"), Some("secret")));
+        assert!(!authorized(&req("Bearer guess"), Some("secret")));
+    }
+}
@@ -0,0 +1,143 @@
+// src/config/cron.rs
+use chrono::{DateTime, Datelike, Duration, DurationRound, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A cron schedule in UTC: `minute hour day-of-month month day-of-week`,
+/// each field `*`, a number, a range `a-b`, a step `*/n` or `a-b/n`, or a
+/// comma-separated list of those; or one of `@hourly`, `@daily`, `@weekly`
+/// and `@monthly`. Sunday is 0 or 7. Like cron, a day matches if either
+/// day field does when both are restricted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day fields start with `*`.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn matches(&self, at: DateTime<Utc>) -> bool {
+        let has = |set: u64, value: u32| set & (1 << value) != 0;
+        let day = has(self.days, at.day());
+        let weekday = has(self.weekdays, at.weekday().num_days_from_sunday());
+        let day = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        day && has(self.minutes, at.minute()) && has(self.hours, at.hour()) && has(self.months, at.month())
+    }
+
+    /// The latest minute up to `now` the schedule fired, if it's less than
+    /// `within` ago.
+    pub fn last_fired(&self, now: DateTime<Utc>, within: std::time::Duration) -> Option<DateTime<Utc>> {
+        let now = now.duration_trunc(Duration::minutes(1)).ok()?;
+        let minutes = within.as_secs().div_ceil(60);
+        (0..minutes as i64)
+            .map(|ago| now - Duration::minutes(ago))
+            .find(|at| self.matches(*at))
+    }
+}
+
+impl TryFrom<String> for Cron {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, String> {
+        let expanded = match source.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("cron schedule needs five fields: {:?}", source));
+        };
+        let weekdays = field(weekday, 0, 7, &source)?;
+        Ok(Self {
+            minutes: field(minute, 0, 59, &source)?,
+            hours: field(hour, 0, 23, &source)?,
+            days: field(day, 1, 31, &source)?,
+            months: field(month, 1, 12, &source)?,
+            // Sunday is both 0 and 7
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+            source,
+        })
+    }
+}
+
+impl From<Cron> for String {
+    fn from(cron: Cron) -> Self {
+        cron.source
+    }
+}
+
+/// The values `spec` allows, as bits `min..=max`.
+fn field(spec: &str, min: u32, max: u32, source: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid cron field {:?} in {:?}", spec, source);
+    let number = |s: &str| s.parse::<u32>().ok().filter(|n| (min..=max).contains(n)).ok_or_else(invalid);
+    let mut bits = 0;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0).ok_or_else(invalid)?),
+            None => (part, 1),
+        };
+        let (from, to) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((from, to)) => (number(from)?, number(to)?),
+            // `a/n` runs from `a` to the end
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if from > to {
+            return Err(invalid());
+        }
+        for value in (from..=to).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parses_and_matches_schedules() {
+        let nightly = Cron::try_from("30 2 * * 1-5".to_string()).unwrap();
+        // 2026-10-14 is a Wednesday
+        assert!(nightly.matches(at("2026-10-14T02:30:00Z")));
+        assert!(!nightly.matches(at("2026-10-14T02:31:00Z")));
+        assert!(!nightly.matches(at("2026-10-18T02:30:00Z")));
+
+        let since = |now| nightly.last_fired(at(now), std::time::Duration::from_secs(3600));
+        assert_eq!(since("2026-10-14T03:29:59Z"), Some(at("2026-10-14T02:30:00Z")));
+        assert_eq!(since("2026-10-14T03:30:00Z"), None);
+
+        // Either day field matches when both are set, and 7 is Sunday
+        let days = Cron::try_from("*/15 0 1 * 7".to_string()).unwrap();
+        assert!(days.matches(at("2026-10-18T00:45:00Z")));
+        assert!(days.matches(at("2026-10-01T00:00:00Z")));
+        assert!(!days.matches(at("2026-10-02T00:00:00Z")));
+
+        let daily = Cron::try_from("@daily".to_string()).unwrap();
+        assert!(daily.matches(at("2026-10-14T00:00:00Z")) && !daily.matches(at("2026-10-14T01:00:00Z")));
+        assert_eq!(String::from(daily), "@daily");
+        for invalid in ["* * * *", "60 * * * *", "5-1 * * * *", "*/0 * * * *", "a * * * *"] {
+            assert!(Cron::try_from(invalid.to_string()).is_err(), "{}", invalid);
+        }
+    }
+}
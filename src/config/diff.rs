@@ -0,0 +1,187 @@
+// src/config/diff.rs
+use super::Config;
+use crate::proxy::Backend;
+use serde::Serialize;
+use serde_json::Value;
+
+const REDACTED: &str = "<redacted>";
+
+/// Fields holding secrets, wherever they appear.
+const SECRET_FIELDS: &[&str] = &["token", "bearer_token", "password", "secret"];
+
+/// `key` is a secret only in these sections; elsewhere (e.g. `fairness`)
+/// it names what to key on.
+const SECRET_KEY_SECTIONS: &[&str] = &["hmac_sign"];
+
+/// What a reload changed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigDiff {
+    /// RFC 3339.
+    pub reloaded_at: String,
+    /// Backend IDs, as in the pool.
+    pub backends_added: Vec<String>,
+    pub backends_removed: Vec<String>,
+    pub backends_changed: Vec<String>,
+    /// Every other setting that differs, redacted like `redacted`.
+    pub settings: Vec<SettingChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SettingChange {
+    /// e.g. `routes[1].pool` or `health_check.interval_secs`.
+    pub path: String,
+    /// `null` when the setting is new.
+    pub old: Value,
+    /// `null` when the setting is gone.
+    pub new: Value,
+}
+
+impl ConfigDiff {
+    pub fn between(current: &Config, new: &Config) -> Self {
+        let id = |backend: &super::BackendConfig| Backend::id_for(&backend.url);
+        let find = |config: &Config, wanted: &str| config.backends.iter().find(|b| id(b) == wanted).cloned();
+        let (mut added, mut changed) = (Vec::new(), Vec::new());
+        for backend in &new.backends {
+            match find(current, &id(backend)) {
+                None => added.push(id(backend)),
+                Some(old) if old != *backend => changed.push(id(backend)),
+                Some(_) => {}
+            }
+        }
+        let removed = current
+            .backends
+            .iter()
+            .filter(|old| find(new, &id(old)).is_none())
+            .map(id)
+            .collect();
+
+        let settings = |config: &Config| {
+            let mut value = redacted(config);
+            if let Value::Object(fields) = &mut value {
+                fields.remove("backends");
+            }
+            value
+        };
+        let mut changes = Vec::new();
+        diff_values(String::new(), &settings(current), &settings(new), &mut changes);
+
+        Self {
+            reloaded_at: chrono::Utc::now().to_rfc3339(),
+            backends_added: added,
+            backends_removed: removed,
+            backends_changed: changed,
+            settings: changes,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.backends_added.is_empty()
+            && self.backends_removed.is_empty()
+            && self.backends_changed.is_empty()
+            && self.settings.is_empty()
+    }
+}
+
+/// `config` as JSON with tokens, passwords, signing keys and the passwords
+/// in URLs replaced by `<redacted>`.
+pub fn redacted(config: &Config) -> Value {
+    let mut value = serde_json::to_value(config).unwrap_or(Value::Null);
+    redact(&mut value, None);
+    value
+}
+
+fn redact(value: &mut Value, section: Option<&str>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                let secret = SECRET_FIELDS.contains(&name.as_str())
+                    || (name == "key" && section.is_some_and(|s| SECRET_KEY_SECTIONS.contains(&s)))
+                    || (name == "keys" && section == Some("api_key"));
+                if secret && !field.is_null() {
+                    *field = Value::from(REDACTED);
+                } else {
+                    redact(field, Some(name));
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact(item, section)),
+        Value::String(s) => {
+            if let Ok(mut url) = url::Url::parse(s) {
+                if url.password().is_some() && url.set_password(Some(REDACTED)).is_ok() {
+                    *s = url.to_string();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Lists of the same length compare item by item; otherwise a list that
+/// changed is reported whole.
+fn diff_values(path: String, old: &Value, new: &Value, changes: &mut Vec<SettingChange>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let names = old.keys().chain(new.keys().filter(|name| !old.contains_key(*name)));
+            for name in names {
+                let path = match path.is_empty() {
+                    true => name.clone(),
+                    false => format!("{}.{}", path, name),
+                };
+                let (old, new) = (old.get(name), new.get(name));
+                diff_values(path, old.unwrap_or(&Value::Null), new.unwrap_or(&Value::Null), changes);
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) if old_items.len() == new_items.len() => {
+            for (i, (old, new)) in old_items.iter().zip(new_items).enumerate() {
+                diff_values(format!("{}[{}]", path, i), old, new, changes);
+            }
+        }
+        _ if old != new => changes.push(SettingChange { path, old: old.clone(), new: new.clone() }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(extra: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            "load_balancer: {{}}\ncircuit_breaker: {{}}\nretry: {{}}\nmetrics: {{}}\n{}",
+            extra
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diff_lists_backends_and_redacted_settings() {
+        let current = config(
+            "backends: [{ url: 'http://127.0.0.1:8001' }, { url: 'http://127.0.0.1:8002' }]\n\
+             cluster: { peers: [], token: old-secret }\n\
+             health_check: {}",
+        );
+        let new = config(
+            "backends: [{ url: 'http://127.0.0.1:8002', weight: 5 }, { url: 'http://127.0.0.1:8003' }]\n\
+             cluster: { peers: [], token: new-secret }\n\
+             health_check: { interval_secs: 3 }",
+        );
+
+        let diff = ConfigDiff::between(&current, &new);
+        assert_eq!(diff.backends_added, ["127.0.0.1:8003"]);
+        assert_eq!(diff.backends_removed, ["127.0.0.1:8001"]);
+        assert_eq!(diff.backends_changed, ["127.0.0.1:8002"]);
+        // A changed secret doesn't show, since both sides are redacted
+        assert_eq!(
+            diff.settings,
+            [SettingChange {
+                path: "health_check.interval_secs".into(),
+                old: Value::from(10),
+                new: Value::from(3),
+            }]
+        );
+
+        let shown = redacted(&new).to_string();
+        assert!(!shown.contains("new-secret"));
+        assert!(ConfigDiff::between(&new, &new).is_empty());
+    }
+}
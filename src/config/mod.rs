@@ -1,6 +1,10 @@
 // src/config/mod.rs
+mod cron;
+mod diff;
 mod models;
 
+pub use cron::Cron;
+pub use diff::{redacted, ConfigDiff, SettingChange};
 pub use models::*;
 
 use anyhow::{Context, Result};
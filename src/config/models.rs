@@ -1,23 +1,106 @@
 // src/config/models.rs
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 use std::time::Duration;
 use url::Url;
 use anyhow::{bail, Result};
+use super::Cron;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
+    #[serde(default = "default_listeners")]
+    pub listeners: Vec<ListenerConfig>,
     pub load_balancer: LoadBalancerConfig,
+    #[serde(default)]
     pub backends: Vec<BackendConfig>,
+    /// Backends found at runtime, in addition to the static `backends`.
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
+    #[serde(default)]
+    pub normalize: NormalizeConfig,
+    /// Checked in order; requests matching none go to the `default` pool.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<RouteConfig>,
+    /// Answer TRACE and CONNECT requests with a 405 on every route.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reject_trace_connect: bool,
     pub health_check: HealthCheckConfig,
     pub circuit_breaker: CircuitBreakerConfig,
     pub retry: RetryConfig,
+    /// Named retry settings routes can pick with `policy.retry_policy`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub retry_policies: HashMap<String, RetryConfig>,
+    /// Defaults for every route; each route's `policy` overrides them.
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    /// Limits of the response cache used by routes with `policy.cache`.
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// Answer requests without contacting backends, everywhere or on
+    /// routes with `maintenance`; also switchable from the admin API.
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    /// Shed low-priority requests while the load balancer is overloaded.
+    #[serde(default)]
+    pub admission: AdmissionConfig,
+    /// Cap each client's share of the requests in flight upstream.
+    #[serde(default)]
+    pub fairness: FairnessConfig,
+    /// Cap how fast responses are sent to each client or connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_limit: Option<DownloadLimitConfig>,
+    /// Country lookups for routes matching on `country` or `continent`.
+    /// Needs the `geoip` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geoip: Option<GeoIpConfig>,
+    /// Backends and routes from etcd, applied live. Needs the `etcd` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etcd: Option<EtcdConfig>,
+    /// Other load balancer instances to share backend failures with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<ClusterConfig>,
+    /// Keep traffic in this instance's region, spilling some of it over to
+    /// backends in other regions while local ones are down.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regions: Option<RegionsConfig>,
     pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub request_id: RequestIdConfig,
+    /// What every request passes through around routing and proxying, in
+    /// order. Leaving out `metrics` or `request_id` turns those off.
+    #[serde(default = "default_filters")]
+    pub filters: Vec<FilterConfig>,
+    /// Ways of authenticating requests, by name, for routes to pick in
+    /// their `auth`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub auth_strategies: HashMap<String, AuthStrategyConfig>,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// How connections to backends are opened.
+    #[serde(default)]
+    pub upstream: UpstreamConfig,
+    /// Responses for errors the load balancer answers itself, by status.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub error_pages: HashMap<u16, ErrorPageConfig>,
+    /// The body of errors without an `error_pages` entry.
+    #[serde(default)]
+    pub error_format: ErrorFormat,
+    /// Times backends are taken out of rotation, e.g. for nightly batch
+    /// jobs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maintenance_windows: Vec<MaintenanceWindowConfig>,
 }
 
 impl Config {
     pub fn validate(&self) -> Result<()> {
-        if self.backends.is_empty() {
-            bail!("At least one backend must be configured");
+        if self.backends.is_empty() && self.discovery.is_empty() && self.etcd.is_none() {
+            bail!("At least one backend or discovery source must be configured");
         }
         
         // Check for duplicate backend IDs
@@ -29,6 +112,14 @@ impl Config {
             }
         }
         
+        if let Some(dynamic_weights) = &self.load_balancer.dynamic_weights {
+            dynamic_weights.validate(self.load_balancer.algorithm)?;
+        }
+        let tiers = &self.load_balancer.least_outstanding.tiers;
+        if tiers.first() == Some(&0) || tiers.windows(2).any(|pair| pair[0] >= pair[1]) {
+            bail!("load_balancer.least_outstanding tiers must be ascending and greater than 0");
+        }
+        
         for (i, backend) in self.backends.iter().enumerate() {
             if backend.weight == 0 {
                 bail!("Backend {} has invalid weight: 0", i);
@@ -37,26 +128,903 @@ impl Config {
             if backend.max_connections == 0 {
                 bail!("Backend {} has invalid max_connections: 0", i);
             }
+            
+            if let Some(queue) = &backend.queue {
+                queue.validate(&format!("Backend {}", i))?;
+            }
+            
+            if backend.idle_validation.is_some_and(|validation| validation.after_secs == 0) {
+                bail!("Backend {} idle_validation after_secs must be greater than 0", i);
+            }
+            
+            if backend.url.scheme() == "unix" && backend.url.path().len() <= 1 {
+                bail!("Backend {} has no socket path in {}", i, backend.url);
+            }
+            
+            if backend.url.scheme() == "tcp"
+                && (backend.url.host_str().is_none() || backend.url.port().is_none())
+            {
+                bail!("Backend {} needs a host and port in {}", i, backend.url);
+            }
+            
+            if let Some(HostHeader::Set(host)) = &backend.host_header {
+                if hyper::header::HeaderValue::from_str(host).is_err() {
+                    bail!("Backend {} has invalid host_header: {:?}", i, host);
+                }
+            }
+            
+            if let Some(bandwidth) = &backend.bandwidth {
+                bandwidth.validate(&format!("Backend {}", i))?;
+            }
+        }
+        
+        self.validate_discovery()?;
+        self.validate_policy("Policy", &self.policy)?;
+        if self.cache.max_entry_bytes == 0 || self.cache.max_entry_bytes > self.cache.max_memory_bytes {
+            bail!(
+                "cache max_entry_bytes must be between 1 and max_memory_bytes ({}), got {}",
+                self.cache.max_memory_bytes,
+                self.cache.max_entry_bytes
+            );
+        }
+        if let Some(disk) = &self.cache.disk {
+            if disk.max_entry_bytes == 0 || disk.max_entry_bytes > disk.max_bytes {
+                bail!(
+                    "cache disk max_entry_bytes must be between 1 and max_bytes ({}), got {}",
+                    disk.max_bytes,
+                    disk.max_entry_bytes
+                );
+            }
+        }
+        if hyper::StatusCode::from_u16(self.maintenance.status).is_err() {
+            bail!("maintenance has invalid status: {}", self.maintenance.status);
+        }
+        self.admission.validate()?;
+        self.fairness.validate()?;
+        if let Some(limit) = &self.download_limit {
+            limit.validate()?;
+        }
+        if let Some(page) = &self.maintenance.page {
+            if !page.is_file() {
+                bail!("maintenance page not found: {}", page.display());
+            }
+        }
+        if let Some(geoip) = &self.geoip {
+            if !cfg!(feature = "geoip") {
+                bail!("geoip is configured but this build lacks the geoip feature");
+            }
+            if !geoip.database.is_file() {
+                bail!("GeoIP database not found: {}", geoip.database.display());
+            }
+        }
+        if let Some(etcd) = &self.etcd {
+            if !cfg!(feature = "etcd") {
+                bail!("etcd is configured but this build lacks the etcd feature");
+            }
+            if !etcd.prefix.ends_with('/') {
+                bail!("etcd prefix must end with '/', got {:?}", etcd.prefix);
+            }
+            if etcd.status_interval_secs == 0 {
+                bail!("etcd status_interval_secs must be greater than 0");
+            }
+        }
+        if let Some(cluster) = &self.cluster {
+            if cluster.peers.is_empty() {
+                bail!("cluster.peers must list at least one peer");
+            }
+            for peer in &cluster.peers {
+                if !matches!(peer.scheme(), "http" | "https") {
+                    bail!("cluster peer {} must be an http or https URL", peer);
+                }
+            }
+            if cluster.token.as_deref() == Some("") {
+                bail!("cluster.token must not be empty");
+            }
+            // Anyone who can reach the listener could eject every backend
+            if cluster.token.is_none() && !cluster.listen.ip().is_loopback() {
+                bail!("cluster.token must be set unless cluster.listen is a loopback address");
+            }
+        }
+        if let Some(regions) = &self.regions {
+            if regions.remote.contains_key(&regions.local) {
+                bail!("regions.remote must not include the local region {}", regions.local);
+            }
+            for (name, remote) in &regions.remote {
+                if remote.spillover_percent > 100 {
+                    bail!("regions.remote.{} spillover_percent must be at most 100", name);
+                }
+            }
         }
+        self.validate_routes()?;
         
         if self.health_check.interval_secs == 0 {
             bail!("Health check interval must be greater than 0");
         }
         
+        for (i, step) in self.health_check.steps.iter().enumerate() {
+            step.validate(&format!("Health check step {}", i + 1))?;
+        }
+        if let Some(warmup) = &self.health_check.warmup {
+            warmup.validate("Health check")?;
+        }
+        for (pool, check) in &self.health_check.pools {
+            if check.path.as_ref().is_some_and(|path| !path.starts_with('/')) {
+                bail!("Health check for pool '{}' has a path not starting with '/'", pool);
+            }
+            if check.timeout_secs == Some(0) {
+                bail!("Health check for pool '{}' needs a timeout_secs greater than 0", pool);
+            }
+            for (i, step) in check.steps.iter().flatten().enumerate() {
+                step.validate(&format!("Health check for pool '{}' step {}", pool, i + 1))?;
+            }
+            if let Some(warmup) = &check.warmup {
+                warmup.validate(&format!("Health check for pool '{}'", pool))?;
+            }
+        }
+        
+        if let Some(fast) = self.health_check.unhealthy_interval_secs {
+            if fast == 0 || !self.health_check.interval_secs.is_multiple_of(fast) {
+                bail!(
+                    "Health check unhealthy_interval_secs must divide interval_secs ({}), got {}",
+                    self.health_check.interval_secs,
+                    fast
+                );
+            }
+        }
+        
         if self.circuit_breaker.failure_threshold == 0 {
             bail!("Circuit breaker failure threshold must be greater than 0");
         }
         
+        if self.listeners.is_empty() {
+            bail!("At least one listener must be configured");
+        }
+        
+        let mut listener_names = std::collections::HashSet::new();
+        for listener in &self.listeners {
+            if !listener_names.insert(listener.name.as_str()) {
+                bail!("Duplicate listener name: {}", listener.name);
+            }
+            if listener.max_connections == Some(0) {
+                bail!("Listener {} has invalid max_connections: 0", listener.name);
+            }
+            if listener.max_connections_per_ip == Some(0) {
+                bail!("Listener {} has invalid max_connections_per_ip: 0", listener.name);
+            }
+            if listener.body_read_timeout_secs == Some(0) {
+                bail!("Listener {} has invalid body_read_timeout_secs: 0", listener.name);
+            }
+            if listener.protocol == ListenerProtocol::Tcp
+                && !self.backends.iter().any(|b| b.url.scheme() == "tcp")
+                && !self.discovery.dns.iter().any(|d| d.scheme == "tcp")
+            {
+                bail!("Listener {} uses protocol tcp but no tcp:// backends are configured", listener.name);
+            }
+            if listener.tls_fingerprint && listener.protocol != ListenerProtocol::Tcp {
+                bail!("Listener {} sets tls_fingerprint, which needs protocol tcp", listener.name);
+            }
+            if (!listener.sni_routes.is_empty() || listener.sni_default_pool.is_some())
+                && listener.protocol != ListenerProtocol::Tcp
+            {
+                bail!("Listener {} sets sni_routes, which needs protocol tcp", listener.name);
+            }
+            for route in &listener.sni_routes {
+                if route.server_names.is_empty() {
+                    bail!("Listener {} has an SNI route without server_names", listener.name);
+                }
+                if let Some(name) = route.server_names.iter().find(|name| {
+                    let exact = name.strip_prefix("*.").unwrap_or(name);
+                    exact.is_empty() || exact.contains('*')
+                }) {
+                    bail!("Listener {} has invalid SNI server name: {}", listener.name, name);
+                }
+            }
+            if listener.routing.is_some() && listener.protocol != ListenerProtocol::Http {
+                bail!("Listener {} sets routing, which needs protocol http", listener.name);
+            }
+            if listener.max_requests_per_connection == Some(0) {
+                bail!("Listener {} has invalid max_requests_per_connection: 0", listener.name);
+            }
+            if listener.unix_socket.is_some() && listener.max_connections_per_ip.is_some() {
+                bail!("Listener {} sets max_connections_per_ip on a Unix socket", listener.name);
+            }
+            if listener.http.http2_only && !listener.http.http2 {
+                bail!("Listener {} sets http2_only with http2 disabled", listener.name);
+            }
+            if let Some(http3) = &listener.http.http3 {
+                if !cfg!(feature = "http3") {
+                    bail!("Listener {} serves http3 but this build lacks the http3 feature", listener.name);
+                }
+                if listener.protocol != ListenerProtocol::Http || listener.unix_socket.is_some() {
+                    bail!("Listener {} sets http3, which needs an HTTP listener on an address", listener.name);
+                }
+                for path in [&http3.cert, &http3.key] {
+                    if !path.is_file() {
+                        bail!("Listener {}: http3 file not found: {}", listener.name, path.display());
+                    }
+                }
+            }
+            if let Some(size) = listener.http.max_header_size {
+                if size < MIN_MAX_HEADER_SIZE {
+                    bail!(
+                        "Listener {} max_header_size must be at least {} bytes",
+                        listener.name,
+                        MIN_MAX_HEADER_SIZE
+                    );
+                }
+            }
+        }
+        
+        if self.runtime.acceptors == 0 {
+            bail!("Runtime acceptors must be greater than 0");
+        }
+        if self.runtime.max_connections == Some(0) {
+            bail!("Runtime max_connections must be greater than 0");
+        }
+        if let Some(ms) = self.runtime.coarse_clock_ms {
+            if !(1..=1000).contains(&ms) {
+                bail!("Runtime coarse_clock_ms must be between 1 and 1000, got {}", ms);
+            }
+        }
+        
+        if let Some(slo) = &self.metrics.slo {
+            if !(slo.target > 0.0 && slo.target < 1.0) {
+                bail!("SLO target must be between 0 and 1 (exclusive), got {}", slo.target);
+            }
+        }
+        
+        self.logging.validate()?;
+        validate_error_pages("Config", &self.error_pages)?;
+        let mut windows = std::collections::HashSet::new();
+        for window in &self.maintenance_windows {
+            if !windows.insert(window.name.as_str()) {
+                bail!("Duplicate maintenance window: {}", window.name);
+            }
+            if window.duration_mins == 0 {
+                bail!("Maintenance window {} needs a duration_mins greater than 0", window.name);
+            }
+            if window.pools.is_empty() && window.backends.is_empty() {
+                bail!("Maintenance window {} needs pools or backends to drain", window.name);
+            }
+        }
+        if !(10..=2000).contains(&self.upstream.happy_eyeballs_delay_ms) {
+            bail!(
+                "Upstream happy_eyeballs_delay_ms must be between 10 and 2000, got {}",
+                self.upstream.happy_eyeballs_delay_ms
+            );
+        }
+        self.upstream.connection_pool.validate("Upstream connection_pool")?;
+        for (pool, config) in &self.upstream.pools {
+            config.validate(&format!("Upstream pool '{}'", pool))?;
+        }
+        self.metrics.access.validate("Metrics")?;
+        self.admin.access.validate("Admin")?;
+        if self.admin.enabled && self.admin.port == self.metrics.port && self.metrics.enabled {
+            bail!("Admin and metrics servers cannot share port {}", self.admin.port);
+        }
+        
+        if hyper::header::HeaderName::from_bytes(self.request_id.header.as_bytes()).is_err() {
+            bail!("Invalid request ID header name: {:?}", self.request_id.header);
+        }
+        
+        for filter in &self.filters {
+            filter.validate("filters")?;
+        }
+        for (name, strategy) in &self.auth_strategies {
+            strategy.validate(&format!("Auth strategy {}", name))?;
+        }
+        
+        Ok(())
+    }
+    
+    fn validate_policy(&self, owner: &str, policy: &PolicyConfig) -> Result<()> {
+        if policy.upstream_timeout_secs == Some(0) {
+            bail!("{} has invalid upstream_timeout_secs: 0", owner);
+        }
+        if policy.latency_budget_ms == Some(0) {
+            bail!("{} has invalid latency_budget_ms: 0", owner);
+        }
+        if let Some(name) = &policy.retry_policy {
+            if !self.retry_policies.contains_key(name) {
+                bail!("{} references unknown retry policy: {}", owner, name);
+            }
+        }
+        if policy.priority.is_some_and(|priority| priority > MAX_PRIORITY) {
+            bail!("{} priority must be between 0 and {}", owner, MAX_PRIORITY);
+        }
+        Ok(())
+    }
+    
+    fn validate_discovery(&self) -> Result<()> {
+        if !(1..=100).contains(&self.discovery.reconcile.max_drain_percent) {
+            bail!(
+                "Discovery max_drain_percent must be between 1 and 100, got {}",
+                self.discovery.reconcile.max_drain_percent
+            );
+        }
+        for dns in &self.discovery.dns {
+            if dns.name.is_empty() {
+                bail!("DNS discovery needs a name");
+            }
+            match dns.record_type {
+                DnsRecordType::A if dns.port.is_none() => {
+                    bail!("DNS discovery {} needs a port for A records", dns.name);
+                }
+                DnsRecordType::Srv if dns.port.is_some() => {
+                    bail!("DNS discovery {} sets a port, but SRV records carry their own", dns.name);
+                }
+                _ => {}
+            }
+            if !["http", "https", "tcp"].contains(&dns.scheme.as_str()) {
+                bail!("DNS discovery {} has unsupported scheme: {}", dns.name, dns.scheme);
+            }
+            if dns.interval_secs == 0 {
+                bail!("DNS discovery {} has invalid interval_secs: 0", dns.name);
+            }
+            if dns.weight == 0 || dns.max_connections == 0 {
+                bail!("DNS discovery {} needs non-zero weight and max_connections", dns.name);
+            }
+        }
+        if !self.discovery.consul.is_empty() && !cfg!(feature = "consul") {
+            bail!("consul discovery is configured but this build lacks the consul feature");
+        }
+        for consul in &self.discovery.consul {
+            if consul.service.is_empty() {
+                bail!("Consul discovery needs a service");
+            }
+            if !["http", "https"].contains(&consul.scheme.as_str()) {
+                bail!("Consul discovery {} has unsupported scheme: {}", consul.service, consul.scheme);
+            }
+            if consul.wait_secs == 0 {
+                bail!("Consul discovery {} has invalid wait_secs: 0", consul.service);
+            }
+            if consul.weight == 0 || consul.max_connections == 0 {
+                bail!("Consul discovery {} needs non-zero weight and max_connections", consul.service);
+            }
+        }
+        if let Some(xds) = &self.discovery.xds {
+            if !cfg!(feature = "xds") {
+                bail!("xds discovery is configured but this build lacks the xds feature");
+            }
+            if xds.server.scheme() != "http" {
+                bail!("xds server must be a plaintext http:// address, got {}", xds.server);
+            }
+            if !["http", "https"].contains(&xds.scheme.as_str()) {
+                bail!("xds discovery has unsupported scheme: {}", xds.scheme);
+            }
+            if xds.max_connections == 0 {
+                bail!("xds discovery has invalid max_connections: 0");
+            }
+        }
+        Ok(())
+    }
+    
+    fn validate_routes(&self) -> Result<()> {
+        let pools: std::collections::HashSet<&str> = self
+            .backends
+            .iter()
+            .map(|b| b.pool.as_str())
+            .chain(self.discovery.pools())
+            .collect();
+        // Pools from CDS or etcd aren't known until they arrive
+        let any_pool = self.discovery.xds.as_ref().is_some_and(|x| x.clusters.is_empty())
+            || self.etcd.is_some();
+        let mut names = std::collections::HashSet::new();
+        
+        for listener in &self.listeners {
+            let sni_pools = listener.sni_routes.iter().map(|r| &r.pool).chain(listener.sni_default_pool.iter());
+            for pool in sni_pools {
+                if !any_pool && !pools.contains(pool.as_str()) {
+                    bail!("Listener {} SNI route references unknown pool: {}", listener.name, pool);
+                }
+            }
+            let Some(routing) = &listener.routing else {
+                continue;
+            };
+            if routing.default_pool != DEFAULT_POOL && !any_pool && !pools.contains(routing.default_pool.as_str()) {
+                bail!("Listener {} default_pool is unknown: {}", listener.name, routing.default_pool);
+            }
+        }
+        for route in self.all_routes() {
+            if !names.insert(route.name.as_str()) {
+                bail!("Duplicate route name: {}", route.name);
+            }
+            if !route.path_prefix.starts_with('/') {
+                bail!("Route {} path_prefix must start with '/'", route.name);
+            }
+            if route.pool.is_some() && !route.split.is_empty() {
+                bail!("Route {} sets both pool and split", route.name);
+            }
+            for method in &route.methods {
+                if hyper::Method::from_bytes(method.as_bytes()).is_err() {
+                    bail!("Route {} has an invalid method: {}", route.name, method);
+                }
+            }
+            self.validate_policy(&format!("Route {}", route.name), &route.policy)?;
+            if let Some(auth) = &route.auth {
+                if auth.strategies.is_empty() {
+                    bail!("Route {} auth lists no strategies", route.name);
+                }
+                for strategy in &auth.strategies {
+                    if !self.auth_strategies.contains_key(strategy) {
+                        bail!("Route {} uses unknown auth strategy {}", route.name, strategy);
+                    }
+                }
+            }
+            for filter in &route.filters {
+                if matches!(filter, FilterConfig::Metrics | FilterConfig::RequestId) {
+                    bail!("Route {} filters can't include metrics or request_id", route.name);
+                }
+                filter.validate(&format!("Route {}", route.name))?;
+            }
+            if route.experiment.is_some() && (route.pool.is_some() || !route.split.is_empty()) {
+                bail!("Route {} sets an experiment together with pool or split", route.name);
+            }
+            if let Some(experiment) = &route.experiment {
+                validate_experiment(&route.name, experiment)?;
+            }
+            validate_error_pages(&format!("Route {}", route.name), &route.error_pages)?;
+            let proxies = route.pool.is_some() || !route.split.is_empty() || route.experiment.is_some();
+            let answers_locally = route.redirect.is_some() || route.direct_response.is_some() || route.static_files.is_some();
+            if route.fallback_pool.is_some() && answers_locally {
+                bail!("Route {} sets fallback_pool but doesn't proxy", route.name);
+            }
+            if let Some(validation) = &route.validate_response {
+                if answers_locally {
+                    bail!("Route {} sets validate_response but doesn't proxy", route.name);
+                }
+                validation.validate(&route.name)?;
+            }
+            let actions = [
+                proxies,
+                route.redirect.is_some(),
+                route.direct_response.is_some(),
+                route.static_files.is_some(),
+            ];
+            if actions.iter().filter(|set| **set).count() > 1 {
+                bail!(
+                    "Route {} sets more than one of pool/split, redirect, direct_response and static_files",
+                    route.name
+                );
+            }
+            if let Some(files) = &route.static_files {
+                if !files.root.is_dir() {
+                    bail!("Route {} static_files root is not a directory: {}", route.name, files.root.display());
+                }
+                if files.index.iter().any(|index| index.is_empty() || index.contains('/')) {
+                    bail!("Route {} static_files index entries must be plain file names", route.name);
+                }
+            }
+            if let Some(redirect) = &route.redirect {
+                if ![301, 302, 303, 307, 308].contains(&redirect.status) {
+                    bail!("Route {} has invalid redirect status: {}", route.name, redirect.status);
+                }
+                validate_location(&route.name, &redirect.location)?;
+            }
+            if let Some(response) = &route.direct_response {
+                if hyper::StatusCode::from_u16(response.status).is_err() {
+                    bail!("Route {} has invalid direct_response status: {}", route.name, response.status);
+                }
+                if hyper::header::HeaderValue::from_str(&response.content_type).is_err() {
+                    bail!("Route {} has invalid direct_response content_type", route.name);
+                }
+            }
+            if route.preload_links.iter().any(|link| hyper::header::HeaderValue::from_str(link).is_err()) {
+                bail!("Route {} has an invalid preload_links value", route.name);
+            }
+            if !route.split.is_empty() && route.split.iter().all(|t| t.weight == 0) {
+                bail!("Route {} split weights must not all be 0", route.name);
+            }
+            if route.split_hash_header.is_some() && route.split.is_empty() {
+                bail!("Route {} sets split_hash_header without split", route.name);
+            }
+            if let Some(header) = &route.split_hash_header {
+                if hyper::header::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                    bail!("Route {} has invalid split_hash_header: {:?}", route.name, header);
+                }
+            }
+            
+            if let Some(HostHeader::Set(host)) = &route.host_header {
+                if hyper::header::HeaderValue::from_str(host).is_err() {
+                    bail!("Route {} has invalid host_header: {:?}", route.name, host);
+                }
+            }
+            
+            for rule in &route.overrides {
+                match (&rule.header, &rule.cookie) {
+                    (Some(header), None) => {
+                        if hyper::header::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                            bail!("Route {} has invalid override header: {:?}", route.name, header);
+                        }
+                    }
+                    (None, Some(cookie)) if !cookie.is_empty() => {}
+                    _ => bail!("Route {} overrides need exactly one of header or cookie", route.name),
+                }
+            }
+            
+            let targets = route
+                .pool
+                .iter()
+                .chain(route.split.iter().map(|t| &t.pool))
+                .chain(route.overrides.iter().map(|rule| &rule.pool))
+                .chain(route.fallback_pool.iter())
+                .chain(route.experiment.iter().flat_map(|e| e.variants.iter().map(|v| &v.pool)));
+            for pool in targets {
+                if !any_pool && !pools.contains(pool.as_str()) {
+                    bail!("Route {} references unknown pool: {}", route.name, pool);
+                }
+            }
+        }
+        
         Ok(())
     }
+    
+    /// The top-level routes, then each listener's own.
+    pub fn all_routes(&self) -> impl Iterator<Item = &RouteConfig> {
+        let listeners = self.listeners.iter().filter_map(|listener| listener.routing.as_ref());
+        self.routes.iter().chain(listeners.flat_map(|routing| &routing.routes))
+    }
+    
+    /// The first 16 hex digits of the SHA-256 of the config as JSON, the
+    /// same for equal configs however their files are laid out.
+    pub fn content_hash(&self) -> String {
+        use sha2::{Digest, Sha256};
+        // Through `Value`, whose maps are sorted, so `HashMap` order doesn't count
+        let json = serde_json::to_value(self).and_then(|value| serde_json::to_vec(&value)).unwrap_or_default();
+        Sha256::digest(&json)[..8].iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+fn validate_experiment(route: &str, experiment: &ExperimentConfig) -> Result<()> {
+    if experiment.variants.iter().all(|variant| variant.weight == 0) {
+        bail!("Route {} experiment {} needs a variant with non-zero weight", route, experiment.name);
+    }
+    let mut names = std::collections::HashSet::new();
+    for variant in &experiment.variants {
+        if !names.insert(variant.name.as_str()) {
+            bail!("Route {} experiment {} has duplicate variant {}", route, experiment.name, variant.name);
+        }
+        if hyper::header::HeaderValue::from_str(&variant.name).is_err() {
+            bail!("Route {} experiment {} has invalid variant name {:?}", route, experiment.name, variant.name);
+        }
+    }
+    for source in &experiment.bucket_by {
+        let valid = match (source.strip_prefix("header:"), source.strip_prefix("cookie:")) {
+            (Some(header), _) => hyper::header::HeaderName::from_bytes(header.as_bytes()).is_ok(),
+            (_, Some(cookie)) => !cookie.is_empty(),
+            _ => source == "ip",
+        };
+        if !valid {
+            bail!("Route {} experiment {} has invalid bucket_by source: {:?}", route, experiment.name, source);
+        }
+    }
+    if hyper::header::HeaderName::from_bytes(experiment.response_header.as_bytes()).is_err() {
+        bail!("Route {} experiment {} has invalid response_header", route, experiment.name);
+    }
+    Ok(())
+}
+
+fn validate_location(route: &str, location: &str) -> Result<()> {
+    let mut rest = location;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            bail!("Route {} redirect location has an unclosed '{{'", route);
+        };
+        let name = &rest[start + 1..start + len];
+        if !REDIRECT_PLACEHOLDERS.contains(&name) {
+            bail!("Route {} redirect location has unknown placeholder {{{}}}", route, name);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ListenerConfig {
+    #[serde(default = "default_listener_name")]
+    pub name: String,
+    #[serde(default = "default_listener_address")]
+    pub address: SocketAddr,
+    /// Listen on this Unix socket path instead of `address`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix_socket: Option<PathBuf>,
+    /// `http` to proxy requests, or `tcp` to forward raw byte streams to the
+    /// `tcp://` backends.
+    #[serde(default)]
+    pub protocol: ListenerProtocol,
+    /// Maximum open connections on this listener; further clients wait in
+    /// the listen backlog until one closes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<usize>,
+    /// Maximum concurrent connections from a single client IP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connections_per_ip: Option<usize>,
+    /// Time allowed to receive a request's headers, including the wait for the
+    /// next request on a kept-alive connection (slowloris protection).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_read_timeout_secs: Option<u64>,
+    /// Close connections with no request in flight and no I/O for this long.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout_secs: Option<u64>,
+    /// Fail a write the client hasn't drained within this long.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_timeout_secs: Option<u64>,
+    /// Time allowed to receive a request's whole body. Connections whose
+    /// body runs out of time are closed, however steadily it trickles in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_read_timeout_secs: Option<u64>,
+    /// Close connections older than this: no new requests are read, and
+    /// ones still running `close_grace_secs` later are cut off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connection_lifetime_secs: Option<u64>,
+    /// How long the requests of a connection closed by `idle_timeout_secs`
+    /// or `max_connection_lifetime_secs` get to finish before it's cut off.
+    #[serde(default = "default_close_grace_secs")]
+    pub close_grace_secs: u64,
+    /// Ask the client to reconnect (`Connection: close`, or GOAWAY on
+    /// HTTP/2) after this many requests, so long-lived clients get
+    /// rebalanced when backends or LB instances are added.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_requests_per_connection: Option<u64>,
+    /// Like `max_requests_per_connection`, but by connection age. Unlike
+    /// `max_connection_lifetime_secs` this only takes effect on the next
+    /// response, so it never cuts off a request, however long it runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connection_age_secs: Option<u64>,
+    /// On `tcp` listeners carrying TLS, read each client's hello before
+    /// connecting upstream and log its JA3 fingerprint.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tls_fingerprint: bool,
+    #[serde(default)]
+    pub http: HttpOptions,
+    /// Route this listener's requests with these routes and default pool
+    /// instead of the top-level ones, e.g. for an internal-only port.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing: Option<ListenerRoutingConfig>,
+    /// On `tcp` listeners carrying TLS, send each connection to the pool
+    /// of the first route listing its ClientHello's server name, without
+    /// terminating TLS.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sni_routes: Vec<SniRouteConfig>,
+    /// Where connections matching none of `sni_routes`, or sending no
+    /// server name, go. Unset, they're closed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sni_default_pool: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SniRouteConfig {
+    /// Exact names, or `*.example.com` for any name under `example.com`.
+    pub server_names: Vec<String>,
+    pub pool: String,
+}
+
+/// A listener's own routing table. Applied live like the top-level
+/// `routes`, whose names its routes can't reuse.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ListenerRoutingConfig {
+    /// Checked in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<RouteConfig>,
+    /// Where requests matching none of `routes` go.
+    #[serde(default = "default_pool")]
+    pub default_pool: String,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            name: default_listener_name(),
+            address: default_listener_address(),
+            unix_socket: None,
+            protocol: ListenerProtocol::default(),
+            max_connections: None,
+            max_connections_per_ip: None,
+            header_read_timeout_secs: None,
+            idle_timeout_secs: None,
+            write_timeout_secs: None,
+            body_read_timeout_secs: None,
+            max_connection_lifetime_secs: None,
+            close_grace_secs: default_close_grace_secs(),
+            max_requests_per_connection: None,
+            max_connection_age_secs: None,
+            tls_fingerprint: false,
+            http: HttpOptions::default(),
+            routing: None,
+            sni_routes: Vec::new(),
+            sni_default_pool: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListenerProtocol {
+    #[default]
+    Http,
+    /// Layer-4 passthrough for databases and other non-HTTP services.
+    Tcp,
+}
+
+/// Checks on HTTP/1 requests beyond those the parser always makes: it
+/// rejects obs-fold, invalid characters in header names and values,
+/// differing `Content-Length`s and a `Transfer-Encoding` not ending in
+/// `chunked` whatever the level.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestValidation {
+    /// A request with both `Transfer-Encoding` and `Content-Length` is
+    /// forwarded without the `Content-Length`, as RFC 9112 asks of proxies.
+    #[default]
+    Standard,
+    /// Reject requests with both, with any `Transfer-Encoding` but a single
+    /// `chunked`, with more than one `Host`, or with header names containing
+    /// `_` (which some backends read as `-`) or values outside printable
+    /// ASCII, and close their connections.
+    Strict,
+}
+
+/// Frontend HTTP protocol settings, applied to each accepted connection.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HttpOptions {
+    /// Accept HTTP/2 (prior knowledge) alongside HTTP/1.
+    #[serde(default = "default_true")]
+    pub http2: bool,
+    /// Serve only HTTP/2, rejecting HTTP/1 clients.
+    #[serde(default)]
+    pub http2_only: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http2_max_concurrent_streams: Option<u32>,
+    /// Interval between HTTP/2 PING frames; disabled when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http2_keep_alive_interval_secs: Option<u64>,
+    #[serde(default = "default_http2_keep_alive_timeout_secs")]
+    pub http2_keep_alive_timeout_secs: u64,
+    #[serde(default = "default_true")]
+    pub http1_keep_alive: bool,
+    /// Keep reading requests after the client half-closes its side.
+    #[serde(default)]
+    pub http1_half_close: bool,
+    /// Upper bound on the size of request headers, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_header_size: Option<usize>,
+    /// How strictly HTTP/1 requests are checked for framing a backend
+    /// could read differently, as in request smuggling.
+    #[serde(default)]
+    pub request_validation: RequestValidation,
+    /// Also serve HTTP/3 over QUIC. Needs the `http3` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http3: Option<Http3Config>,
+}
+
+/// An HTTP/3 listener beside an HTTP listener, passing requests to the
+/// same routes.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Http3Config {
+    /// PEM certificate chain and private key; QUIC is always encrypted.
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// UDP port, if not the listener's own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// How long clients may remember the `Alt-Svc` header advertising
+    /// HTTP/3 on the listener's HTTP/1 and HTTP/2 responses; 0 doesn't
+    /// send it.
+    #[serde(default = "default_alt_svc_max_age_secs")]
+    pub alt_svc_max_age_secs: u64,
+    /// Accept requests in 0-RTT early data from returning clients. Early
+    /// data can be replayed, so only turn this on for routes where a
+    /// repeated request is harmless.
+    #[serde(default)]
+    pub zero_rtt: bool,
+}
+
+impl Http3Config {
+    /// The UDP address for a listener on `address`.
+    pub fn address(&self, address: SocketAddr) -> SocketAddr {
+        SocketAddr::new(address.ip(), self.port.unwrap_or(address.port()))
+    }
+
+    /// The `Alt-Svc` value for a listener on `address`, if it's sent.
+    pub fn alt_svc(&self, address: SocketAddr) -> Option<String> {
+        (self.alt_svc_max_age_secs > 0)
+            .then(|| format!("h3=\":{}\"; ma={}", self.address(address).port(), self.alt_svc_max_age_secs))
+    }
+}
+
+fn default_alt_svc_max_age_secs() -> u64 { 86400 }
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            http2: true,
+            http2_only: false,
+            http2_max_concurrent_streams: None,
+            http2_keep_alive_interval_secs: None,
+            http2_keep_alive_timeout_secs: default_http2_keep_alive_timeout_secs(),
+            http1_keep_alive: true,
+            http1_half_close: false,
+            max_header_size: None,
+            request_validation: RequestValidation::default(),
+            http3: None,
+        }
+    }
 }
 
+fn default_true() -> bool { true }
+fn default_http2_keep_alive_timeout_secs() -> u64 { 20 }
+
+/// Hyper refuses read buffers smaller than this.
+pub const MIN_MAX_HEADER_SIZE: usize = 8192;
+
+fn default_listeners() -> Vec<ListenerConfig> { vec![ListenerConfig::default()] }
+fn default_listener_name() -> String { "http".to_string() }
+fn default_listener_address() -> SocketAddr { ([0, 0, 0, 0], 8080).into() }
+fn default_close_grace_secs() -> u64 { 30 }
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LoadBalancerConfig {
     #[serde(default = "default_algorithm")]
     pub algorithm: LoadBalancerAlgorithm,
+    /// Scale weights down for backends slower than their pool's median.
+    /// Needs `weighted_round_robin`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dynamic_weights: Option<DynamicWeightsConfig>,
+    #[serde(default)]
+    pub least_outstanding: LeastOutstandingConfig,
+}
+
+/// `least_outstanding` groups backends into tiers by their requests in
+/// flight, split at each of `tiers`, and picks the one in the lowest tier
+/// with the lowest recent median latency.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LeastOutstandingConfig {
+    #[serde(default = "default_outstanding_tiers")]
+    pub tiers: Vec<usize>,
+}
+
+impl Default for LeastOutstandingConfig {
+    fn default() -> Self {
+        Self { tiers: default_outstanding_tiers() }
+    }
+}
+
+fn default_outstanding_tiers() -> Vec<usize> { vec![1, 4, 16] }
+
+/// Every `interval_secs`, each backend's mean latency (health checks and
+/// requests alike) since the last update is smoothed over `window_secs`,
+/// and its weight scaled by its pool's median over that, down to
+/// `min_weight_percent` of what's configured. Backends at or under the
+/// median keep their full weight.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DynamicWeightsConfig {
+    #[serde(default = "default_dynamic_weights_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_dynamic_weights_window_secs")]
+    pub window_secs: u64,
+    #[serde(default = "default_min_weight_percent")]
+    pub min_weight_percent: u32,
+}
+
+impl DynamicWeightsConfig {
+    fn validate(&self, algorithm: LoadBalancerAlgorithm) -> Result<()> {
+        if algorithm != LoadBalancerAlgorithm::WeightedRoundRobin {
+            bail!("load_balancer.dynamic_weights needs algorithm: weighted_round_robin");
+        }
+        if self.interval_secs == 0 || self.window_secs < self.interval_secs {
+            bail!("dynamic_weights interval_secs must be greater than 0 and at most window_secs");
+        }
+        if !(1..=100).contains(&self.min_weight_percent) {
+            bail!("dynamic_weights min_weight_percent must be between 1 and 100");
+        }
+        Ok(())
+    }
 }
 
+fn default_dynamic_weights_interval_secs() -> u64 { 10 }
+fn default_dynamic_weights_window_secs() -> u64 { 60 }
+fn default_min_weight_percent() -> u32 { 10 }
+
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum LoadBalancerAlgorithm {
@@ -64,21 +1032,133 @@ pub enum LoadBalancerAlgorithm {
     WeightedRoundRobin,  // Add this for the benchmarks
     LeastConnections,    // Optional: add more algorithms
     IpHash,             // Optional: add more algorithms
+    /// Fewest requests in flight by tier, then lowest median latency.
+    LeastOutstanding,
 }
 
 fn default_algorithm() -> LoadBalancerAlgorithm {
     LoadBalancerAlgorithm::RoundRobin
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct BackendConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,  // Add ID field
+    /// `http://host:port`, `unix:///path/to/app.sock` for a backend on a
+    /// Unix domain socket, or `tcp://host:port` for `protocol: tcp` listeners.
     pub url: Url,
     #[serde(default = "default_weight")]
     pub weight: u32,
     #[serde(default = "default_max_connections")]
     pub max_connections: usize,
+    /// Pool this backend belongs to; routes send traffic to pools.
+    #[serde(default = "default_pool")]
+    pub pool: String,
+    /// Host header sent to this backend; takes precedence over the route's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_header: Option<HostHeader>,
+    /// Availability zone the backend runs in, e.g. from discovery tags.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+    /// Region the backend runs in, for `regions`; unset means local.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Cap on the response bytes sent on from this backend, across all of
+    /// its responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bandwidth: Option<BandwidthConfig>,
+    /// Requests finding the backend at `max_connections` wait here for one
+    /// to finish instead of failing at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue: Option<BackendQueueConfig>,
+    /// Don't trust pooled connections to this backend that sat idle for
+    /// long, e.g. behind a NAT that forgets them silently.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_validation: Option<IdleValidationConfig>,
+}
+
+/// What happens to a backend's pooled connections once they've been idle
+/// for `after_secs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct IdleValidationConfig {
+    #[serde(default = "default_idle_validation_after")]
+    pub after_secs: u64,
+    #[serde(default)]
+    pub mode: IdleValidationMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdleValidationMode {
+    /// Close them instead of reusing them, so the next request dials anew.
+    #[default]
+    Reconnect,
+    /// Send TCP keepalive probes after that long idle, which keep NAT
+    /// entries alive and close connections that stopped answering.
+    Probe,
+}
+
+fn default_idle_validation_after() -> u64 { 30 }
+
+/// A backend's wait queue. `max_connections` is the soft limit at which
+/// requests start queueing, and `max_connections + depth` the hard limit
+/// past which they fail.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BackendQueueConfig {
+    #[serde(default = "default_queue_depth")]
+    pub depth: usize,
+    /// How long a request waits for a connection before it fails.
+    #[serde(default = "default_queue_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl Default for BackendQueueConfig {
+    fn default() -> Self {
+        Self {
+            depth: default_queue_depth(),
+            timeout_ms: default_queue_timeout_ms(),
+        }
+    }
+}
+
+impl BackendQueueConfig {
+    fn validate(&self, owner: &str) -> Result<()> {
+        if self.depth == 0 || self.timeout_ms == 0 {
+            bail!("{} queue depth and timeout_ms must be greater than 0", owner);
+        }
+        Ok(())
+    }
+}
+
+fn default_queue_depth() -> usize {
+    50
+}
+
+fn default_queue_timeout_ms() -> u64 {
+    100
+}
+
+/// A token bucket of bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BandwidthConfig {
+    pub bytes_per_sec: u64,
+    /// Bytes that may go out at once after a quiet spell; one second's
+    /// worth when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst_bytes: Option<u64>,
+}
+
+impl BandwidthConfig {
+    pub fn burst(&self) -> u64 {
+        self.burst_bytes.unwrap_or(self.bytes_per_sec)
+    }
+
+    fn validate(&self, owner: &str) -> Result<()> {
+        if self.bytes_per_sec == 0 || self.burst() == 0 {
+            bail!("{} bandwidth bytes_per_sec and burst_bytes must be greater than 0", owner);
+        }
+        Ok(())
+    }
 }
 
 impl BackendConfig {
@@ -99,9 +1179,676 @@ fn default_max_connections() -> usize {
     100
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct HealthCheckConfig {
-    #[serde(default = "default_health_interval")]
+/// Pool of backends without an explicit `pool`, and of unrouted requests.
+pub const DEFAULT_POOL: &str = "default";
+
+fn default_pool() -> String { DEFAULT_POOL.to_string() }
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DiscoveryConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns: Vec<DnsDiscoveryConfig>,
+    /// Needs the `consul` feature.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub consul: Vec<ConsulDiscoveryConfig>,
+    /// Experimental; needs the `xds` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xds: Option<XdsDiscoveryConfig>,
+    #[serde(default)]
+    pub reconcile: ReconcileConfig,
+}
+
+/// Limits on how fast discovery sources may take backends away, so a bad
+/// answer (e.g. an empty one) can't empty a pool at once.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReconcileConfig {
+    /// Share of a source's backends that may start draining per round
+    /// (always at least one).
+    #[serde(default = "default_max_drain_percent")]
+    pub max_drain_percent: u32,
+    /// Minimum time between two rounds that drain backends; 0 disables the
+    /// limit.
+    #[serde(default = "default_churn_interval")]
+    pub churn_interval_secs: u64,
+}
+
+impl Default for ReconcileConfig {
+    fn default() -> Self {
+        Self {
+            max_drain_percent: default_max_drain_percent(),
+            churn_interval_secs: default_churn_interval(),
+        }
+    }
+}
+
+impl ReconcileConfig {
+    pub fn churn_interval(&self) -> Duration {
+        Duration::from_secs(self.churn_interval_secs)
+    }
+}
+
+fn default_max_drain_percent() -> u32 { 50 }
+fn default_churn_interval() -> u64 { 10 }
+
+impl DiscoveryConfig {
+    pub fn is_empty(&self) -> bool {
+        self.dns.is_empty() && self.consul.is_empty() && self.xds.is_none()
+    }
+    
+    /// Pools that discovery sources add backends to.
+    pub fn pools(&self) -> impl Iterator<Item = &str> {
+        self.dns
+            .iter()
+            .map(|d| d.pool.as_str())
+            .chain(self.consul.iter().map(|c| c.pool.as_str()))
+            .chain(self.xds.iter().flat_map(|x| x.clusters.iter().map(String::as_str)))
+    }
+}
+
+/// Periodically resolves `name` and keeps `pool` in line with the answer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DnsDiscoveryConfig {
+    pub name: String,
+    #[serde(default)]
+    pub record_type: DnsRecordType,
+    /// Port for A/AAAA answers; SRV records carry their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default = "default_discovery_scheme")]
+    pub scheme: String,
+    #[serde(default = "default_pool")]
+    pub pool: String,
+    /// Weight of A/AAAA backends; SRV backends use the record's weight.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    #[serde(default = "default_discovery_interval")]
+    pub interval_secs: u64,
+    /// How long a backend that left the answer keeps its open requests.
+    #[serde(default = "default_discovery_drain_timeout")]
+    pub drain_timeout_secs: u64,
+}
+
+impl DnsDiscoveryConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+    
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
+}
+
+/// `a` looks up both A and AAAA records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsRecordType {
+    #[default]
+    A,
+    Srv,
+}
+
+/// Watches the passing instances of a Consul service with blocking queries.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConsulDiscoveryConfig {
+    #[serde(default = "default_consul_address")]
+    pub address: Url,
+    pub service: String,
+    /// Only instances carrying this tag.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub datacenter: Option<String>,
+    /// ACL token sent as `X-Consul-Token`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(default = "default_discovery_scheme")]
+    pub scheme: String,
+    #[serde(default = "default_pool")]
+    pub pool: String,
+    /// Weight of instances without a `weight=<n>` tag or Consul weights.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    /// How long Consul may hold a blocking query open.
+    #[serde(default = "default_consul_wait")]
+    pub wait_secs: u64,
+    #[serde(default = "default_discovery_drain_timeout")]
+    pub drain_timeout_secs: u64,
+}
+
+impl ConsulDiscoveryConfig {
+    pub fn wait(&self) -> Duration {
+        Duration::from_secs(self.wait_secs)
+    }
+    
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
+}
+
+/// Clusters and their endpoints from an xDS control plane over ADS. Each
+/// cluster becomes the pool of the same name.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct XdsDiscoveryConfig {
+    /// gRPC address of the control plane, e.g. `http://xds:18000`.
+    pub server: Url,
+    #[serde(default = "default_xds_node")]
+    pub node_id: String,
+    #[serde(default = "default_xds_node")]
+    pub node_cluster: String,
+    /// Clusters to fetch endpoints for; empty asks for every cluster (CDS).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clusters: Vec<String>,
+    #[serde(default = "default_discovery_scheme")]
+    pub scheme: String,
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    #[serde(default = "default_discovery_drain_timeout")]
+    pub drain_timeout_secs: u64,
+}
+
+impl XdsDiscoveryConfig {
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
+}
+
+fn default_xds_node() -> String { "rust-load-balancer".to_string() }
+fn default_consul_address() -> Url { Url::parse("http://127.0.0.1:8500").unwrap() }
+fn default_consul_wait() -> u64 { 60 }
+fn default_discovery_scheme() -> String { "http".to_string() }
+fn default_discovery_interval() -> u64 { 30 }
+fn default_discovery_drain_timeout() -> u64 { 30 }
+
+/// Host header sent upstream: `preserve` (the default), `backend`, or
+/// `{ set: <value> }`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(from = "HostHeaderRepr", into = "HostHeaderRepr")]
+pub enum HostHeader {
+    /// Forward the client's Host.
+    Preserve,
+    /// Rewrite to the backend URL's `host:port`.
+    Backend,
+    /// Send a fixed value.
+    Set(String),
+}
+
+// serde_yaml only reads newtype variants from `!tag` syntax, hence the detour
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum HostHeaderRepr {
+    Mode(HostHeaderMode),
+    Set { set: String },
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum HostHeaderMode {
+    Preserve,
+    Backend,
+}
+
+impl From<HostHeaderRepr> for HostHeader {
+    fn from(repr: HostHeaderRepr) -> Self {
+        match repr {
+            HostHeaderRepr::Mode(HostHeaderMode::Preserve) => Self::Preserve,
+            HostHeaderRepr::Mode(HostHeaderMode::Backend) => Self::Backend,
+            HostHeaderRepr::Set { set } => Self::Set(set),
+        }
+    }
+}
+
+impl From<HostHeader> for HostHeaderRepr {
+    fn from(host: HostHeader) -> Self {
+        match host {
+            HostHeader::Preserve => Self::Mode(HostHeaderMode::Preserve),
+            HostHeader::Backend => Self::Mode(HostHeaderMode::Backend),
+            HostHeader::Set(set) => Self::Set { set },
+        }
+    }
+}
+
+/// Canonicalizes request URLs before routing, so routes and backends see
+/// one spelling of each path and `/%2e%2e/` can't sneak past a prefix.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NormalizeConfig {
+    /// Decode escaped letters, digits and `-._~` (`%41` is `A`); other
+    /// escapes get upper-case hex.
+    #[serde(default = "default_true")]
+    pub decode_unreserved: bool,
+    /// Resolve `.` and `..` segments: `/a/./b/../c` becomes `/a/c`.
+    #[serde(default = "default_true")]
+    pub resolve_dot_segments: bool,
+    /// Collapse runs of slashes: `/a//b` becomes `/a/b`.
+    #[serde(default)]
+    pub merge_slashes: bool,
+    #[serde(default)]
+    pub lowercase_path: bool,
+    #[serde(default)]
+    pub lowercase_host: bool,
+}
+
+impl Default for NormalizeConfig {
+    fn default() -> Self {
+        Self {
+            decode_unreserved: true,
+            resolve_dot_segments: true,
+            merge_slashes: false,
+            lowercase_path: false,
+            lowercase_host: false,
+        }
+    }
+}
+
+/// Sends requests matching `host` and `path_prefix` to `pool`, or splits
+/// them between the pools in `split`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RouteConfig {
+    pub name: String,
+    /// Matches any host when unset. Compared case-insensitively, without port.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default = "default_path_prefix")]
+    pub path_prefix: String,
+    /// Request attributes set by classifiers, e.g. `country: [DE, AT]`;
+    /// each must have one of the listed values.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attributes: HashMap<String, Vec<String>>,
+    /// Methods the route accepts, e.g. `[GET, POST]`; any when empty.
+    /// Matching requests with others get a 405 instead of trying later
+    /// routes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<String>,
+    /// Target pool; defaults to `default` when `split` is empty too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    /// Share of traffic per pool, e.g. 95 to `stable` and 5 to `canary`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub split: Vec<SplitTargetConfig>,
+    /// Pick the split side from a hash of this header (e.g. a user ID) so
+    /// a client stays on one side. Random per request when the header is
+    /// unset or missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split_hash_header: Option<String>,
+    /// Bucket clients into the variants of an A/B experiment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub experiment: Option<ExperimentConfig>,
+    /// Checked before `pool` and `split`; the first matching rule picks the
+    /// pool, e.g. to let QA reach the canary with `x-canary: true`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overrides: Vec<PoolOverrideConfig>,
+    /// Used instead of the assigned pool while it has no healthy backends,
+    /// such as a static status page, rather than answering 503.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_pool: Option<String>,
+    /// Host header sent upstream, unless the backend sets its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_header: Option<HostHeader>,
+    /// Answer with a redirect instead of proxying.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect: Option<RedirectConfig>,
+    /// Answer with a fixed response instead of proxying.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direct_response: Option<DirectResponseConfig>,
+    /// Serve files from a local directory instead of proxying.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub static_files: Option<StaticFilesConfig>,
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    /// Answer with the `maintenance` response instead of routing.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub maintenance: bool,
+    /// `Link` values added to the route's responses, such as
+    /// `</app.css>; rel=preload; as=style`, for CDNs and browsers that
+    /// turn them into early hints. The load balancer itself can't send
+    /// `103 Early Hints`, nor relay the ones backends send.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preload_links: Vec<String>,
+    /// Filters for this route only (all but `metrics` and `request_id`),
+    /// run after the global ones on the way in.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<FilterConfig>,
+    /// Which `auth_strategies` requests must pass, checked before the
+    /// route's `filters`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<RouteAuthConfig>,
+    /// Replace the top-level `error_pages` for these statuses.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub error_pages: HashMap<u16, ErrorPageConfig>,
+    /// Replaces the top-level `error_format`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_format: Option<ErrorFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validate_response: Option<ResponseValidationConfig>,
+}
+
+/// Checks on the responses a route's backends send, for catching ones
+/// that answer API clients with an HTML error page.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ResponseValidationConfig {
+    /// Headers every response must have.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_headers: Vec<String>,
+    /// Responses with a higher status fail.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_status: Option<u16>,
+    /// Media types a response with a body may have, such as
+    /// `application/json`, or `text/*` for any subtype.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content_types: Vec<String>,
+    #[serde(default)]
+    pub action: ValidationAction,
+}
+
+/// What happens to a response that fails validation; it's logged and
+/// counted in every case.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationAction {
+    /// Pass it on.
+    #[default]
+    Log,
+    /// Pass it on, but count it against the backend's circuit breaker.
+    Penalize,
+    /// Count it as a failed attempt, retried like one, and answer 502 if
+    /// none is left.
+    Reject,
+}
+
+impl ResponseValidationConfig {
+    fn validate(&self, route: &str) -> Result<()> {
+        for name in &self.required_headers {
+            if hyper::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                bail!("Route {} validate_response has an invalid header name: {:?}", route, name);
+            }
+        }
+        if let Some(status) = self.max_status {
+            if !(100..=599).contains(&status) {
+                bail!("Route {} validate_response max_status must be between 100 and 599", route);
+            }
+        }
+        for content_type in &self.content_types {
+            if !content_type.contains('/') {
+                bail!("Route {} validate_response content type must be type/subtype: {:?}", route, content_type);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What errors the load balancer answers itself look like.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorFormat {
+    /// A line of plain text.
+    #[default]
+    Text,
+    /// RFC 7807 `application/problem+json`.
+    Json,
+    Html,
+    /// Whichever of the three the client's `Accept` prefers, so API
+    /// clients get JSON and browsers HTML.
+    Negotiate,
+}
+
+/// What's sent instead of the built-in plain text for an error status.
+/// `body` may use `{status}`, `{reason}`, `{message}`, `{error_code}`,
+/// `{request_id}` and `{route}`, escaped to suit `content_type` when it's
+/// JSON or HTML.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ErrorPageConfig {
+    #[serde(default = "default_error_page_content_type")]
+    pub content_type: String,
+    pub body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+fn default_error_page_content_type() -> String { "text/plain; charset=utf-8".to_string() }
+
+fn validate_error_pages(owner: &str, pages: &HashMap<u16, ErrorPageConfig>) -> Result<()> {
+    for (status, page) in pages {
+        if !(400..=599).contains(status) {
+            bail!("{} error_pages status must be between 400 and 599, got {}", owner, status);
+        }
+        if hyper::header::HeaderValue::from_str(&page.content_type).is_err() {
+            bail!("{} error_pages {} has an invalid content_type", owner, status);
+        }
+    }
+    Ok(())
+}
+
+/// Keys under `prefix`: `backends/<name>` and `routes/<name>` hold a
+/// backend or route definition (JSON or YAML); `instances/<id>` is where
+/// each load balancer reports its status.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EtcdConfig {
+    /// etcd's HTTP/JSON gateway.
+    #[serde(default = "default_etcd_endpoint")]
+    pub endpoint: Url,
+    #[serde(default = "default_etcd_prefix")]
+    pub prefix: String,
+    /// Name of this instance's status key; defaults to `$HOSTNAME`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<String>,
+    #[serde(default = "default_etcd_status_interval")]
+    pub status_interval_secs: u64,
+    #[serde(default = "default_discovery_drain_timeout")]
+    pub drain_timeout_secs: u64,
+}
+
+impl EtcdConfig {
+    pub fn status_interval(&self) -> Duration {
+        Duration::from_secs(self.status_interval_secs)
+    }
+    
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
+}
+
+fn default_etcd_endpoint() -> Url { Url::parse("http://127.0.0.1:2379").unwrap() }
+fn default_etcd_prefix() -> String { "/rust-load-balancer/".to_string() }
+fn default_etcd_status_interval() -> u64 { 10 }
+
+/// Instances tell each other when a backend's circuit opens or its health
+/// check fails, so the others take it out of rotation straight away
+/// instead of waiting to see the failures themselves.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClusterConfig {
+    /// Where peers send their events.
+    #[serde(default = "default_cluster_listen")]
+    pub listen: SocketAddr,
+    /// Base URLs of the other instances' cluster listeners.
+    pub peers: Vec<Url>,
+    /// Shared secret peers must send as a bearer token. Only optional when
+    /// `listen` is a loopback address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// How this instance names itself to peers; defaults to `$HOSTNAME`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<String>,
+}
+
+fn default_cluster_listen() -> SocketAddr { ([0, 0, 0, 0], 7946).into() }
+
+/// Backends in `local` (or with no `region`) get the traffic. When some of
+/// a pool's local backends are out of rotation, up to that share of its
+/// requests goes to remote regions instead, nearest (lowest penalty) first
+/// and each capped at its `spillover_percent`. Backends in regions not
+/// listed here get none.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RegionsConfig {
+    /// The region this instance runs in.
+    pub local: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub remote: HashMap<String, RemoteRegionConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RemoteRegionConfig {
+    /// Most of a pool's requests sent here, whatever the local losses; 100
+    /// fails over completely.
+    #[serde(default)]
+    pub spillover_percent: u8,
+    /// Extra latency of going here. Requests with less than this left of
+    /// their latency budget stay local.
+    #[serde(default)]
+    pub latency_penalty_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GeoIpConfig {
+    /// MaxMind GeoIP2 or GeoLite2 Country/City database (`.mmdb`).
+    pub database: PathBuf,
+}
+
+/// Per-request behaviour. Unset fields fall back to the top-level `policy`,
+/// then to the built-in default.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PolicyConfig {
+    /// Time a backend has to send response headers, per attempt. Unlimited
+    /// by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_timeout_secs: Option<u64>,
+    /// Time from a request's arrival to its response headers, across every
+    /// attempt; past it the attempt in flight is cancelled with a 504 and
+    /// not retried. Unlimited by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_budget_ms: Option<u64>,
+    /// Buffer request bodies so failed attempts can be retried (the
+    /// default). Unbuffered bodies are streamed with a single attempt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer_request_body: Option<bool>,
+    /// Reject request bodies larger than this with 413.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_request_body_bytes: Option<u64>,
+    /// Gzip text-like responses for clients that accept it. Off by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<bool>,
+    /// Entry of `retry_policies` to use instead of the top-level `retry`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_policy: Option<String>,
+    /// Serve GET and HEAD responses from the shared response cache. Off by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<bool>,
+    /// From 0, shed first under overload, to 9; 5 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+}
+
+impl PolicyConfig {
+    /// This policy with unset fields taken from `defaults`.
+    pub fn or(&self, defaults: &PolicyConfig) -> PolicyConfig {
+        PolicyConfig {
+            upstream_timeout_secs: self.upstream_timeout_secs.or(defaults.upstream_timeout_secs),
+            latency_budget_ms: self.latency_budget_ms.or(defaults.latency_budget_ms),
+            buffer_request_body: self.buffer_request_body.or(defaults.buffer_request_body),
+            max_request_body_bytes: self.max_request_body_bytes.or(defaults.max_request_body_bytes),
+            compression: self.compression.or(defaults.compression),
+            retry_policy: self.retry_policy.clone().or_else(|| defaults.retry_policy.clone()),
+            cache: self.cache.or(defaults.cache),
+            priority: self.priority.or(defaults.priority),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RedirectConfig {
+    /// 301, 302, 303, 307 or 308.
+    #[serde(default = "default_redirect_status")]
+    pub status: u16,
+    /// Target URL; `{host}` (without port), `{path}`, `{query}` and
+    /// `{path_and_query}` are filled in from the request, e.g.
+    /// `https://{host}{path_and_query}`.
+    pub location: String,
+}
+
+/// Placeholders allowed in [`RedirectConfig::location`].
+pub const REDIRECT_PLACEHOLDERS: &[&str] = &["host", "path", "query", "path_and_query"];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DirectResponseConfig {
+    #[serde(default = "default_direct_response_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default = "default_direct_response_content_type")]
+    pub content_type: String,
+}
+
+/// Serves `root` at the route's `path_prefix`: with prefix `/assets`,
+/// `/assets/app.js` is read from `<root>/app.js`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StaticFilesConfig {
+    pub root: PathBuf,
+    /// Files tried, in order, for requests naming a directory.
+    #[serde(default = "default_index_files")]
+    pub index: Vec<String>,
+}
+
+fn default_index_files() -> Vec<String> { vec!["index.html".to_string()] }
+fn default_redirect_status() -> u16 { 302 }
+fn default_direct_response_status() -> u16 { 200 }
+fn default_direct_response_content_type() -> String { "text/plain; charset=utf-8".to_string() }
+
+/// Sends requests carrying `header` or `cookie` to `pool`. Set exactly one
+/// of the two.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PoolOverrideConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cookie: Option<String>,
+    /// Required value, compared exactly; any value matches when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    pub pool: String,
+}
+
+/// Deterministically assigns each client to a variant, and each variant to
+/// a pool. The variant is echoed in `response_header` and counted in
+/// `lb_experiment_requests_total`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExperimentConfig {
+    pub name: String,
+    /// Mixed into the hash so experiments bucket independently; the name
+    /// when unset. Changing it reshuffles every client.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+    /// Client key sources, tried in order: `header:<name>`, `cookie:<name>`
+    /// or `ip`. Clients with none of them get a random variant per request.
+    pub bucket_by: Vec<String>,
+    pub variants: Vec<VariantConfig>,
+    #[serde(default = "default_variant_header")]
+    pub response_header: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct VariantConfig {
+    pub name: String,
+    pub pool: String,
+    pub weight: u32,
+}
+
+fn default_variant_header() -> String { "x-experiment-variant".to_string() }
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SplitTargetConfig {
+    pub pool: String,
+    /// Relative to the other targets' weights; 0 stops traffic to the pool.
+    pub weight: u32,
+}
+
+fn default_path_prefix() -> String { "/".to_string() }
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HealthCheckConfig {
+    #[serde(default = "default_health_interval")]
     pub interval_secs: u64,
     #[serde(default = "default_health_timeout")]
     pub timeout_secs: u64,
@@ -111,6 +1858,125 @@ pub struct HealthCheckConfig {
     pub healthy_threshold: u32,
     #[serde(default = "default_health_path")]
     pub path: String,
+    /// A shorter interval for backends whose last check failed, so they're
+    /// back in rotation sooner; healthy ones stay at `interval_secs`, which
+    /// must be a multiple of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unhealthy_interval_secs: Option<u64>,
+    /// Requests run in order once `path` passes, e.g. a login and then a
+    /// page behind it; all must pass, within `timeout_secs`, for the backend
+    /// to count as healthy. Cookies set by one step are sent with the next.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<HealthCheckStep>,
+    /// Requests sent to a backend once it passes after failing (or on its
+    /// first check), before it's put in rotation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warmup: Option<WarmupConfig>,
+    /// Settings for the backends of a pool, by pool name, over these ones.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pools: HashMap<String, PoolHealthCheckConfig>,
+}
+
+/// Priming requests, so caches and JITs are warm before real traffic.
+/// Their responses are ignored, and the backend goes into rotation once
+/// they're done or `timeout_secs` passes.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WarmupConfig {
+    /// Requested in order with GET.
+    pub paths: Vec<String>,
+    /// Times each path is requested.
+    #[serde(default = "default_warmup_repeat")]
+    pub repeat: u32,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    #[serde(default = "default_warmup_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_warmup_repeat() -> u32 { 1 }
+fn default_warmup_timeout() -> u64 { 30 }
+
+impl WarmupConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+    
+    fn validate(&self, owner: &str) -> Result<()> {
+        if self.paths.is_empty() || self.repeat == 0 || self.timeout_secs == 0 {
+            bail!("{} warmup needs paths, and repeat and timeout_secs greater than 0", owner);
+        }
+        if let Some(path) = self.paths.iter().find(|path| !path.starts_with('/')) {
+            bail!("{} warmup path must start with '/': {:?}", owner, path);
+        }
+        for (name, value) in &self.headers {
+            if hyper::header::HeaderName::from_bytes(name.as_bytes()).is_err()
+                || hyper::header::HeaderValue::from_str(value).is_err()
+            {
+                bail!("{} warmup has invalid header: {:?}", owner, name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a pool's backends check differently; the rest comes from
+/// `health_check`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PoolHealthCheckConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unhealthy_threshold: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub healthy_threshold: Option<u32>,
+    /// Replaces the top-level `steps`; `[]` runs none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steps: Option<Vec<HealthCheckStep>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warmup: Option<WarmupConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HealthCheckStep {
+    #[serde(default = "default_step_method")]
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Any 2xx when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect_status: Option<u16>,
+    /// Text the response body must contain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect_body: Option<String>,
+}
+
+fn default_step_method() -> String { "GET".to_string() }
+
+impl HealthCheckStep {
+    fn validate(&self, owner: &str) -> Result<()> {
+        if hyper::Method::from_bytes(self.method.as_bytes()).is_err() {
+            bail!("{} has invalid method: {:?}", owner, self.method);
+        }
+        if !self.path.starts_with('/') {
+            bail!("{} path must start with '/': {:?}", owner, self.path);
+        }
+        for (name, value) in &self.headers {
+            if hyper::header::HeaderName::from_bytes(name.as_bytes()).is_err()
+                || hyper::header::HeaderValue::from_str(value).is_err()
+            {
+                bail!("{} has invalid header: {:?}", owner, name);
+            }
+        }
+        if self.expect_status.is_some_and(|status| !(100..=599).contains(&status)) {
+            bail!("{} has invalid expect_status: {:?}", owner, self.expect_status);
+        }
+        Ok(())
+    }
 }
 
 fn default_health_interval() -> u64 { 10 }
@@ -127,6 +1993,30 @@ impl HealthCheckConfig {
     pub fn timeout(&self) -> Duration {
         Duration::from_secs(self.timeout_secs)
     }
+    
+    /// How often checks run: every unhealthy interval, or else every interval.
+    pub fn tick(&self) -> Duration {
+        Duration::from_secs(self.unhealthy_interval_secs.unwrap_or(self.interval_secs))
+    }
+    
+    /// Ticks between checks of healthy backends.
+    pub fn healthy_every(&self) -> u64 {
+        self.unhealthy_interval_secs.map_or(1, |fast| self.interval_secs / fast)
+    }
+    
+    /// These settings with `pool`'s in place of them, and no `pools`.
+    pub fn for_pool(&self, pool: &PoolHealthCheckConfig) -> HealthCheckConfig {
+        HealthCheckConfig {
+            path: pool.path.clone().unwrap_or_else(|| self.path.clone()),
+            timeout_secs: pool.timeout_secs.unwrap_or(self.timeout_secs),
+            unhealthy_threshold: pool.unhealthy_threshold.unwrap_or(self.unhealthy_threshold),
+            healthy_threshold: pool.healthy_threshold.unwrap_or(self.healthy_threshold),
+            steps: pool.steps.clone().unwrap_or_else(|| self.steps.clone()),
+            warmup: pool.warmup.clone().or_else(|| self.warmup.clone()),
+            pools: HashMap::new(),
+            ..self.clone()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -181,8 +2071,1352 @@ pub struct MetricsConfig {
     pub port: u16,
     #[serde(default = "default_metrics_path")]
     pub path: String,
+    /// Address to listen on; every interface by default.
+    #[serde(default = "default_metrics_bind")]
+    pub bind: IpAddr,
+    #[serde(default)]
+    pub access: AccessConfig,
+    /// Serve HTTPS instead of plain HTTP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// Attach the trace ID of requests with a `traceparent` header to the
+    /// `lb_request_duration_seconds` bucket they land in. Exemplars are only
+    /// served to scrapers asking for OpenMetrics.
+    #[serde(default)]
+    pub exemplars: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slo: Option<SloConfig>,
 }
 
 fn default_metrics_enabled() -> bool { true }
 fn default_metrics_port() -> u16 { 9090 }
-fn default_metrics_path() -> String { "/metrics".to_string() }
\ No newline at end of file
+fn default_metrics_path() -> String { "/metrics".to_string() }
+fn default_metrics_bind() -> IpAddr { [0, 0, 0, 0].into() }
+
+/// A certificate for a server terminating TLS.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain and PKCS#8 private key.
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Who may use the metrics or admin server. With both `basic_auth` and
+/// `bearer_token` set, either is accepted; with neither, no credentials are
+/// asked for.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AccessConfig {
+    /// Clients that may connect; everyone when empty.
+    #[serde(default, skip_serializing_if = "IpList::is_empty")]
+    pub allow: IpList,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub basic_auth: Option<BasicAuthConfig>,
+    /// Accepted as `Authorization: Bearer <token>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bearer_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BasicAuthConfig {
+    pub username: String,
+    pub password: String,
+}
+
+impl AccessConfig {
+    pub fn requires_credentials(&self) -> bool {
+        self.basic_auth.is_some() || self.bearer_token.is_some()
+    }
+
+    fn validate(&self, server: &str) -> Result<()> {
+        if self.bearer_token.as_deref() == Some("") {
+            bail!("{} bearer_token must not be empty", server);
+        }
+        if let Some(basic) = &self.basic_auth {
+            if basic.username.is_empty() || basic.username.contains(':') {
+                bail!("{} basic_auth username must be non-empty and without ':'", server);
+            }
+            if basic.password.is_empty() {
+                bail!("{} basic_auth password must not be empty", server);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SloConfig {
+    /// Fraction of requests that must be good, e.g. 0.999.
+    #[serde(default = "default_slo_target")]
+    pub target: f64,
+    /// Requests slower than this count against the SLO.
+    #[serde(default = "default_slo_latency_threshold_ms")]
+    pub latency_threshold_ms: u64,
+}
+
+fn default_slo_target() -> f64 { 0.999 }
+fn default_slo_latency_threshold_ms() -> u64 { 500 }
+
+impl SloConfig {
+    pub fn latency_threshold(&self) -> Duration {
+        Duration::from_millis(self.latency_threshold_ms)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RequestIdConfig {
+    /// Incoming headers trusted to carry a request ID, checked in order.
+    #[serde(default = "default_trusted_request_id_headers")]
+    pub trusted_headers: Vec<String>,
+    /// Header used to forward the ID upstream and echo it to the client.
+    #[serde(default = "default_request_id_header")]
+    pub header: String,
+    #[serde(default = "default_echo_request_id")]
+    pub echo_to_client: bool,
+}
+
+impl Default for RequestIdConfig {
+    fn default() -> Self {
+        Self {
+            trusted_headers: default_trusted_request_id_headers(),
+            header: default_request_id_header(),
+            echo_to_client: default_echo_request_id(),
+        }
+    }
+}
+
+/// Highest request priority.
+pub const MAX_PRIORITY: u8 = 9;
+
+/// Load shedding. Pressure is the highest of the measured values over
+/// their limits, so 1.0 means a limit was reached. Past that, requests
+/// below `protected_priority` are rejected with 503 at random, priority 0
+/// most often, until at `shed_all_at` every one of priority 0 is.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdmissionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// How late the runtime runs a timer, smoothed.
+    #[serde(default = "default_max_event_loop_lag_ms")]
+    pub max_event_loop_lag_ms: u64,
+    /// Resident memory of the process; only measured on Linux.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory_bytes: Option<u64>,
+    /// Requests being routed or proxied at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_in_flight: Option<usize>,
+    /// A header whose value (0 to 9) replaces the route's priority, for
+    /// a trusted gateway in front to set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority_header: Option<String>,
+    #[serde(default = "default_protected_priority")]
+    pub protected_priority: u8,
+    #[serde(default = "default_shed_all_at")]
+    pub shed_all_at: f64,
+}
+
+fn default_max_event_loop_lag_ms() -> u64 { 50 }
+fn default_protected_priority() -> u8 { MAX_PRIORITY }
+fn default_shed_all_at() -> f64 { 1.5 }
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_event_loop_lag_ms: default_max_event_loop_lag_ms(),
+            max_memory_bytes: None,
+            max_in_flight: None,
+            priority_header: None,
+            protected_priority: default_protected_priority(),
+            shed_all_at: default_shed_all_at(),
+        }
+    }
+}
+
+impl AdmissionConfig {
+    fn validate(&self) -> Result<()> {
+        if self.max_event_loop_lag_ms == 0 || self.max_memory_bytes == Some(0) || self.max_in_flight == Some(0) {
+            bail!("admission limits must be greater than 0");
+        }
+        if !(1..=MAX_PRIORITY).contains(&self.protected_priority) {
+            bail!("admission protected_priority must be between 1 and {}", MAX_PRIORITY);
+        }
+        if self.shed_all_at.is_nan() || self.shed_all_at <= 1.0 {
+            bail!("admission shed_all_at must be greater than 1.0, got {}", self.shed_all_at);
+        }
+        if let Some(header) = &self.priority_header {
+            if hyper::header::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                bail!("Invalid admission priority_header: {:?}", header);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Per-client concurrency. Each client may have `max_share` of `slots`
+/// requests upstream at once (at least one); more wait up to
+/// `queue_timeout_ms` for one of its own to finish, then get 429.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FairnessConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub key: FairnessKey,
+    #[serde(default = "default_fairness_slots")]
+    pub slots: usize,
+    #[serde(default = "default_fairness_max_share")]
+    pub max_share: f64,
+    /// 0 rejects a client's excess requests straight away.
+    #[serde(default = "default_fairness_queue_timeout")]
+    pub queue_timeout_ms: u64,
+    #[serde(default = "default_fairness_max_queued")]
+    pub max_queued_per_client: usize,
+}
+
+fn default_fairness_slots() -> usize { 1000 }
+fn default_fairness_max_share() -> f64 { 0.1 }
+fn default_fairness_queue_timeout() -> u64 { 1000 }
+fn default_fairness_max_queued() -> usize { 100 }
+
+impl Default for FairnessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key: FairnessKey::default(),
+            slots: default_fairness_slots(),
+            max_share: default_fairness_max_share(),
+            queue_timeout_ms: default_fairness_queue_timeout(),
+            max_queued_per_client: default_fairness_max_queued(),
+        }
+    }
+}
+
+impl FairnessConfig {
+    /// Requests one client may have upstream at once.
+    pub fn per_client(&self) -> usize {
+        ((self.slots as f64 * self.max_share) as usize).max(1)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.slots == 0 {
+            bail!("fairness slots must be greater than 0");
+        }
+        if !(self.max_share > 0.0 && self.max_share <= 1.0) {
+            bail!("fairness max_share must be above 0 and at most 1, got {}", self.max_share);
+        }
+        if let FairnessKey::Header(header) = &self.key {
+            if hyper::header::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                bail!("Invalid fairness key header: {:?}", header);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Response bytes per second for each client (or connection), with
+/// `tiers` picked by a request header, e.g. one that forward auth sets.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DownloadLimitConfig {
+    #[serde(default)]
+    pub per: DownloadLimitScope,
+    /// How clients are told apart with `per: client`.
+    #[serde(default)]
+    pub key: FairnessKey,
+    /// The limit for requests in no tier.
+    #[serde(flatten)]
+    pub limit: BandwidthConfig,
+    /// Request header naming the tier.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier_header: Option<String>,
+    /// Limits by tier, `null` for none.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tiers: HashMap<String, Option<BandwidthConfig>>,
+}
+
+impl DownloadLimitConfig {
+    fn validate(&self) -> Result<()> {
+        self.limit.validate("download_limit")?;
+        for (tier, limit) in &self.tiers {
+            if let Some(limit) = limit {
+                limit.validate(&format!("download_limit tier {}", tier))?;
+            }
+        }
+        let headers = self.tier_header.iter().chain(match &self.key {
+            FairnessKey::Header(header) => Some(header),
+            FairnessKey::ClientIp => None,
+        });
+        for header in headers {
+            if hyper::header::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                bail!("Invalid download_limit header: {:?}", header);
+            }
+        }
+        if !self.tiers.is_empty() && self.tier_header.is_none() {
+            bail!("download_limit tiers need a tier_header");
+        }
+        Ok(())
+    }
+}
+
+/// What shares a download limit: everything one `client` has in flight,
+/// or each `connection`'s responses.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadLimitScope {
+    #[default]
+    Client,
+    Connection,
+}
+
+/// Who a request counts against: `client_ip` (the default), the
+/// connection's IP rather than `X-Forwarded-For`'s, or `{ header: <name> }`,
+/// such as an API key. Requests without the header count against their IP.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(from = "FairnessKeyRepr", into = "FairnessKeyRepr")]
+pub enum FairnessKey {
+    #[default]
+    ClientIp,
+    Header(String),
+}
+
+// Same detour as `HostHeaderRepr`
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum FairnessKeyRepr {
+    ClientIp(FairnessKeyName),
+    Header { header: String },
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum FairnessKeyName {
+    ClientIp,
+}
+
+impl From<FairnessKeyRepr> for FairnessKey {
+    fn from(repr: FairnessKeyRepr) -> Self {
+        match repr {
+            FairnessKeyRepr::ClientIp(FairnessKeyName::ClientIp) => Self::ClientIp,
+            FairnessKeyRepr::Header { header } => Self::Header(header),
+        }
+    }
+}
+
+impl From<FairnessKey> for FairnessKeyRepr {
+    fn from(key: FairnessKey) -> Self {
+        match key {
+            FairnessKey::ClientIp => Self::ClientIp(FairnessKeyName::ClientIp),
+            FairnessKey::Header(header) => Self::Header { header },
+        }
+    }
+}
+
+/// Backends drained while a window is open, then put back in rotation.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MaintenanceWindowConfig {
+    pub name: String,
+    /// When the window opens, in UTC; see `Cron`.
+    pub schedule: Cron,
+    pub duration_mins: u64,
+    /// Pools whose backends are drained.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pools: Vec<String>,
+    /// Backend IDs (`host:port`) drained.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backends: Vec<String>,
+}
+
+impl MaintenanceWindowConfig {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.duration_mins * 60)
+    }
+}
+
+/// What requests get while in maintenance.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MaintenanceConfig {
+    /// For every route; routes can also turn it on one at a time.
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_maintenance_status")]
+    pub status: u16,
+    #[serde(default = "default_maintenance_body")]
+    pub body: String,
+    /// A file served instead of `body`, typed by its extension.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+    /// Clients that are routed as usual, e.g. to smoke-test a release, by
+    /// socket address; `X-Forwarded-For` is ignored.
+    #[serde(default, skip_serializing_if = "IpList::is_empty")]
+    pub allow: IpList,
+}
+
+fn default_maintenance_status() -> u16 { 503 }
+fn default_maintenance_body() -> String { "Down for maintenance".to_string() }
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            status: default_maintenance_status(),
+            body: default_maintenance_body(),
+            page: None,
+            retry_after_secs: None,
+            allow: IpList::default(),
+        }
+    }
+}
+
+/// Client addresses and CIDR ranges, e.g. `[10.0.0.0/8, 192.0.2.7]`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct IpList(Vec<ipnet::IpNet>);
+
+impl IpList {
+    pub fn contains(&self, ip: std::net::IpAddr) -> bool {
+        // IPv4 clients on a dual-stack socket show up as ::ffff:a.b.c.d
+        let ip = ip.to_canonical();
+        self.0.iter().any(|net| net.contains(&ip))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl TryFrom<Vec<String>> for IpList {
+    type Error = String;
+
+    fn try_from(entries: Vec<String>) -> std::result::Result<Self, String> {
+        entries
+            .iter()
+            .map(|entry| {
+                entry
+                    .parse::<ipnet::IpNet>()
+                    .or_else(|_| entry.parse::<std::net::IpAddr>().map(ipnet::IpNet::from))
+                    .map_err(|_| format!("invalid IP address or CIDR range: {:?}", entry))
+            })
+            .collect::<std::result::Result<_, _>>()
+            .map(IpList)
+    }
+}
+
+impl From<IpList> for Vec<String> {
+    fn from(list: IpList) -> Self {
+        list.0.iter().map(|net| net.to_string()).collect()
+    }
+}
+
+/// The in-memory cache shared by every route with `policy.cache`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheConfig {
+    /// Bodies and headers kept in total; least recently used entries are
+    /// evicted past this.
+    #[serde(default = "default_cache_max_memory_bytes")]
+    pub max_memory_bytes: u64,
+    /// Larger responses are passed through without being cached.
+    #[serde(default = "default_cache_max_entry_bytes")]
+    pub max_entry_bytes: u64,
+    /// How long responses without `Cache-Control: max-age`, `s-maxage` or
+    /// `Expires` stay fresh. Such responses aren't cached when 0.
+    #[serde(default)]
+    pub default_ttl_secs: u64,
+    /// A second tier on local disk that survives restarts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk: Option<DiskCacheConfig>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_memory_bytes: default_cache_max_memory_bytes(),
+            max_entry_bytes: default_cache_max_entry_bytes(),
+            default_ttl_secs: 0,
+            disk: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiskCacheConfig {
+    /// Directory for cache files, created if missing. Use one per instance.
+    pub path: PathBuf,
+    /// Files kept in total before the least recently used are deleted.
+    #[serde(default = "default_disk_cache_max_bytes")]
+    pub max_bytes: u64,
+    /// Responses up to this size are cached on disk, even when they're too
+    /// large for memory.
+    #[serde(default = "default_disk_cache_max_entry_bytes")]
+    pub max_entry_bytes: u64,
+}
+
+fn default_disk_cache_max_bytes() -> u64 { 1024 * 1024 * 1024 }
+fn default_disk_cache_max_entry_bytes() -> u64 { 64 * 1024 * 1024 }
+
+fn default_cache_max_memory_bytes() -> u64 { 64 * 1024 * 1024 }
+fn default_cache_max_entry_bytes() -> u64 { 1024 * 1024 }
+
+fn default_trusted_request_id_headers() -> Vec<String> { vec!["x-request-id".to_string()] }
+fn default_request_id_header() -> String { "x-request-id".to_string() }
+fn default_echo_request_id() -> bool { true }
+
+/// A built-in entry of the filter chain: `metrics`, `request_id`, or one
+/// with settings such as `{ headers: { ... } }`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(from = "FilterRepr", into = "FilterRepr")]
+pub enum FilterConfig {
+    /// Request and response metrics, traffic stats and the completion log.
+    Metrics,
+    /// Forward the request ID upstream, and echo it if `echo_to_client`.
+    RequestId,
+    /// Set or remove fixed request and response headers.
+    Headers(HeaderFilterConfig),
+    /// Custom logic from a WebAssembly module. Needs the `wasm` feature.
+    Wasm(WasmFilterConfig),
+    /// Ask an external service whether each request may pass.
+    ForwardAuth(ForwardAuthConfig),
+    /// Sign requests sent upstream with an HMAC.
+    HmacSign(HmacSignConfig),
+    /// Baseline security headers on responses.
+    SecurityHeaders(SecurityHeadersConfig),
+    /// Replay the response to a request whose `Idempotency-Key` was seen.
+    Idempotency(IdempotencyConfig),
+    /// Timing and client headers upstream, and timings for the client.
+    DebugHeaders(DebugHeadersConfig),
+}
+
+impl FilterConfig {
+    fn validate(&self, owner: &str) -> Result<()> {
+        match self {
+            FilterConfig::Metrics | FilterConfig::RequestId => Ok(()),
+            FilterConfig::Headers(headers) => headers.validate(),
+            FilterConfig::Wasm(wasm) => {
+                if !cfg!(feature = "wasm") {
+                    bail!("{} uses a wasm filter but this build lacks the wasm feature", owner);
+                }
+                if !wasm.module.is_file() {
+                    bail!("{}: wasm module not found: {}", owner, wasm.module.display());
+                }
+                if wasm.fuel == 0 {
+                    bail!("{}: wasm fuel must be greater than 0", owner);
+                }
+                Ok(())
+            }
+            FilterConfig::ForwardAuth(auth) => auth.validate(owner),
+            FilterConfig::HmacSign(sign) => sign.validate(owner),
+            FilterConfig::SecurityHeaders(headers) => headers.validate(owner),
+            FilterConfig::Idempotency(idempotency) => idempotency.validate(owner),
+            FilterConfig::DebugHeaders(debug) => debug.validate(owner),
+        }
+    }
+}
+
+/// A WebAssembly filter. The module may export `on_request()` and
+/// `on_response(status)` and calls the host functions imported from `lb`
+/// to read and change headers or answer the request itself.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WasmFilterConfig {
+    /// A `.wasm` file, or `.wat` text.
+    pub module: PathBuf,
+    /// Handed to the module as-is through `lb.get_config`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub config: String,
+    /// Instructions (roughly) one call may run before it's stopped.
+    #[serde(default = "default_wasm_fuel")]
+    pub fuel: u64,
+    /// Let requests through when the module fails instead of answering 500.
+    #[serde(default)]
+    pub fail_open: bool,
+}
+
+fn default_wasm_fuel() -> u64 { 10_000_000 }
+
+/// Checks each request with an auth service, like nginx's `auth_request`
+/// in front of oauth2-proxy. The service gets a GET with the request's
+/// headers plus `X-Forwarded-Method`, `-Uri` and `-Host`. A 2xx lets the
+/// request through; anything else is sent back to the client as-is, or
+/// as a redirect to `signin_url` for a 401.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ForwardAuthConfig {
+    pub url: Url,
+    /// Request headers sent to the auth service; all of them if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_headers: Option<Vec<String>>,
+    /// Headers copied from a 2xx auth response onto the upstream request,
+    /// such as `X-Auth-Request-User`. Clients can't set these themselves.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub response_headers: Vec<String>,
+    /// Where to send clients the auth service rejects with 401. `{url}`
+    /// becomes the original URL, percent-encoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signin_url: Option<String>,
+    #[serde(default = "default_forward_auth_timeout_ms")]
+    pub timeout_ms: u64,
+    /// How long to remember that a set of credentials was let through for
+    /// a method, host and URI; 0 asks the auth service every time.
+    #[serde(default)]
+    pub cache_ttl_secs: u64,
+    /// Request headers that identify the caller, keying cached decisions.
+    #[serde(default = "default_forward_auth_cache_key")]
+    pub cache_key_headers: Vec<String>,
+    #[serde(default = "default_forward_auth_cache_entries")]
+    pub cache_max_entries: usize,
+}
+
+fn default_forward_auth_timeout_ms() -> u64 { 2000 }
+fn default_forward_auth_cache_key() -> Vec<String> { vec!["cookie".to_string(), "authorization".to_string()] }
+fn default_forward_auth_cache_entries() -> usize { 10_000 }
+
+impl ForwardAuthConfig {
+    fn validate(&self, owner: &str) -> Result<()> {
+        if !matches!(self.url.scheme(), "http" | "https") {
+            bail!("{}: forward_auth url must be http or https", owner);
+        }
+        let names = self.request_headers.iter().flatten()
+            .chain(&self.response_headers).chain(&self.cache_key_headers);
+        for name in names {
+            if hyper::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                bail!("{}: invalid header name in forward_auth: {:?}", owner, name);
+            }
+        }
+        if self.timeout_ms == 0 {
+            bail!("{}: forward_auth timeout_ms must be greater than 0", owner);
+        }
+        if self.cache_ttl_secs > 0 && self.cache_key_headers.is_empty() {
+            bail!("{}: forward_auth caching needs cache_key_headers", owner);
+        }
+        Ok(())
+    }
+}
+
+/// A way of authenticating requests, shared by the routes naming it.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(from = "AuthStrategyRepr", into = "AuthStrategyRepr")]
+pub enum AuthStrategyConfig {
+    /// Ask an external service, as the `forward_auth` filter does. A 401
+    /// from it counts as no credentials.
+    ForwardAuth(ForwardAuthConfig),
+    /// One of a fixed list of keys in a request header.
+    ApiKey(ApiKeyConfig),
+}
+
+impl AuthStrategyConfig {
+    fn validate(&self, owner: &str) -> Result<()> {
+        match self {
+            AuthStrategyConfig::ForwardAuth(auth) => auth.validate(owner),
+            AuthStrategyConfig::ApiKey(api_key) => {
+                if hyper::header::HeaderName::from_bytes(api_key.header.as_bytes()).is_err() {
+                    bail!("{}: invalid api_key header name: {:?}", owner, api_key.header);
+                }
+                if api_key.keys.iter().all(String::is_empty) {
+                    bail!("{}: api_key needs at least one key", owner);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ApiKeyConfig {
+    #[serde(default = "default_api_key_header")]
+    pub header: String,
+    pub keys: Vec<String>,
+}
+
+fn default_api_key_header() -> String { "x-api-key".to_string() }
+
+/// Which of the `auth_strategies` a route's requests must pass.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RouteAuthConfig {
+    pub strategies: Vec<String>,
+    #[serde(default)]
+    pub mode: AuthMode,
+    /// Let requests without credentials for any of the strategies through
+    /// unauthenticated, e.g. for pages that show signed-in users more.
+    /// Credentials a strategy rejects still fail.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_anonymous: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    /// The first strategy, in order, to accept a request lets it through.
+    #[default]
+    AnyOf,
+    /// Every strategy must accept it.
+    AllOf,
+}
+
+/// Adds an HMAC-SHA256 of the request to each attempt sent upstream, so
+/// backends can tell it came through the load balancer. The signed string
+/// is the `components`, in order, each on its own line: the method, the
+/// path and query as sent, the `Date` header (set just before sending),
+/// and the hex SHA-256 of the body. The signature goes in `header` as
+/// lowercase hex.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct HmacSignConfig {
+    pub key: String,
+    #[serde(default = "default_hmac_header")]
+    pub header: String,
+    #[serde(default = "default_hmac_components")]
+    pub components: Vec<SignedComponent>,
+    /// Signing the body buffers it; larger ones are answered with 413.
+    #[serde(default = "default_hmac_max_body_bytes")]
+    pub max_body_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignedComponent {
+    Method,
+    Path,
+    Date,
+    BodyHash,
+}
+
+fn default_hmac_header() -> String { "x-lb-signature".to_string() }
+fn default_hmac_components() -> Vec<SignedComponent> {
+    vec![SignedComponent::Method, SignedComponent::Path, SignedComponent::Date, SignedComponent::BodyHash]
+}
+fn default_hmac_max_body_bytes() -> u64 { 1024 * 1024 }
+
+impl HmacSignConfig {
+    fn validate(&self, owner: &str) -> Result<()> {
+        if self.key.is_empty() {
+            bail!("{}: hmac_sign key must not be empty", owner);
+        }
+        if hyper::header::HeaderName::from_bytes(self.header.as_bytes()).is_err() {
+            bail!("{}: invalid hmac_sign header name: {:?}", owner, self.header);
+        }
+        if self.components.is_empty() {
+            bail!("{}: hmac_sign needs at least one component", owner);
+        }
+        Ok(())
+    }
+}
+
+/// Response headers for apps that don't set their own; set a header to
+/// `null` to leave it out. `security_headers` on its own uses the defaults.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SecurityHeadersConfig {
+    #[serde(default = "default_hsts")]
+    pub strict_transport_security: Option<String>,
+    #[serde(default = "default_content_type_options")]
+    pub content_type_options: Option<String>,
+    #[serde(default = "default_frame_options")]
+    pub frame_options: Option<String>,
+    /// Off by default, since a policy has to fit the app.
+    #[serde(default)]
+    pub content_security_policy: Option<String>,
+    /// Replace headers the backend sent instead of keeping them.
+    #[serde(default)]
+    pub override_backend: bool,
+}
+
+fn default_hsts() -> Option<String> { Some("max-age=31536000; includeSubDomains".to_string()) }
+fn default_content_type_options() -> Option<String> { Some("nosniff".to_string()) }
+fn default_frame_options() -> Option<String> { Some("DENY".to_string()) }
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            strict_transport_security: default_hsts(),
+            content_type_options: default_content_type_options(),
+            frame_options: default_frame_options(),
+            content_security_policy: None,
+            override_backend: false,
+        }
+    }
+}
+
+impl SecurityHeadersConfig {
+    fn validate(&self, owner: &str) -> Result<()> {
+        let values = [
+            &self.strict_transport_security,
+            &self.content_type_options,
+            &self.frame_options,
+            &self.content_security_policy,
+        ];
+        for value in values.into_iter().flatten() {
+            if hyper::header::HeaderValue::from_str(value).is_err() {
+                bail!("{}: invalid header value in security_headers: {:?}", owner, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Remembers the response to each request with an `Idempotency-Key`, so
+/// a client retrying a POST gets the first attempt's answer instead of
+/// the backend doing the work twice. Keys are scoped to the method, path
+/// and `scope_headers`, so two callers can't read each other's responses.
+/// `idempotency` on its own uses the defaults.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct IdempotencyConfig {
+    #[serde(default = "default_idempotency_header")]
+    pub header: String,
+    #[serde(default = "default_idempotency_methods")]
+    pub methods: Vec<String>,
+    /// How long a response is replayed for.
+    #[serde(default = "default_idempotency_ttl")]
+    pub ttl_secs: u64,
+    /// Request headers that identify the caller.
+    #[serde(default = "default_idempotency_scope")]
+    pub scope_headers: Vec<String>,
+    /// Responses with larger bodies aren't remembered.
+    #[serde(default = "default_idempotency_max_body_bytes")]
+    pub max_body_bytes: u64,
+    #[serde(default = "default_idempotency_max_keys")]
+    pub max_keys: usize,
+    /// How long duplicates of a request still in flight are answered 409
+    /// before it's given up on.
+    #[serde(default = "default_idempotency_in_flight_timeout")]
+    pub in_flight_timeout_secs: u64,
+}
+
+fn default_idempotency_header() -> String { "idempotency-key".to_string() }
+fn default_idempotency_methods() -> Vec<String> { vec!["POST".to_string(), "PATCH".to_string()] }
+fn default_idempotency_ttl() -> u64 { 86_400 }
+fn default_idempotency_scope() -> Vec<String> { vec!["authorization".to_string()] }
+fn default_idempotency_max_body_bytes() -> u64 { 1024 * 1024 }
+fn default_idempotency_max_keys() -> usize { 10_000 }
+fn default_idempotency_in_flight_timeout() -> u64 { 60 }
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            header: default_idempotency_header(),
+            methods: default_idempotency_methods(),
+            ttl_secs: default_idempotency_ttl(),
+            scope_headers: default_idempotency_scope(),
+            max_body_bytes: default_idempotency_max_body_bytes(),
+            max_keys: default_idempotency_max_keys(),
+            in_flight_timeout_secs: default_idempotency_in_flight_timeout(),
+        }
+    }
+}
+
+impl IdempotencyConfig {
+    fn validate(&self, owner: &str) -> Result<()> {
+        for name in std::iter::once(&self.header).chain(&self.scope_headers) {
+            if hyper::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                bail!("{}: invalid header name in idempotency: {:?}", owner, name);
+            }
+        }
+        for method in &self.methods {
+            if hyper::Method::from_bytes(method.as_bytes()).is_err() {
+                bail!("{}: invalid method in idempotency: {:?}", owner, method);
+            }
+        }
+        if self.ttl_secs == 0 || self.max_keys == 0 || self.in_flight_timeout_secs == 0 {
+            bail!("{}: idempotency ttl_secs, max_keys and in_flight_timeout_secs must be greater than 0", owner);
+        }
+        Ok(())
+    }
+}
+
+/// Headers for tracing requests across teams: the time the load balancer
+/// received a request, which attempt this is and the client's address go
+/// upstream; with `echo` set, the client gets a `Server-Timing` breakdown
+/// of where the time went.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DebugHeadersConfig {
+    /// Add `x-lb-received-at`, `x-lb-attempt` and `x-lb-client-ip` to
+    /// requests sent upstream.
+    #[serde(default = "default_true")]
+    pub upstream: bool,
+    #[serde(default)]
+    pub echo: DebugEcho,
+    /// The request header that turns the echo on with `echo: on_request`.
+    #[serde(default = "default_debug_echo_header")]
+    pub echo_header: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DebugEcho {
+    #[default]
+    Never,
+    /// For requests that send `echo_header`.
+    OnRequest,
+    Always,
+}
+
+fn default_debug_echo_header() -> String { "x-lb-debug".to_string() }
+
+impl Default for DebugHeadersConfig {
+    fn default() -> Self {
+        Self {
+            upstream: true,
+            echo: DebugEcho::default(),
+            echo_header: default_debug_echo_header(),
+        }
+    }
+}
+
+impl DebugHeadersConfig {
+    fn validate(&self, owner: &str) -> Result<()> {
+        if hyper::header::HeaderName::from_bytes(self.echo_header.as_bytes()).is_err() {
+            bail!("{}: invalid debug_headers echo_header: {:?}", owner, self.echo_header);
+        }
+        Ok(())
+    }
+}
+
+// Same detour as `HostHeaderRepr`, for `{ headers: ... }`
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum FilterRepr {
+    Name(FilterName),
+    Headers { headers: HeaderFilterConfig },
+    Wasm { wasm: WasmFilterConfig },
+    ForwardAuth { forward_auth: ForwardAuthConfig },
+    HmacSign { hmac_sign: HmacSignConfig },
+    SecurityHeaders { security_headers: SecurityHeadersConfig },
+    Idempotency { idempotency: IdempotencyConfig },
+    DebugHeaders { debug_headers: DebugHeadersConfig },
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum FilterName {
+    Metrics,
+    RequestId,
+    SecurityHeaders,
+    Idempotency,
+    DebugHeaders,
+}
+
+impl From<FilterRepr> for FilterConfig {
+    fn from(repr: FilterRepr) -> Self {
+        match repr {
+            FilterRepr::Name(FilterName::Metrics) => Self::Metrics,
+            FilterRepr::Name(FilterName::RequestId) => Self::RequestId,
+            FilterRepr::Name(FilterName::SecurityHeaders) => Self::SecurityHeaders(Default::default()),
+            FilterRepr::Name(FilterName::Idempotency) => Self::Idempotency(Default::default()),
+            FilterRepr::Name(FilterName::DebugHeaders) => Self::DebugHeaders(Default::default()),
+            FilterRepr::Headers { headers } => Self::Headers(headers),
+            FilterRepr::Wasm { wasm } => Self::Wasm(wasm),
+            FilterRepr::ForwardAuth { forward_auth } => Self::ForwardAuth(forward_auth),
+            FilterRepr::HmacSign { hmac_sign } => Self::HmacSign(hmac_sign),
+            FilterRepr::SecurityHeaders { security_headers } => Self::SecurityHeaders(security_headers),
+            FilterRepr::Idempotency { idempotency } => Self::Idempotency(idempotency),
+            FilterRepr::DebugHeaders { debug_headers } => Self::DebugHeaders(debug_headers),
+        }
+    }
+}
+
+impl From<FilterConfig> for FilterRepr {
+    fn from(filter: FilterConfig) -> Self {
+        match filter {
+            FilterConfig::Metrics => Self::Name(FilterName::Metrics),
+            FilterConfig::RequestId => Self::Name(FilterName::RequestId),
+            FilterConfig::Headers(headers) => Self::Headers { headers },
+            FilterConfig::Wasm(wasm) => Self::Wasm { wasm },
+            FilterConfig::ForwardAuth(forward_auth) => Self::ForwardAuth { forward_auth },
+            FilterConfig::HmacSign(hmac_sign) => Self::HmacSign { hmac_sign },
+            FilterConfig::SecurityHeaders(security_headers) => Self::SecurityHeaders { security_headers },
+            FilterConfig::Idempotency(idempotency) => Self::Idempotency { idempotency },
+            FilterConfig::DebugHeaders(debug_headers) => Self::DebugHeaders { debug_headers },
+        }
+    }
+}
+
+// Same detour as `FilterRepr`, for `{ api_key: ... }`
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum AuthStrategyRepr {
+    ForwardAuth { forward_auth: ForwardAuthConfig },
+    ApiKey { api_key: ApiKeyConfig },
+}
+
+impl From<AuthStrategyRepr> for AuthStrategyConfig {
+    fn from(repr: AuthStrategyRepr) -> Self {
+        match repr {
+            AuthStrategyRepr::ForwardAuth { forward_auth } => Self::ForwardAuth(forward_auth),
+            AuthStrategyRepr::ApiKey { api_key } => Self::ApiKey(api_key),
+        }
+    }
+}
+
+impl From<AuthStrategyConfig> for AuthStrategyRepr {
+    fn from(strategy: AuthStrategyConfig) -> Self {
+        match strategy {
+            AuthStrategyConfig::ForwardAuth(forward_auth) => Self::ForwardAuth { forward_auth },
+            AuthStrategyConfig::ApiKey(api_key) => Self::ApiKey { api_key },
+        }
+    }
+}
+
+fn default_filters() -> Vec<FilterConfig> { vec![FilterConfig::Metrics, FilterConfig::RequestId] }
+
+/// Request headers change before routing, so routes see the result;
+/// removals apply before additions.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct HeaderFilterConfig {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub request_set: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub request_remove: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub response_set: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub response_remove: Vec<String>,
+}
+
+impl HeaderFilterConfig {
+    fn validate(&self) -> Result<()> {
+        let names = self.request_set.keys().chain(&self.request_remove)
+            .chain(self.response_set.keys()).chain(&self.response_remove);
+        for name in names {
+            if hyper::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                bail!("Invalid header name in headers filter: {:?}", name);
+            }
+        }
+        for value in self.request_set.values().chain(self.response_set.values()) {
+            if hyper::header::HeaderValue::from_str(value).is_err() {
+                bail!("Invalid header value in headers filter: {:?}", value);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_admin_port")]
+    pub port: u16,
+    /// Address to listen on; loopback only by default, since the admin API
+    /// can change how traffic is served.
+    #[serde(default = "default_admin_bind")]
+    pub bind: IpAddr,
+    #[serde(default)]
+    pub access: AccessConfig,
+    /// Serve HTTPS instead of plain HTTP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// Cap on distinct keys tracked per traffic statistics dimension.
+    #[serde(default = "default_traffic_stats_max_keys")]
+    pub traffic_stats_max_keys: usize,
+    /// Rolling window for admin latency percentiles.
+    #[serde(default = "default_latency_window_secs")]
+    pub latency_window_secs: u64,
+    /// Cap on distinct backends, and on routes, with latency percentiles.
+    /// Each keeps a histogram of about 20KB per 10 seconds of window.
+    #[serde(default = "default_latency_stats_max_keys")]
+    pub latency_stats_max_keys: usize,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_admin_port(),
+            bind: default_admin_bind(),
+            access: AccessConfig::default(),
+            tls: None,
+            traffic_stats_max_keys: default_traffic_stats_max_keys(),
+            latency_window_secs: default_latency_window_secs(),
+            latency_stats_max_keys: default_latency_stats_max_keys(),
+        }
+    }
+}
+
+fn default_admin_port() -> u16 { 9091 }
+fn default_admin_bind() -> IpAddr { [127, 0, 0, 1].into() }
+fn default_traffic_stats_max_keys() -> usize { 10_000 }
+fn default_latency_window_secs() -> u64 { 60 }
+fn default_latency_stats_max_keys() -> usize { 200 }
+
+impl AdminConfig {
+    pub fn latency_window(&self) -> Duration {
+        Duration::from_secs(self.latency_window_secs)
+    }
+}
+
+/// How the binary logs. Read once at startup; `RUST_LOG` directives, when
+/// set, are applied on top of `level` and `modules`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub format: LogFormat,
+    /// For modules not in `modules`.
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    /// Levels by module path, e.g. `rust_load_balancer::health: debug`.
+    #[serde(default = "default_log_modules")]
+    pub modules: HashMap<String, String>,
+    /// Only these fields (span fields included), when set. The message is
+    /// always kept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include_fields: Vec<String>,
+    /// Fields to leave out, e.g. `[client]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_fields: Vec<String>,
+    /// Write here instead of stdout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<LogFileConfig>,
+    #[serde(default)]
+    pub sampling: LogSamplingConfig,
+    /// Warn about requests over these thresholds, with where their time went.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow: Option<SlowLogConfig>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            level: default_log_level(),
+            modules: default_log_modules(),
+            include_fields: Vec::new(),
+            exclude_fields: Vec::new(),
+            file: None,
+            sampling: LogSamplingConfig::default(),
+            slow: None,
+        }
+    }
+}
+
+fn default_log_level() -> String { "error".to_string() }
+fn default_log_modules() -> HashMap<String, String> {
+    [("rust_load_balancer", "debug"), ("hyper", "info")]
+        .into_iter()
+        .map(|(module, level)| (module.to_string(), level.to_string()))
+        .collect()
+}
+
+impl LoggingConfig {
+    fn validate(&self) -> Result<()> {
+        let levels = std::iter::once(("logging.level", &self.level))
+            .chain(self.modules.values().map(|level| ("logging.modules", level)));
+        for (setting, level) in levels {
+            if level.parse::<tracing::level_filters::LevelFilter>().is_err() {
+                bail!("Invalid {}: {:?}", setting, level);
+            }
+        }
+        if let Some(file) = &self.file {
+            if file.max_size_mb == 0 {
+                bail!("logging.file max_size_mb must be greater than 0");
+            }
+        }
+        if self.sampling.success_one_in == 0 {
+            bail!("logging.sampling success_one_in must be greater than 0");
+        }
+        if let Some(slow) = &self.slow {
+            if slow.total_ms.is_none() && slow.upstream_ms.is_none() {
+                bail!("logging.slow needs total_ms, upstream_ms or both");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, with span and event fields at the top level.
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LogFileConfig {
+    pub path: PathBuf,
+    /// Past this, the file is renamed to `<path>.1` (and older ones shifted
+    /// up) and a new one started.
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Rotated files kept besides the current one.
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+/// Which "Request completed" lines are logged. Failed requests (5xx or no
+/// response) always are.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LogSamplingConfig {
+    /// Log one in this many other requests; 1 logs them all.
+    #[serde(default = "default_success_one_in")]
+    pub success_one_in: u64,
+    /// Always log requests that took at least this long.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow_ms: Option<u64>,
+}
+
+impl Default for LogSamplingConfig {
+    fn default() -> Self {
+        Self {
+            success_one_in: default_success_one_in(),
+            slow_ms: None,
+        }
+    }
+}
+
+fn default_success_one_in() -> u64 { 1 }
+
+/// A request is slow once either threshold is reached.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SlowLogConfig {
+    /// From receiving the request to sending the last of the response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_ms: Option<u64>,
+    /// From sending the request upstream to the backend's response headers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_ms: Option<u64>,
+}
+
+fn default_log_max_size_mb() -> u64 { 100 }
+fn default_log_max_files() -> usize { 5 }
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UpstreamConfig {
+    /// The family tried first when a backend's name has both A and AAAA
+    /// records.
+    #[serde(default)]
+    pub prefer: IpFamily,
+    /// How long a connection attempt gets before the next address is tried
+    /// alongside it (RFC 8305's Connection Attempt Delay).
+    #[serde(default = "default_happy_eyeballs_delay")]
+    pub happy_eyeballs_delay_ms: u64,
+    /// Connection pooling for each backend pool without an entry in `pools`.
+    #[serde(default)]
+    pub connection_pool: UpstreamPoolConfig,
+    /// Connection pooling for particular backend pools, by name.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pools: HashMap<String, UpstreamPoolConfig>,
+}
+
+impl UpstreamConfig {
+    pub fn happy_eyeballs_delay(&self) -> Duration {
+        Duration::from_millis(self.happy_eyeballs_delay_ms)
+    }
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            prefer: IpFamily::default(),
+            happy_eyeballs_delay_ms: default_happy_eyeballs_delay(),
+            connection_pool: UpstreamPoolConfig::default(),
+            pools: HashMap::new(),
+        }
+    }
+}
+
+fn default_happy_eyeballs_delay() -> u64 { 250 }
+
+/// The HTTP client one backend pool's requests go through. Every pool has
+/// its own, so one pool's stuck backends can't use up another's connections.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UpstreamPoolConfig {
+    /// Requests in flight to the pool at once, response bodies included.
+    /// Unlimited when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<usize>,
+    /// How long a request waits for one of `max_connections` before it
+    /// fails with a 503.
+    #[serde(default = "default_pool_queue_timeout")]
+    pub queue_timeout_ms: u64,
+    /// Idle connections kept per backend.
+    #[serde(default = "default_pool_max_idle")]
+    pub max_idle_per_host: usize,
+    /// How long an idle connection is kept.
+    #[serde(default = "default_pool_idle_timeout")]
+    pub idle_timeout_secs: u64,
+}
+
+impl UpstreamPoolConfig {
+    fn validate(&self, owner: &str) -> Result<()> {
+        if self.max_connections == Some(0) {
+            bail!("{} max_connections must be at least 1", owner);
+        }
+        Ok(())
+    }
+}
+
+impl Default for UpstreamPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: None,
+            queue_timeout_ms: default_pool_queue_timeout(),
+            max_idle_per_host: default_pool_max_idle(),
+            idle_timeout_secs: default_pool_idle_timeout(),
+        }
+    }
+}
+
+fn default_pool_queue_timeout() -> u64 { 1000 }
+fn default_pool_max_idle() -> usize { 50 }
+fn default_pool_idle_timeout() -> u64 { 90 }
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IpFamily {
+    #[default]
+    Ipv6,
+    Ipv4,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RuntimeConfig {
+    /// Number of tasks accepting connections on the main listener.
+    #[serde(default = "default_acceptors")]
+    pub acceptors: usize,
+    /// Bind one `SO_REUSEPORT` socket per acceptor so the kernel spreads
+    /// incoming connections, instead of all acceptors sharing one socket.
+    #[serde(default)]
+    pub reuse_port: bool,
+    #[serde(default = "default_listen_backlog")]
+    pub listen_backlog: u32,
+    /// Control socket for zero-downtime upgrades: a new process connects to
+    /// it to take over the listening sockets, and the old one drains.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handover_socket: Option<PathBuf>,
+    /// How long to wait for open connections when shutting down or after a
+    /// handover.
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout_secs: u64,
+    /// Open client connections across all HTTP listeners, on top of each
+    /// listener's own `max_connections`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<usize>,
+    /// Once `max_connections` are open, accept and close new connections
+    /// straight away instead of leaving them in the listen backlog.
+    #[serde(default)]
+    pub reject_when_full: bool,
+    /// Time requests for metrics and logs on a clock updated every this
+    /// many milliseconds instead of reading the system clock each time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coarse_clock_ms: Option<u64>,
+}
+
+impl RuntimeConfig {
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            acceptors: default_acceptors(),
+            reuse_port: false,
+            listen_backlog: default_listen_backlog(),
+            handover_socket: None,
+            drain_timeout_secs: default_drain_timeout(),
+            max_connections: None,
+            reject_when_full: false,
+            coarse_clock_ms: None,
+        }
+    }
+}
+
+fn default_acceptors() -> usize { 1 }
+fn default_listen_backlog() -> u32 { 1024 }
+fn default_drain_timeout() -> u64 { 30 }
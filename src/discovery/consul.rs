@@ -0,0 +1,207 @@
+// src/discovery/consul.rs
+use super::{backend_url, Reconciler};
+use crate::config::{BackendConfig, ReconcileConfig, ConsulDiscoveryConfig};
+use crate::proxy::Proxy;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+use url::Url;
+
+/// Pause before asking again after a failed query.
+const ERROR_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Keeps a pool in line with the passing instances of a Consul service.
+pub struct ConsulDiscovery {
+    config: ConsulDiscoveryConfig,
+    client: reqwest::Client,
+}
+
+impl ConsulDiscovery {
+    pub fn new(config: ConsulDiscoveryConfig) -> Result<Self> {
+        // Consul adds up to wait/16 of jitter before answering
+        let timeout = config.wait() + config.wait() / 16 + Duration::from_secs(5);
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("Failed to create Consul client")?;
+        Ok(Self { config, client })
+    }
+
+    /// Watch until the process exits. A failed query keeps the backends
+    /// from the last successful one.
+    pub fn spawn(self, proxy: Arc<Proxy>, reconcile: &ReconcileConfig) {
+        let source = format!("consul:{}", self.config.service);
+        let reconciler = Reconciler::new(source, proxy, self.config.drain_timeout(), reconcile);
+        tokio::spawn(async move {
+            let mut index = 0;
+            loop {
+                match self.query(index).await {
+                    Ok((next, backends)) => {
+                        debug!(service = %self.config.service, index = next, backends = backends.len(), "Consul answered");
+                        // The index may go backwards, e.g. after a Consul restore
+                        index = if next < index { 0 } else { next };
+                        reconciler.update(backends);
+                    }
+                    Err(e) => {
+                        warn!(service = %self.config.service, error = %e, "Consul discovery failed");
+                        tokio::time::sleep(ERROR_BACKOFF).await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// One blocking query: returns once the service changes after `index`
+    /// or `wait_secs` passes.
+    async fn query(&self, index: u64) -> Result<(u64, Vec<BackendConfig>)> {
+        let mut request = self
+            .client
+            .get(self.url(index)?)
+            .header("Accept", "application/json");
+        if let Some(token) = &self.config.token {
+            request = request.header("X-Consul-Token", token);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            bail!("Consul returned {}", response.status());
+        }
+
+        let next = response
+            .headers()
+            .get("X-Consul-Index")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let entries: Vec<ServiceEntry> = response.json().await?;
+        let backends = entries
+            .iter()
+            .map(|entry| self.backend(entry))
+            .collect::<Result<_>>()?;
+        Ok((next, backends))
+    }
+
+    fn url(&self, index: u64) -> Result<Url> {
+        let mut url = self.config.address.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid Consul address {}", self.config.address))?
+            .pop_if_empty()
+            .extend(["v1", "health", "service", self.config.service.as_str()]);
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("passing", "true")
+                .append_pair("index", &index.to_string())
+                .append_pair("wait", &format!("{}s", self.config.wait_secs));
+            if let Some(tag) = &self.config.tag {
+                query.append_pair("tag", tag);
+            }
+            if let Some(datacenter) = &self.config.datacenter {
+                query.append_pair("dc", datacenter);
+            }
+        }
+        Ok(url)
+    }
+
+    /// `weight=<n>`, `zone=<name>` and `region=<name>` tags set the backend's
+    /// weight, zone and region.
+    fn backend(&self, entry: &ServiceEntry) -> Result<BackendConfig> {
+        let service = &entry.service;
+        // Instances without their own address run on the node's
+        let address = match service.address.as_str() {
+            "" => entry.node.address.as_str(),
+            address => address,
+        };
+
+        let tag = |name: &str| {
+            service
+                .tags
+                .iter()
+                .flatten()
+                .find_map(|tag| tag.strip_prefix(name)?.strip_prefix('='))
+        };
+        let weight = tag("weight")
+            .and_then(|w| w.parse().ok())
+            .or(service.weights.as_ref().map(|w| w.passing))
+            .filter(|w| *w > 0)
+            .unwrap_or(self.config.weight);
+
+        Ok(BackendConfig {
+            id: None,
+            url: backend_url(&self.config.scheme, address, service.port)?,
+            weight,
+            max_connections: self.config.max_connections,
+            pool: self.config.pool.clone(),
+            host_header: None,
+            zone: tag("zone").map(str::to_string),
+            region: tag("region").map(str::to_string),
+            bandwidth: None,
+            queue: None,
+            idle_validation: None,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ServiceEntry {
+    node: Node,
+    service: Service,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Node {
+    address: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Service {
+    #[serde(default)]
+    address: String,
+    port: u16,
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+    #[serde(default)]
+    weights: Option<Weights>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Weights {
+    passing: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instances_become_backends() {
+        let config: ConsulDiscoveryConfig =
+            serde_yaml::from_str("{ service: api, pool: api, weight: 3 }").unwrap();
+        let discovery = ConsulDiscovery::new(config).unwrap();
+        let entries: Vec<ServiceEntry> = serde_json::from_str(
+            r#"[
+                {"Node": {"Address": "10.0.0.1"},
+                 "Service": {"Address": "", "Port": 8080, "Tags": ["zone=us-east-1a", "weight=5"]}},
+                {"Node": {"Address": "10.0.0.2"},
+                 "Service": {"Address": "fd00::2", "Port": 8080, "Tags": null}}
+            ]"#,
+        )
+        .unwrap();
+
+        let first = discovery.backend(&entries[0]).unwrap();
+        assert_eq!(first.url.as_str(), "http://10.0.0.1:8080/");
+        assert_eq!((first.weight, first.zone.as_deref()), (5, Some("us-east-1a")));
+        let second = discovery.backend(&entries[1]).unwrap();
+        assert_eq!(second.url.as_str(), "http://[fd00::2]:8080/");
+        assert_eq!((second.weight, second.zone), (3, None));
+        assert_eq!(
+            discovery.url(7).unwrap().as_str(),
+            "http://127.0.0.1:8500/v1/health/service/api?passing=true&index=7&wait=60s"
+        );
+    }
+}
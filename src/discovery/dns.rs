@@ -0,0 +1,115 @@
+// src/discovery/dns.rs
+use super::{backend_url, Reconciler};
+use crate::config::{BackendConfig, ReconcileConfig, DnsDiscoveryConfig, DnsRecordType};
+use crate::proxy::Proxy;
+use anyhow::Result;
+use hickory_resolver::proto::rr::rdata::SRV;
+use hickory_resolver::TokioAsyncResolver;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Keeps a pool in line with the A/AAAA or SRV records of a DNS name, e.g.
+/// a headless Kubernetes service.
+pub struct DnsDiscovery {
+    config: DnsDiscoveryConfig,
+    resolver: TokioAsyncResolver,
+}
+
+impl DnsDiscovery {
+    pub fn new(config: DnsDiscoveryConfig) -> Self {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf().unwrap_or_else(|e| {
+            warn!(error = %e, "Failed to read system resolver config, using defaults");
+            TokioAsyncResolver::tokio(Default::default(), Default::default())
+        });
+        Self { config, resolver }
+    }
+
+    /// Resolve every `interval_secs` until the process exits. A failed
+    /// lookup keeps the backends from the last successful one.
+    pub fn spawn(self, proxy: Arc<Proxy>, reconcile: &ReconcileConfig) {
+        let source = format!("dns:{}", self.config.name);
+        let reconciler = Reconciler::new(source, proxy, self.config.drain_timeout(), reconcile);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.interval());
+            loop {
+                interval.tick().await;
+                match self.resolve().await {
+                    Ok(backends) => {
+                        debug!(name = %self.config.name, backends = backends.len(), "Resolved");
+                        reconciler.update(backends);
+                    }
+                    Err(e) => warn!(name = %self.config.name, error = %e, "DNS discovery failed"),
+                }
+            }
+        });
+    }
+
+    pub async fn resolve(&self) -> Result<Vec<BackendConfig>> {
+        let name = self.config.name.as_str();
+        match self.config.record_type {
+            DnsRecordType::A => {
+                // Validation makes sure A lookups have a port
+                let port = self.config.port.unwrap_or_default();
+                let lookup = self.resolver.lookup_ip(name).await?;
+                lookup
+                    .iter()
+                    .map(|ip| self.backend(&ip.to_string(), port, self.config.weight))
+                    .collect()
+            }
+            DnsRecordType::Srv => {
+                let lookup = self.resolver.srv_lookup(name).await?;
+                let records: Vec<SRV> = lookup.iter().cloned().collect();
+                preferred(&records)
+                    .into_iter()
+                    .map(|srv| {
+                        let target = srv.target().to_utf8();
+                        let host = target.trim_end_matches('.');
+                        // Backends can't have weight 0; SRV uses it for "rarely"
+                        self.backend(host, srv.port(), srv.weight().max(1).into())
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn backend(&self, host: &str, port: u16, weight: u32) -> Result<BackendConfig> {
+        Ok(BackendConfig {
+            id: None,
+            url: backend_url(&self.config.scheme, host, port)?,
+            weight,
+            max_connections: self.config.max_connections,
+            pool: self.config.pool.clone(),
+            host_header: None,
+            zone: None,
+            region: None,
+            bandwidth: None,
+            queue: None,
+            idle_validation: None,
+        })
+    }
+}
+
+/// SRV targets with the lowest priority value; the others are fallbacks we
+/// don't send traffic to.
+fn preferred(records: &[SRV]) -> Vec<&SRV> {
+    let Some(best) = records.iter().map(|srv| srv.priority()).min() else {
+        return Vec::new();
+    };
+    records.iter().filter(|srv| srv.priority() == best).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::Name;
+
+    #[test]
+    fn test_only_lowest_priority_srv_targets_are_used() {
+        let srv = |priority, port| SRV::new(priority, 10, port, Name::from_ascii("api.local.").unwrap());
+        let records = [srv(20, 8001), srv(10, 8002), srv(10, 8003)];
+
+        let ports: Vec<u16> = preferred(&records).iter().map(|srv| srv.port()).collect();
+        assert_eq!(ports, [8002, 8003]);
+        assert!(preferred(&[]).is_empty());
+    }
+}
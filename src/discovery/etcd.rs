@@ -0,0 +1,388 @@
+// src/discovery/etcd.rs
+use super::Reconciler;
+use crate::config::{BackendConfig, EtcdConfig, ReconcileConfig, RouteConfig};
+use crate::proxy::Proxy;
+use crate::tcp_proxy::TCP_SCHEME;
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Pause before reconnecting after an error.
+const RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Backends and routes kept in etcd, applied as they change, plus a status
+/// key per instance so several load balancers can be run from one place.
+pub struct EtcdStore {
+    config: EtcdConfig,
+    instance_id: String,
+    client: reqwest::Client,
+    proxy: Arc<Proxy>,
+    backends: Reconciler,
+}
+
+impl EtcdStore {
+    pub fn new(config: EtcdConfig, proxy: Arc<Proxy>, reconcile: &ReconcileConfig) -> Self {
+        let instance_id = config
+            .instance_id
+            .clone()
+            .or_else(|| std::env::var("HOSTNAME").ok())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let backends = Reconciler::new("etcd", proxy.clone(), config.drain_timeout(), reconcile);
+        Self {
+            config,
+            instance_id,
+            client: reqwest::Client::new(),
+            proxy,
+            backends,
+        }
+    }
+
+    /// Watch for changes and report status until the process exits. While
+    /// etcd is unreachable the last applied state stays.
+    pub fn spawn(self) {
+        let store = Arc::new(self);
+        tokio::spawn(store.clone().watch());
+        tokio::spawn(store.report());
+    }
+
+    async fn watch(self: Arc<Self>) {
+        loop {
+            if let Err(e) = self.sync().await {
+                warn!(endpoint = %self.config.endpoint, error = %e, "etcd watch failed");
+            }
+            tokio::time::sleep(RETRY_BACKOFF).await;
+        }
+    }
+
+    async fn sync(&self) -> Result<()> {
+        loop {
+            let revision = self.load().await?;
+            self.wait_for_change(revision).await?;
+        }
+    }
+
+    /// Apply everything under the prefix; returns the revision read.
+    async fn load(&self) -> Result<i64> {
+        let prefix = self.config.prefix.as_bytes();
+        let range: RangeResponse = self
+            .post(
+                "/v3/kv/range",
+                &json!({ "key": STANDARD.encode(prefix), "range_end": STANDARD.encode(prefix_end(prefix)) }),
+            )
+            .await?;
+
+        let (backends, routes) = definitions(&self.config.prefix, &range.kvs);
+        info!(
+            revision = range.header.revision,
+            backends = backends.len(),
+            routes = routes.len(),
+            "Loaded configuration from etcd"
+        );
+        self.backends.update(backends);
+        if let Err(e) = self.proxy.set_dynamic_routes(routes) {
+            warn!(error = %e, "Ignoring invalid routes from etcd");
+        }
+        Ok(range.header.revision)
+    }
+
+    /// Returns once a backend or route key changes after `revision`.
+    async fn wait_for_change(&self, revision: i64) -> Result<()> {
+        let prefix = self.config.prefix.as_bytes();
+        let body = json!({ "create_request": {
+            "key": STANDARD.encode(prefix),
+            "range_end": STANDARD.encode(prefix_end(prefix)),
+            "start_revision": (revision + 1).to_string(),
+        }});
+        let mut response = self
+            .client
+            .post(self.config.endpoint.join("/v3/watch")?)
+            .json(&body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            bail!("etcd returned {} for /v3/watch", response.status());
+        }
+
+        // The gateway streams one JSON message per line
+        let mut buffer = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                let message: WatchMessage = serde_json::from_slice(&line)?;
+                if let Some(error) = message.error {
+                    bail!("watch failed: {}", error.message);
+                }
+                let result = message.result.unwrap_or_default();
+                if result.canceled {
+                    bail!("watch canceled: {}", result.cancel_reason);
+                }
+                // Our own status updates don't need a reload
+                let status_prefix = format!("{}instances/", self.config.prefix);
+                let changed = result
+                    .events
+                    .iter()
+                    .any(|event| !decode(&event.kv.key).starts_with(status_prefix.as_bytes()));
+                if changed {
+                    debug!("etcd configuration changed");
+                    return Ok(());
+                }
+            }
+        }
+        bail!("etcd closed the watch")
+    }
+
+    /// Write this instance's status every `status_interval_secs` under a
+    /// lease, so instances that go away drop out on their own.
+    async fn report(self: Arc<Self>) {
+        let mut lease = None;
+        let mut interval = tokio::time::interval(self.config.status_interval());
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.put_status(&mut lease).await {
+                warn!(instance = %self.instance_id, error = %e, "Failed to write status to etcd");
+                lease = None;
+            }
+        }
+    }
+
+    async fn put_status(&self, lease: &mut Option<String>) -> Result<()> {
+        if let Some(id) = lease.as_deref() {
+            let response: KeepAliveResponse = self.post("/v3/lease/keepalive", &json!({ "ID": id })).await?;
+            if response.result.map_or(0, |result| result.ttl) <= 0 {
+                *lease = None;
+            }
+        }
+        let id = match lease {
+            Some(id) => id.clone(),
+            None => {
+                let ttl = self.config.status_interval_secs * 3;
+                let grant: LeaseGrant = self.post("/v3/lease/grant", &json!({ "TTL": ttl.to_string() })).await?;
+                lease.insert(grant.id.to_string()).clone()
+            }
+        };
+
+        let pool = self.proxy.pool();
+        let mut backends = Vec::new();
+        for backend in pool.all_backends() {
+            backends.push(BackendStatus {
+                id: backend.id.clone(),
+                pool: backend.pool.clone(),
+                healthy: backend.is_healthy(),
+            });
+        }
+        let status = InstanceStatus {
+            instance: &self.instance_id,
+            healthy_backends: backends.iter().filter(|b| b.healthy).count(),
+            total_backends: backends.len(),
+            backends,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        let key = format!("{}instances/{}", self.config.prefix, self.instance_id);
+        let _: serde_json::Value = self
+            .post(
+                "/v3/kv/put",
+                &json!({
+                    "key": STANDARD.encode(key),
+                    "value": STANDARD.encode(serde_json::to_vec(&status)?),
+                    "lease": id,
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn post<T: DeserializeOwned>(&self, path: &str, body: &serde_json::Value) -> Result<T> {
+        let response = self
+            .client
+            .post(self.config.endpoint.join(path)?)
+            .json(body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            bail!("etcd returned {} for {}", response.status(), path);
+        }
+        Ok(response.json().await?)
+    }
+}
+
+/// Backends and routes defined under `prefix`, in key order. Entries that
+/// don't parse are skipped so one bad key can't block the rest.
+fn definitions(prefix: &str, kvs: &[KeyValue]) -> (Vec<BackendConfig>, Vec<RouteConfig>) {
+    let mut backends = Vec::new();
+    let mut routes = Vec::new();
+    for kv in kvs {
+        let key = String::from_utf8_lossy(&decode(&kv.key)).into_owned();
+        let value = decode(&kv.value);
+        let Some(name) = key.strip_prefix(prefix) else {
+            continue;
+        };
+        if name.starts_with("backends/") {
+            match serde_yaml::from_slice::<BackendConfig>(&value) {
+                Ok(backend) if backend.url.scheme() == TCP_SCHEME && backend.url.port().is_none() => {
+                    warn!(key = %key, "Skipping etcd tcp backend without a port")
+                }
+                Ok(backend) if backend.weight > 0 && backend.max_connections > 0 => backends.push(backend),
+                Ok(_) => warn!(key = %key, "Skipping etcd backend with zero weight or max_connections"),
+                Err(e) => warn!(key = %key, error = %e, "Skipping invalid etcd backend"),
+            }
+        } else if name.starts_with("routes/") {
+            match serde_yaml::from_slice::<RouteConfig>(&value) {
+                Ok(route) => routes.push(route),
+                Err(e) => warn!(key = %key, error = %e, "Skipping invalid etcd route"),
+            }
+        }
+    }
+    (backends, routes)
+}
+
+fn decode(value: &str) -> Vec<u8> {
+    STANDARD.decode(value).unwrap_or_default()
+}
+
+/// End of etcd's range for keys starting with `prefix`.
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < 0xff {
+            end.push(last + 1);
+            return end;
+        }
+    }
+    // All 0xff: the range runs to the end of the keyspace
+    vec![0]
+}
+
+// The JSON gateway writes 64-bit integers as strings
+fn int64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Int64 {
+        Number(i64),
+        Text(String),
+    }
+    match Int64::deserialize(deserializer)? {
+        Int64::Number(n) => Ok(n),
+        Int64::Text(s) => s.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RangeResponse {
+    header: ResponseHeader,
+    #[serde(default)]
+    kvs: Vec<KeyValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseHeader {
+    #[serde(deserialize_with = "int64")]
+    revision: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct KeyValue {
+    key: String,
+    #[serde(default)]
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchMessage {
+    result: Option<WatchResult>,
+    error: Option<WatchError>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct WatchResult {
+    #[serde(default)]
+    events: Vec<WatchEvent>,
+    #[serde(default)]
+    canceled: bool,
+    #[serde(default)]
+    cancel_reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchEvent {
+    #[serde(default)]
+    kv: KeyValue,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaseGrant {
+    #[serde(rename = "ID", deserialize_with = "int64")]
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeepAliveResponse {
+    result: Option<KeepAliveResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeepAliveResult {
+    #[serde(rename = "TTL", default, deserialize_with = "int64")]
+    ttl: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct InstanceStatus<'a> {
+    instance: &'a str,
+    healthy_backends: usize,
+    total_backends: usize,
+    backends: Vec<BackendStatus>,
+    updated_at: String,
+}
+
+#[derive(Debug, Serialize)]
+struct BackendStatus {
+    id: String,
+    pool: String,
+    healthy: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kv(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: STANDARD.encode(key),
+            value: STANDARD.encode(value),
+        }
+    }
+
+    #[test]
+    fn test_definitions_under_prefix() {
+        let kvs = [
+            kv("/lb/backends/a", r#"{"url": "http://10.0.0.1:8080", "pool": "api"}"#),
+            kv("/lb/backends/b", "url: not a url"),
+            kv("/lb/backends/c", "url: 'tcp://10.0.0.2'"),
+            kv("/lb/instances/lb-1", r#"{"instance": "lb-1"}"#),
+            kv("/lb/routes/10-api", "{ name: api, path_prefix: /api, pool: api }"),
+            kv("/lb/routes/20-web", "{ name: web, pool: web }"),
+        ];
+
+        let (backends, routes) = definitions("/lb/", &kvs);
+        assert_eq!(backends.len(), 1);
+        assert_eq!(backends[0].pool, "api");
+        let names: Vec<&str> = routes.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["api", "web"]);
+        assert_eq!(prefix_end(b"/lb/"), b"/lb0");
+    }
+}
@@ -0,0 +1,34 @@
+//
+// src/discovery/mod.rs
+//
+#[cfg(feature = "consul")]
+mod consul;
+mod dns;
+#[cfg(feature = "etcd")]
+mod etcd;
+mod reconcile;
+#[cfg(feature = "xds")]
+mod xds;
+
+#[cfg(feature = "consul")]
+pub use consul::ConsulDiscovery;
+pub use dns::DnsDiscovery;
+#[cfg(feature = "etcd")]
+pub use etcd::EtcdStore;
+pub use reconcile::Reconciler;
+pub(crate) use reconcile::drain;
+#[cfg(feature = "xds")]
+pub use xds::XdsDiscovery;
+
+use anyhow::{Context, Result};
+use std::net::IpAddr;
+use url::Url;
+
+/// URL of a discovered backend, bracketing IPv6 addresses.
+fn backend_url(scheme: &str, host: &str, port: u16) -> Result<Url> {
+    let url = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("{}://[{}]:{}", scheme, ip, port),
+        _ => format!("{}://{}:{}", scheme, host, port),
+    };
+    Url::parse(&url).with_context(|| format!("Invalid backend URL {}", url))
+}
@@ -0,0 +1,253 @@
+// src/discovery/reconcile.rs
+use crate::config::{BackendConfig, ReconcileConfig};
+use crate::proxy::{Backend, Proxy};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What discovery sources hand their answers to; they never touch the
+/// pool themselves.
+///
+/// Each source only ever changes the backends it added, so static backends
+/// and other sources are left alone. Backends that drop out of the answer
+/// stop getting traffic immediately but are only removed once their open
+/// requests finish or `drain_timeout` passes, and `ReconcileConfig` caps
+/// how many may start draining at once.
+pub struct Reconciler {
+    desired: watch::Sender<Option<Vec<BackendConfig>>>,
+}
+
+impl Reconciler {
+    /// Spawns the task applying updates; it stops when this is dropped.
+    pub fn new(
+        source: impl Into<String>,
+        proxy: Arc<Proxy>,
+        drain_timeout: Duration,
+        config: &ReconcileConfig,
+    ) -> Self {
+        let (desired, updates) = watch::channel(None);
+        let engine = Engine {
+            source: source.into(),
+            proxy,
+            drain_timeout,
+            max_drain_percent: config.max_drain_percent as usize,
+            churn_interval: config.churn_interval(),
+            owned: HashMap::new(),
+            last_drain: None,
+        };
+        tokio::spawn(engine.run(updates));
+        Self { desired }
+    }
+
+    /// Replace the source's backends with `desired`. If updates arrive
+    /// faster than they're applied, only the latest one is.
+    pub fn update(&self, desired: Vec<BackendConfig>) {
+        self.desired.send_replace(Some(desired));
+    }
+}
+
+struct Engine {
+    source: String,
+    proxy: Arc<Proxy>,
+    drain_timeout: Duration,
+    max_drain_percent: usize,
+    churn_interval: Duration,
+    /// Backends this source added, as it last described them.
+    owned: HashMap<String, BackendConfig>,
+    last_drain: Option<Instant>,
+}
+
+impl Engine {
+    async fn run(mut self, mut updates: watch::Receiver<Option<Vec<BackendConfig>>>) {
+        let mut retry = None;
+        loop {
+            let next = match retry {
+                // Drains held back by the churn limit go out once it allows
+                Some(wait) => tokio::select! {
+                    changed = updates.changed() => changed,
+                    _ = tokio::time::sleep(wait) => Ok(()),
+                },
+                None => updates.changed().await,
+            };
+            if next.is_err() {
+                debug!(source = %self.source, "Discovery source stopped");
+                return;
+            }
+            let Some(desired) = updates.borrow_and_update().clone() else {
+                continue;
+            };
+            retry = self.apply(&desired).await;
+        }
+    }
+
+    /// Returns how long to wait before retrying drains it had to defer.
+    async fn apply(&mut self, desired: &[BackendConfig]) -> Option<Duration> {
+        let pool = self.proxy.pool();
+        // Forget backends whose drain has finished
+        self.owned.retain(|id, _| pool.get_backend(id).is_some());
+
+        let plan = plan(&self.owned, desired, |id| pool.get_backend(id).is_some());
+        for config in &plan.add {
+            let id = Backend::id_for(&config.url);
+            pool.add_backend((*config).clone()).await;
+            info!(source = %self.source, backend = %id, "Discovered backend");
+            self.owned.insert(id, (*config).clone());
+        }
+        for config in &plan.update {
+            let id = Backend::id_for(&config.url);
+            pool.replace_backend((*config).clone()).await;
+            info!(source = %self.source, backend = %id, "Discovered backend changed");
+            self.owned.insert(id, (*config).clone());
+        }
+        for id in &plan.keep {
+            // Leaving them to the window that drained them
+            if let Some(backend) = pool.get_backend(id).filter(|b| b.is_draining() && !b.in_maintenance()) {
+                backend.set_draining(false);
+                info!(source = %self.source, backend = %id, "Backend reappeared, no longer draining");
+            }
+        }
+
+        let leaving: Vec<Arc<Backend>> = plan
+            .remove
+            .iter()
+            .filter_map(|id| pool.get_backend(id))
+            .filter(|backend| !backend.is_draining())
+            .collect();
+        let budget = self.drain_budget();
+        let mut drained = 0;
+        for backend in leaving.iter().take(budget) {
+            if pool.drain_backend(&backend.id).await {
+                info!(source = %self.source, backend = %backend.id, "Backend disappeared, draining");
+                tokio::spawn(drain(self.proxy.clone(), backend.clone(), self.drain_timeout));
+                drained += 1;
+            }
+        }
+        if drained > 0 {
+            self.last_drain = Some(Instant::now());
+        }
+        let deferred = leaving.len().saturating_sub(budget);
+
+        let metrics = self.proxy.metrics();
+        for (change, count) in [
+            ("added", plan.add.len()),
+            ("updated", plan.update.len()),
+            ("drained", drained),
+            ("deferred", deferred),
+        ] {
+            if count > 0 {
+                metrics.record_discovery_changes(&self.source, change, count);
+            }
+        }
+        metrics.update_discovery_backends(&self.source, self.owned.len());
+
+        if deferred == 0 {
+            return None;
+        }
+        warn!(source = %self.source, deferred, "Churn limit reached, delaying drains");
+        let since = self.last_drain.map_or(self.churn_interval, |last| last.elapsed());
+        Some(self.churn_interval.saturating_sub(since))
+    }
+
+    /// How many backends may start draining now.
+    fn drain_budget(&self) -> usize {
+        if self.churn_interval.is_zero() {
+            return usize::MAX;
+        }
+        if self.last_drain.is_some_and(|last| last.elapsed() < self.churn_interval) {
+            return 0;
+        }
+        (self.owned.len() * self.max_drain_percent / 100).max(1)
+    }
+}
+
+/// Differences between what a source owns and what it now reports.
+#[derive(Debug, Default)]
+struct Plan<'a> {
+    add: Vec<&'a BackendConfig>,
+    update: Vec<&'a BackendConfig>,
+    keep: Vec<String>,
+    remove: Vec<String>,
+}
+
+/// `in_pool` tells whether an ID is already taken, by a static backend or
+/// another source; those are skipped rather than claimed.
+fn plan<'a>(
+    owned: &HashMap<String, BackendConfig>,
+    desired: &'a [BackendConfig],
+    in_pool: impl Fn(&str) -> bool,
+) -> Plan<'a> {
+    let mut plan = Plan::default();
+    let mut wanted = HashSet::new();
+    for config in desired {
+        let id = Backend::id_for(&config.url);
+        if !wanted.insert(id.clone()) {
+            continue;
+        }
+        match owned.get(&id) {
+            Some(current) if current == config => plan.keep.push(id),
+            Some(_) => plan.update.push(config),
+            None if in_pool(&id) => debug!(backend = %id, "Backend is managed elsewhere"),
+            None => plan.add.push(config),
+        }
+    }
+    plan.remove = owned.keys().filter(|id| !wanted.contains(*id)).cloned().collect();
+    plan.remove.sort();
+    plan
+}
+
+/// Wait for `backend`'s open requests, then drop it unless it came back.
+pub(crate) async fn drain(proxy: Arc<Proxy>, backend: Arc<Backend>, timeout: Duration) {
+    let idle = async {
+        while backend.active_connections() > 0 {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    };
+    if tokio::time::timeout(timeout, idle).await.is_err() {
+        warn!(
+            backend = %backend.id,
+            connections = backend.active_connections(),
+            "Drain timeout reached, removing backend anyway"
+        );
+    }
+
+    let current = proxy.pool().get_backend(&backend.id);
+    if backend.is_draining() && current.is_some_and(|current| Arc::ptr_eq(&current, &backend)) {
+        proxy.remove_backend(&backend.id).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(url: &str, weight: u32) -> BackendConfig {
+        serde_yaml::from_str(&format!("{{ url: \"{}\", weight: {} }}", url, weight)).unwrap()
+    }
+
+    #[test]
+    fn test_plan_diffs_owned_backends() {
+        let owned: HashMap<String, BackendConfig> = ["10.0.0.1", "10.0.0.2", "10.0.0.3"]
+            .iter()
+            .map(|ip| (format!("{}:80", ip), backend(&format!("http://{}", ip), 1)))
+            .collect();
+        let desired = [
+            backend("http://10.0.0.1", 5),
+            backend("http://10.0.0.2", 1),
+            backend("http://10.0.0.4", 1),
+            backend("http://10.0.0.9", 1),
+        ];
+
+        let plan = plan(&owned, &desired, |id| id == "10.0.0.9:80");
+        let ids = |configs: &[&BackendConfig]| -> Vec<String> {
+            configs.iter().map(|c| Backend::id_for(&c.url)).collect()
+        };
+        assert_eq!(ids(&plan.update), ["10.0.0.1:80"]);
+        assert_eq!(plan.keep, ["10.0.0.2:80"]);
+        assert_eq!(ids(&plan.add), ["10.0.0.4:80"]);
+        assert_eq!(plan.remove, ["10.0.0.3:80"]);
+    }
+}
@@ -0,0 +1,367 @@
+// src/discovery/xds.rs
+use super::{backend_url, Reconciler};
+use crate::config::{BackendConfig, ReconcileConfig, XdsDiscoveryConfig};
+use crate::proxy::Proxy;
+use anyhow::{bail, Result};
+use envoy_types::pb::envoy::config::cluster::v3::{cluster, Cluster};
+use envoy_types::pb::envoy::config::core::v3::{address, socket_address, HealthStatus, Node};
+use envoy_types::pb::envoy::config::endpoint::v3::{lb_endpoint, ClusterLoadAssignment};
+use envoy_types::pb::envoy::service::discovery::v3::{
+    aggregated_discovery_service_client::AggregatedDiscoveryServiceClient, DiscoveryRequest,
+    DiscoveryResponse,
+};
+use envoy_types::pb::google::protobuf::Any;
+use envoy_types::pb::google::rpc::Status;
+use futures::channel::mpsc;
+use prost::Message;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const CLUSTER_TYPE: &str = "type.googleapis.com/envoy.config.cluster.v3.Cluster";
+const ENDPOINT_TYPE: &str = "type.googleapis.com/envoy.config.endpoint.v3.ClusterLoadAssignment";
+
+/// Pause before reconnecting to the control plane.
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(5);
+
+/// gRPC `INVALID_ARGUMENT`, sent back with rejected resources.
+const INVALID_ARGUMENT: i32 = 3;
+
+/// Experimental xDS client: clusters (CDS) become pools and their endpoints
+/// (EDS) become backends, all over one ADS stream.
+pub struct XdsDiscovery {
+    config: XdsDiscoveryConfig,
+    proxy: Arc<Proxy>,
+    reconcile: ReconcileConfig,
+    /// One reconciler per cluster, kept so a returning cluster reclaims
+    /// backends that are still draining.
+    pools: HashMap<String, Reconciler>,
+    /// EDS service name to the cluster it feeds.
+    eds_clusters: HashMap<String, String>,
+    cds: Subscription,
+    eds: Subscription,
+}
+
+/// What we last asked for and accepted for one resource type.
+#[derive(Default)]
+struct Subscription {
+    names: Vec<String>,
+    version: String,
+    nonce: String,
+}
+
+impl XdsDiscovery {
+    pub fn new(config: XdsDiscoveryConfig, proxy: Arc<Proxy>, reconcile: ReconcileConfig) -> Self {
+        // Named clusters skip CDS and are read straight from EDS
+        let eds_clusters = config
+            .clusters
+            .iter()
+            .map(|name| (name.clone(), name.clone()))
+            .collect();
+        Self {
+            config,
+            proxy,
+            reconcile,
+            pools: HashMap::new(),
+            eds_clusters,
+            cds: Subscription::default(),
+            eds: Subscription::default(),
+        }
+    }
+
+    /// Stream from the control plane until the process exits, reconnecting
+    /// after errors. Backends stay as they are while disconnected.
+    pub fn spawn(mut self) {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.stream().await {
+                    warn!(server = %self.config.server, error = %e, "xDS stream failed");
+                }
+                tokio::time::sleep(RECONNECT_BACKOFF).await;
+            }
+        });
+    }
+
+    async fn stream(&mut self) -> Result<()> {
+        let mut client = AggregatedDiscoveryServiceClient::connect(self.config.server.to_string()).await?;
+        let (requests, outbound) = mpsc::unbounded();
+        // A new stream starts over: the server forgets our nonces
+        self.cds.nonce.clear();
+        self.eds.nonce.clear();
+        if self.config.clusters.is_empty() {
+            requests.unbounded_send(self.request(CLUSTER_TYPE, None))?;
+        } else {
+            self.eds.names = self.config.clusters.clone();
+            requests.unbounded_send(self.request(ENDPOINT_TYPE, None))?;
+        }
+
+        let mut responses = client.stream_aggregated_resources(outbound).await?.into_inner();
+        info!(server = %self.config.server, "Connected to xDS control plane");
+        while let Some(response) = responses.message().await? {
+            let type_url = response.type_url.clone();
+            let result = match type_url.as_str() {
+                CLUSTER_TYPE => self.on_clusters(&response),
+                ENDPOINT_TYPE => self.on_endpoints(&response),
+                other => {
+                    debug!(type_url = other, "Ignoring unrequested xDS resources");
+                    continue;
+                }
+            };
+
+            let subscription = self.subscription(&type_url);
+            subscription.nonce = response.nonce.clone();
+            let error = match result {
+                Ok(()) => {
+                    subscription.version = response.version_info.clone();
+                    None
+                }
+                Err(e) => {
+                    warn!(type_url = %type_url, version = %response.version_info, error = %e, "Rejected xDS update");
+                    Some(Status {
+                        code: INVALID_ARGUMENT,
+                        message: e.to_string(),
+                        details: Vec::new(),
+                    })
+                }
+            };
+            requests.unbounded_send(self.request(&type_url, error))?;
+
+            // New clusters may need a wider EDS subscription
+            if type_url == CLUSTER_TYPE {
+                let mut names: Vec<String> = self.eds_clusters.keys().cloned().collect();
+                names.sort();
+                if names != self.eds.names {
+                    self.eds.names = names;
+                    requests.unbounded_send(self.request(ENDPOINT_TYPE, None))?;
+                }
+            }
+        }
+        bail!("control plane closed the stream")
+    }
+
+    /// Request (or ACK/NACK) for `type_url`, echoing the last accepted
+    /// version and the last nonce seen.
+    fn request(&mut self, type_url: &str, error_detail: Option<Status>) -> DiscoveryRequest {
+        let node = Node {
+            id: self.config.node_id.clone(),
+            cluster: self.config.node_cluster.clone(),
+            ..Default::default()
+        };
+        let subscription = self.subscription(type_url);
+        DiscoveryRequest {
+            version_info: subscription.version.clone(),
+            node: Some(node),
+            resource_names: subscription.names.clone(),
+            type_url: type_url.to_string(),
+            response_nonce: subscription.nonce.clone(),
+            error_detail,
+            ..Default::default()
+        }
+    }
+
+    fn subscription(&mut self, type_url: &str) -> &mut Subscription {
+        if type_url == CLUSTER_TYPE {
+            &mut self.cds
+        } else {
+            &mut self.eds
+        }
+    }
+
+    /// CDS responses carry every cluster, so missing ones are gone.
+    fn on_clusters(&mut self, response: &DiscoveryResponse) -> Result<()> {
+        let clusters: Vec<Cluster> = decode(&response.resources)?;
+        let mut eds_clusters = HashMap::new();
+        let mut assignments = Vec::new();
+        for cluster in clusters {
+            match cluster.cluster_discovery_type {
+                Some(cluster::ClusterDiscoveryType::Type(kind)) if kind == cluster::DiscoveryType::Eds as i32 => {
+                    let service = cluster
+                        .eds_cluster_config
+                        .as_ref()
+                        .map(|eds| eds.service_name.clone())
+                        .filter(|name| !name.is_empty())
+                        .unwrap_or_else(|| cluster.name.clone());
+                    eds_clusters.insert(service, cluster.name);
+                }
+                _ => match cluster.load_assignment {
+                    Some(assignment) => assignments.push((cluster.name, assignment)),
+                    None => warn!(cluster = %cluster.name, "Skipping xDS cluster without EDS or endpoints"),
+                },
+            }
+        }
+
+        let kept: Vec<&String> = eds_clusters.values().chain(assignments.iter().map(|(name, _)| name)).collect();
+        let removed: Vec<String> = self.pools.keys().filter(|name| !kept.contains(name)).cloned().collect();
+        for name in removed {
+            info!(cluster = %name, "xDS cluster removed");
+            self.reconcile(&name, Vec::new());
+        }
+        for (name, assignment) in assignments {
+            let backends = backends(&self.config, &name, &assignment)?;
+            self.reconcile(&name, backends);
+        }
+        self.eds_clusters = eds_clusters;
+        Ok(())
+    }
+
+    /// EDS responses may only carry the assignments that changed.
+    fn on_endpoints(&mut self, response: &DiscoveryResponse) -> Result<()> {
+        let assignments: Vec<ClusterLoadAssignment> = decode(&response.resources)?;
+        for assignment in assignments {
+            let Some(cluster) = self.eds_clusters.get(&assignment.cluster_name).cloned() else {
+                debug!(cluster = %assignment.cluster_name, "Ignoring endpoints of unknown cluster");
+                continue;
+            };
+            let backends = backends(&self.config, &cluster, &assignment)?;
+            self.reconcile(&cluster, backends);
+        }
+        Ok(())
+    }
+
+    fn reconcile(&mut self, cluster: &str, backends: Vec<BackendConfig>) {
+        let reconciler = self.pools.entry(cluster.to_string()).or_insert_with(|| {
+            Reconciler::new(
+                format!("xds:{}", cluster),
+                self.proxy.clone(),
+                self.config.drain_timeout(),
+                &self.reconcile,
+            )
+        });
+        reconciler.update(backends);
+    }
+}
+
+fn decode<T: Message + Default>(resources: &[Any]) -> Result<Vec<T>> {
+    resources
+        .iter()
+        .map(|any| Ok(T::decode(any.value.as_slice())?))
+        .collect()
+}
+
+/// Endpoints of the highest-priority locality level that the control plane
+/// doesn't mark unhealthy or draining.
+fn backends(
+    config: &XdsDiscoveryConfig,
+    pool: &str,
+    assignment: &ClusterLoadAssignment,
+) -> Result<Vec<BackendConfig>> {
+    let Some(best) = assignment
+        .endpoints
+        .iter()
+        .filter(|locality| !locality.lb_endpoints.is_empty())
+        .map(|locality| locality.priority)
+        .min()
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut backends = Vec::new();
+    for locality in assignment.endpoints.iter().filter(|l| l.priority == best) {
+        let zone = locality
+            .locality
+            .as_ref()
+            .map(|l| l.zone.clone())
+            .filter(|zone| !zone.is_empty());
+        let region = locality
+            .locality
+            .as_ref()
+            .map(|l| l.region.clone())
+            .filter(|region| !region.is_empty());
+        for endpoint in &locality.lb_endpoints {
+            let status = HealthStatus::try_from(endpoint.health_status).unwrap_or(HealthStatus::Unknown);
+            if matches!(status, HealthStatus::Unhealthy | HealthStatus::Draining | HealthStatus::Timeout) {
+                continue;
+            }
+            let Some(lb_endpoint::HostIdentifier::Endpoint(host)) = &endpoint.host_identifier else {
+                continue;
+            };
+            let Some(address::Address::SocketAddress(socket)) = host.address.as_ref().and_then(|a| a.address.as_ref()) else {
+                continue;
+            };
+            let Some(socket_address::PortSpecifier::PortValue(port)) = socket.port_specifier else {
+                continue;
+            };
+            let weight = endpoint.load_balancing_weight.map_or(1, |w| w.value).max(1);
+            backends.push(BackendConfig {
+                id: None,
+                url: backend_url(&config.scheme, &socket.address, u16::try_from(port)?)?,
+                weight,
+                max_connections: config.max_connections,
+                pool: pool.to_string(),
+                host_header: None,
+                zone: zone.clone(),
+                region: region.clone(),
+                bandwidth: None,
+                queue: None,
+                idle_validation: None,
+            });
+        }
+    }
+    Ok(backends)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use envoy_types::pb::envoy::config::core::v3::{Address, Locality, SocketAddress};
+    use envoy_types::pb::envoy::config::endpoint::v3::{Endpoint, LbEndpoint, LocalityLbEndpoints};
+    use envoy_types::pb::google::protobuf::UInt32Value;
+
+    fn endpoint(ip: &str, weight: Option<u32>, status: HealthStatus) -> LbEndpoint {
+        let socket = SocketAddress {
+            address: ip.to_string(),
+            port_specifier: Some(socket_address::PortSpecifier::PortValue(8080)),
+            ..Default::default()
+        };
+        LbEndpoint {
+            host_identifier: Some(lb_endpoint::HostIdentifier::Endpoint(Endpoint {
+                address: Some(Address {
+                    address: Some(address::Address::SocketAddress(socket)),
+                }),
+                ..Default::default()
+            })),
+            health_status: status as i32,
+            load_balancing_weight: weight.map(|value| UInt32Value { value }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_assignment_becomes_backends() {
+        let config: XdsDiscoveryConfig = serde_yaml::from_str("server: http://xds:18000").unwrap();
+        let assignment = ClusterLoadAssignment {
+            cluster_name: "api".to_string(),
+            endpoints: vec![
+                LocalityLbEndpoints {
+                    locality: Some(Locality { zone: "eu-west-1a".to_string(), ..Default::default() }),
+                    lb_endpoints: vec![
+                        endpoint("10.0.0.1", Some(5), HealthStatus::Healthy),
+                        endpoint("fd00::2", None, HealthStatus::Unknown),
+                        endpoint("10.0.0.3", None, HealthStatus::Draining),
+                    ],
+                    ..Default::default()
+                },
+                LocalityLbEndpoints {
+                    lb_endpoints: vec![endpoint("10.1.0.1", None, HealthStatus::Healthy)],
+                    priority: 1,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let backends = backends(&config, "api", &assignment).unwrap();
+        let summary: Vec<_> = backends
+            .iter()
+            .map(|b| (b.url.as_str(), b.weight, b.zone.as_deref(), b.pool.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("http://10.0.0.1:8080/", 5, Some("eu-west-1a"), "api"),
+                ("http://[fd00::2]:8080/", 1, Some("eu-west-1a"), "api"),
+            ]
+        );
+    }
+}
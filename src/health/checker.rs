@@ -1,26 +1,42 @@
 // src/health/checker.rs
-use crate::metrics::MetricsCollector;
-use crate::config::HealthCheckConfig;
-use crate::proxy::{Backend, BackendPool};
-use anyhow::Result;
+use crate::metrics::MetricsSink;
+use crate::config::{HealthCheckConfig, HealthCheckStep, WarmupConfig};
+use crate::proxy::{unix_uri, Backend, BackendEvent, BackendPool, Dialer, UpstreamConnector};
+use anyhow::{bail, Result};
+use hyper::body::Bytes;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, COOKIE, SET_COOKIE};
+use hyper::{header, Body, Method, Request, StatusCode};
 use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::time::{interval, timeout, Duration};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio::time::{interval, timeout};
 use tracing::{debug, error, info, warn};
 
+/// Results buffered per subscriber before the oldest are dropped.
+const RESULT_CAPACITY: usize = 1024;
+
 pub struct HealthChecker {
     config: HealthCheckConfig,
+    /// `config` with each `health_check.pools` entry applied.
+    pools: HashMap<String, HealthCheckConfig>,
     pool: Arc<BackendPool>,
     client: Client,
-    metrics: Option<Arc<MetricsCollector>>, // Add this field
+    // reqwest can't dial Unix sockets, so `unix://` backends use hyper
+    unix_client: hyper::Client<UpstreamConnector>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    results: broadcast::Sender<HealthCheckResult>,
     shutdown_tx: tokio::sync::watch::Sender<bool>,
     shutdown_rx: tokio::sync::watch::Receiver<bool>,
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct HealthCheckResult {
     pub backend_id: String,
+    pub pool: String,
     pub healthy: bool,
     pub response_time_ms: u64,
     pub error: Option<String>,
@@ -30,39 +46,54 @@ impl HealthChecker {
     pub fn new(
         config: HealthCheckConfig, 
         pool: Arc<BackendPool>,
-        metrics: Option<Arc<MetricsCollector>>, // Add parameter
+        metrics: Option<Arc<dyn MetricsSink>>,
     ) -> Self {
+        // Checks are timed out as a whole, with their pool's timeout
         let client = Client::builder()
-            .timeout(Duration::from_secs(config.timeout_secs))
             .build()
             .expect("Failed to create HTTP client");
+        let unix_client = hyper::Client::builder()
+            .build::<_, Body>(UpstreamConnector::new(Dialer::default()));
         
         let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
         
+        let pools = config
+            .pools
+            .iter()
+            .map(|(name, pool)| (name.clone(), config.for_pool(pool)))
+            .collect();
         Self {
             config,
+            pools,
             pool,
             client,
+            unix_client,
             metrics, // Store it
+            results: broadcast::channel(RESULT_CAPACITY).0,
             shutdown_tx,
             shutdown_rx,
         }
     }
     
     pub async fn start(self: Arc<Self>) {
-        let mut interval = interval(self.config.interval());
+        let mut interval = interval(self.config.tick());
         let mut shutdown_rx = self.shutdown_rx.clone();
         
         info!(
             "Starting health checker with interval: {:?}", 
             self.config.interval()
         );
+        if let Some(fast) = self.config.unhealthy_interval_secs {
+            info!("Rechecking unhealthy backends every {}s", fast);
+        }
         
+        let mut round = 0;
         loop {
             tokio::select! {
                 _ = interval.tick() => {
                     // pass an Arc<Self> so the method can clone internally for spawning
-                    self.clone().check_all_backends().await;
+                    self.clone().check_all_backends(round).await;
+                    round += 1;
                 }
                 _ = shutdown_rx.changed() => {
                     if *shutdown_rx.borrow() {
@@ -78,8 +109,65 @@ impl HealthChecker {
         let _ = self.shutdown_tx.send(true);
     }
     
-    async fn check_all_backends(self: Arc<Self>) {
+    /// Every check's result from now on, as it completes. A subscriber that
+    /// falls more than `RESULT_CAPACITY` behind misses the oldest ones.
+    pub fn subscribe(&self) -> broadcast::Receiver<HealthCheckResult> {
+        self.results.subscribe()
+    }
+    
+    /// Backends to check on tick `round`: all of them every
+    /// `healthy_every` ticks, and in between only the ones that aren't
+    /// healthy.
+    fn due(&self, round: u64) -> Vec<Arc<Backend>> {
+        let backends = self.pool.all_backends();
+        if round.is_multiple_of(self.config.healthy_every()) {
+            return backends;
+        }
+        backends.into_iter().filter(|backend| !backend.is_healthy()).collect()
+    }
+    
+    /// Check every backend now, as a full scheduled round would, and
+    /// return the results once they're all in. The schedule carries on
+    /// regardless.
+    pub async fn run_now(self: &Arc<Self>) -> Vec<HealthCheckResult> {
         let backends = self.pool.all_backends();
+        let total = backends.len();
+        let results = self.clone().check_backends(backends).await;
+        let healthy_count = results.iter().filter(|result| result.healthy).count();
+        info!(
+            "On-demand health check complete: {} healthy, {} unhealthy", 
+            healthy_count, total - healthy_count
+        );
+        results
+    }
+    
+    async fn check_all_backends(self: Arc<Self>, round: u64) {
+        let backends = self.due(round);
+        if backends.is_empty() {
+            return;
+        }
+        let everyone = round.is_multiple_of(self.config.healthy_every());
+        let total = backends.len();
+        let results = self.check_backends(backends).await;
+        
+        // Checks that errored count as unhealthy
+        let healthy_count = results.iter().filter(|result| result.healthy).count();
+        let unhealthy_count = total - healthy_count;
+        if everyone {
+            info!(
+                "Health check complete: {} healthy, {} unhealthy", 
+                healthy_count, unhealthy_count
+            );
+        } else {
+            debug!(
+                "Recheck of unhealthy backends complete: {} healthy, {} unhealthy", 
+                healthy_count, unhealthy_count
+            );
+        }
+    }
+    
+    /// Check `backends` concurrently, then update the pool's healthy set.
+    async fn check_backends(self: Arc<Self>, backends: Vec<Arc<Backend>>) -> Vec<HealthCheckResult> {
         let mut tasks = Vec::new();
         
         for backend in backends {
@@ -94,31 +182,26 @@ impl HealthChecker {
         let results = futures::future::join_all(tasks).await; // Vec<Result<Result<HealthCheckResult, anyhow::Error>, JoinError>>
         
         // Process results
-        let mut healthy_count = 0;
-        let mut unhealthy_count = 0;
-        
+        let mut checked = Vec::with_capacity(results.len());
         for result in results {
             match result {
                 Ok(Ok(check_result)) => {
                     if check_result.healthy {
-                        healthy_count += 1;
                         debug!("Backend {} is healthy", check_result.backend_id);
                     } else {
-                        unhealthy_count += 1;
                         warn!(
                             "Backend {} is unhealthy: {:?}", 
                             check_result.backend_id, 
                             check_result.error
                         );
                     }
+                    checked.push(check_result);
                 }
                 Ok(Err(e)) => {
                     error!("Health check error: {}", e);
-                    unhealthy_count += 1;
                 }
                 Err(e) => {
                     error!("Task join error: {}", e);
-                    unhealthy_count += 1;
                 }
             }
         }
@@ -128,46 +211,41 @@ impl HealthChecker {
         
         // Update metrics with counts
         if let Some(metrics) = &self.metrics {
-            let healthy_count = self.pool.get_healthy_backends().await.len();
-            let total_count = self.pool.all_backends().len();
-            metrics.update_backend_counts(healthy_count, total_count);
+            metrics.update_pool_stats(&self.pool.pool_stats());
         }
-        
-        info!(
-            "Health check complete: {} healthy, {} unhealthy", 
-            healthy_count, unhealthy_count
-        );
+        checked
     }
     
     async fn check_backend(&self, backend: Arc<Backend>) -> Result<HealthCheckResult> {
         let start = std::time::Instant::now();
-        let url = backend.url.join(&self.config.path)?;
         
         // Read previous health state for transition logging
-        let was_healthy = backend.is_healthy().await;
+        let was_healthy = backend.is_healthy();
         
-        let result = timeout(
-            self.config.timeout(),
-            self.client.get(url.as_str()).send()
-        ).await;
+        let config = self.config_for(&backend);
+        let result = timeout(config.timeout(), self.probe(&backend, config)).await;
         
         let response_time_ms = start.elapsed().as_millis() as u64;
         
         let (healthy, error) = match result {
-            Ok(Ok(response)) => {
-                let status = response.status();
-                if status.is_success() {
-                    (true, None)
-                } else {
-                    (false, Some(format!("HTTP {}", status)))
-                }
-            }
+            Ok(Ok(())) => (true, None),
             Ok(Err(e)) => (false, Some(e.to_string())),
             Err(_) => (false, Some("Request timeout".to_string())),
         };
         
+        // Still out of rotation while it warms up
+        if let Some(warmup) = config.warmup.as_ref().filter(|_| healthy && !was_healthy) {
+            self.warm_up(&backend, warmup).await;
+        }
+        
         // Update backend health status
+        if healthy {
+            backend.record_latency(start.elapsed());
+        }
         backend.update_health(healthy).await;
+        if !healthy && backend.consecutive_failures() == 1 {
+            self.pool.publish(BackendEvent::Unhealthy(backend.id.clone()));
+        }
         
         //update metrics
         if let Some(metrics) = &self.metrics {
@@ -176,7 +254,7 @@ impl HealthChecker {
         
         // Transition logging using helpers and previous state
         if healthy {
-            if backend.is_stably_healthy(self.config.healthy_threshold as usize)
+            if backend.is_stably_healthy(config.healthy_threshold as usize)
                 && !was_healthy
             {
                 info!(
@@ -186,7 +264,7 @@ impl HealthChecker {
                 );
             }
         } else {
-            if backend.is_stably_unhealthy(self.config.unhealthy_threshold as usize)
+            if backend.is_stably_unhealthy(config.unhealthy_threshold as usize)
                 && was_healthy
             {
                 warn!(
@@ -197,11 +275,283 @@ impl HealthChecker {
             }
         }
         
-        Ok(HealthCheckResult {
+        let result = HealthCheckResult {
             backend_id: backend.id.clone(),
+            pool: backend.pool.clone(),
             healthy,
             response_time_ms,
             error,
+        };
+        // Nothing is kept if nobody is listening
+        let _ = self.results.send(result.clone());
+        Ok(result)
+    }
+    
+    /// The settings for `backend`'s pool.
+    fn config_for(&self, backend: &Backend) -> &HealthCheckConfig {
+        self.pools.get(&backend.pool).unwrap_or(&self.config)
+    }
+    
+    /// Probe one backend: a successful HTTP check, or for `tcp://` backends
+    /// just a completed connect.
+    async fn probe(&self, backend: &Backend, config: &HealthCheckConfig) -> Result<()> {
+        if backend.is_tcp() {
+            TcpStream::connect(backend.tcp_address()).await?;
+            return Ok(());
+        }
+        
+        let status = if let Some(socket_path) = backend.unix_socket_path() {
+            let req = Request::get(unix_uri(socket_path, &config.path)?)
+                .header(header::HOST, "localhost")
+                .body(Body::empty())?;
+            self.unix_client.request(req).await?.status()
+        } else {
+            let url = backend.url.join(&config.path)?;
+            self.client.get(url.as_str()).send().await?.status()
+        };
+        
+        if !status.is_success() {
+            bail!("HTTP {}", status);
+        }
+        self.run_steps(backend, &config.steps).await
+    }
+    
+    /// Send `warmup`'s requests, giving up once its timeout passes.
+    async fn warm_up(&self, backend: &Backend, warmup: &WarmupConfig) {
+        let started = std::time::Instant::now();
+        let mut headers = HeaderMap::new();
+        for (name, value) in &warmup.headers {
+            // Validated in Config::validate
+            if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                headers.insert(name, value);
+            }
+        }
+        let mut sent = 0;
+        let requests = async {
+            for path in &warmup.paths {
+                for _ in 0..warmup.repeat {
+                    if let Err(e) = self.send(backend, Method::GET, path, headers.clone(), String::new()).await {
+                        debug!(backend = %backend.id, path = %path, error = %e, "Warm-up request failed");
+                    }
+                    sent += 1;
+                }
+            }
+        };
+        if timeout(warmup.timeout(), requests).await.is_err() {
+            warn!(backend = %backend.id, sent, "Warm-up timed out, putting backend in rotation anyway");
+            return;
+        }
+        info!(backend = %backend.id, sent, took = ?started.elapsed(), "Warmed up backend");
+    }
+    
+    /// The configured `steps`, in order, each with the cookies the ones
+    /// before it were given.
+    async fn run_steps(&self, backend: &Backend, steps: &[HealthCheckStep]) -> Result<()> {
+        let mut cookies: Vec<(String, String)> = Vec::new();
+        for (i, step) in steps.iter().enumerate() {
+            let mut headers = HeaderMap::new();
+            for (name, value) in &step.headers {
+                headers.insert(HeaderName::from_bytes(name.as_bytes())?, HeaderValue::from_str(value)?);
+            }
+            if !cookies.is_empty() {
+                let cookie: Vec<String> = cookies.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+                headers.insert(COOKIE, HeaderValue::from_str(&cookie.join("; "))?);
+            }
+            let method = Method::from_bytes(step.method.as_bytes())?;
+            let body = step.body.clone().unwrap_or_default();
+            let (status, response_headers, body) = self.send(backend, method, &step.path, headers, body).await?;
+            
+            for set_cookie in response_headers.get_all(SET_COOKIE) {
+                let pair = set_cookie.to_str().ok().and_then(|c| c.split(';').next()?.split_once('='));
+                if let Some((name, value)) = pair {
+                    cookies.retain(|(kept, _)| kept != name.trim());
+                    cookies.push((name.trim().to_string(), value.trim().to_string()));
+                }
+            }
+            
+            let passed = match step.expect_status {
+                Some(expected) => status.as_u16() == expected,
+                None => status.is_success(),
+            };
+            if !passed {
+                bail!("Step {} ({} {}): HTTP {}", i + 1, step.method, step.path, status);
+            }
+            if let Some(expected) = &step.expect_body {
+                if !String::from_utf8_lossy(&body).contains(expected.as_str()) {
+                    bail!("Step {} ({} {}): body doesn't contain {:?}", i + 1, step.method, step.path, expected);
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    async fn send(
+        &self,
+        backend: &Backend,
+        method: Method,
+        path: &str,
+        headers: HeaderMap,
+        body: String,
+    ) -> Result<(StatusCode, HeaderMap, Bytes)> {
+        if let Some(socket_path) = backend.unix_socket_path() {
+            let mut req = Request::builder()
+                .method(method)
+                .uri(unix_uri(socket_path, path)?)
+                .header(header::HOST, "localhost")
+                .body(Body::from(body))?;
+            req.headers_mut().extend(headers);
+            let (parts, body) = self.unix_client.request(req).await?.into_parts();
+            return Ok((parts.status, parts.headers, hyper::body::to_bytes(body).await?));
+        }
+        let url = backend.url.join(path)?;
+        let response = self.client.request(method, url.as_str()).headers(headers).body(body).send().await?;
+        let (status, headers) = (response.status(), response.headers().clone());
+        Ok((status, headers, response.bytes().await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackendConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_rechecks_unhealthy_backends_between_rounds() {
+        let config: HealthCheckConfig = serde_yaml::from_str("{ interval_secs: 10, unhealthy_interval_secs: 2 }").unwrap();
+        let backends: Vec<BackendConfig> =
+            serde_yaml::from_str("[{ url: 'http://10.0.0.1:80' }, { url: 'http://10.0.0.2:80' }]").unwrap();
+        let pool = Arc::new(BackendPool::new(backends));
+        pool.get_backend("10.0.0.1:80").unwrap().update_health(true).await;
+        pool.get_backend("10.0.0.2:80").unwrap().update_health(false).await;
+        let checker = HealthChecker::new(config, pool, None);
+        assert_eq!(checker.config.tick(), Duration::from_secs(2));
+
+        let ids = |round| checker.due(round).iter().map(|b| b.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(0).len(), 2);
+        for round in 1..5 {
+            assert_eq!(ids(round), ["10.0.0.2:80"]);
+        }
+        assert_eq!(ids(5).len(), 2);
+    }
+
+    /// Healthy at `/health`, with a `/profile` that needs the session
+    /// cookie `POST /login` sets, and a `/warm` that counts its requests.
+    async fn app(warmed: Arc<AtomicUsize>) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let make_service = hyper::service::make_service_fn(move |_| {
+            let warmed = warmed.clone();
+            async move {
+                Ok::<_, std::convert::Infallible>(hyper::service::service_fn(move |req| {
+                    respond(warmed.clone(), req)
+                }))
+            }
+        });
+        tokio::spawn(hyper::Server::from_tcp(listener).unwrap().serve(make_service));
+        format!("http://{}", addr)
+    }
+
+    async fn respond(
+        warmed: Arc<AtomicUsize>,
+        req: Request<Body>,
+    ) -> Result<hyper::Response<Body>, std::convert::Infallible> {
+        Ok(match (req.method().as_str(), req.uri().path()) {
+            ("GET", "/health") => hyper::Response::new(Body::empty()),
+            ("GET", "/warm") => {
+                warmed.fetch_add(1, Ordering::SeqCst);
+                hyper::Response::new(Body::empty())
+            }
+            ("POST", "/login") => hyper::Response::builder()
+                .header(SET_COOKIE, "session=abc; Path=/; HttpOnly")
+                .body(Body::empty())
+                .unwrap(),
+            ("GET", "/profile") if req.headers().get(COOKIE).is_some_and(|c| c == "session=abc") => {
+                hyper::Response::new(Body::from("welcome back"))
+            }
+            _ => hyper::Response::builder().status(401).body(Body::empty()).unwrap(),
         })
     }
+
+    #[tokio::test]
+    async fn test_runs_steps_with_cookies() {
+        let url = app(Arc::new(AtomicUsize::new(0))).await;
+        let backend: BackendConfig = serde_yaml::from_str(&format!("url: '{}'", url)).unwrap();
+        let backend = Backend::new(&backend);
+        let checker = |steps: &str| {
+            let config = serde_yaml::from_str(&format!("steps: {}", steps)).unwrap();
+            HealthChecker::new(config, Arc::new(BackendPool::new(Vec::new())), None)
+        };
+
+        let login = "{ method: POST, path: /login, body: 'user=probe' }";
+        let passing = checker(&format!("[{}, {{ path: /profile, expect_body: welcome }}]", login));
+        passing.probe(&backend, &passing.config).await.unwrap();
+
+        let wrong_body = checker(&format!("[{}, {{ path: /profile, expect_body: goodbye }}]", login));
+        assert!(wrong_body.probe(&backend, &wrong_body.config).await.unwrap_err().to_string().contains("Step 2"));
+        let no_login = checker("[{ path: /profile, expect_status: 200 }]");
+        assert!(no_login.probe(&backend, &no_login.config).await.unwrap_err().to_string().contains("HTTP 401"));
+
+        // Subscribers see each check as it's made
+        let mut results = no_login.subscribe();
+        no_login.check_backend(Arc::new(backend)).await.unwrap();
+        let result = results.recv().await.unwrap();
+        assert_eq!((result.backend_id.as_str(), result.pool.as_str(), result.healthy), (url.trim_start_matches("http://"), "default", false));
+        assert!(result.error.unwrap().contains("HTTP 401"));
+    }
+
+    #[tokio::test]
+    async fn test_warms_up_backends_coming_into_rotation() {
+        let warmed = Arc::new(AtomicUsize::new(0));
+        let url = app(warmed.clone()).await;
+        let backend: BackendConfig = serde_yaml::from_str(&format!("url: '{}'", url)).unwrap();
+        let pool = Arc::new(BackendPool::new(vec![backend]));
+        let backend = pool.all_backends().remove(0);
+        let config = serde_yaml::from_str("warmup: { paths: [/warm, /missing], repeat: 3 }").unwrap();
+        let checker = HealthChecker::new(config, pool, None);
+
+        assert!(checker.check_backend(backend.clone()).await.unwrap().healthy);
+        assert_eq!(warmed.load(Ordering::SeqCst), 3);
+        // Only on the way in
+        checker.check_backend(backend).await.unwrap();
+        assert_eq!(warmed.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_runs_a_sweep_on_demand() {
+        let url = app(Arc::new(AtomicUsize::new(0))).await;
+        let backends: Vec<BackendConfig> =
+            serde_yaml::from_str(&format!("[{{ url: '{}' }}, {{ url: 'http://127.0.0.1:9' }}]", url)).unwrap();
+        let pool = Arc::new(BackendPool::new(backends));
+        let config = serde_yaml::from_str("{ healthy_threshold: 1 }").unwrap();
+        let checker = Arc::new(HealthChecker::new(config, pool.clone(), None));
+
+        let mut results = checker.run_now().await;
+        results.sort_by_key(|result| !result.healthy);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].backend_id, url.trim_start_matches("http://"));
+        assert!(results[0].healthy && !results[1].healthy);
+        assert_eq!(pool.get_healthy_backends().len(), 1);
+        let json = serde_json::to_value(&results[1]).unwrap();
+        assert_eq!(json["backend_id"], "127.0.0.1:9");
+    }
+
+    #[test]
+    fn test_pools_override_top_level_settings() {
+        let config: HealthCheckConfig = serde_yaml::from_str(
+            "{ path: /health, steps: [{ path: /login }], pools: { search: { path: /ready, timeout_secs: 1, steps: [] } } }",
+        )
+        .unwrap();
+        let backends: Vec<BackendConfig> =
+            serde_yaml::from_str("[{ url: 'http://10.0.0.1:80', pool: search }, { url: 'http://10.0.0.2:80' }]").unwrap();
+        let pool = Arc::new(BackendPool::new(backends));
+        let checker = HealthChecker::new(config, pool.clone(), None);
+
+        let search = checker.config_for(&pool.get_backend("10.0.0.1:80").unwrap());
+        assert_eq!((search.path.as_str(), search.timeout_secs, search.steps.len()), ("/ready", 1, 0));
+        assert_eq!(search.healthy_threshold, 2);
+        let default = checker.config_for(&pool.get_backend("10.0.0.2:80").unwrap());
+        assert_eq!((default.path.as_str(), default.timeout_secs, default.steps.len()), ("/health", 3, 1));
+    }
 }
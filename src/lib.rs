@@ -1,9 +1,24 @@
 // src/lib.rs
+pub mod admin;
+mod builder;
+pub mod cache;
 pub mod config;
 pub mod server;
 pub mod proxy;
 pub mod load_balancer;
 pub mod health;
+mod lifecycle;
+pub mod logging;
 pub mod circuit_breaker;
+pub mod cluster;
 pub mod retry;
-pub mod metrics;
\ No newline at end of file
+pub mod metrics;
+pub mod middleware;
+pub mod tcp_proxy;
+pub mod routing;
+pub mod startup;
+pub mod discovery;
+pub mod testing;
+
+pub use builder::{Builder, Instance};
+pub use lifecycle::{Lifecycle, LoadBalancerHandle};
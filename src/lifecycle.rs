@@ -0,0 +1,149 @@
+// src/lifecycle.rs
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+
+use crate::config::Config;
+use crate::proxy::Proxy;
+
+/// Where an `Instance` is in its life. Each state follows the one before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lifecycle {
+    /// Built; `serve` hasn't been called.
+    Built,
+    /// `serve` is running the listeners.
+    Started,
+    /// No longer accepting; waiting for open connections.
+    Draining,
+    /// `serve` has returned.
+    Stopped,
+}
+
+/// A cloneable handle to an `Instance`, for whatever hosts it to stop or
+/// reload it and watch it change state.
+#[derive(Clone)]
+pub struct LoadBalancerHandle {
+    proxy: Arc<Proxy>,
+    /// How long to drain for, once shutdown is asked for.
+    pub(crate) shutdown: Arc<watch::Sender<Option<Duration>>>,
+    state: Arc<watch::Sender<Lifecycle>>,
+    events: broadcast::Sender<Lifecycle>,
+}
+
+impl LoadBalancerHandle {
+    pub(crate) fn new(proxy: Arc<Proxy>) -> Self {
+        Self {
+            proxy,
+            shutdown: Arc::new(watch::channel(None).0),
+            state: Arc::new(watch::channel(Lifecycle::Built).0),
+            // There are only three changes to send
+            events: broadcast::channel(4).0,
+        }
+    }
+
+    pub fn state(&self) -> Lifecycle {
+        *self.state.borrow()
+    }
+
+    /// Each state entered from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Lifecycle> {
+        self.events.subscribe()
+    }
+
+    /// Stop accepting, give open connections up to `grace` to finish and
+    /// return once `serve` has. If it isn't serving yet, it stops as soon
+    /// as it starts.
+    pub async fn shutdown(&self, grace: Duration) {
+        self.request_shutdown(grace);
+        if self.state() == Lifecycle::Built {
+            return;
+        }
+        let mut states = self.state.subscribe();
+        let _ = states.wait_for(|state| *state == Lifecycle::Stopped).await;
+    }
+
+    /// See `Proxy::reload`.
+    pub async fn reload(&self, config: Config) -> Result<()> {
+        self.proxy.reload(config).await
+    }
+
+    /// Read the config file at `path` and reload from it. A file that
+    /// doesn't load counts as a failed reload in `lb_config_reload_total`.
+    pub async fn reload_from(&self, path: &str) -> Result<()> {
+        match crate::config::load_config(path).await {
+            Ok(config) => self.reload(config).await,
+            Err(e) => {
+                self.proxy.metrics().record_config_reload("failure");
+                Err(e)
+            }
+        }
+    }
+
+    pub(crate) fn request_shutdown(&self, grace: Duration) {
+        self.shutdown.send_if_modified(|shutdown| {
+            // The first deadline asked for stands
+            let first = shutdown.is_none();
+            if first {
+                *shutdown = Some(grace);
+            }
+            first
+        });
+    }
+
+    pub(crate) fn set_state(&self, state: Lifecycle) {
+        self.state.send_replace(state);
+        let _ = self.events.send(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Builder;
+
+    fn config(backends: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            "listeners: [{{ name: http, address: '127.0.0.1:0' }}]\n\
+             load_balancer: {{}}\nbackends: {}\nhealth_check: {{}}\n\
+             circuit_breaker: {{}}\nretry: {{}}\nmetrics: {{ enabled: false }}",
+            backends
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handle_reloads_and_shuts_down() {
+        let lb = Arc::new(
+            Builder::new(config("[{ url: 'http://127.0.0.1:8001' }]"))
+                .build()
+                .await
+                .unwrap(),
+        );
+        let handle = lb.handle();
+        let mut events = handle.subscribe();
+        let serving = tokio::spawn({
+            let lb = lb.clone();
+            async move { lb.serve().await }
+        });
+        assert_eq!(events.recv().await.unwrap(), Lifecycle::Started);
+
+        handle
+            .reload(config("[{ url: 'http://127.0.0.1:8002' }]"))
+            .await
+            .unwrap();
+        let pool = lb.pool();
+        assert!(pool.get_backend("127.0.0.1:8002").is_some());
+        assert!(pool.get_backend("127.0.0.1:8001").is_none_or(|a| a.is_draining()));
+        assert_eq!(lb.config().backends[0].url.port(), Some(8002));
+        // Invalid configs are turned away whole
+        assert!(handle.reload(config("[]")).await.is_err());
+        assert!(pool.get_backend("127.0.0.1:8002").is_some());
+
+        handle.shutdown(Duration::from_secs(1)).await;
+        assert_eq!(handle.state(), Lifecycle::Stopped);
+        assert_eq!(events.recv().await.unwrap(), Lifecycle::Draining);
+        assert_eq!(events.recv().await.unwrap(), Lifecycle::Stopped);
+        serving.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+// src/load_balancer/dynamic_weights.rs
+use crate::config::DynamicWeightsConfig;
+use crate::proxy::BackendPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+
+/// Scales backends' weights by how their latency compares with the rest
+/// of their pool's; see [`DynamicWeightsConfig`].
+pub struct DynamicWeights {
+    interval: Duration,
+    /// Weight of each interval's mean in the smoothed latency.
+    smoothing: f64,
+    min_scale: f64,
+    /// Smoothed latency in seconds, by backend ID.
+    latencies: HashMap<String, f64>,
+}
+
+impl DynamicWeights {
+    /// Validated in Config::validate.
+    pub fn new(config: &DynamicWeightsConfig) -> Self {
+        Self {
+            interval: Duration::from_secs(config.interval_secs),
+            smoothing: config.interval_secs as f64 / config.window_secs as f64,
+            min_scale: f64::from(config.min_weight_percent) / 100.0,
+            latencies: HashMap::new(),
+        }
+    }
+
+    /// Update `pool`'s weights every interval until the process exits.
+    pub fn spawn(mut self, pool: Arc<BackendPool>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            // The first tick is immediate, before anything was measured
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                self.update(&pool);
+            }
+        });
+    }
+
+    /// Fold the latencies seen since the last update in and rescale.
+    fn update(&mut self, pool: &BackendPool) {
+        let backends = pool.all_backends();
+        let latencies = &mut self.latencies;
+        latencies.retain(|id, _| backends.iter().any(|backend| &backend.id == id));
+        for backend in &backends {
+            if let Some(latency) = backend.take_latency() {
+                let latency = latency.as_secs_f64();
+                latencies
+                    .entry(backend.id.clone())
+                    .and_modify(|smoothed| *smoothed += self.smoothing * (latency - *smoothed))
+                    .or_insert(latency);
+            }
+        }
+
+        let mut by_pool: HashMap<&str, Vec<f64>> = HashMap::new();
+        for backend in &backends {
+            if let Some(latency) = latencies.get(&backend.id) {
+                by_pool.entry(&backend.pool).or_default().push(*latency);
+            }
+        }
+        let medians: HashMap<&str, f64> = by_pool.into_iter().map(|(pool, latencies)| (pool, median(latencies))).collect();
+        for backend in &backends {
+            let scale = match (latencies.get(&backend.id), medians.get(backend.pool.as_str())) {
+                (Some(&latency), Some(&median)) if latency > median => (median / latency).max(self.min_scale),
+                _ => 1.0,
+            };
+            debug!(backend = %backend.id, scale, "Scaled backend weight");
+            backend.set_weight_scale(scale);
+        }
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    match values.len() % 2 {
+        0 => (values[middle - 1] + values[middle]) / 2.0,
+        _ => values[middle],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackendConfig;
+
+    #[test]
+    fn test_scales_down_backends_slower_than_the_median() {
+        let configs: Vec<BackendConfig> = ["http://10.0.0.1", "http://10.0.0.2", "http://10.0.0.3"]
+            .iter()
+            .map(|url| serde_yaml::from_str(&format!("{{ url: '{}', weight: 10 }}", url)).unwrap())
+            .collect();
+        let pool = Arc::new(BackendPool::new(configs));
+        let config = DynamicWeightsConfig { interval_secs: 10, window_secs: 20, min_weight_percent: 10 };
+        let mut weights = DynamicWeights::new(&config);
+        let backend = |id: &str| pool.get_backend(id).unwrap();
+        for (id, millis) in [("10.0.0.1:80", 10), ("10.0.0.2:80", 20), ("10.0.0.3:80", 40)] {
+            backend(id).record_latency(Duration::from_millis(millis));
+        }
+
+        weights.update(&pool);
+        assert_eq!(backend("10.0.0.1:80").effective_weight(), 10);
+        assert_eq!(backend("10.0.0.2:80").effective_weight(), 10);
+        assert_eq!(backend("10.0.0.3:80").effective_weight(), 5);
+
+        // Smoothed over two intervals, half of a slow one shows
+        backend("10.0.0.3:80").record_latency(Duration::from_millis(1960));
+        weights.update(&pool);
+        assert_eq!(backend("10.0.0.3:80").effective_weight(), 1);
+        // The floor is a share of the weight in effect
+        backend("10.0.0.3:80").set_weight(50);
+        assert_eq!(backend("10.0.0.3:80").effective_weight(), 5);
+    }
+}
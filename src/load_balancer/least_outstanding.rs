@@ -0,0 +1,82 @@
+// src/load_balancer/least_outstanding.rs
+use crate::config::LeastOutstandingConfig;
+use crate::load_balancer::LoadBalancer;
+use crate::proxy::Backend;
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Picks from the backends with the fewest requests in flight, counted in
+/// tiers so small differences don't matter, the one whose recent median
+/// latency is lowest. Backends nothing has been measured on yet go first,
+/// and remaining ties take turns.
+pub struct LeastOutstandingBalancer {
+    /// Ascending; a backend's tier is how many of these it has reached.
+    tiers: Vec<usize>,
+    counter: AtomicUsize,
+}
+
+impl LeastOutstandingBalancer {
+    pub fn new(config: &LeastOutstandingConfig) -> Self {
+        Self { tiers: config.tiers.clone(), counter: AtomicUsize::new(0) }
+    }
+
+    fn tier(&self, backend: &Backend) -> usize {
+        let outstanding = backend.active_connections();
+        self.tiers.iter().take_while(|&&threshold| outstanding >= threshold).count()
+    }
+}
+
+#[async_trait]
+impl LoadBalancer for LeastOutstandingBalancer {
+    async fn select_backend(
+        &self,
+        backends: &[Arc<Backend>],
+        _client_addr: Option<SocketAddr>,
+    ) -> Option<Arc<Backend>> {
+        // Starting somewhere new each time spreads ties around
+        let start = self.counter.fetch_add(1, Ordering::Relaxed) % backends.len().max(1);
+        let rotated = || backends[start..].iter().chain(&backends[..start]);
+        let lowest = rotated().map(|backend| self.tier(backend)).min()?;
+        rotated()
+            .filter(|backend| self.tier(backend) == lowest)
+            .min_by_key(|backend| (backend.recent_p50().unwrap_or(Duration::ZERO), backend.active_connections()))
+            .cloned()
+    }
+
+    fn name(&self) -> &'static str {
+        "least_outstanding"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackendConfig;
+
+    fn backend(url: &str, latency_ms: u64) -> Arc<Backend> {
+        let config: BackendConfig = serde_yaml::from_str(&format!("url: '{}'", url)).unwrap();
+        let backend = Arc::new(Backend::new(&config));
+        backend.record_latency(Duration::from_millis(latency_ms));
+        backend
+    }
+
+    #[tokio::test]
+    async fn test_prefers_idle_then_fast_backends() {
+        let backends = vec![backend("http://10.0.0.1", 50), backend("http://10.0.0.2", 10), backend("http://10.0.0.3", 30)];
+        let balancer = LeastOutstandingBalancer::new(&LeastOutstandingConfig { tiers: vec![1, 4] });
+        let pick = async || balancer.select_backend(&backends, None).await.unwrap().id.clone();
+        assert_eq!(pick().await, "10.0.0.2:80");
+
+        // Busy, if only just, so it's behind the idle ones
+        let _busy = backends[1].acquire().unwrap();
+        assert_eq!(pick().await, "10.0.0.3:80");
+
+        // Within a tier, in-flight counts don't matter but latency does
+        let _held: Vec<_> = (0..2).map(|_| backends[0].acquire().unwrap()).collect();
+        let _also = backends[2].acquire().unwrap();
+        assert_eq!(pick().await, "10.0.0.2:80");
+    }
+}
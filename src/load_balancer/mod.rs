@@ -1,21 +1,26 @@
 // src/load_balancer/mod.rs
+mod dynamic_weights;
+mod least_outstanding;
+mod regions;
 mod round_robin;
 mod traits;
+mod weighted_round_robin;
 
+pub use dynamic_weights::DynamicWeights;
+pub use regions::RegionSpillover;
 pub use traits::LoadBalancer;
+use least_outstanding::LeastOutstandingBalancer;
 use round_robin::RoundRobinBalancer;
+use weighted_round_robin::WeightedRoundRobinBalancer;
 
-use crate::config::LoadBalancerAlgorithm as ConfigAlgorithm;
+use crate::config::{LoadBalancerAlgorithm as ConfigAlgorithm, LoadBalancerConfig};
 use std::sync::Arc;
 
 /// Factory function to create a load balancer based on the algorithm
-pub fn create_load_balancer(algorithm: ConfigAlgorithm) -> Arc<dyn LoadBalancer> {
-    match algorithm {
+pub fn create_load_balancer(config: &LoadBalancerConfig) -> Arc<dyn LoadBalancer> {
+    match config.algorithm {
         ConfigAlgorithm::RoundRobin => Arc::new(RoundRobinBalancer::new()),
-        ConfigAlgorithm::WeightedRoundRobin => {
-            // TODO: Implement weighted round robin
-            Arc::new(RoundRobinBalancer::new())
-        }
+        ConfigAlgorithm::WeightedRoundRobin => Arc::new(WeightedRoundRobinBalancer::new()),
         ConfigAlgorithm::LeastConnections => {
             // TODO: Implement least connections
             Arc::new(RoundRobinBalancer::new())
@@ -24,5 +29,6 @@ pub fn create_load_balancer(algorithm: ConfigAlgorithm) -> Arc<dyn LoadBalancer>
             // TODO: Implement IP hash
             Arc::new(RoundRobinBalancer::new())
         }
+        ConfigAlgorithm::LeastOutstanding => Arc::new(LeastOutstandingBalancer::new(&config.least_outstanding)),
     }
 }
\ No newline at end of file
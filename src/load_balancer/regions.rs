@@ -0,0 +1,136 @@
+// src/load_balancer/regions.rs
+use crate::config::RegionsConfig;
+use crate::proxy::Backend;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Narrows a pool's healthy backends to one region's; see [`RegionsConfig`].
+pub struct RegionSpillover {
+    local: String,
+    /// Nearest first.
+    remote: Vec<RemoteRegion>,
+}
+
+struct RemoteRegion {
+    name: String,
+    /// Largest fraction of a pool's requests sent here.
+    share: f64,
+    penalty: Duration,
+}
+
+impl RegionSpillover {
+    pub fn new(config: &RegionsConfig) -> Self {
+        let mut remote: Vec<_> = config
+            .remote
+            .iter()
+            .map(|(name, remote)| RemoteRegion {
+                name: name.clone(),
+                share: f64::from(remote.spillover_percent) / 100.0,
+                penalty: Duration::from_millis(remote.latency_penalty_ms),
+            })
+            .collect();
+        remote.sort_by(|a, b| a.penalty.cmp(&b.penalty).then_with(|| a.name.cmp(&b.name)));
+        Self { local: config.local.clone(), remote }
+    }
+
+    pub fn is_local(&self, backend: &Backend) -> bool {
+        backend.region.as_deref().is_none_or(|region| region == self.local)
+    }
+
+    /// The backends of `healthy` to pick from, and the remote region they're
+    /// in if the request spills over. `local_total` counts the pool's local
+    /// backends in or out of rotation; pools without any aren't narrowed.
+    pub fn select(
+        &self,
+        local_total: usize,
+        healthy: Vec<Arc<Backend>>,
+        budget_left: Option<Duration>,
+    ) -> (Vec<Arc<Backend>>, Option<&str>) {
+        self.select_with(local_total, healthy, budget_left, rand::random())
+    }
+
+    /// `select` at the point `roll`, in `[0, 1)`, of the spillover range.
+    fn select_with(
+        &self,
+        local_total: usize,
+        healthy: Vec<Arc<Backend>>,
+        budget_left: Option<Duration>,
+        mut roll: f64,
+    ) -> (Vec<Arc<Backend>>, Option<&str>) {
+        if local_total == 0 {
+            return (healthy, None);
+        }
+        let (local, remote): (Vec<_>, Vec<_>) = healthy.into_iter().partition(|backend| self.is_local(backend));
+        let mut lost = 1.0 - local.len().min(local_total) as f64 / local_total as f64;
+        for region in &self.remote {
+            if lost <= 0.0 {
+                break;
+            }
+            if budget_left.is_some_and(|left| left < region.penalty) {
+                continue;
+            }
+            let in_region: Vec<_> = remote
+                .iter()
+                .filter(|backend| backend.region.as_deref() == Some(region.name.as_str()))
+                .cloned()
+                .collect();
+            if in_region.is_empty() {
+                continue;
+            }
+            let share = region.share.min(lost);
+            if roll < share {
+                return (in_region, Some(&region.name));
+            }
+            roll -= share;
+            lost -= share;
+        }
+        (local, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackendConfig;
+
+    fn backend(url: &str, region: &str) -> Arc<Backend> {
+        let config: BackendConfig = serde_yaml::from_str(&format!("{{ url: '{}', region: {} }}", url, region)).unwrap();
+        Arc::new(Backend::new(&config))
+    }
+
+    #[test]
+    fn test_spills_a_bounded_share_to_the_nearest_regions() {
+        let config: RegionsConfig = serde_yaml::from_str(
+            "{ local: us-east, remote: { eu-west: { spillover_percent: 20, latency_penalty_ms: 80 }, \
+              us-west: { spillover_percent: 10, latency_penalty_ms: 40 } } }",
+        )
+        .unwrap();
+        let regions = RegionSpillover::new(&config);
+        let healthy = vec![
+            backend("http://10.0.0.1", "us-east"),
+            backend("http://10.1.0.1", "us-west"),
+            backend("http://10.2.0.1", "eu-west"),
+        ];
+        let pick = |local_total, budget_left, roll| {
+            let (backends, region) = regions.select_with(local_total, healthy.clone(), budget_left, roll);
+            (backends.iter().map(|b| b.id.clone()).collect::<Vec<_>>(), region.map(str::to_string))
+        };
+        let local = (vec!["10.0.0.1:80".to_string()], None);
+        let us_west = (vec!["10.1.0.1:80".to_string()], Some("us-west".to_string()));
+        let eu_west = (vec!["10.2.0.1:80".to_string()], Some("eu-west".to_string()));
+
+        // Nothing lost, nothing spilled
+        assert_eq!(pick(1, None, 0.0), local);
+        // Half the local backends down: us-west takes its 10%, eu-west its 20%
+        assert_eq!(pick(2, None, 0.05), us_west);
+        assert_eq!(pick(2, None, 0.25), eu_west);
+        assert_eq!(pick(2, None, 0.35), local);
+        // With every local backend down, the rest still get no backends
+        let (backends, region) = regions.select_with(1, healthy[1..].to_vec(), None, 0.5);
+        assert!(backends.is_empty() && region.is_none());
+        // Regions further away than the budget left get nothing
+        assert_eq!(pick(2, Some(Duration::from_millis(50)), 0.05), us_west);
+        assert_eq!(pick(2, Some(Duration::from_millis(50)), 0.25), local);
+        assert_eq!(pick(2, Some(Duration::from_millis(30)), 0.05), local);
+    }
+}
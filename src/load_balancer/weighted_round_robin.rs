@@ -0,0 +1,87 @@
+// src/load_balancer/weighted_round_robin.rs
+use crate::load_balancer::LoadBalancer;
+use crate::proxy::Backend;
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Each backend gets `weight` consecutive turns out of every total-weight
+/// requests. Weights are read on every pick, so live changes, and those
+/// `dynamic_weights` makes, apply to the next request.
+pub struct WeightedRoundRobinBalancer {
+    counter: AtomicU64,
+}
+
+impl WeightedRoundRobinBalancer {
+    pub fn new() -> Self {
+        Self {
+            counter: AtomicU64::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl LoadBalancer for WeightedRoundRobinBalancer {
+    async fn select_backend(
+        &self,
+        backends: &[Arc<Backend>],
+        _client_addr: Option<SocketAddr>,
+    ) -> Option<Arc<Backend>> {
+        if backends.is_empty() {
+            return None;
+        }
+        
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        let total: u64 = backends.iter().map(|b| u64::from(b.effective_weight())).sum();
+        if total == 0 {
+            return Some(backends[(count % backends.len() as u64) as usize].clone());
+        }
+        let mut point = count % total;
+        for backend in backends {
+            let weight = u64::from(backend.effective_weight());
+            if point < weight {
+                return Some(backend.clone());
+            }
+            point -= weight;
+        }
+        // A weight changed between the sum and the walk
+        backends.last().cloned()
+    }
+    
+    fn name(&self) -> &'static str {
+        "weighted_round_robin"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackendConfig;
+
+    fn backend(url: &str, weight: u32) -> Arc<Backend> {
+        let config: BackendConfig = serde_yaml::from_str(&format!("{{ url: \"{}\", weight: {} }}", url, weight)).unwrap();
+        Arc::new(Backend::new(&config))
+    }
+
+    #[tokio::test]
+    async fn test_follows_live_weights() {
+        let backends = vec![backend("http://10.0.0.1", 3), backend("http://10.0.0.2", 1)];
+        let balancer = WeightedRoundRobinBalancer::new();
+        let mut picks = Vec::new();
+        for _ in 0..8 {
+            picks.push(balancer.select_backend(&backends, None).await.unwrap().id.clone());
+        }
+        assert_eq!(picks.iter().filter(|id| *id == "10.0.0.1:80").count(), 6);
+
+        backends[0].set_weight(1);
+        backends[1].set_weight(9);
+        let mut second = 0;
+        for _ in 0..10 {
+            if balancer.select_backend(&backends, None).await.unwrap().id == "10.0.0.2:80" {
+                second += 1;
+            }
+        }
+        assert_eq!(second, 9);
+    }
+}
@@ -0,0 +1,217 @@
+// src/logging/format.rs
+use crate::config::{LogFormat, LoggingConfig};
+use serde_json::Value;
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// A span's fields, kept in its extensions by `SpanFields` so events can
+/// carry them as fields of their own.
+#[derive(Default)]
+struct Recorded(Vec<(&'static str, Value)>);
+
+impl Visit for Recorded {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name(), Value::from(value)));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.push((field.name(), Value::from(value)));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.push((field.name(), Value::from(value)));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.push((field.name(), Value::from(value)));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.push((field.name(), Value::from(value)));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.push((field.name(), Value::from(format!("{:?}", value))));
+    }
+}
+
+/// Records span fields for `Format`.
+pub struct SpanFields;
+
+impl<S> Layer<S> for SpanFields
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut recorded = Recorded::default();
+        attrs.record(&mut recorded);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(recorded);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(recorded) = span.extensions_mut().get_mut::<Recorded>() {
+                values.record(recorded);
+            }
+        }
+    }
+}
+
+/// The fmt layer's own span field formatting, which `Format` doesn't use.
+pub struct NoFields;
+
+impl<'writer> FormatFields<'writer> for NoFields {
+    fn format_fields<R: RecordFields>(&self, _writer: Writer<'writer>, _fields: R) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// One line per event: text (`<time> <level> <target>: <message> k=v ...`)
+/// or a JSON object, with the fields of the spans it's in before its own.
+pub struct Format {
+    format: LogFormat,
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl Format {
+    pub fn new(config: &LoggingConfig) -> Self {
+        Self {
+            format: config.format,
+            include: config.include_fields.clone(),
+            exclude: config.exclude_fields.clone(),
+        }
+    }
+
+    fn keep(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|f| f == name))
+            && !self.exclude.iter().any(|f| f == name)
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for Format
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let mut fields = Recorded::default();
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                if let Some(recorded) = span.extensions().get::<Recorded>() {
+                    fields.0.extend(recorded.0.iter().cloned());
+                }
+            }
+        }
+        event.record(&mut fields);
+        let mut message = None;
+        let mut kept: Vec<(&'static str, Value)> = Vec::with_capacity(fields.0.len());
+        for (name, value) in fields.0 {
+            if name == "message" {
+                message = Some(value);
+            } else if self.keep(name) {
+                // An event's field replaces a span's of the same name
+                kept.retain(|(other, _)| *other != name);
+                kept.push((name, value));
+            }
+        }
+
+        let meta = event.metadata();
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+        match self.format {
+            LogFormat::Text => {
+                write!(writer, "{} {:>5} {}:", timestamp, meta.level(), meta.target())?;
+                if let Some(Value::String(message)) = &message {
+                    write!(writer, " {}", message)?;
+                }
+                for (name, value) in &kept {
+                    match value {
+                        Value::String(s) if s.is_empty() || s.contains([' ', '"', '=']) => {
+                            write!(writer, " {}={:?}", name, s)?
+                        }
+                        Value::String(s) => write!(writer, " {}={}", name, s)?,
+                        other => write!(writer, " {}={}", name, other)?,
+                    }
+                }
+            }
+            LogFormat::Json => {
+                let mut line = serde_json::Map::new();
+                line.insert("timestamp".into(), Value::from(timestamp));
+                line.insert("level".into(), Value::from(meta.level().as_str()));
+                line.insert("target".into(), Value::from(meta.target()));
+                if let Some(message) = message {
+                    line.insert("message".into(), message);
+                }
+                for (name, value) in kept {
+                    line.entry(name.to_string()).or_insert(value);
+                }
+                write!(writer, "{}", Value::Object(line))?;
+            }
+        }
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn log_with(config: &str) -> String {
+        let config: LoggingConfig = serde_yaml::from_str(config).unwrap();
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::registry().with(SpanFields).with(
+            tracing_subscriber::fmt::layer()
+                .event_format(Format::new(&config))
+                .fmt_fields(NoFields)
+                .with_writer(move || writer.clone()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", request_id = "abc-123");
+            let _entered = span.enter();
+            tracing::info!(backend = "10.0.0.1:80", status = 200, client = %"192.0.2.1", "Request completed");
+        });
+        let output = captured.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_formats_with_span_fields_and_exclusions() {
+        let json: Value = serde_json::from_str(&log_with("{ format: json, exclude_fields: [client] }")).unwrap();
+        assert_eq!(json["level"], "INFO");
+        assert_eq!(json["message"], "Request completed");
+        assert_eq!(json["request_id"], "abc-123");
+        assert_eq!(json["backend"], "10.0.0.1:80");
+        assert_eq!(json["status"], 200);
+        assert!(json.get("client").is_none());
+
+        let text = log_with("{ include_fields: [request_id, status] }");
+        assert!(text.ends_with(" INFO rust_load_balancer::logging::format::tests: Request completed request_id=abc-123 status=200\n"), "{}", text);
+    }
+}
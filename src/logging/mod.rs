@@ -0,0 +1,58 @@
+// src/logging/mod.rs
+//! The binary's tracing setup, from the `logging` config section, the
+//! sampling of per-request log lines, the slow request log and the admin
+//! API's live tap. Embedders that install their own subscriber needn't
+//! call `init`.
+mod file;
+mod format;
+mod sample;
+mod slow;
+mod tap;
+
+pub use file::RotatingFile;
+pub use format::{Format, NoFields, SpanFields};
+pub use sample::Sampler;
+pub use slow::SlowLog;
+pub use tap::{RequestTap, TapEvent, TapFilter};
+
+use crate::config::LoggingConfig;
+use anyhow::{anyhow, Context, Result};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Install the global subscriber `config` describes.
+pub fn init(config: &LoggingConfig) -> Result<()> {
+    let writer = match &config.file {
+        Some(file) => BoxMakeWriter::new(
+            RotatingFile::open(file).with_context(|| format!("Failed to open log file {}", file.path.display()))?,
+        ),
+        None => BoxMakeWriter::new(std::io::stdout),
+    };
+    tracing_subscriber::registry()
+        .with(filter(config)?)
+        .with(SpanFields)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .event_format(Format::new(config))
+                .fmt_fields(NoFields)
+                .with_writer(writer),
+        )
+        .try_init()
+        .map_err(|e| anyhow!("Failed to install the log subscriber: {}", e))
+}
+
+/// `level` and `modules`, then `RUST_LOG`'s directives over them.
+pub fn filter(config: &LoggingConfig) -> Result<EnvFilter> {
+    let mut filter = EnvFilter::try_new(&config.level)?;
+    for (module, level) in &config.modules {
+        filter = filter.add_directive(format!("{}={}", module, level).parse()?);
+    }
+    if let Ok(env) = std::env::var(EnvFilter::DEFAULT_ENV) {
+        for directive in env.split(',').filter(|d| !d.trim().is_empty()) {
+            filter = filter.add_directive(directive.parse().with_context(|| format!("Invalid RUST_LOG directive {:?}", directive))?);
+        }
+    }
+    Ok(filter)
+}
@@ -0,0 +1,43 @@
+// src/logging/sample.rs
+use crate::config::LogSamplingConfig;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Decides which finished requests get a log line, per `logging.sampling`.
+pub struct Sampler {
+    one_in: u64,
+    slow: Option<Duration>,
+    seen: AtomicU64,
+}
+
+impl Sampler {
+    pub fn new(config: &LogSamplingConfig) -> Self {
+        Self {
+            one_in: config.success_one_in.max(1),
+            slow: config.slow_ms.map(Duration::from_millis),
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    pub fn should_log(&self, status: u16, elapsed: Duration) -> bool {
+        if status >= 500 || self.slow.is_some_and(|slow| elapsed >= slow) {
+            return true;
+        }
+        self.one_in == 1 || self.seen.fetch_add(1, Ordering::Relaxed).is_multiple_of(self.one_in)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_successes_and_keeps_errors_and_slow_requests() {
+        let sampler = Sampler::new(&LogSamplingConfig { success_one_in: 10, slow_ms: Some(500) });
+        let fast = Duration::from_millis(5);
+        let logged = (0..100).filter(|_| sampler.should_log(200, fast)).count();
+        assert_eq!(logged, 10);
+        assert!((0..10).all(|_| sampler.should_log(502, fast)));
+        assert!((0..10).all(|_| sampler.should_log(200, Duration::from_millis(500))));
+    }
+}
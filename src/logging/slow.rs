@@ -0,0 +1,159 @@
+// src/logging/slow.rs
+use crate::config::SlowLogConfig;
+use crate::metrics::{Timer, TimingsSnapshot};
+use crate::middleware::FilterContext;
+use futures::Stream;
+use hyper::body::{Bytes, HttpBody};
+use hyper::{Body, Response};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tracing::warn;
+
+/// Warns about requests over `logging.slow`'s thresholds, with a breakdown
+/// of where their time went.
+pub struct SlowLog {
+    total: Option<Duration>,
+    upstream: Option<Duration>,
+}
+
+impl SlowLog {
+    pub fn new(config: &SlowLogConfig) -> Self {
+        Self {
+            total: config.total_ms.map(Duration::from_millis),
+            upstream: config.upstream_ms.map(Duration::from_millis),
+        }
+    }
+
+    pub fn is_slow(&self, total: Duration, ttfb: Duration) -> bool {
+        self.total.is_some_and(|limit| total >= limit) || self.upstream.is_some_and(|limit| ttfb >= limit)
+    }
+
+    /// Check `response` once its body has been sent, or the client has
+    /// gone, so the total includes the body.
+    pub fn watch(self: &Arc<Self>, response: &mut Response<Body>, ctx: &FilterContext, backend: &str) {
+        let request = SlowRequest {
+            request_id: ctx.request_id.clone(),
+            method: ctx.method.to_string(),
+            path: ctx.path.clone(),
+            route: ctx.route.as_deref().unwrap_or("").to_string(),
+            backend: backend.to_string(),
+            status: Some(response.status().as_u16()),
+            headers_after: ctx.timer.elapsed(),
+            timings: ctx.timings.snapshot(),
+        };
+        if response.body().is_end_stream() {
+            return request.check(self, Duration::ZERO, true);
+        }
+        let body = std::mem::take(response.body_mut());
+        *response.body_mut() = Body::wrap_stream(WatchedBody {
+            body,
+            request: Some(request),
+            log: self.clone(),
+            timer: Timer::new(),
+            complete: false,
+        });
+    }
+
+    /// Check a request that got no response.
+    pub fn check_failed(&self, ctx: &FilterContext) {
+        SlowRequest {
+            request_id: ctx.request_id.clone(),
+            method: ctx.method.to_string(),
+            path: ctx.path.clone(),
+            route: ctx.route.as_deref().unwrap_or("").to_string(),
+            backend: "none".to_string(),
+            status: None,
+            headers_after: ctx.timer.elapsed(),
+            timings: ctx.timings.snapshot(),
+        }
+        .check(self, Duration::ZERO, false);
+    }
+}
+
+/// What's logged about a request, taken when its response headers are ready.
+struct SlowRequest {
+    request_id: String,
+    method: String,
+    path: String,
+    route: String,
+    backend: String,
+    status: Option<u16>,
+    headers_after: Duration,
+    timings: TimingsSnapshot,
+}
+
+impl SlowRequest {
+    fn check(self, log: &SlowLog, body: Duration, complete: bool) {
+        let total = self.headers_after + body;
+        if !log.is_slow(total, self.timings.ttfb) {
+            return;
+        }
+        warn!(
+            request_id = %self.request_id,
+            method = %self.method,
+            path = %self.path,
+            route = %self.route,
+            backend = %self.backend,
+            status = self.status,
+            total_ms = total.as_millis(),
+            queue_ms = self.timings.queue.as_millis(),
+            connect_ms = self.timings.connect.as_millis(),
+            ttfb_ms = self.timings.ttfb.as_millis(),
+            body_ms = body.as_millis(),
+            attempts = self.timings.attempts,
+            complete,
+            "Slow request"
+        );
+    }
+}
+
+/// A response body that checks its request when it's dropped.
+struct WatchedBody {
+    body: Body,
+    request: Option<SlowRequest>,
+    log: Arc<SlowLog>,
+    timer: Timer,
+    complete: bool,
+}
+
+impl Stream for WatchedBody {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let polled = Pin::new(&mut self.body).poll_next(cx);
+        if let Poll::Ready(None) = polled {
+            self.complete = true;
+        }
+        polled
+    }
+}
+
+impl Drop for WatchedBody {
+    fn drop(&mut self) {
+        if let Some(request) = self.request.take() {
+            request.check(&self.log, self.timer.elapsed(), self.complete);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_watches_thresholds_and_keeps_the_body() {
+        let log = Arc::new(SlowLog::new(&SlowLogConfig { total_ms: Some(1000), upstream_ms: Some(200) }));
+        assert!(!log.is_slow(Duration::from_millis(999), Duration::from_millis(199)));
+        assert!(log.is_slow(Duration::from_millis(1000), Duration::ZERO));
+        assert!(log.is_slow(Duration::from_millis(300), Duration::from_millis(250)));
+
+        let req = hyper::Request::get("/slow").body(Body::empty()).unwrap();
+        let ctx = FilterContext::new("abc-123".to_string(), &req, None);
+        let mut response = Response::new(Body::from("hello"));
+        log.watch(&mut response, &ctx, "10.0.0.1:80");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "hello");
+    }
+}
@@ -0,0 +1,173 @@
+// src/logging/tap.rs
+use crate::middleware::FilterContext;
+use crate::proxy::{ProxyError, ERROR_CODE_HEADER};
+use hyper::body::Bytes;
+use hyper::{Body, Response};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::debug;
+
+/// Events a slow tap can fall behind by before it skips some.
+const CAPACITY: usize = 1024;
+
+/// A summary of one finished request, as `/admin/tap` streams it.
+#[derive(Debug, Clone, Serialize)]
+pub struct TapEvent {
+    pub time: String,
+    pub request_id: String,
+    pub method: String,
+    pub path: String,
+    pub route: Option<String>,
+    pub status: u16,
+    /// The backend that answered, `local` or `none`.
+    pub backend: String,
+    pub duration_ms: u64,
+    pub client: Option<IpAddr>,
+    /// The error code of responses the load balancer made for an error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TapEvent {
+    pub fn new(ctx: &FilterContext, result: &Result<Response<Body>, ProxyError>) -> Self {
+        let (status, backend, error) = match result {
+            Ok(response) => {
+                let backend = response.headers().get("x-backend-id").and_then(|v| v.to_str().ok());
+                let local = if ctx.answered_locally { "local" } else { "none" };
+                let error = response.headers().get(ERROR_CODE_HEADER).and_then(|v| v.to_str().ok());
+                (response.status().as_u16(), backend.unwrap_or(local).to_string(), error.map(str::to_string))
+            }
+            Err(e) => (e.status_and_message().0.as_u16(), "none".to_string(), Some(e.code().to_string())),
+        };
+        Self {
+            time: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            request_id: ctx.request_id.clone(),
+            method: ctx.method.to_string(),
+            path: ctx.path.clone(),
+            route: ctx.route.as_deref().map(str::to_string),
+            status,
+            backend,
+            duration_ms: ctx.timer.elapsed().as_millis() as u64,
+            client: ctx.client_addr.map(|addr| addr.ip()),
+            error,
+        }
+    }
+}
+
+/// Which requests a tap streams.
+#[derive(Debug, Clone)]
+pub struct TapFilter {
+    /// Only requests this route matched, if set.
+    pub route: Option<String>,
+    /// The fraction of matching requests to stream, in (0, 1].
+    pub sample: f64,
+    pub duration: Duration,
+}
+
+/// Fans finished requests out to live taps. Costs nothing while no one
+/// is watching.
+pub struct RequestTap {
+    events: broadcast::Sender<Arc<TapEvent>>,
+}
+
+impl Default for RequestTap {
+    fn default() -> Self {
+        Self { events: broadcast::channel(CAPACITY).0 }
+    }
+}
+
+impl RequestTap {
+    pub fn is_watched(&self) -> bool {
+        self.events.receiver_count() > 0
+    }
+
+    /// Send the event `event` builds to every tap, building it only if
+    /// there are any.
+    pub fn publish(&self, event: impl FnOnce() -> TapEvent) {
+        if self.is_watched() {
+            let _ = self.events.send(Arc::new(event()));
+        }
+    }
+
+    /// A body streaming the requests `filter` picks as NDJSON until its
+    /// duration is up or the client goes away. A tap that falls behind
+    /// gets a `{"skipped":N}` line in place of what it missed.
+    pub fn stream(&self, filter: TapFilter) -> Body {
+        let mut events = self.events.subscribe();
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            let deadline = tokio::time::sleep(filter.duration);
+            tokio::pin!(deadline);
+            loop {
+                let mut line = tokio::select! {
+                    _ = &mut deadline => break,
+                    event = events.recv() => match event {
+                        Ok(event) => {
+                            let wanted = filter.route.as_ref().is_none_or(|route| event.route.as_ref() == Some(route));
+                            if !wanted || (filter.sample < 1.0 && rand::random::<f64>() >= filter.sample) {
+                                continue;
+                            }
+                            serde_json::to_vec(&*event).expect("tap events serialize")
+                        }
+                        Err(RecvError::Lagged(skipped)) => format!("{{\"skipped\":{}}}", skipped).into_bytes(),
+                        Err(RecvError::Closed) => break,
+                    },
+                };
+                line.push(b'\n');
+                if sender.send_data(Bytes::from(line)).await.is_err() {
+                    debug!("Tap client went away");
+                    break;
+                }
+            }
+        });
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Request;
+
+    fn ctx(route: &str) -> FilterContext {
+        let req = Request::get("/orders").body(Body::empty()).unwrap();
+        let mut ctx = FilterContext::new("id".to_string(), &req, None);
+        ctx.route = Some(route.into());
+        ctx
+    }
+
+    fn answered(route: &str) -> TapEvent {
+        let response = Response::builder().status(201).header("x-backend-id", "b1").body(Body::empty()).unwrap();
+        TapEvent::new(&ctx(route), &Ok(response))
+    }
+
+    #[tokio::test]
+    async fn test_streams_matching_requests_until_the_deadline() {
+        let tap = RequestTap::default();
+        tap.publish(|| unreachable!("built without a tap"));
+
+        let filter = TapFilter { route: Some("orders".to_string()), sample: 1.0, duration: Duration::from_millis(200) };
+        let body = tap.stream(filter);
+        assert!(tap.is_watched());
+        tap.publish(|| answered("orders"));
+        tap.publish(|| answered("users"));
+        tap.publish(|| TapEvent::new(&ctx("orders"), &Err(ProxyError::NoHealthyBackends)));
+
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        let lines: Vec<serde_json::Value> = body
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["status"], 201);
+        assert_eq!(lines[0]["backend"], "b1");
+        assert_eq!(lines[0]["path"], "/orders");
+        assert_eq!(lines[1]["status"], 503);
+        assert_eq!(lines[1]["error"], "LB_NO_HEALTHY_BACKENDS");
+        assert!(!tap.is_watched());
+    }
+}
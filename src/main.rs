@@ -1,140 +1,83 @@
 // src/main.rs
 use anyhow::Result;
-use hyper::{Body, Request, Response, Server, StatusCode};
-use std::convert::Infallible;
-use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::signal;
 use tracing::{error, info};
 
-mod config;
-mod server;
-mod proxy;
-mod load_balancer;
-mod health;
-mod circuit_breaker;
-mod retry;
-mod metrics;
-
-use crate::{
-    config::Config,
-    metrics::{MetricsCollector, MetricsRegistry},
-    proxy::{BackendPool, Proxy},
-    server::{handler::RequestHandler, ServerBuilder},
-};
+use rust_load_balancer::{config, logging, startup, Builder};
 
+/// Usage: `rust-load-balancer [config.yaml] [--check] [--startup-report PATH]`.
+/// `--check` exits after the startup checks; see `startup::ExitReason` for
+/// the exit codes.
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive("rust_load_balancer=debug".parse()?)
-                .add_directive("hyper=info".parse()?),
-        )
-        .init();
-    
-    // Load configuration
-    let config_path = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| "config.yaml".to_string());
-    
-    info!("Loading configuration from: {}", config_path);
-    let config = config::load_config(&config_path).await?;
-    
-    // Initialize metrics
-    let metrics_registry = MetricsRegistry::new()?;
-    let metrics = metrics_registry.collector();
-    
-    // Create backend pool
-    let pool = Arc::new(BackendPool::new(config.backends.clone()));
-    
-    // Create proxy
-    let proxy = Arc::new(Proxy::new(config.clone(), pool, metrics.clone()));
-    
-    // Start health checker
-    proxy.start_health_checker();
-    
-    // Start metrics server if enabled
-    if config.metrics.enabled {
-        let metrics_addr: SocketAddr = ([0, 0, 0, 0], config.metrics.port).into();
-        start_metrics_server(metrics_addr, metrics_registry, config.metrics.path).await?;
+    let mut config_path = "config.yaml".to_string();
+    let mut check_only = false;
+    let mut report_path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check_only = true,
+            "--startup-report" => report_path = args.next().map(PathBuf::from),
+            _ => config_path = arg,
+        }
     }
     
-    // Create request handler
-    let handler = RequestHandler::new(proxy);
-    
-    // Start main server
-    let addr: SocketAddr = "0.0.0.0:8080".parse()?;
-    info!("Starting load balancer on {}", addr);
+    // Load configuration, then log as it says
+    let config = match config::load_config(&config_path).await {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration in {}: {:#}", config_path, e);
+            exit_with(startup::StartupReport::config_error(&e), report_path.as_deref());
+        }
+    };
+    logging::init(&config.logging)?;
+    info!("Loaded configuration from: {}", config_path);
     
-    ServerBuilder::new(addr)
-        .with_handler(handler)
-        .serve()
-        .await?;
+    let report = startup::self_check(&config).await;
+    report.log();
+    if !report.ok || check_only {
+        exit_with(report, report_path.as_deref());
+    }
+    if let Some(path) = &report_path {
+        report.write(path)?;
+    }
     
-    Ok(())
+    let lb = Arc::new(Builder::new(config).build().await?);
+    let signalled = lb.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        signalled.shutdown();
+    });
+    #[cfg(unix)]
+    tokio::spawn(reload_on_hangup(lb.handle(), config_path));
+    lb.serve().await
 }
 
-async fn start_metrics_server(
-    addr: SocketAddr,
-    registry: MetricsRegistry,
-    path: String,
-) -> Result<()> {
-    let registry = Arc::new(registry);
-    let metrics_path = Arc::new(path); // keep this for logging
-    let service_path = metrics_path.clone(); // clone for the service closure
-
-    let make_service = hyper::service::make_service_fn(move |_| {
-        let registry = registry.clone();
-        let path = service_path.clone();
-
-        async move {
-            Ok::<_, Infallible>(hyper::service::service_fn(move |req: Request<Body>| {
-                let registry = registry.clone();
-                let path = path.clone();
-
-                async move {
-                    if req.uri().path() == path.as_str() {
-                        let metrics = registry.gather();
-                        Ok::<_, Infallible>(
-                            Response::builder()
-                                .status(StatusCode::OK)
-                                .header("Content-Type", "text/plain; version=0.0.4")
-                                .body(Body::from(metrics))
-                                .unwrap(),
-                        )
-                    } else {
-                        Ok::<_, Infallible>(
-                            Response::builder()
-                                .status(StatusCode::NOT_FOUND)
-                                .body(Body::from("Not Found"))
-                                .unwrap(),
-                        )
-                    }
-                }
-            }))
+/// Write the startup report if asked to and exit with its code.
+fn exit_with(report: startup::StartupReport, report_path: Option<&Path>) -> ! {
+    if let Some(path) = report_path {
+        if let Err(e) = report.write(path) {
+            eprintln!("Failed to write startup report to {}: {:#}", path.display(), e);
         }
-    });
-
-    let server = Server::bind(&addr).serve(make_service);
-
-    info!(
-        "Metrics server listening on http://{}{}",
-        addr,
-        metrics_path.as_str()
-    );
+    }
+    std::process::exit(report.exit_code)
+}
 
-    tokio::spawn(async move {
-        if let Err(e) = server.await {
-            error!("Metrics server error: {}", e);
+/// Reread the config file on SIGHUP and apply its backends and routes.
+#[cfg(unix)]
+async fn reload_on_hangup(handle: rust_load_balancer::LoadBalancerHandle, config_path: String) {
+    let mut hangups = signal::unix::signal(signal::unix::SignalKind::hangup())
+        .expect("Failed to install signal handler");
+    while hangups.recv().await.is_some() {
+        info!("Reloading configuration from: {}", config_path);
+        if let Err(e) = handle.reload_from(&config_path).await {
+            error!("Failed to reload configuration: {:#}", e);
         }
-    });
-
-    Ok(())
+    }
 }
 
-
 // Graceful shutdown handler
 async fn shutdown_signal() {
     let ctrl_c = async {
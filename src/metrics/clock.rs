@@ -0,0 +1,80 @@
+// src/metrics/clock.rs
+//! A monotonic clock that's only read from memory, for timings where being
+//! a few milliseconds off doesn't matter. A thread stores the time every
+//! `precision`, so `now` costs an atomic load rather than a clock read.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+static INSTALLED: OnceLock<Arc<CoarseClock>> = OnceLock::new();
+
+pub struct CoarseClock {
+    precision: Duration,
+    base: Instant,
+    /// Since `base`, as of the last tick.
+    elapsed_micros: AtomicU64,
+}
+
+impl CoarseClock {
+    /// Tick every `precision` on a thread of its own, until dropped.
+    pub fn start(precision: Duration) -> Arc<Self> {
+        let clock = Arc::new(Self {
+            precision,
+            base: Instant::now(),
+            elapsed_micros: AtomicU64::new(0),
+        });
+        let ticking = Arc::downgrade(&clock);
+        std::thread::Builder::new()
+            .name("coarse-clock".into())
+            .spawn(move || {
+                while let Some(clock) = ticking.upgrade() {
+                    let elapsed = clock.base.elapsed().as_micros() as u64;
+                    clock.elapsed_micros.store(elapsed, Ordering::Relaxed);
+                    drop(clock);
+                    std::thread::sleep(precision);
+                }
+            })
+            .expect("Failed to spawn the coarse clock thread");
+        clock
+    }
+
+    pub fn precision(&self) -> Duration {
+        self.precision
+    }
+
+    /// The time at the last tick.
+    pub fn now(&self) -> Instant {
+        self.base + Duration::from_micros(self.elapsed_micros.load(Ordering::Relaxed))
+    }
+}
+
+/// Make `now` coarse for the rest of the process. Only the first call
+/// starts a clock; returns the precision of the one running.
+pub fn install(precision: Duration) -> Duration {
+    INSTALLED.get_or_init(|| CoarseClock::start(precision)).precision()
+}
+
+/// The installed clock's time, or `Instant::now()` if there's none.
+pub fn now() -> Instant {
+    match INSTALLED.get() {
+        Some(clock) => clock.now(),
+        None => Instant::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coarse_clock_ticks() {
+        let clock = CoarseClock::start(Duration::from_millis(5));
+        let first = clock.now();
+        std::thread::sleep(Duration::from_millis(50));
+        let later = clock.now();
+        assert!(later > first);
+        // Never ahead of the real clock, and at most a few ticks behind
+        assert!(later <= Instant::now());
+        assert!(Instant::now() - later < Duration::from_millis(40));
+    }
+}
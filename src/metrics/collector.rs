@@ -1,12 +1,22 @@
 // src/metrics/collector.rs
 use prometheus::{
-    Encoder, IntCounterVec, IntGauge, IntGaugeVec, HistogramVec, HistogramOpts,
+    core::{Collector, MetricVec, MetricVecBuilder},
+    CounterVec, Encoder, Gauge, GaugeVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, HistogramVec, HistogramOpts,
     Opts, Registry, TextEncoder,
 };
-use std::sync::Arc;
-use std::time::Instant;
+use dashmap::DashMap;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use anyhow::Result;
 
+use super::openmetrics::{self, Exemplar};
+use super::{slo::SloTracker, MetricsSink};
+use crate::circuit_breaker::CircuitBreakerState;
+use crate::config::SloConfig;
+use crate::proxy::PoolStats;
+
 pub struct MetricsRegistry {
     registry: Registry,
     collector: Arc<MetricsCollector>,
@@ -14,12 +24,20 @@ pub struct MetricsRegistry {
 
 impl MetricsRegistry {
     pub fn new() -> Result<Self> {
+        Self::with_slo(None)
+    }
+    
+    /// Like [`MetricsRegistry::new`], additionally tracking the given SLO.
+    pub fn with_slo(slo: Option<SloConfig>) -> Result<Self> {
         let registry = Registry::new();
-        let collector = Arc::new(MetricsCollector::new(&registry)?);
+        let mut collector = MetricsCollector::new(&registry)?;
+        if let Some(slo) = slo {
+            collector.slo = Some(SloTracker::new(slo, &registry)?);
+        }
         
         Ok(Self {
             registry,
-            collector,
+            collector: Arc::new(collector),
         })
     }
     
@@ -28,35 +46,133 @@ impl MetricsRegistry {
     }
     
     pub fn gather(&self) -> Vec<u8> {
+        if let Some(slo) = &self.collector.slo {
+            slo.refresh();
+        }
+        
         let encoder = TextEncoder::new();
         let metric_families = self.registry.gather();
         let mut buffer = Vec::new();
         encoder.encode(&metric_families, &mut buffer).unwrap();
         buffer
     }
+    
+    /// Like [`MetricsRegistry::gather`], in the OpenMetrics format and with
+    /// the latest exemplar for each request duration bucket.
+    pub fn gather_openmetrics(&self) -> Vec<u8> {
+        if let Some(slo) = &self.collector.slo {
+            slo.refresh();
+        }
+        
+        let exemplars = &self.collector.request_exemplars;
+        let metric_families = self.registry.gather();
+        openmetrics::encode(&metric_families, |family, metric, bucket| {
+            if family.get_name() != REQUEST_DURATION || exemplars.is_empty() {
+                return None;
+            }
+            let label = |name: &str| {
+                metric.get_label().iter().find(|pair| pair.get_name() == name).map_or("", |pair| pair.get_value())
+            };
+            let key = (label("method").to_string(), label("status_code").to_string(), label("backend").to_string());
+            exemplars.get(&key)?.get(bucket)?.clone()
+        })
+        .into_bytes()
+    }
 }
 
+const REQUEST_DURATION: &str = "lb_request_duration_seconds";
+
+/// Prometheus-backed [`MetricsSink`], the default recorder.
 pub struct MetricsCollector {
     // Request metrics
-    pub requests_total: IntCounterVec,
-    pub request_duration_seconds: HistogramVec,
-    pub request_size_bytes: HistogramVec,
-    pub response_size_bytes: HistogramVec,
+    requests_total: IntCounterVec,
+    request_duration_seconds: HistogramVec,
+    request_size_bytes: HistogramVec,
+    response_size_bytes: HistogramVec,
     
     // Backend metrics
-    pub backend_requests_total: IntCounterVec,
-    pub backend_request_duration_seconds: HistogramVec,
-    pub backend_connections_active: IntGaugeVec,
-    pub backend_health_status: IntGaugeVec,
+    backend_requests_total: IntCounterVec,
+    backend_request_duration_seconds: HistogramVec,
+    backend_connections_active: IntGaugeVec,
+    backend_throttle_delay_seconds_total: CounterVec,
+    backend_health_status: IntGaugeVec,
     
     // Circuit breaker metrics
-    pub circuit_breaker_state: IntGaugeVec,
-    pub circuit_breaker_failures_total: IntCounterVec,
+    circuit_breaker_state: IntGaugeVec,
+    circuit_breaker_failures_total: IntCounterVec,
     
     // System metrics
-    pub active_connections: IntGauge,
-    pub healthy_backends: IntGauge,
-    pub total_backends: IntGauge,
+    active_connections: IntGauge,
+    healthy_backends: IntGauge,
+    total_backends: IntGauge,
+    draining_backends: IntGauge,
+    
+    // Client connection metrics, per listener
+    connections_accepted_total: IntCounterVec,
+    connections_open: IntGaugeVec,
+    connections_rejected_total: IntCounterVec,
+    accept_errors_total: IntCounterVec,
+    connection_duration_seconds: HistogramVec,
+    connection_first_byte_seconds: HistogramVec,
+    tls_handshake_seconds: HistogramVec,
+    requests_rejected_total: IntCounterVec,
+    
+    // TCP passthrough metrics
+    tcp_bytes_total: IntCounterVec,
+    
+    // Routing metrics
+    route_requests_total: IntCounterVec,
+    route_fallback_total: IntCounterVec,
+    region_spillover_total: IntCounterVec,
+    response_violations_total: IntCounterVec,
+    latency_budget_exceeded_total: IntCounterVec,
+    backend_queue_depth: HistogramVec,
+    backend_queue_wait_seconds: HistogramVec,
+    backend_queue_timeouts_total: IntCounterVec,
+    experiment_requests_total: IntCounterVec,
+    
+    // Discovery metrics
+    discovery_changes_total: IntCounterVec,
+    discovery_backends: IntGaugeVec,
+    
+    // Cluster metrics
+    cluster_events_total: IntCounterVec,
+    
+    // Response cache metrics
+    cache_requests_total: IntCounterVec,
+    cache_evictions_total: IntCounter,
+    cache_entries: IntGauge,
+    cache_bytes: IntGauge,
+    cache_disk_operations_total: IntCounterVec,
+    cache_disk_entries: IntGauge,
+    cache_disk_bytes: IntGauge,
+    
+    // Admission control metrics
+    load_shed_total: IntCounterVec,
+    admission_pressure: Gauge,
+    event_loop_lag_seconds: Gauge,
+    fairness_requests_total: IntCounterVec,
+    client_throttle_delay_seconds_total: CounterVec,
+    
+    // Per-pool upstream clients
+    upstream_pool_in_use: IntGaugeVec,
+    upstream_pool_saturation: GaugeVec,
+    upstream_pool_wait_seconds: HistogramVec,
+    upstream_connections: IntGaugeVec,
+    upstream_connections_created_total: IntCounterVec,
+    upstream_connections_closed_total: IntCounterVec,
+    
+    // Config metrics
+    config_reloads_total: IntCounterVec,
+    config_last_reload_success_timestamp_seconds: Gauge,
+    config_info: IntGaugeVec,
+    
+    /// The latest traced request in each `lb_request_duration_seconds`
+    /// bucket, by method, status and backend.
+    request_exemplars: DashMap<(String, String, String), Vec<Option<Exemplar>>>,
+    
+    // SLO tracking, when configured
+    slo: Option<SloTracker>,
 }
 
 impl MetricsCollector {
@@ -69,10 +185,7 @@ impl MetricsCollector {
         registry.register(Box::new(requests_total.clone()))?;
         
         let request_duration_seconds = HistogramVec::new(
-            HistogramOpts::new(
-                "lb_request_duration_seconds",
-                "Request duration in seconds",
-            ),
+            HistogramOpts::new(REQUEST_DURATION, "Request duration in seconds"),
             &["method", "status_code", "backend"],
         )?;
         registry.register(Box::new(request_duration_seconds.clone()))?;
@@ -117,6 +230,15 @@ impl MetricsCollector {
         )?;
         registry.register(Box::new(backend_connections_active.clone()))?;
         
+        let backend_throttle_delay_seconds_total = CounterVec::new(
+            Opts::new(
+                "lb_backend_throttle_delay_seconds_total",
+                "Time response bodies were held back by their backend's bandwidth cap",
+            ),
+            &["backend"],
+        )?;
+        registry.register(Box::new(backend_throttle_delay_seconds_total.clone()))?;
+        
         let backend_health_status = IntGaugeVec::new(
             Opts::new(
                 "lb_backend_health_status",
@@ -158,6 +280,301 @@ impl MetricsCollector {
             IntGauge::new("lb_total_backends", "Total number of backends")?;
         registry.register(Box::new(total_backends.clone()))?;
         
+        let draining_backends =
+            IntGauge::new("lb_draining_backends", "Number of backends draining")?;
+        registry.register(Box::new(draining_backends.clone()))?;
+        
+        // Client connection metrics
+        let connections_accepted_total = IntCounterVec::new(
+            Opts::new(
+                "lb_connections_accepted_total",
+                "Client connections accepted",
+            ),
+            &["listener"],
+        )?;
+        registry.register(Box::new(connections_accepted_total.clone()))?;
+        
+        let connections_open = IntGaugeVec::new(
+            Opts::new("lb_connections_open", "Currently open client connections"),
+            &["listener"],
+        )?;
+        registry.register(Box::new(connections_open.clone()))?;
+        
+        let connections_rejected_total = IntCounterVec::new(
+            Opts::new(
+                "lb_connections_rejected_total",
+                "Client connections refused or closed by listener limits",
+            ),
+            &["listener", "reason"],
+        )?;
+        registry.register(Box::new(connections_rejected_total.clone()))?;
+        
+        let accept_errors_total = IntCounterVec::new(
+            Opts::new("lb_accept_errors_total", "Failed accept() calls on listeners"),
+            &["listener", "kind"],
+        )?;
+        registry.register(Box::new(accept_errors_total.clone()))?;
+        
+        let connection_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "lb_connection_duration_seconds",
+                "Client connection lifetime in seconds",
+            )
+            .buckets(vec![0.01, 0.1, 0.5, 1.0, 5.0, 15.0, 60.0, 300.0, 900.0, 3600.0]),
+            &["listener"],
+        )?;
+        registry.register(Box::new(connection_duration_seconds.clone()))?;
+        
+        let connection_first_byte_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "lb_connection_first_byte_seconds",
+                "Time from accepting a connection to its first response headers",
+            ),
+            &["listener"],
+        )?;
+        registry.register(Box::new(connection_first_byte_seconds.clone()))?;
+        
+        let tls_handshake_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "lb_tls_handshake_seconds",
+                "Time from accepting a client connection to completing its TLS handshake",
+            ),
+            &["listener"],
+        )?;
+        registry.register(Box::new(tls_handshake_seconds.clone()))?;
+        
+        let requests_rejected_total = IntCounterVec::new(
+            Opts::new(
+                "lb_requests_rejected_total",
+                "Client requests refused as malformed or ambiguously framed, before routing",
+            ),
+            &["listener", "reason"],
+        )?;
+        registry.register(Box::new(requests_rejected_total.clone()))?;
+        
+        // TCP passthrough metrics
+        let tcp_bytes_total = IntCounterVec::new(
+            Opts::new(
+                "lb_tcp_bytes_total",
+                "Bytes proxied by TCP listeners, received from or sent to clients",
+            ),
+            &["listener", "backend", "direction"],
+        )?;
+        registry.register(Box::new(tcp_bytes_total.clone()))?;
+        
+        // Routing metrics
+        let route_requests_total = IntCounterVec::new(
+            Opts::new("lb_route_requests_total", "Requests per route and the pool they were sent to"),
+            &["route", "pool"],
+        )?;
+        registry.register(Box::new(route_requests_total.clone()))?;
+        
+        let route_fallback_total = IntCounterVec::new(
+            Opts::new("lb_route_fallback_total", "Requests sent to a route's fallback pool"),
+            &["route", "pool"],
+        )?;
+        registry.register(Box::new(route_fallback_total.clone()))?;
+        
+        let region_spillover_total = IntCounterVec::new(
+            Opts::new("lb_region_spillover_total", "Requests sent to backends in a remote region"),
+            &["route", "region"],
+        )?;
+        registry.register(Box::new(region_spillover_total.clone()))?;
+        
+        let response_violations_total = IntCounterVec::new(
+            Opts::new("lb_response_violations_total", "Backend responses that failed a route's validate_response"),
+            &["route", "backend"],
+        )?;
+        registry.register(Box::new(response_violations_total.clone()))?;
+        
+        let latency_budget_exceeded_total = IntCounterVec::new(
+            Opts::new("lb_latency_budget_exceeded_total", "Backend attempts cancelled at the end of a route's latency budget"),
+            &["route", "backend"],
+        )?;
+        registry.register(Box::new(latency_budget_exceeded_total.clone()))?;
+        
+        let backend_queue_depth = HistogramVec::new(
+            HistogramOpts::new(
+                "lb_backend_queue_depth",
+                "Requests already waiting when one joined a backend's queue",
+            )
+            .buckets(vec![0.0, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0]),
+            &["backend"],
+        )?;
+        registry.register(Box::new(backend_queue_depth.clone()))?;
+        
+        let backend_queue_wait_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "lb_backend_queue_wait_seconds",
+                "Time requests spent waiting for a backend connection",
+            )
+            .buckets(vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+            &["backend"],
+        )?;
+        registry.register(Box::new(backend_queue_wait_seconds.clone()))?;
+        
+        let backend_queue_timeouts_total = IntCounterVec::new(
+            Opts::new(
+                "lb_backend_queue_timeouts_total",
+                "Queued requests that gave up waiting for a backend connection",
+            ),
+            &["backend"],
+        )?;
+        registry.register(Box::new(backend_queue_timeouts_total.clone()))?;
+        
+        let experiment_requests_total = IntCounterVec::new(
+            Opts::new("lb_experiment_requests_total", "Requests per A/B experiment variant"),
+            &["experiment", "variant"],
+        )?;
+        registry.register(Box::new(experiment_requests_total.clone()))?;
+        
+        // Discovery metrics
+        let discovery_changes_total = IntCounterVec::new(
+            Opts::new("lb_discovery_changes_total", "Backend changes applied by discovery sources"),
+            &["source", "change"],
+        )?;
+        registry.register(Box::new(discovery_changes_total.clone()))?;
+        
+        let discovery_backends = IntGaugeVec::new(
+            Opts::new("lb_discovery_backends", "Backends managed by each discovery source"),
+            &["source"],
+        )?;
+        registry.register(Box::new(discovery_backends.clone()))?;
+        
+        // Cluster metrics
+        let cluster_events_total = IntCounterVec::new(
+            Opts::new("lb_cluster_events_total", "Backend events exchanged with peer instances"),
+            &["kind", "outcome"],
+        )?;
+        registry.register(Box::new(cluster_events_total.clone()))?;
+        
+        // Response cache metrics
+        let cache_requests_total = IntCounterVec::new(
+            Opts::new("lb_cache_requests_total", "Requests on caching routes by cache result"),
+            &["result"],
+        )?;
+        registry.register(Box::new(cache_requests_total.clone()))?;
+        
+        let cache_evictions_total = IntCounter::new(
+            "lb_cache_evictions_total",
+            "Cache entries evicted to stay within the memory budget",
+        )?;
+        registry.register(Box::new(cache_evictions_total.clone()))?;
+        
+        let cache_entries = IntGauge::new("lb_cache_entries", "Responses held in the cache")?;
+        registry.register(Box::new(cache_entries.clone()))?;
+        
+        let cache_bytes = IntGauge::new("lb_cache_bytes", "Memory used by cached responses")?;
+        registry.register(Box::new(cache_bytes.clone()))?;
+        
+        let cache_disk_operations_total = IntCounterVec::new(
+            Opts::new("lb_cache_disk_operations_total", "Reads, writes and evictions of the disk cache tier"),
+            &["operation"],
+        )?;
+        registry.register(Box::new(cache_disk_operations_total.clone()))?;
+        
+        let cache_disk_entries = IntGauge::new("lb_cache_disk_entries", "Responses held in the disk cache tier")?;
+        registry.register(Box::new(cache_disk_entries.clone()))?;
+        
+        let cache_disk_bytes = IntGauge::new("lb_cache_disk_bytes", "Disk space used by cached responses")?;
+        registry.register(Box::new(cache_disk_bytes.clone()))?;
+        
+        // Admission control metrics
+        let load_shed_total = IntCounterVec::new(
+            Opts::new("lb_load_shed_total", "Requests rejected by admission control"),
+            &["route"],
+        )?;
+        registry.register(Box::new(load_shed_total.clone()))?;
+        
+        let admission_pressure = Gauge::new(
+            "lb_admission_pressure",
+            "Highest measured load over its admission limit; shedding starts above 1",
+        )?;
+        registry.register(Box::new(admission_pressure.clone()))?;
+        
+        let event_loop_lag_seconds = Gauge::new(
+            "lb_event_loop_lag_seconds",
+            "How late the runtime runs timers, smoothed",
+        )?;
+        registry.register(Box::new(event_loop_lag_seconds.clone()))?;
+        
+        let fairness_requests_total = IntCounterVec::new(
+            Opts::new("lb_fairness_requests_total", "Requests over their client's fair share"),
+            &["outcome"],
+        )?;
+        registry.register(Box::new(fairness_requests_total.clone()))?;
+        
+        let client_throttle_delay_seconds_total = CounterVec::new(
+            Opts::new(
+                "lb_client_throttle_delay_seconds_total",
+                "Time responses were held back by download limits, by tier",
+            ),
+            &["tier"],
+        )?;
+        registry.register(Box::new(client_throttle_delay_seconds_total.clone()))?;
+        
+        let upstream_pool_in_use = IntGaugeVec::new(
+            Opts::new("lb_upstream_pool_in_use", "Requests holding one of a backend pool's upstream slots"),
+            &["pool"],
+        )?;
+        registry.register(Box::new(upstream_pool_in_use.clone()))?;
+        
+        let upstream_pool_saturation = GaugeVec::new(
+            Opts::new(
+                "lb_upstream_pool_saturation",
+                "Fraction of a backend pool's max_connections in use",
+            ),
+            &["pool"],
+        )?;
+        registry.register(Box::new(upstream_pool_saturation.clone()))?;
+        
+        let upstream_pool_wait_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "lb_upstream_pool_wait_seconds",
+                "Time requests waited for one of their pool's upstream slots, by backend",
+            )
+            .buckets(vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+            &["backend"],
+        )?;
+        registry.register(Box::new(upstream_pool_wait_seconds.clone()))?;
+        
+        let upstream_connections = IntGaugeVec::new(
+            Opts::new("lb_upstream_connections", "Open upstream connections to a backend, idle or in use"),
+            &["backend", "state"],
+        )?;
+        registry.register(Box::new(upstream_connections.clone()))?;
+        
+        let upstream_connections_created_total = IntCounterVec::new(
+            Opts::new("lb_upstream_connections_created_total", "Upstream connections made to a backend"),
+            &["backend"],
+        )?;
+        registry.register(Box::new(upstream_connections_created_total.clone()))?;
+        
+        let upstream_connections_closed_total = IntCounterVec::new(
+            Opts::new("lb_upstream_connections_closed_total", "Upstream connections to a backend closed"),
+            &["backend"],
+        )?;
+        registry.register(Box::new(upstream_connections_closed_total.clone()))?;
+        
+        // Config metrics
+        let config_reloads_total = IntCounterVec::new(
+            Opts::new("lb_config_reload_total", "Config reloads by result"),
+            &["result"],
+        )?;
+        registry.register(Box::new(config_reloads_total.clone()))?;
+        
+        let config_last_reload_success_timestamp_seconds = Gauge::new(
+            "lb_config_last_reload_success_timestamp_seconds",
+            "When the config in effect was loaded, at startup or by a reload",
+        )?;
+        registry.register(Box::new(config_last_reload_success_timestamp_seconds.clone()))?;
+        
+        let config_info = IntGaugeVec::new(
+            Opts::new("lb_config_info", "Always 1, labelled with the content hash of the config in effect"),
+            &["hash"],
+        )?;
+        registry.register(Box::new(config_info.clone()))?;
+        
         Ok(Self {
             requests_total,
             request_duration_seconds,
@@ -166,23 +583,71 @@ impl MetricsCollector {
             backend_requests_total,
             backend_request_duration_seconds,
             backend_connections_active,
+            backend_throttle_delay_seconds_total,
             backend_health_status,
             circuit_breaker_state,
             circuit_breaker_failures_total,
             active_connections,
             healthy_backends,
             total_backends,
+            draining_backends,
+            connections_accepted_total,
+            connections_open,
+            connections_rejected_total,
+            accept_errors_total,
+            connection_duration_seconds,
+            connection_first_byte_seconds,
+            tls_handshake_seconds,
+            requests_rejected_total,
+            tcp_bytes_total,
+            route_requests_total,
+            route_fallback_total,
+            region_spillover_total,
+            response_violations_total,
+            latency_budget_exceeded_total,
+            backend_queue_depth,
+            backend_queue_wait_seconds,
+            backend_queue_timeouts_total,
+            experiment_requests_total,
+            discovery_changes_total,
+            discovery_backends,
+            cluster_events_total,
+            cache_requests_total,
+            cache_evictions_total,
+            cache_entries,
+            cache_bytes,
+            cache_disk_operations_total,
+            cache_disk_entries,
+            cache_disk_bytes,
+            load_shed_total,
+            admission_pressure,
+            event_loop_lag_seconds,
+            fairness_requests_total,
+            client_throttle_delay_seconds_total,
+            upstream_pool_in_use,
+            upstream_pool_saturation,
+            upstream_pool_wait_seconds,
+            upstream_connections,
+            upstream_connections_created_total,
+            upstream_connections_closed_total,
+            config_reloads_total,
+            config_last_reload_success_timestamp_seconds,
+            config_info,
+            request_exemplars: DashMap::new(),
+            slo: None,
         })
     }
-    
-    pub fn record_request(
+}
+
+impl MetricsSink for MetricsCollector {
+    fn record_request(
         &self,
         method: &str,
         status_code: u16,
         backend: &str,
-        duration: std::time::Duration,
+        duration: Duration,
     ) {
-        let status = status_code.to_string();
+        let status = status_label(status_code);
         self.requests_total
             .with_label_values(&[method, &status, backend])
             .inc();
@@ -190,13 +655,53 @@ impl MetricsCollector {
         self.request_duration_seconds
             .with_label_values(&[method, &status, backend])
             .observe(duration.as_secs_f64());
+        
+        if let Some(slo) = &self.slo {
+            slo.record(status_code, duration);
+        }
     }
     
-    pub fn record_backend_request(
+    fn record_request_exemplar(
+        &self,
+        method: &str,
+        status_code: u16,
+        backend: &str,
+        duration: Duration,
+        trace_id: &str,
+    ) {
+        let value = duration.as_secs_f64();
+        // Same buckets as the histogram; past the last is `+Inf`
+        let bucket = prometheus::DEFAULT_BUCKETS.partition_point(|bound| *bound < value);
+        let exemplar = Exemplar {
+            trace_id: trace_id.to_string(),
+            value,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64(),
+        };
+        let key = (method.to_string(), status_label(status_code).into_owned(), backend.to_string());
+        let mut buckets = self
+            .request_exemplars
+            .entry(key)
+            .or_insert_with(|| vec![None; prometheus::DEFAULT_BUCKETS.len() + 1]);
+        buckets[bucket] = Some(exemplar);
+    }
+    
+    fn record_request_size(&self, method: &str, bytes: u64) {
+        self.request_size_bytes
+            .with_label_values(&[method])
+            .observe(bytes as f64);
+    }
+    
+    fn record_response_size(&self, method: &str, status_code: u16, bytes: u64) {
+        self.response_size_bytes
+            .with_label_values(&[method, &status_label(status_code)])
+            .observe(bytes as f64);
+    }
+    
+    fn record_backend_request(
         &self,
         backend: &str,
         success: bool,
-        duration: std::time::Duration,
+        duration: Duration,
     ) {
         let status = if success { "success" } else { "failure" };
         self.backend_requests_total
@@ -208,28 +713,34 @@ impl MetricsCollector {
             .observe(duration.as_secs_f64());
     }
     
-    pub fn update_backend_connections(&self, backend: &str, count: i64) {
+    fn update_backend_connections(&self, backend: &str, count: i64) {
         self.backend_connections_active
             .with_label_values(&[backend])
             .set(count);
     }
     
-    pub fn update_backend_health(&self, backend: &str, healthy: bool) {
+    fn record_backend_throttle(&self, backend: &str, delay: Duration) {
+        self.backend_throttle_delay_seconds_total
+            .with_label_values(&[backend])
+            .inc_by(delay.as_secs_f64());
+    }
+    
+    fn update_backend_health(&self, backend: &str, healthy: bool) {
         let value = if healthy { 1 } else { 0 };
         self.backend_health_status
             .with_label_values(&[backend])
             .set(value);
     }
     
-    pub fn update_circuit_breaker_state(
+    fn update_circuit_breaker_state(
         &self,
         backend: &str,
-        state: crate::circuit_breaker::CircuitBreakerState,
+        state: CircuitBreakerState,
     ) {
         let value = match state {
-            crate::circuit_breaker::CircuitBreakerState::Closed => 0,
-            crate::circuit_breaker::CircuitBreakerState::Open => 1,
-            crate::circuit_breaker::CircuitBreakerState::HalfOpen => 2,
+            CircuitBreakerState::Closed => 0,
+            CircuitBreakerState::Open => 1,
+            CircuitBreakerState::HalfOpen => 2,
         };
         
         self.circuit_breaker_state
@@ -237,33 +748,348 @@ impl MetricsCollector {
             .set(value);
     }
     
-    pub fn increment_active_connections(&self) {
+    fn increment_active_connections(&self) {
         self.active_connections.inc();
     }
     
-    pub fn decrement_active_connections(&self) {
+    fn decrement_active_connections(&self) {
         self.active_connections.dec();
     }
     
-    pub fn update_backend_counts(&self, healthy: usize, total: usize) {
-        self.healthy_backends.set(healthy as i64);
-        self.total_backends.set(total as i64);
+    fn record_connection_opened(&self, listener: &str) {
+        self.connections_accepted_total
+            .with_label_values(&[listener])
+            .inc();
+        self.connections_open.with_label_values(&[listener]).inc();
+    }
+    
+    fn record_connection_rejected(&self, listener: &str, reason: &str) {
+        self.connections_rejected_total
+            .with_label_values(&[listener, reason])
+            .inc();
+    }
+    
+    fn record_accept_error(&self, listener: &str, kind: &str) {
+        self.accept_errors_total
+            .with_label_values(&[listener, kind])
+            .inc();
+    }
+    
+    fn record_connection_closed(&self, listener: &str, lifetime: Duration) {
+        self.connections_open.with_label_values(&[listener]).dec();
+        self.connection_duration_seconds
+            .with_label_values(&[listener])
+            .observe(lifetime.as_secs_f64());
+    }
+    
+    fn record_connection_first_byte(&self, listener: &str, elapsed: Duration) {
+        self.connection_first_byte_seconds
+            .with_label_values(&[listener])
+            .observe(elapsed.as_secs_f64());
+    }
+    
+    fn record_tls_handshake(&self, listener: &str, elapsed: Duration) {
+        self.tls_handshake_seconds
+            .with_label_values(&[listener])
+            .observe(elapsed.as_secs_f64());
+    }
+    
+    fn record_request_rejected(&self, listener: &str, reason: &str) {
+        self.requests_rejected_total
+            .with_label_values(&[listener, reason])
+            .inc();
+    }
+    
+    fn record_tcp_bytes(&self, listener: &str, backend: &str, received: u64, sent: u64) {
+        self.tcp_bytes_total
+            .with_label_values(&[listener, backend, "received"])
+            .inc_by(received);
+        self.tcp_bytes_total
+            .with_label_values(&[listener, backend, "sent"])
+            .inc_by(sent);
+    }
+    
+    fn record_route(&self, route: &str, pool: &str) {
+        self.route_requests_total
+            .with_label_values(&[route, pool])
+            .inc();
+    }
+    
+    fn record_route_fallback(&self, route: &str, pool: &str) {
+        self.route_fallback_total.with_label_values(&[route, pool]).inc();
+    }
+    
+    fn record_region_spillover(&self, route: &str, region: &str) {
+        self.region_spillover_total.with_label_values(&[route, region]).inc();
+    }
+    
+    fn record_response_violation(&self, route: &str, backend: &str) {
+        self.response_violations_total.with_label_values(&[route, backend]).inc();
+    }
+    
+    fn record_latency_budget_exceeded(&self, route: &str, backend: &str) {
+        self.latency_budget_exceeded_total.with_label_values(&[route, backend]).inc();
+    }
+    
+    fn record_backend_queue(&self, backend: &str, ahead: usize, waited: Duration, admitted: bool) {
+        self.backend_queue_depth.with_label_values(&[backend]).observe(ahead as f64);
+        self.backend_queue_wait_seconds.with_label_values(&[backend]).observe(waited.as_secs_f64());
+        if !admitted {
+            self.backend_queue_timeouts_total.with_label_values(&[backend]).inc();
+        }
+    }
+    
+    fn record_experiment(&self, experiment: &str, variant: &str) {
+        self.experiment_requests_total
+            .with_label_values(&[experiment, variant])
+            .inc();
+    }
+    
+    fn record_discovery_changes(&self, source: &str, change: &str, count: usize) {
+        self.discovery_changes_total
+            .with_label_values(&[source, change])
+            .inc_by(count as u64);
+    }
+    
+    fn update_discovery_backends(&self, source: &str, count: usize) {
+        self.discovery_backends
+            .with_label_values(&[source])
+            .set(count as i64);
+    }
+    
+    fn record_cluster_event(&self, kind: &str, outcome: &str) {
+        self.cluster_events_total
+            .with_label_values(&[kind, outcome])
+            .inc();
+    }
+    
+    fn record_cache_lookup(&self, result: &str) {
+        self.cache_requests_total.with_label_values(&[result]).inc();
+    }
+    
+    fn record_cache_evictions(&self, count: usize) {
+        self.cache_evictions_total.inc_by(count as u64);
+    }
+    
+    fn update_cache_size(&self, entries: usize, bytes: usize) {
+        self.cache_entries.set(entries as i64);
+        self.cache_bytes.set(bytes as i64);
+    }
+    
+    fn record_cache_disk(&self, operation: &str) {
+        self.cache_disk_operations_total.with_label_values(&[operation]).inc();
+    }
+    
+    fn update_cache_disk_size(&self, entries: usize, bytes: usize) {
+        self.cache_disk_entries.set(entries as i64);
+        self.cache_disk_bytes.set(bytes as i64);
+    }
+    
+    fn record_load_shed(&self, route: &str) {
+        self.load_shed_total.with_label_values(&[route]).inc();
+    }
+    
+    fn update_admission_pressure(&self, pressure: f64, event_loop_lag: Duration) {
+        self.admission_pressure.set(pressure);
+        self.event_loop_lag_seconds.set(event_loop_lag.as_secs_f64());
+    }
+    
+    fn record_fairness(&self, outcome: &str) {
+        self.fairness_requests_total.with_label_values(&[outcome]).inc();
+    }
+    
+    fn record_client_throttle(&self, tier: &str, delay: Duration) {
+        self.client_throttle_delay_seconds_total
+            .with_label_values(&[tier])
+            .inc_by(delay.as_secs_f64());
+    }
+    
+    fn update_upstream_pool(&self, pool: &str, in_use: usize, max: usize) {
+        self.upstream_pool_in_use.with_label_values(&[pool]).set(in_use as i64);
+        self.upstream_pool_saturation
+            .with_label_values(&[pool])
+            .set(in_use as f64 / max as f64);
+    }
+    
+    fn record_upstream_pool_wait(&self, backend: &str, waited: Duration) {
+        self.upstream_pool_wait_seconds.with_label_values(&[backend]).observe(waited.as_secs_f64());
+    }
+    
+    fn update_upstream_connections(&self, backend: &str, idle: usize, in_use: usize) {
+        self.upstream_connections.with_label_values(&[backend, "idle"]).set(idle as i64);
+        self.upstream_connections.with_label_values(&[backend, "in_use"]).set(in_use as i64);
+    }
+    
+    fn record_upstream_connection_opened(&self, backend: &str) {
+        self.upstream_connections_created_total.with_label_values(&[backend]).inc();
+    }
+    
+    fn record_upstream_connection_closed(&self, backend: &str) {
+        self.upstream_connections_closed_total.with_label_values(&[backend]).inc();
+    }
+    
+    fn update_pool_stats(&self, stats: &PoolStats) {
+        self.healthy_backends.set(stats.in_rotation as i64);
+        self.total_backends.set(stats.total as i64);
+        self.draining_backends.set(stats.draining as i64);
+    }
+    
+    fn update_config(&self, hash: &str) {
+        self.config_info.reset();
+        self.config_info.with_label_values(&[hash]).set(1);
+        self.config_last_reload_success_timestamp_seconds
+            .set(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64());
+    }
+    
+    fn record_config_reload(&self, result: &str) {
+        self.config_reloads_total.with_label_values(&[result]).inc();
+    }
+    
+    // Drops every series labelled with `backend` so removed or renamed
+    // backends don't linger in `/metrics` forever.
+    fn remove_backend(&self, backend: &str) {
+        remove_series(&self.requests_total, "backend", backend);
+        remove_series(&self.request_duration_seconds, "backend", backend);
+        remove_series(&self.backend_requests_total, "backend", backend);
+        remove_series(&self.backend_request_duration_seconds, "backend", backend);
+        remove_series(&self.backend_connections_active, "backend", backend);
+        remove_series(&self.backend_throttle_delay_seconds_total, "backend", backend);
+        remove_series(&self.backend_health_status, "backend", backend);
+        remove_series(&self.circuit_breaker_state, "backend", backend);
+        remove_series(&self.circuit_breaker_failures_total, "backend", backend);
+        remove_series(&self.tcp_bytes_total, "backend", backend);
+        remove_series(&self.response_violations_total, "backend", backend);
+        remove_series(&self.latency_budget_exceeded_total, "backend", backend);
+        remove_series(&self.backend_queue_depth, "backend", backend);
+        remove_series(&self.backend_queue_wait_seconds, "backend", backend);
+        remove_series(&self.backend_queue_timeouts_total, "backend", backend);
+        remove_series(&self.upstream_pool_wait_seconds, "backend", backend);
+        remove_series(&self.upstream_connections, "backend", backend);
+        remove_series(&self.upstream_connections_created_total, "backend", backend);
+        remove_series(&self.upstream_connections_closed_total, "backend", backend);
+        self.request_exemplars.retain(|(_, _, labelled), _| labelled != backend);
+    }
+}
+
+/// Remove all children of `vec` whose `label` equals `value`, whatever their
+/// other label values are.
+fn remove_series<T: MetricVecBuilder>(vec: &MetricVec<T>, label: &str, value: &str) {
+    for family in vec.collect() {
+        for metric in family.get_metric() {
+            let labels: HashMap<&str, &str> = metric
+                .get_label()
+                .iter()
+                .map(|pair| (pair.get_name(), pair.get_value()))
+                .collect();
+            
+            if labels.get(label) == Some(&value) {
+                let _ = vec.remove(&labels);
+            }
+        }
     }
 }
 
 // Helper for timing operations
+/// `status_code` as a label, formatted once per code rather than per request.
+fn status_label(status_code: u16) -> Cow<'static, str> {
+    static LABELS: OnceLock<Vec<String>> = OnceLock::new();
+    let labels = LABELS.get_or_init(|| (100..600).map(|code: u16| code.to_string()).collect());
+    match status_code.checked_sub(100).and_then(|i| labels.get(i as usize)) {
+        Some(label) => Cow::Borrowed(label),
+        None => Cow::Owned(status_code.to_string()),
+    }
+}
+
+/// Times on `clock::now`, so only as finely as `runtime.coarse_clock_ms`.
 pub struct Timer {
     start: Instant,
 }
 
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Timer {
     pub fn new() -> Self {
         Self {
-            start: Instant::now(),
+            start: super::clock::now(),
         }
     }
     
-    pub fn elapsed(&self) -> std::time::Duration {
-        self.start.elapsed()
+    pub fn elapsed(&self) -> Duration {
+        super::clock::now().saturating_duration_since(self.start)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn series_for(registry: &MetricsRegistry, backend: &str) -> usize {
+        let output = String::from_utf8(registry.gather()).unwrap();
+        let needle = format!("backend=\"{}\"", backend);
+        output.lines().filter(|line| line.contains(&needle)).count()
+    }
+    
+    #[test]
+    fn test_reload_cycles_do_not_leak_series() {
+        let registry = MetricsRegistry::new().unwrap();
+        let metrics = registry.collector();
+        
+        for cycle in 0..5 {
+            let backend = format!("backend-{}", cycle);
+            metrics.record_request("GET", 200, &backend, Duration::from_millis(5));
+            metrics.record_request("POST", 502, &backend, Duration::from_millis(5));
+            metrics.record_backend_request(&backend, true, Duration::from_millis(3));
+            metrics.update_backend_connections(&backend, 1);
+            metrics.update_backend_health(&backend, true);
+            metrics.update_circuit_breaker_state(&backend, CircuitBreakerState::Closed);
+            assert!(series_for(&registry, &backend) > 0);
+            
+            metrics.remove_backend(&backend);
+            assert_eq!(series_for(&registry, &backend), 0);
+        }
+    }
+    
+    #[test]
+    fn test_openmetrics_attaches_exemplars_to_buckets() {
+        let registry = MetricsRegistry::new().unwrap();
+        let metrics = registry.collector();
+        metrics.record_request("GET", 200, "b1", Duration::from_millis(70));
+        metrics.record_request_exemplar("GET", 200, "b1", Duration::from_millis(70), "4bf92f3577b34da6a3ce929d0e0e4736");
+        
+        let output = String::from_utf8(registry.gather_openmetrics()).unwrap();
+        assert!(output.ends_with("# EOF\n"));
+        assert!(output.contains("# TYPE lb_requests counter\n"));
+        assert!(output.contains("lb_requests_total{backend=\"b1\",method=\"GET\",status_code=\"200\"} 1.0\n"));
+        let bucket = output
+            .lines()
+            .find(|line| line.starts_with("lb_request_duration_seconds_bucket") && line.contains("le=\"0.1\""))
+            .unwrap();
+        assert!(bucket.contains(" 1 # {trace_id=\"4bf92f3577b34da6a3ce929d0e0e4736\"} 0.07 "), "{}", bucket);
+        assert_eq!(output.matches("trace_id=").count(), 1);
+        
+        metrics.remove_backend("b1");
+        assert!(!String::from_utf8(registry.gather_openmetrics()).unwrap().contains("trace_id="));
+    }
+    
+    #[test]
+    fn test_config_info_follows_the_config_in_effect() {
+        let registry = MetricsRegistry::new().unwrap();
+        let metrics = registry.collector();
+        metrics.update_config("0123456789abcdef");
+        metrics.record_config_reload("failure");
+        metrics.update_config("fedcba9876543210");
+        metrics.record_config_reload("success");
+        
+        let output = String::from_utf8(registry.gather()).unwrap();
+        assert!(output.contains("lb_config_info{hash=\"fedcba9876543210\"} 1\n"));
+        assert!(!output.contains("0123456789abcdef"));
+        assert!(output.contains("lb_config_reload_total{result=\"failure\"} 1\n"));
+        assert!(output.contains("lb_config_reload_total{result=\"success\"} 1\n"));
+        assert!(!output.contains("lb_config_last_reload_success_timestamp_seconds 0\n"));
     }
 }
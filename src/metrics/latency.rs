@@ -0,0 +1,243 @@
+// src/metrics/latency.rs
+//
+// Rolling-window latency percentiles per backend and per route, served by the
+// admin API without needing a Prometheus server to run histogram_quantile.
+use dashmap::DashMap;
+use hdrhistogram::Histogram;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BUCKET_SECS: u64 = 10;
+/// Highest trackable latency; slower samples are clamped.
+const MAX_LATENCY_MICROS: u64 = 60_000_000;
+const SIGNIFICANT_FIGURES: u8 = 2;
+
+fn new_histogram() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, MAX_LATENCY_MICROS, SIGNIFICANT_FIGURES)
+        .expect("valid histogram bounds")
+}
+
+/// Ring of per-bucket histograms merged on read.
+struct RollingHistogram {
+    buckets: Mutex<Vec<(u64, Histogram<u64>)>>,
+}
+
+impl RollingHistogram {
+    fn new(bucket_count: usize) -> Self {
+        Self {
+            buckets: Mutex::new((0..bucket_count).map(|_| (0, new_histogram())).collect()),
+        }
+    }
+
+    fn record(&self, epoch: u64, micros: u64) {
+        let mut buckets = self.buckets.lock().unwrap();
+        let len = buckets.len() as u64;
+        let slot = &mut buckets[(epoch % len) as usize];
+        if slot.0 != epoch {
+            slot.0 = epoch;
+            slot.1.reset();
+        }
+        slot.1.saturating_record(micros.max(1));
+    }
+
+    fn merged(&self, now: u64) -> Histogram<u64> {
+        let buckets = self.buckets.lock().unwrap();
+        let span = buckets.len() as u64;
+        let mut merged = new_histogram();
+        for (epoch, histogram) in buckets.iter() {
+            if *epoch <= now && now - *epoch < span {
+                // Same bounds on both sides, so this cannot fail
+                let _ = merged.add(histogram);
+            }
+        }
+        merged
+    }
+
+    /// Whether any bucket in the window up to `now` has samples.
+    fn is_active(&self, now: u64) -> bool {
+        let buckets = self.buckets.lock().unwrap();
+        let span = buckets.len() as u64;
+        buckets
+            .iter()
+            .any(|(epoch, histogram)| *epoch <= now && now - *epoch < span && !histogram.is_empty())
+    }
+}
+
+/// Histograms for one dimension, capped: each holds a histogram per bucket.
+struct Dimension {
+    histograms: DashMap<String, RollingHistogram>,
+    last_prune: AtomicU64,
+}
+
+impl Dimension {
+    fn new() -> Self {
+        Self { histograms: DashMap::new(), last_prune: AtomicU64::new(u64::MAX) }
+    }
+
+    /// Drop keys with no samples in the window; at most once per bucket.
+    fn prune(&self, epoch: u64) {
+        if self.last_prune.swap(epoch, Ordering::Relaxed) == epoch {
+            return;
+        }
+        self.histograms.retain(|_, histogram| histogram.is_active(epoch));
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencySummary {
+    pub key: String,
+    pub count: u64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencySnapshot {
+    pub window_secs: u64,
+    pub backends: Vec<LatencySummary>,
+    pub routes: Vec<LatencySummary>,
+}
+
+pub struct LatencyStats {
+    started: Instant,
+    bucket_count: usize,
+    max_keys: usize,
+    backends: Dimension,
+    routes: Dimension,
+}
+
+impl LatencyStats {
+    pub fn new(window: Duration, max_keys: usize) -> Self {
+        let bucket_count = (window.as_secs() / BUCKET_SECS).max(1) as usize;
+        Self {
+            started: Instant::now(),
+            bucket_count,
+            max_keys,
+            backends: Dimension::new(),
+            routes: Dimension::new(),
+        }
+    }
+
+    fn epoch(&self) -> u64 {
+        self.started.elapsed().as_secs() / BUCKET_SECS
+    }
+
+    pub fn record_backend(&self, backend: &str, duration: Duration) {
+        self.record(&self.backends, backend, duration);
+    }
+
+    pub fn record_route(&self, route: &str, duration: Duration) {
+        self.record(&self.routes, route, duration);
+    }
+
+    fn record(&self, dimension: &Dimension, key: &str, duration: Duration) {
+        let epoch = self.epoch();
+        let micros = duration.as_micros().min(MAX_LATENCY_MICROS as u128) as u64;
+
+        if let Some(histogram) = dimension.histograms.get(key) {
+            histogram.record(epoch, micros);
+            return;
+        }
+
+        if dimension.histograms.len() >= self.max_keys {
+            dimension.prune(epoch);
+        }
+        let key = if dimension.histograms.len() < self.max_keys {
+            key
+        } else {
+            super::traffic::OVERFLOW_KEY
+        };
+        dimension.histograms.entry(key.to_string())
+            .or_insert_with(|| RollingHistogram::new(self.bucket_count))
+            .record(epoch, micros);
+    }
+
+    pub fn remove_backend(&self, backend: &str) {
+        self.backends.histograms.remove(backend);
+    }
+
+    pub fn snapshot(&self) -> LatencySnapshot {
+        let now = self.epoch();
+        LatencySnapshot {
+            window_secs: self.bucket_count as u64 * BUCKET_SECS,
+            backends: summarize(&self.backends.histograms, now),
+            routes: summarize(&self.routes.histograms, now),
+        }
+    }
+}
+
+fn summarize(map: &DashMap<String, RollingHistogram>, now: u64) -> Vec<LatencySummary> {
+    let to_ms = |micros: u64| micros as f64 / 1000.0;
+    let mut summaries: Vec<LatencySummary> = map
+        .iter()
+        .filter_map(|entry| {
+            let histogram = entry.value().merged(now);
+            if histogram.is_empty() {
+                return None;
+            }
+            Some(LatencySummary {
+                key: entry.key().clone(),
+                count: histogram.len(),
+                p50_ms: to_ms(histogram.value_at_quantile(0.50)),
+                p90_ms: to_ms(histogram.value_at_quantile(0.90)),
+                p99_ms: to_ms(histogram.value_at_quantile(0.99)),
+                max_ms: to_ms(histogram.max()),
+            })
+        })
+        .collect();
+    summaries.sort_by(|a, b| a.key.cmp(&b.key));
+    summaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles_over_window() {
+        let stats = LatencyStats::new(Duration::from_secs(60), 100);
+        for ms in 1..=100 {
+            stats.record_backend("b1", Duration::from_millis(ms));
+        }
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.window_secs, 60);
+        let b1 = &snapshot.backends[0];
+        assert_eq!(b1.count, 100);
+        assert!((49.0..=51.0).contains(&b1.p50_ms), "p50 = {}", b1.p50_ms);
+        assert!((98.0..=101.0).contains(&b1.p99_ms), "p99 = {}", b1.p99_ms);
+    }
+
+    #[test]
+    fn test_old_buckets_are_excluded() {
+        let histogram = RollingHistogram::new(6);
+        histogram.record(0, 1_000);
+        histogram.record(7, 2_000);
+
+        let merged = histogram.merged(7);
+        assert_eq!(merged.len(), 1);
+    }
+
+    #[test]
+    fn test_idle_keys_make_room_for_new_ones() {
+        let stats = LatencyStats::new(Duration::from_secs(60), 2);
+        stats.record(&stats.routes, "a", Duration::from_millis(1));
+        stats.record(&stats.routes, "b", Duration::from_millis(1));
+        stats.record(&stats.routes, "c", Duration::from_millis(1));
+        let keys = |stats: &LatencyStats| {
+            let mut keys: Vec<_> = stats.routes.histograms.iter().map(|entry| entry.key().clone()).collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(&stats), ["(other)", "a", "b"]);
+
+        // Once the window has passed them by, they're dropped for new keys
+        let stats = LatencyStats { started: Instant::now() - Duration::from_secs(600), ..stats };
+        stats.record(&stats.routes, "d", Duration::from_millis(1));
+        assert_eq!(keys(&stats), ["d"]);
+    }
+}
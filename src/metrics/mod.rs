@@ -1,4 +1,15 @@
 // src/metrics/mod.rs
+pub mod clock;
 mod collector;
+mod latency;
+pub mod openmetrics;
+mod sink;
+mod slo;
+mod timings;
+mod traffic;
 
 pub use collector::{Timer, MetricsCollector, MetricsRegistry};
+pub use sink::{MetricsSink, NoopMetrics};
+pub use latency::{LatencySnapshot, LatencyStats};
+pub use timings::{RequestTimings, TimingsSnapshot};
+pub use traffic::{TrafficSnapshot, TrafficStats, UNROUTED_KEY};
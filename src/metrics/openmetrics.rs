@@ -0,0 +1,132 @@
+// src/metrics/openmetrics.rs
+//! The OpenMetrics text format, which the `prometheus` crate can't write.
+//! It's needed for exemplars: Prometheus only reads them from this format,
+//! and asks for it with `Accept: application/openmetrics-text`.
+use prometheus::proto::{Metric, MetricFamily, MetricType};
+use std::fmt::Write;
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// One observation attached to the histogram bucket it fell in, so a
+/// dashboard can link from the bucket to the trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exemplar {
+    pub trace_id: String,
+    pub value: f64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: f64,
+}
+
+/// `families` as OpenMetrics. `exemplar(family, metric, bucket)` is asked
+/// for each histogram bucket, `+Inf` being the one past the last bound.
+pub fn encode<F>(families: &[MetricFamily], exemplar: F) -> String
+where
+    F: Fn(&MetricFamily, &Metric, usize) -> Option<Exemplar>,
+{
+    let mut out = String::new();
+    for family in families {
+        let name = family.get_name();
+        let (name, kind) = match family.get_field_type() {
+            MetricType::COUNTER => match name.strip_suffix("_total") {
+                Some(base) => (base, "counter"),
+                // OpenMetrics counters must end in `_total`
+                None => (name, "unknown"),
+            },
+            MetricType::GAUGE => (name, "gauge"),
+            MetricType::HISTOGRAM => (name, "histogram"),
+            MetricType::SUMMARY => (name, "summary"),
+            MetricType::UNTYPED => (name, "unknown"),
+        };
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        if !family.get_help().is_empty() {
+            let _ = writeln!(out, "# HELP {} {}", name, escape(family.get_help()));
+        }
+
+        for metric in family.get_metric() {
+            let labels = labels(metric, None);
+            match family.get_field_type() {
+                MetricType::COUNTER if kind == "counter" => {
+                    sample(&mut out, &format!("{}_total", name), &labels, metric.get_counter().get_value());
+                }
+                MetricType::COUNTER => sample(&mut out, name, &labels, metric.get_counter().get_value()),
+                MetricType::GAUGE => sample(&mut out, name, &labels, metric.get_gauge().get_value()),
+                MetricType::UNTYPED => sample(&mut out, name, &labels, metric.get_untyped().get_value()),
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        let labels = self::labels(metric, Some(("quantile", quantile.get_quantile())));
+                        sample(&mut out, name, &labels, quantile.get_value());
+                    }
+                    sample(&mut out, &format!("{}_sum", name), &labels, summary.get_sample_sum());
+                    sample(&mut out, &format!("{}_count", name), &labels, summary.get_sample_count() as f64);
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let bucket_name = format!("{}_bucket", name);
+                    let mut buckets: Vec<(f64, u64)> = histogram
+                        .get_bucket()
+                        .iter()
+                        .map(|bucket| (bucket.get_upper_bound(), bucket.get_cumulative_count()))
+                        .collect();
+                    if buckets.last().is_none_or(|(bound, _)| bound.is_finite()) {
+                        buckets.push((f64::INFINITY, histogram.get_sample_count()));
+                    }
+                    for (i, (bound, count)) in buckets.into_iter().enumerate() {
+                        let labels = self::labels(metric, Some(("le", bound)));
+                        let _ = write!(out, "{}{} {}", bucket_name, labels, count);
+                        if let Some(exemplar) = exemplar(family, metric, i) {
+                            let _ = write!(
+                                out,
+                                " # {{trace_id=\"{}\"}} {} {:.3}",
+                                escape(&exemplar.trace_id),
+                                number(exemplar.value),
+                                exemplar.timestamp
+                            );
+                        }
+                        out.push('\n');
+                    }
+                    sample(&mut out, &format!("{}_sum", name), &labels, histogram.get_sample_sum());
+                    sample(&mut out, &format!("{}_count", name), &labels, histogram.get_sample_count() as f64);
+                }
+            }
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+/// `{name="value",...}`, or nothing for a metric without labels.
+fn labels(metric: &Metric, extra: Option<(&str, f64)>) -> String {
+    let mut pairs: Vec<String> = metric
+        .get_label()
+        .iter()
+        .map(|pair| format!("{}=\"{}\"", pair.get_name(), escape(pair.get_value())))
+        .collect();
+    if let Some((name, value)) = extra {
+        pairs.push(format!("{}=\"{}\"", name, number(value)));
+    }
+    match pairs.is_empty() {
+        true => String::new(),
+        false => format!("{{{}}}", pairs.join(",")),
+    }
+}
+
+fn sample(out: &mut String, name: &str, labels: &str, value: f64) {
+    let _ = writeln!(out, "{}{} {}", name, labels, number(value));
+}
+
+/// OpenMetrics spells infinities `+Inf`/`-Inf`, and `le` and `quantile`
+/// values must be floats, so whole numbers get a `.0`.
+fn number(value: f64) -> String {
+    match value {
+        v if v == f64::INFINITY => "+Inf".to_string(),
+        v if v == f64::NEG_INFINITY => "-Inf".to_string(),
+        v if v.is_nan() => "NaN".to_string(),
+        v if v.fract() == 0.0 && v.abs() < 1e15 => format!("{:.1}", v),
+        v => v.to_string(),
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -0,0 +1,152 @@
+// src/metrics/sink.rs
+use crate::circuit_breaker::CircuitBreakerState;
+use crate::proxy::PoolStats;
+use std::time::Duration;
+
+/// Recorder for everything the load balancer measures.
+///
+/// [`MetricsCollector`](super::MetricsCollector) is the Prometheus-backed
+/// default; embedders can plug in their own recorder instead. Every method
+/// defaults to a no-op so a sink only needs to implement what it cares about.
+pub trait MetricsSink: Send + Sync {
+    fn record_request(&self, _method: &str, _status_code: u16, _backend: &str, _duration: Duration) {}
+
+    /// Follows `record_request` for requests carrying a trace ID, when
+    /// `metrics.exemplars` is set.
+    fn record_request_exemplar(&self, _method: &str, _status_code: u16, _backend: &str, _duration: Duration, _trace_id: &str) {}
+
+    fn record_request_size(&self, _method: &str, _bytes: u64) {}
+
+    fn record_response_size(&self, _method: &str, _status_code: u16, _bytes: u64) {}
+
+    fn record_backend_request(&self, _backend: &str, _success: bool, _duration: Duration) {}
+
+    fn update_backend_connections(&self, _backend: &str, _count: i64) {}
+
+    /// A response body from `backend` held back `delay` by its bandwidth cap.
+    fn record_backend_throttle(&self, _backend: &str, _delay: Duration) {}
+
+    /// A response to a client in download limit `tier` held back `delay`.
+    fn record_client_throttle(&self, _tier: &str, _delay: Duration) {}
+
+    /// A request found `backend` at `max_connections` and waited behind
+    /// `ahead` others, getting a connection or giving up after `waited`.
+    fn record_backend_queue(&self, _backend: &str, _ahead: usize, _waited: Duration, _admitted: bool) {}
+
+    /// Requests holding one of backend pool `pool`'s `max` upstream slots.
+    fn update_upstream_pool(&self, _pool: &str, _in_use: usize, _max: usize) {}
+
+    /// How long a request to `backend` waited for an upstream slot.
+    fn record_upstream_pool_wait(&self, _backend: &str, _waited: Duration) {}
+
+    /// `backend`'s open upstream connections, idle or being used.
+    fn update_upstream_connections(&self, _backend: &str, _idle: usize, _in_use: usize) {}
+
+    fn record_upstream_connection_opened(&self, _backend: &str) {}
+
+    fn record_upstream_connection_closed(&self, _backend: &str) {}
+
+    fn update_backend_health(&self, _backend: &str, _healthy: bool) {}
+
+    fn update_circuit_breaker_state(&self, _backend: &str, _state: CircuitBreakerState) {}
+
+    fn increment_active_connections(&self) {}
+
+    fn decrement_active_connections(&self) {}
+
+    fn record_connection_opened(&self, _listener: &str) {}
+
+    fn record_connection_rejected(&self, _listener: &str, _reason: &str) {}
+
+    fn record_accept_error(&self, _listener: &str, _kind: &str) {}
+
+    fn record_connection_closed(&self, _listener: &str, _lifetime: Duration) {}
+
+    fn record_connection_first_byte(&self, _listener: &str, _elapsed: Duration) {}
+
+    /// A client connection finished its TLS handshake.
+    fn record_tls_handshake(&self, _listener: &str, _elapsed: Duration) {}
+
+    /// A request was refused by a listener's request validation or the
+    /// HTTP/1 parser, before routing.
+    fn record_request_rejected(&self, _listener: &str, _reason: &str) {}
+
+    /// Bytes a TCP passthrough connection received from and sent to its client.
+    fn record_tcp_bytes(&self, _listener: &str, _backend: &str, _received: u64, _sent: u64) {}
+
+    /// A request matched `route` and was sent to `pool`; for split routes
+    /// this is the observed traffic split.
+    fn record_route(&self, _route: &str, _pool: &str) {}
+
+    /// A request on `route` went to its fallback `pool`.
+    fn record_route_fallback(&self, _route: &str, _pool: &str) {}
+
+    /// A request on `route` spilled over to backends in remote `region`.
+    fn record_region_spillover(&self, _route: &str, _region: &str) {}
+
+    /// `backend`'s response on `route` failed `validate_response`.
+    fn record_response_violation(&self, _route: &str, _backend: &str) {}
+
+    /// `backend` hadn't answered a request on `route` by the end of its
+    /// latency budget.
+    fn record_latency_budget_exceeded(&self, _route: &str, _backend: &str) {}
+
+    /// A request on an experiment route was assigned `variant`.
+    fn record_experiment(&self, _experiment: &str, _variant: &str) {}
+
+    /// A discovery source `added`, `updated`, `drained` or `deferred`
+    /// (held back by the churn limit) `count` backends in one reconcile.
+    fn record_discovery_changes(&self, _source: &str, _change: &str, _count: usize) {}
+
+    /// Backends a discovery source currently manages, draining ones included.
+    fn update_discovery_backends(&self, _source: &str, _count: usize) {}
+
+    /// A backend event of `kind` was `sent` to peers (one count per peer
+    /// reached), `received` and applied, or `failed` to reach a peer.
+    fn record_cluster_event(&self, _kind: &str, _outcome: &str) {}
+
+    /// A request on a caching route was a cache `hit`, `revalidated` with
+    /// the backend, a `miss`, or had to `bypass` the cache.
+    fn record_cache_lookup(&self, _result: &str) {}
+
+    /// Entries evicted to stay within the cache's memory budget.
+    fn record_cache_evictions(&self, _count: usize) {}
+
+    fn update_cache_size(&self, _entries: usize, _bytes: usize) {}
+
+    /// The disk cache tier had a `hit`, did a `write`, dropped one because
+    /// it was behind (`write_dropped`) or it failed (`write_failed`),
+    /// `evicted` a file, or found a `corrupt` one.
+    fn record_cache_disk(&self, _operation: &str) {}
+
+    fn update_cache_disk_size(&self, _entries: usize, _bytes: usize) {}
+
+    /// A request on `route` was rejected by admission control.
+    fn record_load_shed(&self, _route: &str) {}
+
+    /// The pressure admission control measured, and the event loop lag
+    /// that went into it.
+    fn update_admission_pressure(&self, _pressure: f64, _event_loop_lag: Duration) {}
+
+    /// A request over its client's fair share was `queued` or `rejected`.
+    fn record_fairness(&self, _outcome: &str) {}
+
+    /// The pool changed, or its backends' health did.
+    fn update_pool_stats(&self, _stats: &PoolStats) {}
+
+    /// The config in effect is now the one with `hash`, loaded at startup
+    /// or by a reload.
+    fn update_config(&self, _hash: &str) {}
+
+    /// A reload was applied (`success`) or turned away (`failure`).
+    fn record_config_reload(&self, _result: &str) {}
+
+    /// Forget all state kept for a backend that left the pool.
+    fn remove_backend(&self, _backend: &str) {}
+}
+
+/// Sink that discards everything.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopMetrics;
+
+impl MetricsSink for NoopMetrics {}
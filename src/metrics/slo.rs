@@ -0,0 +1,158 @@
+// src/metrics/slo.rs
+//
+// Precomputed SLO good/bad event counters and error-budget burn rates, so
+// alerts can be plain thresholds instead of per-deployment PromQL.
+use crate::config::SloConfig;
+use anyhow::Result;
+use prometheus::{GaugeVec, IntCounterVec, Opts, Registry};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Burn-rate windows as (label, length); the usual fast/slow pair.
+const BURN_WINDOWS: [(&str, Duration); 2] = [
+    ("5m", Duration::from_secs(300)),
+    ("1h", Duration::from_secs(3600)),
+];
+const SLOTS_PER_WINDOW: u64 = 30;
+
+/// Good/bad counts over a rolling window split into fixed slots.
+struct EventWindow {
+    slot_secs: u64,
+    slots: Vec<(u64, u64, u64)>, // (slot epoch, good, bad)
+}
+
+impl EventWindow {
+    fn new(length: Duration) -> Self {
+        Self {
+            slot_secs: (length.as_secs() / SLOTS_PER_WINDOW).max(1),
+            slots: vec![(0, 0, 0); SLOTS_PER_WINDOW as usize],
+        }
+    }
+
+    fn record(&mut self, elapsed_secs: u64, good: bool) {
+        let epoch = elapsed_secs / self.slot_secs;
+        let slot = &mut self.slots[(epoch % SLOTS_PER_WINDOW) as usize];
+        if slot.0 != epoch {
+            *slot = (epoch, 0, 0);
+        }
+        if good {
+            slot.1 += 1;
+        } else {
+            slot.2 += 1;
+        }
+    }
+
+    fn totals(&self, elapsed_secs: u64) -> (u64, u64) {
+        let now = elapsed_secs / self.slot_secs;
+        self.slots
+            .iter()
+            .filter(|(epoch, _, _)| *epoch <= now && now - *epoch < SLOTS_PER_WINDOW)
+            .fold((0, 0), |(good, bad), (_, g, b)| (good + g, bad + b))
+    }
+}
+
+pub struct SloTracker {
+    config: SloConfig,
+    started: Instant,
+    windows: Mutex<Vec<EventWindow>>,
+    events_total: IntCounterVec,
+    burn_rate: GaugeVec,
+}
+
+impl SloTracker {
+    pub fn new(config: SloConfig, registry: &Registry) -> Result<Self> {
+        let events_total = IntCounterVec::new(
+            Opts::new("lb_slo_events_total", "Requests classified against the SLO"),
+            &["result"],
+        )?;
+        registry.register(Box::new(events_total.clone()))?;
+
+        let burn_rate = GaugeVec::new(
+            Opts::new(
+                "lb_slo_error_budget_burn_rate",
+                "Error budget burn rate (1.0 = exactly on budget)",
+            ),
+            &["window"],
+        )?;
+        registry.register(Box::new(burn_rate.clone()))?;
+
+        let target = prometheus::Gauge::new("lb_slo_target", "SLO target ratio of good requests")?;
+        target.set(config.target);
+        registry.register(Box::new(target))?;
+
+        // Initialize every series so they're present before the first request
+        for result in ["good", "bad"] {
+            events_total.with_label_values(&[result]);
+        }
+        for (label, _) in BURN_WINDOWS {
+            burn_rate.with_label_values(&[label]).set(0.0);
+        }
+
+        Ok(Self {
+            config,
+            started: Instant::now(),
+            windows: Mutex::new(BURN_WINDOWS.iter().map(|(_, len)| EventWindow::new(*len)).collect()),
+            events_total,
+            burn_rate,
+        })
+    }
+
+    /// A request is good when it didn't fail server-side and met the latency
+    /// threshold.
+    pub fn record(&self, status_code: u16, duration: Duration) {
+        let good = status_code < 500 && duration <= self.config.latency_threshold();
+        self.events_total
+            .with_label_values(&[if good { "good" } else { "bad" }])
+            .inc();
+
+        let elapsed = self.started.elapsed().as_secs();
+        for window in self.windows.lock().unwrap().iter_mut() {
+            window.record(elapsed, good);
+        }
+    }
+
+    /// Recompute the burn-rate gauges; called right before metrics are gathered.
+    pub fn refresh(&self) {
+        let elapsed = self.started.elapsed().as_secs();
+        let budget = 1.0 - self.config.target;
+        let windows = self.windows.lock().unwrap();
+
+        for ((label, _), window) in BURN_WINDOWS.iter().zip(windows.iter()) {
+            let (good, bad) = window.totals(elapsed);
+            let total = good + bad;
+            let rate = if total == 0 || budget <= 0.0 {
+                0.0
+            } else {
+                (bad as f64 / total as f64) / budget
+            };
+            self.burn_rate.with_label_values(&[label]).set(rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burn_rate_reflects_bad_ratio() {
+        let registry = Registry::new();
+        let config = SloConfig {
+            target: 0.99,
+            latency_threshold_ms: 500,
+        };
+        let tracker = SloTracker::new(config, &registry).unwrap();
+
+        for _ in 0..98 {
+            tracker.record(200, Duration::from_millis(10));
+        }
+        tracker.record(503, Duration::from_millis(10));
+        tracker.record(200, Duration::from_secs(2));
+        tracker.refresh();
+
+        // 2% bad against a 1% budget burns at 2x
+        let rate = tracker.burn_rate.with_label_values(&["5m"]).get();
+        assert!((rate - 2.0).abs() < 1e-9, "burn rate = {}", rate);
+        assert_eq!(tracker.events_total.with_label_values(&["bad"]).get(), 2);
+    }
+}
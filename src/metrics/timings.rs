@@ -0,0 +1,57 @@
+// src/metrics/timings.rs
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Where a request's time went before its response started, filled in as
+/// it passes through the proxy. Shared by reference, hence the atomics.
+#[derive(Debug, Default)]
+pub struct RequestTimings {
+    queue_micros: AtomicU64,
+    connect_micros: AtomicU64,
+    ttfb_micros: AtomicU64,
+    attempts: AtomicU32,
+}
+
+/// `RequestTimings` as of one moment.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimingsSnapshot {
+    /// Waiting for a fairness slot or room in an upstream pool.
+    pub queue: Duration,
+    /// Dialing backends, over every attempt that opened a connection.
+    pub connect: Duration,
+    /// From sending the last attempt that got a response to its headers.
+    pub ttfb: Duration,
+    pub attempts: u32,
+}
+
+impl RequestTimings {
+    pub fn add_queue(&self, took: Duration) {
+        self.queue_micros.fetch_add(micros(took), Ordering::Relaxed);
+    }
+
+    pub fn add_connect(&self, took: Duration) {
+        self.connect_micros.fetch_add(micros(took), Ordering::Relaxed);
+    }
+
+    /// Counts an attempt. One that got a response replaces the time to
+    /// first byte of any before it.
+    pub fn record_attempt(&self, ttfb: Option<Duration>) {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+        if let Some(ttfb) = ttfb {
+            self.ttfb_micros.store(micros(ttfb), Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> TimingsSnapshot {
+        TimingsSnapshot {
+            queue: Duration::from_micros(self.queue_micros.load(Ordering::Relaxed)),
+            connect: Duration::from_micros(self.connect_micros.load(Ordering::Relaxed)),
+            ttfb: Duration::from_micros(self.ttfb_micros.load(Ordering::Relaxed)),
+            attempts: self.attempts.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().min(u64::MAX as u128) as u64
+}
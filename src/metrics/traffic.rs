@@ -0,0 +1,229 @@
+// src/metrics/traffic.rs
+//
+// In-memory rolling request counters ("top talkers"), kept independent of
+// Prometheus so they can be inspected through the admin API.
+use dashmap::DashMap;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+const BUCKET_SECS: u64 = 10;
+/// 90 ten-second buckets cover the longest (15 minute) window.
+const BUCKET_COUNT: usize = 90;
+
+/// Keys arriving after a dimension is full are counted under this key.
+pub const OVERFLOW_KEY: &str = "(other)";
+
+/// The route of requests answered before routing.
+pub const UNROUTED_KEY: &str = "(unrouted)";
+
+/// Reported windows as (label, seconds).
+pub const WINDOWS: [(&str, u64); 3] = [("1m", 60), ("5m", 300), ("15m", 900)];
+
+/// Ring of time buckets; each slot remembers which bucket epoch it holds so
+/// stale slots are recycled lazily instead of by a background task.
+struct RollingCounter {
+    buckets: Mutex<[(u64, u64); BUCKET_COUNT]>,
+}
+
+impl RollingCounter {
+    fn new() -> Self {
+        Self {
+            buckets: Mutex::new([(0, 0); BUCKET_COUNT]),
+        }
+    }
+
+    fn record(&self, epoch: u64) {
+        let mut buckets = self.buckets.lock().unwrap();
+        let slot = &mut buckets[(epoch % BUCKET_COUNT as u64) as usize];
+        if slot.0 != epoch {
+            *slot = (epoch, 0);
+        }
+        slot.1 += 1;
+    }
+
+    /// Sum of the `span` most recent buckets up to and including `now`.
+    fn sum(&self, now: u64, span: u64) -> u64 {
+        let buckets = self.buckets.lock().unwrap();
+        buckets
+            .iter()
+            .filter(|(epoch, _)| *epoch <= now && now - *epoch < span)
+            .map(|(_, count)| *count)
+            .sum()
+    }
+}
+
+/// Rolling counters for one dimension (route, client, backend), capped so an
+/// attacker rotating source IPs can't grow memory without bound.
+struct Dimension {
+    counters: DashMap<String, RollingCounter>,
+    max_keys: usize,
+    last_prune: AtomicU64,
+}
+
+impl Dimension {
+    fn new(max_keys: usize) -> Self {
+        Self {
+            counters: DashMap::new(),
+            max_keys,
+            last_prune: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    fn record(&self, key: &str, epoch: u64) {
+        if let Some(counter) = self.counters.get(key) {
+            counter.record(epoch);
+            return;
+        }
+
+        if self.counters.len() >= self.max_keys {
+            self.prune(epoch);
+        }
+
+        let key = if self.counters.len() < self.max_keys {
+            key
+        } else {
+            OVERFLOW_KEY
+        };
+
+        self.counters
+            .entry(key.to_string())
+            .or_insert_with(RollingCounter::new)
+            .record(epoch);
+    }
+
+    /// Drop keys with no traffic in the longest window; at most once per bucket.
+    fn prune(&self, epoch: u64) {
+        if self.last_prune.swap(epoch, Ordering::Relaxed) == epoch {
+            return;
+        }
+        self.counters
+            .retain(|_, counter| counter.sum(epoch, BUCKET_COUNT as u64) > 0);
+    }
+
+    fn top(&self, now: u64, span: u64, limit: usize) -> (u64, Vec<TopEntry>) {
+        let mut entries: Vec<TopEntry> = self
+            .counters
+            .iter()
+            .map(|entry| TopEntry {
+                key: entry.key().clone(),
+                requests: entry.value().sum(now, span),
+            })
+            .filter(|entry| entry.requests > 0)
+            .collect();
+
+        let total = entries.iter().map(|e| e.requests).sum();
+        entries.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.key.cmp(&b.key)));
+        entries.truncate(limit);
+        (total, entries)
+    }
+}
+
+pub struct TrafficStats {
+    started: Instant,
+    routes: Dimension,
+    clients: Dimension,
+    backends: Dimension,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopEntry {
+    pub key: String,
+    pub requests: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WindowStats {
+    pub window: &'static str,
+    pub total_requests: u64,
+    pub routes: Vec<TopEntry>,
+    pub clients: Vec<TopEntry>,
+    pub backends: Vec<TopEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrafficSnapshot {
+    pub windows: Vec<WindowStats>,
+}
+
+impl TrafficStats {
+    pub fn new(max_keys_per_dimension: usize) -> Self {
+        Self {
+            started: Instant::now(),
+            routes: Dimension::new(max_keys_per_dimension),
+            clients: Dimension::new(max_keys_per_dimension),
+            backends: Dimension::new(max_keys_per_dimension),
+        }
+    }
+
+    fn epoch(&self) -> u64 {
+        self.started.elapsed().as_secs() / BUCKET_SECS
+    }
+
+    pub fn record(&self, route: &str, client: Option<IpAddr>, backend: &str) {
+        let epoch = self.epoch();
+        self.routes.record(route, epoch);
+        match client {
+            Some(ip) => self.clients.record(&ip.to_string(), epoch),
+            None => self.clients.record("unknown", epoch),
+        }
+        self.backends.record(backend, epoch);
+    }
+
+    /// Top `limit` keys per dimension for every reported window.
+    pub fn snapshot(&self, limit: usize) -> TrafficSnapshot {
+        let now = self.epoch();
+        let windows = WINDOWS
+            .iter()
+            .map(|(label, secs)| {
+                let span = secs / BUCKET_SECS;
+                let (total_requests, backends) = self.backends.top(now, span, limit);
+                WindowStats {
+                    window: label,
+                    total_requests,
+                    routes: self.routes.top(now, span, limit).1,
+                    clients: self.clients.top(now, span, limit).1,
+                    backends,
+                }
+            })
+            .collect();
+
+        TrafficSnapshot { windows }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_counter_expires_old_buckets() {
+        let counter = RollingCounter::new();
+        counter.record(1);
+        counter.record(1);
+        counter.record(10);
+
+        assert_eq!(counter.sum(10, 6), 1);
+        assert_eq!(counter.sum(10, 90), 3);
+        // Slot 1 is reused once the ring wraps around
+        counter.record(91);
+        assert_eq!(counter.sum(91, 90), 2);
+    }
+
+    #[test]
+    fn test_dimension_caps_keys() {
+        let dimension = Dimension::new(2);
+        dimension.record("a", 0);
+        dimension.record("b", 0);
+        dimension.record("c", 0);
+        dimension.record("a", 0);
+
+        let (total, top) = dimension.top(0, 6, 10);
+        assert_eq!(total, 4);
+        assert_eq!(top[0].key, "a");
+        assert!(top.iter().any(|e| e.key == OVERFLOW_KEY));
+        assert!(!top.iter().any(|e| e.key == "c"));
+    }
+}
@@ -0,0 +1,222 @@
+// src/middleware/auth.rs
+use super::chain::{Filter, FilterContext};
+use super::forward_auth::ForwardAuthFilter;
+use crate::config::{ApiKeyConfig, AuthMode, AuthStrategyConfig, RouteAuthConfig};
+use async_trait::async_trait;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{debug, error};
+
+/// What an auth strategy made of a request.
+pub enum AuthOutcome {
+    /// Headers to add to the request upstream, such as who the user is.
+    Allowed(Vec<(HeaderName, HeaderValue)>),
+    /// It carried no credentials this strategy understands; the response
+    /// is what the client gets if that's final.
+    Missing(Response<Body>),
+    /// Its credentials were rejected, or couldn't be checked.
+    Denied(Response<Body>),
+}
+
+/// A way of authenticating requests that routes can combine in `auth`.
+#[async_trait]
+pub trait AuthStrategy: Send + Sync {
+    async fn authenticate(&self, req: &Request<Body>) -> AuthOutcome;
+
+    /// Remove headers clients mustn't set themselves, such as the identity
+    /// headers `authenticate` adds.
+    fn strip(&self, _req: &mut Request<Body>) {}
+}
+
+/// Accepts requests carrying one of a fixed list of keys in a header.
+pub struct ApiKeyAuth {
+    header: HeaderName,
+    /// SHA-256 of each key, so lookups don't compare the keys themselves.
+    keys: HashSet<[u8; 32]>,
+}
+
+impl ApiKeyAuth {
+    pub fn new(config: &ApiKeyConfig) -> Self {
+        Self {
+            // Validated in Config::validate
+            header: HeaderName::from_bytes(config.header.as_bytes()).expect("invalid api_key header"),
+            keys: config.keys.iter().filter(|key| !key.is_empty()).map(|key| Sha256::digest(key).into()).collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthStrategy for ApiKeyAuth {
+    async fn authenticate(&self, req: &Request<Body>) -> AuthOutcome {
+        match req.headers().get(&self.header) {
+            None => AuthOutcome::Missing(status_response(StatusCode::UNAUTHORIZED)),
+            Some(key) if self.keys.contains(&<[u8; 32]>::from(Sha256::digest(key.as_bytes()))) => {
+                AuthOutcome::Allowed(Vec::new())
+            }
+            Some(_) => AuthOutcome::Denied(status_response(StatusCode::FORBIDDEN)),
+        }
+    }
+}
+
+/// A route's `auth`: runs its strategies and combines what they say.
+pub struct RouteAuthFilter {
+    strategies: Vec<Arc<dyn AuthStrategy>>,
+    mode: AuthMode,
+    allow_anonymous: bool,
+}
+
+impl RouteAuthFilter {
+    /// The filter for `route`'s `auth`, with strategies from `strategies`.
+    /// One that can't be set up denies every request.
+    pub fn new(route: &str, auth: &RouteAuthConfig, strategies: &HashMap<String, AuthStrategyConfig>) -> Self {
+        let strategies = auth
+            .strategies
+            .iter()
+            .map(|name| -> Arc<dyn AuthStrategy> {
+                match strategies.get(name) {
+                    Some(AuthStrategyConfig::ForwardAuth(config)) => match ForwardAuthFilter::new(config) {
+                        Ok(filter) => Arc::new(filter),
+                        Err(e) => {
+                            error!(route, strategy = %name, error = %e, "Failed to set up forward auth");
+                            Arc::new(BrokenStrategy)
+                        }
+                    },
+                    Some(AuthStrategyConfig::ApiKey(config)) => Arc::new(ApiKeyAuth::new(config)),
+                    // Rejected by Config::validate
+                    None => {
+                        error!(route, strategy = %name, "Unknown auth strategy");
+                        Arc::new(BrokenStrategy)
+                    }
+                }
+            })
+            .collect();
+        Self { strategies, mode: auth.mode, allow_anonymous: auth.allow_anonymous }
+    }
+
+    async fn check(&self, req: &Request<Body>) -> Result<Vec<(HeaderName, HeaderValue)>, Response<Body>> {
+        let mut granted = Vec::new();
+        let mut missing = None;
+        let mut denied = None;
+        for strategy in &self.strategies {
+            match strategy.authenticate(req).await {
+                AuthOutcome::Allowed(headers) if self.mode == AuthMode::AnyOf => return Ok(headers),
+                AuthOutcome::Allowed(headers) => granted.push(headers),
+                AuthOutcome::Missing(response) => {
+                    missing.get_or_insert(response);
+                }
+                AuthOutcome::Denied(response) if self.mode == AuthMode::AllOf => return Err(response),
+                AuthOutcome::Denied(response) => {
+                    denied.get_or_insert(response);
+                }
+            }
+        }
+        // Anonymous means no credentials at all, not some that didn't pass
+        let anonymous = granted.is_empty() && denied.is_none();
+        match denied.or(missing) {
+            Some(_) if anonymous && self.allow_anonymous => Ok(Vec::new()),
+            Some(response) => Err(response),
+            None => Ok(granted.into_iter().flatten().collect()),
+        }
+    }
+}
+
+#[async_trait]
+impl Filter for RouteAuthFilter {
+    fn name(&self) -> &str {
+        "auth"
+    }
+
+    async fn on_request(&self, req: &mut Request<Body>, _ctx: &mut FilterContext) -> Option<Response<Body>> {
+        for strategy in &self.strategies {
+            strategy.strip(req);
+        }
+        match self.check(req).await {
+            Ok(headers) => {
+                for (name, value) in headers {
+                    req.headers_mut().append(name, value);
+                }
+                None
+            }
+            Err(response) => {
+                debug!(status = response.status().as_u16(), "Route auth denied the request");
+                Some(response)
+            }
+        }
+    }
+}
+
+/// Stands in for a strategy that couldn't be set up, failing closed.
+struct BrokenStrategy;
+
+#[async_trait]
+impl AuthStrategy for BrokenStrategy {
+    async fn authenticate(&self, _req: &Request<Body>) -> AuthOutcome {
+        AuthOutcome::Denied(super::builtin::filter_error_response())
+    }
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(status.canonical_reason().unwrap_or_default()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(mode: &str, allow_anonymous: bool) -> RouteAuthFilter {
+        let strategies: HashMap<String, AuthStrategyConfig> = serde_yaml::from_str(
+            "{ partners: { api_key: { keys: [partner-key] } }, \
+               internal: { api_key: { header: x-internal-key, keys: [internal-key] } } }",
+        )
+        .unwrap();
+        let auth: RouteAuthConfig = serde_yaml::from_str(&format!(
+            "{{ strategies: [partners, internal], mode: {}, allow_anonymous: {} }}",
+            mode, allow_anonymous
+        ))
+        .unwrap();
+        RouteAuthFilter::new("api", &auth, &strategies)
+    }
+
+    async fn status(filter: &RouteAuthFilter, headers: &[(&str, &str)]) -> u16 {
+        let mut req = Request::get("/api");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        let mut req = req.body(Body::empty()).unwrap();
+        let mut ctx = FilterContext::new("id".to_string(), &req, None);
+        match filter.on_request(&mut req, &mut ctx).await {
+            Some(response) => response.status().as_u16(),
+            None => 200,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_combines_route_auth_strategies() {
+        let partner = ("x-api-key", "partner-key");
+        let internal = ("x-internal-key", "internal-key");
+        let wrong = ("x-api-key", "guess");
+
+        let any_of = filter("any_of", false);
+        assert_eq!(status(&any_of, &[partner]).await, 200);
+        assert_eq!(status(&any_of, &[internal]).await, 200);
+        assert_eq!(status(&any_of, &[wrong]).await, 403);
+        assert_eq!(status(&any_of, &[]).await, 401);
+
+        let all_of = filter("all_of", false);
+        assert_eq!(status(&all_of, &[partner, internal]).await, 200);
+        assert_eq!(status(&all_of, &[partner]).await, 401);
+        assert_eq!(status(&all_of, &[wrong, internal]).await, 403);
+
+        // Only requests with no credentials at all pass anonymously
+        let anonymous = filter("all_of", true);
+        assert_eq!(status(&anonymous, &[]).await, 200);
+        assert_eq!(status(&anonymous, &[partner]).await, 401);
+        assert_eq!(status(&filter("any_of", true), &[wrong]).await, 403);
+    }
+}
@@ -0,0 +1,462 @@
+// src/middleware/builtin.rs
+use super::auth::RouteAuthFilter;
+use super::chain::{Filter, FilterChain, FilterContext};
+use crate::config::{
+    AuthStrategyConfig, Config, FilterConfig, HeaderFilterConfig, RouteConfig, SecurityHeadersConfig,
+};
+use crate::logging::{Sampler, SlowLog};
+use crate::metrics::{LatencyStats, MetricsSink, TrafficStats, UNROUTED_KEY};
+use crate::proxy::{Backend, ProxyError, ERROR_CODE_HEADER};
+use async_trait::async_trait;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{error, info};
+
+impl FilterChain {
+    /// The chain `filters` describes, sharing the proxy's metrics and stats.
+    pub fn from_config(
+        config: &Config,
+        metrics: Arc<dyn MetricsSink>,
+        traffic_stats: Arc<TrafficStats>,
+        latency_stats: Arc<LatencyStats>,
+    ) -> Self {
+        let mut chain = FilterChain::new();
+        for filter in &config.filters {
+            let filter: Arc<dyn Filter> = match filter {
+                FilterConfig::Metrics => Arc::new(MetricsFilter {
+                    metrics: metrics.clone(),
+                    traffic_stats: traffic_stats.clone(),
+                    latency_stats: latency_stats.clone(),
+                    stats_enabled: config.admin.enabled,
+                    exemplars: config.metrics.exemplars,
+                    sampler: Sampler::new(&config.logging.sampling),
+                    slow: config.logging.slow.as_ref().map(|slow| Arc::new(SlowLog::new(slow))),
+                }),
+                FilterConfig::RequestId => Arc::new(RequestIdFilter {
+                    // Validated in Config::validate, so this only fails on hand-built configs
+                    header: HeaderName::from_bytes(config.request_id.header.as_bytes())
+                        .expect("invalid request ID header name"),
+                    echo_to_client: config.request_id.echo_to_client,
+                }),
+                other => custom_filter("filters", other),
+            };
+            chain.push(filter);
+        }
+        chain
+    }
+
+    /// The chain for a route's own `filters`, after its `auth` if it has one.
+    pub fn for_route(route: &RouteConfig, auth_strategies: &HashMap<String, AuthStrategyConfig>) -> Self {
+        let mut chain = FilterChain::new();
+        if let Some(auth) = &route.auth {
+            chain.push(Arc::new(RouteAuthFilter::new(&route.name, auth, auth_strategies)));
+        }
+        for filter in &route.filters {
+            chain.push(custom_filter(&route.name, filter));
+        }
+        chain
+    }
+}
+
+/// Filters that don't need the proxy's state. One that can't be loaded
+/// fails closed, answering every request with a 500.
+fn custom_filter(owner: &str, filter: &FilterConfig) -> Arc<dyn Filter> {
+    match filter {
+        FilterConfig::Headers(headers) => Arc::new(HeaderFilter::new(headers)),
+        #[cfg(feature = "wasm")]
+        FilterConfig::Wasm(wasm) => match super::WasmFilter::load(wasm) {
+            Ok(filter) => Arc::new(filter),
+            Err(e) => {
+                error!(owner, module = %wasm.module.display(), error = %e, "Failed to load wasm filter");
+                Arc::new(BrokenFilter("wasm"))
+            }
+        },
+        #[cfg(not(feature = "wasm"))]
+        FilterConfig::Wasm(_) => {
+            error!(owner, "wasm filters need the wasm feature");
+            Arc::new(BrokenFilter("wasm"))
+        }
+        FilterConfig::ForwardAuth(auth) => match super::ForwardAuthFilter::new(auth) {
+            Ok(filter) => Arc::new(filter),
+            Err(e) => {
+                error!(owner, url = %auth.url, error = %e, "Failed to set up forward auth");
+                Arc::new(BrokenFilter("forward_auth"))
+            }
+        },
+        FilterConfig::HmacSign(sign) => Arc::new(super::HmacSignFilter::new(sign)),
+        FilterConfig::SecurityHeaders(headers) => Arc::new(SecurityHeadersFilter::new(headers)),
+        FilterConfig::Idempotency(idempotency) => Arc::new(super::IdempotencyFilter::new(idempotency)),
+        FilterConfig::DebugHeaders(debug) => Arc::new(super::DebugHeadersFilter::new(debug)),
+        FilterConfig::Metrics | FilterConfig::RequestId => {
+            // Rejected by Config::validate on routes
+            error!(owner, "metrics and request_id only work as global filters");
+            Arc::new(BrokenFilter("invalid"))
+        }
+    }
+}
+
+/// Stands in for a filter that couldn't be built.
+struct BrokenFilter(&'static str);
+
+#[async_trait]
+impl Filter for BrokenFilter {
+    fn name(&self) -> &str {
+        self.0
+    }
+
+    async fn on_request(
+        &self,
+        _req: &mut Request<Body>,
+        _ctx: &mut FilterContext,
+    ) -> Option<Response<Body>> {
+        Some(filter_error_response())
+    }
+}
+
+/// What clients get when a filter fails.
+pub(crate) fn filter_error_response() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .body(Body::from("Filter error"))
+        .unwrap()
+}
+
+/// Request and response metrics, the admin API's traffic and latency
+/// stats, and the log line for each finished request.
+pub struct MetricsFilter {
+    metrics: Arc<dyn MetricsSink>,
+    traffic_stats: Arc<TrafficStats>,
+    latency_stats: Arc<LatencyStats>,
+    /// Stats are only kept when the admin API can serve them.
+    stats_enabled: bool,
+    exemplars: bool,
+    /// Which completion lines to log.
+    sampler: Sampler,
+    slow: Option<Arc<SlowLog>>,
+}
+
+impl MetricsFilter {
+    fn record_traffic(&self, ctx: &FilterContext, backend: &str) {
+        if self.stats_enabled {
+            let route = ctx.route.as_deref().unwrap_or(UNROUTED_KEY);
+            // Not `client_addr`, which a client could spoof to hide or to
+            // blame someone else
+            self.traffic_stats.record(route, ctx.peer_addr.map(|addr| addr.ip()), backend);
+        }
+    }
+}
+
+#[async_trait]
+impl Filter for MetricsFilter {
+    fn name(&self) -> &str {
+        "metrics"
+    }
+
+    async fn on_request(
+        &self,
+        req: &mut Request<Body>,
+        ctx: &mut FilterContext,
+    ) -> Option<Response<Body>> {
+        if let Some(size) = content_length(req.headers()) {
+            self.metrics.record_request_size(ctx.method.as_str(), size);
+        }
+        self.metrics.increment_active_connections();
+        None
+    }
+
+    async fn on_response(&self, response: &mut Response<Body>, ctx: &FilterContext) {
+        self.metrics.decrement_active_connections();
+
+        let status = response.status().as_u16();
+        let backend_id = response
+            .headers()
+            .get("x-backend-id")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or(if ctx.answered_locally { "local" } else { "unknown" });
+        if let Some(size) = content_length(response.headers()) {
+            self.metrics.record_response_size(ctx.method.as_str(), status, size);
+        }
+
+        let elapsed = ctx.timer.elapsed();
+        self.metrics.record_request(ctx.method.as_str(), status, backend_id, elapsed);
+        if let (true, Some(trace_id)) = (self.exemplars, &ctx.trace_id) {
+            self.metrics
+                .record_request_exemplar(ctx.method.as_str(), status, backend_id, elapsed, trace_id);
+        }
+        self.record_traffic(ctx, backend_id);
+        if self.stats_enabled {
+            let route = ctx.route.as_deref().unwrap_or(UNROUTED_KEY);
+            self.latency_stats.record_route(route, ctx.timer.elapsed());
+        }
+
+        if self.sampler.should_log(status, elapsed) {
+            info!(
+                request_id = %ctx.request_id,
+                method = %ctx.method,
+                path = %ctx.path,
+                status = status,
+                backend = backend_id,
+                duration_ms = elapsed.as_millis(),
+                error_code = response.headers().get(ERROR_CODE_HEADER).and_then(|v| v.to_str().ok()),
+                "Request completed"
+            );
+        }
+        if let Some(slow) = &self.slow {
+            let backend_id = backend_id.to_string();
+            slow.watch(response, ctx, &backend_id);
+        }
+    }
+
+    async fn on_error(&self, error: &ProxyError, ctx: &FilterContext) {
+        self.metrics.decrement_active_connections();
+        self.metrics
+            .record_request(ctx.method.as_str(), 503, "none", ctx.timer.elapsed());
+        self.record_traffic(ctx, "none");
+
+        error!(
+            request_id = %ctx.request_id,
+            error = %error,
+            error_code = error.code(),
+            duration_ms = ctx.timer.elapsed().as_millis(),
+            "Request failed"
+        );
+        if let Some(slow) = &self.slow {
+            slow.check_failed(ctx);
+        }
+    }
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get("content-length")?.to_str().ok()?.parse().ok()
+}
+
+/// Sends the request ID to every backend attempt and, if configured, back
+/// to the client.
+pub struct RequestIdFilter {
+    header: HeaderName,
+    echo_to_client: bool,
+}
+
+#[async_trait]
+impl Filter for RequestIdFilter {
+    fn name(&self) -> &str {
+        "request_id"
+    }
+
+    async fn on_backend_selected(
+        &self,
+        req: &mut Request<Body>,
+        _backend: &Backend,
+        ctx: &FilterContext,
+    ) {
+        if let Ok(value) = HeaderValue::from_str(&ctx.request_id) {
+            req.headers_mut().insert(self.header.clone(), value);
+        }
+    }
+
+    async fn on_response(&self, response: &mut Response<Body>, ctx: &FilterContext) {
+        if !self.echo_to_client {
+            return;
+        }
+        if let Ok(value) = HeaderValue::from_str(&ctx.request_id) {
+            response.headers_mut().insert(self.header.clone(), value);
+        }
+    }
+}
+
+/// Fixed header changes from a `headers` filter entry.
+pub struct HeaderFilter {
+    request: HeaderEdits,
+    response: HeaderEdits,
+}
+
+#[derive(Default)]
+struct HeaderEdits {
+    remove: Vec<HeaderName>,
+    set: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl HeaderEdits {
+    // Names and values are checked in Config::validate; anything invalid
+    // in a hand-built config is skipped
+    fn new<'a>(
+        set: impl IntoIterator<Item = (&'a String, &'a String)>,
+        remove: &[String],
+    ) -> Self {
+        Self {
+            remove: remove
+                .iter()
+                .filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+                .collect(),
+            set: set
+                .into_iter()
+                .filter_map(|(name, value)| {
+                    Some((
+                        HeaderName::from_bytes(name.as_bytes()).ok()?,
+                        HeaderValue::from_str(value).ok()?,
+                    ))
+                })
+                .collect(),
+        }
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        for name in &self.remove {
+            headers.remove(name);
+        }
+        for (name, value) in &self.set {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+}
+
+impl HeaderFilter {
+    pub fn new(config: &HeaderFilterConfig) -> Self {
+        Self {
+            request: HeaderEdits::new(&config.request_set, &config.request_remove),
+            response: HeaderEdits::new(&config.response_set, &config.response_remove),
+        }
+    }
+}
+
+#[async_trait]
+impl Filter for HeaderFilter {
+    fn name(&self) -> &str {
+        "headers"
+    }
+
+    async fn on_request(
+        &self,
+        req: &mut Request<Body>,
+        _ctx: &mut FilterContext,
+    ) -> Option<Response<Body>> {
+        self.request.apply(req.headers_mut());
+        None
+    }
+
+    async fn on_response(&self, response: &mut Response<Body>, _ctx: &FilterContext) {
+        self.response.apply(response.headers_mut());
+    }
+}
+
+/// Baseline hardening headers from a `security_headers` filter entry.
+pub struct SecurityHeadersFilter {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    override_backend: bool,
+}
+
+impl SecurityHeadersFilter {
+    pub fn new(config: &SecurityHeadersConfig) -> Self {
+        let headers = [
+            ("strict-transport-security", &config.strict_transport_security),
+            ("x-content-type-options", &config.content_type_options),
+            ("x-frame-options", &config.frame_options),
+            ("content-security-policy", &config.content_security_policy),
+        ];
+        Self {
+            // Values are checked in Config::validate
+            headers: headers
+                .into_iter()
+                .filter_map(|(name, value)| {
+                    Some((HeaderName::from_static(name), HeaderValue::from_str(value.as_ref()?).ok()?))
+                })
+                .collect(),
+            override_backend: config.override_backend,
+        }
+    }
+}
+
+#[async_trait]
+impl Filter for SecurityHeadersFilter {
+    fn name(&self) -> &str {
+        "security_headers"
+    }
+
+    async fn on_response(&self, response: &mut Response<Body>, _ctx: &FilterContext) {
+        let headers = response.headers_mut();
+        for (name, value) in &self.headers {
+            if self.override_backend || !headers.contains_key(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_header_filter_removes_then_sets() {
+        let config: HeaderFilterConfig = serde_yaml::from_str(
+            "{ request_set: { x-env: prod }, request_remove: [x-debug, x-env], response_remove: [server] }",
+        )
+        .unwrap();
+        let filter = HeaderFilter::new(&config);
+
+        let mut req = Request::get("/")
+            .header("x-debug", "1")
+            .header("x-env", "dev")
+            .body(Body::empty())
+            .unwrap();
+        let mut ctx = FilterContext::new("id".to_string(), &req, None);
+        assert!(filter.on_request(&mut req, &mut ctx).await.is_none());
+        assert!(req.headers().get("x-debug").is_none());
+        assert_eq!(req.headers()["x-env"], "prod");
+
+        let mut response = Response::builder().header("server", "app/1.0").body(Body::empty()).unwrap();
+        filter.on_response(&mut response, &ctx).await;
+        assert!(response.headers().get("server").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_security_headers_keep_backend_values() {
+        let config: SecurityHeadersConfig =
+            serde_yaml::from_str("{ frame_options: null, content_security_policy: \"default-src 'self'\" }").unwrap();
+        let filter = SecurityHeadersFilter::new(&config);
+        let req = Request::get("/").body(Body::empty()).unwrap();
+        let ctx = FilterContext::new("id".to_string(), &req, None);
+
+        let mut response = Response::builder()
+            .header("content-security-policy", "default-src *")
+            .body(Body::empty())
+            .unwrap();
+        filter.on_response(&mut response, &ctx).await;
+        assert_eq!(response.headers()["strict-transport-security"], "max-age=31536000; includeSubDomains");
+        assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+        assert_eq!(response.headers()["content-security-policy"], "default-src *");
+        assert!(!response.headers().contains_key("x-frame-options"));
+    }
+
+    #[tokio::test]
+    async fn test_traffic_stats_count_routes_and_socket_peers() {
+        let traffic_stats = Arc::new(TrafficStats::new(100));
+        let filter = MetricsFilter {
+            metrics: Arc::new(crate::metrics::NoopMetrics),
+            traffic_stats: traffic_stats.clone(),
+            latency_stats: Arc::new(LatencyStats::new(std::time::Duration::from_secs(60), 100)),
+            stats_enabled: true,
+            exemplars: false,
+            sampler: Sampler::new(&Default::default()),
+            slow: None,
+        };
+        for path in ["/users/1", "/users/2"] {
+            let mut req = Request::get(path).header("x-forwarded-for", "203.0.113.9").body(Body::empty()).unwrap();
+            req.extensions_mut().insert(crate::server::PeerAddr("192.0.2.1:50000".parse().unwrap()));
+            let mut ctx = FilterContext::new("id".to_string(), &req, Some("203.0.113.9:0".parse().unwrap()));
+            ctx.route = Some("users".into());
+            let mut response = Response::new(Body::empty());
+            filter.on_response(&mut response, &ctx).await;
+        }
+
+        let snapshot = traffic_stats.snapshot(10);
+        let window = &snapshot.windows[0];
+        let routes: Vec<_> = window.routes.iter().map(|entry| (entry.key.as_str(), entry.requests)).collect();
+        assert_eq!(routes, [("users", 2)]);
+        // Not the X-Forwarded-For hop
+        let clients: Vec<_> = window.clients.iter().map(|entry| (entry.key.as_str(), entry.requests)).collect();
+        assert_eq!(clients, [("192.0.2.1", 2)]);
+    }
+}
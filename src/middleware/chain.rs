@@ -0,0 +1,241 @@
+// src/middleware/chain.rs
+use super::idempotency::IdempotencyKey;
+use crate::metrics::{RequestTimings, Timer};
+use crate::proxy::{Backend, ProxyError};
+use crate::server::PeerAddr;
+use async_trait::async_trait;
+use hyper::{Body, Method, Request, Response};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::debug;
+
+/// One step of request handling. Every phase has a default that does
+/// nothing, so a filter only implements the ones it needs.
+#[async_trait]
+pub trait Filter: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Before routing. Returning a response answers the request without
+    /// routing it or running the filters after this one.
+    async fn on_request(
+        &self,
+        _req: &mut Request<Body>,
+        _ctx: &mut FilterContext,
+    ) -> Option<Response<Body>> {
+        None
+    }
+
+    /// Once per attempt, after a backend is picked and just before the
+    /// request is sent to it.
+    async fn on_backend_selected(
+        &self,
+        _req: &mut Request<Body>,
+        _backend: &Backend,
+        _ctx: &FilterContext,
+    ) {
+    }
+
+    /// Before the response goes back to the client.
+    async fn on_response(&self, _response: &mut Response<Body>, _ctx: &FilterContext) {}
+
+    /// When no response could be produced.
+    async fn on_error(&self, _error: &ProxyError, _ctx: &FilterContext) {}
+}
+
+/// What filters know about the request they're handling.
+pub struct FilterContext {
+    pub request_id: String,
+    pub method: Method,
+    /// The path after normalization.
+    pub path: String,
+    /// The first `X-Forwarded-For` hop, or else the socket peer.
+    pub client_addr: Option<SocketAddr>,
+    /// The socket peer, which unlike `client_addr` the client can't choose.
+    pub peer_addr: Option<SocketAddr>,
+    /// From the W3C `traceparent` header, if the client sent a valid one.
+    pub trace_id: Option<String>,
+    pub timer: Timer,
+    /// The route that matched, once routing has run.
+    pub route: Option<Arc<str>>,
+    pub timings: RequestTimings,
+    /// Set when a route or filter answered without contacting a backend.
+    pub answered_locally: bool,
+    /// Set by `debug_headers` when the response should carry timings.
+    pub echo_timings: bool,
+    /// The key an `idempotency` filter holds until the response is known.
+    pub idempotency_key: Option<IdempotencyKey>,
+}
+
+impl FilterContext {
+    pub fn new(request_id: String, req: &Request<Body>, client_addr: Option<SocketAddr>) -> Self {
+        Self {
+            request_id,
+            method: req.method().clone(),
+            path: req.uri().path().to_string(),
+            client_addr,
+            peer_addr: req.extensions().get::<PeerAddr>().map(|peer| peer.0),
+            trace_id: req.headers().get("traceparent").and_then(|value| trace_id(value.to_str().ok()?)),
+            timer: Timer::new(),
+            route: None,
+            timings: RequestTimings::default(),
+            answered_locally: false,
+            echo_timings: false,
+            idempotency_key: None,
+        }
+    }
+}
+
+/// The trace ID of `version-traceid-parentid-flags`: 32 lowercase hex
+/// digits, not all zero.
+fn trace_id(traceparent: &str) -> Option<String> {
+    let id = traceparent.split('-').nth(1)?;
+    let valid = id.len() == 32
+        && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        && id.bytes().any(|b| b != b'0');
+    valid.then(|| id.to_string())
+}
+
+/// Filters in the order requests pass through them. Responses and errors
+/// pass back through the same filters in reverse, like layers of an onion.
+#[derive(Clone, Default)]
+pub struct FilterChain {
+    filters: Vec<Arc<dyn Filter>>,
+}
+
+/// How many filters of a chain saw the request; only those see the outcome.
+#[derive(Debug, Clone, Copy)]
+pub struct Entered(usize);
+
+impl std::fmt::Debug for FilterChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a filter; it sees requests after the ones already added.
+    pub fn push(&mut self, filter: Arc<dyn Filter>) {
+        self.filters.push(filter);
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.filters.iter().map(|filter| filter.name()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Run `on_request` until a filter answers. Pass the returned `Entered`
+    /// to `on_response` or `on_error`.
+    pub async fn on_request(
+        &self,
+        req: &mut Request<Body>,
+        ctx: &mut FilterContext,
+    ) -> (Entered, Option<Response<Body>>) {
+        for (i, filter) in self.filters.iter().enumerate() {
+            if let Some(response) = filter.on_request(req, ctx).await {
+                debug!(filter = filter.name(), "Filter answered the request");
+                ctx.answered_locally = true;
+                return (Entered(i + 1), Some(response));
+            }
+        }
+        (Entered(self.filters.len()), None)
+    }
+
+    pub async fn on_backend_selected(
+        &self,
+        req: &mut Request<Body>,
+        backend: &Backend,
+        ctx: &FilterContext,
+    ) {
+        for filter in &self.filters {
+            filter.on_backend_selected(req, backend, ctx).await;
+        }
+    }
+
+    pub async fn on_response(
+        &self,
+        entered: Entered,
+        response: &mut Response<Body>,
+        ctx: &FilterContext,
+    ) {
+        for filter in self.filters[..entered.0].iter().rev() {
+            filter.on_response(response, ctx).await;
+        }
+    }
+
+    pub async fn on_error(&self, entered: Entered, error: &ProxyError, ctx: &FilterContext) {
+        for filter in self.filters[..entered.0].iter().rev() {
+            filter.on_error(error, ctx).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    /// Appends its name to `x-trace` on the way in and out, and answers
+    /// requests for `/stop` itself.
+    struct Trace(&'static str);
+
+    #[async_trait]
+    impl Filter for Trace {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn on_request(
+            &self,
+            req: &mut Request<Body>,
+            _ctx: &mut FilterContext,
+        ) -> Option<Response<Body>> {
+            req.headers_mut().append("x-trace", HeaderValue::from_static(self.0));
+            (self.0 == "b" && req.uri().path() == "/stop").then(|| Response::new(Body::empty()))
+        }
+
+        async fn on_response(&self, response: &mut Response<Body>, _ctx: &FilterContext) {
+            response.headers_mut().append("x-trace", HeaderValue::from_static(self.0));
+        }
+    }
+
+    fn trace(headers: &hyper::HeaderMap) -> Vec<&str> {
+        headers
+            .get_all("x-trace")
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_filters_run_in_order_and_unwind_in_reverse() {
+        let mut chain = FilterChain::new();
+        for name in ["a", "b", "c"] {
+            chain.push(Arc::new(Trace(name)));
+        }
+
+        let mut req = Request::get("/").body(Body::empty()).unwrap();
+        let mut ctx = FilterContext::new("id".to_string(), &req, None);
+        let (entered, response) = chain.on_request(&mut req, &mut ctx).await;
+        assert!(response.is_none());
+        assert_eq!(trace(req.headers()), ["a", "b", "c"]);
+        let mut response = Response::new(Body::empty());
+        chain.on_response(entered, &mut response, &ctx).await;
+        assert_eq!(trace(response.headers()), ["c", "b", "a"]);
+
+        // Filters after the one answering never see the request
+        let mut req = Request::get("/stop").body(Body::empty()).unwrap();
+        let mut ctx = FilterContext::new("id".to_string(), &req, None);
+        let (entered, response) = chain.on_request(&mut req, &mut ctx).await;
+        let mut response = response.unwrap();
+        assert!(ctx.answered_locally);
+        chain.on_response(entered, &mut response, &ctx).await;
+        assert_eq!(trace(response.headers()), ["b", "a"]);
+    }
+}
@@ -0,0 +1,135 @@
+// src/middleware/debug_headers.rs
+use super::chain::{Filter, FilterContext};
+use crate::config::{DebugEcho, DebugHeadersConfig};
+use crate::proxy::Backend;
+use async_trait::async_trait;
+use chrono::{SecondsFormat, Utc};
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{Body, Request, Response};
+use std::time::Duration;
+
+const RECEIVED_AT: &str = "x-lb-received-at";
+const ATTEMPT: &str = "x-lb-attempt";
+const CLIENT_IP: &str = "x-lb-client-ip";
+
+/// Tells backends when the load balancer got a request, which attempt
+/// they're seeing and who sent it, and tells clients where the time went;
+/// see `DebugHeadersConfig`.
+pub struct DebugHeadersFilter {
+    upstream: bool,
+    echo: DebugEcho,
+    echo_header: HeaderName,
+}
+
+impl DebugHeadersFilter {
+    pub fn new(config: &DebugHeadersConfig) -> Self {
+        Self {
+            upstream: config.upstream,
+            echo: config.echo,
+            // Validated in Config::validate, so this only fails on hand-built configs
+            echo_header: HeaderName::from_bytes(config.echo_header.as_bytes())
+                .expect("invalid debug_headers echo_header"),
+        }
+    }
+}
+
+#[async_trait]
+impl Filter for DebugHeadersFilter {
+    fn name(&self) -> &str {
+        "debug_headers"
+    }
+
+    async fn on_request(
+        &self,
+        req: &mut Request<Body>,
+        ctx: &mut FilterContext,
+    ) -> Option<Response<Body>> {
+        ctx.echo_timings |= match self.echo {
+            DebugEcho::Never => false,
+            DebugEcho::OnRequest => req.headers_mut().remove(&self.echo_header).is_some(),
+            DebugEcho::Always => true,
+        };
+        None
+    }
+
+    async fn on_backend_selected(
+        &self,
+        req: &mut Request<Body>,
+        _backend: &Backend,
+        ctx: &FilterContext,
+    ) {
+        if !self.upstream {
+            return;
+        }
+        let received = Utc::now() - ctx.timer.elapsed();
+        let headers = req.headers_mut();
+        let received = received.to_rfc3339_opts(SecondsFormat::Millis, true);
+        headers.insert(RECEIVED_AT, HeaderValue::from_str(&received).unwrap());
+        headers.insert(ATTEMPT, HeaderValue::from(ctx.timings.snapshot().attempts + 1));
+        match ctx.client_addr {
+            Some(addr) => headers.insert(CLIENT_IP, HeaderValue::from_str(&addr.ip().to_string()).unwrap()),
+            None => headers.remove(CLIENT_IP),
+        };
+    }
+
+    async fn on_response(&self, response: &mut Response<Body>, ctx: &FilterContext) {
+        if !ctx.echo_timings {
+            return;
+        }
+        let timings = ctx.timings.snapshot();
+        let server_timing = [
+            ("queue", timings.queue),
+            ("connect", timings.connect),
+            ("upstream", timings.ttfb),
+            ("total", ctx.timer.elapsed()),
+        ]
+        .map(|(name, took)| format!("{};dur={}", name, millis(took)))
+        .join(", ");
+        let headers = response.headers_mut();
+        headers.append("server-timing", HeaderValue::from_str(&server_timing).unwrap());
+        headers.insert("x-lb-attempts", HeaderValue::from(timings.attempts));
+    }
+}
+
+/// Milliseconds to one decimal place, as `Server-Timing` has them.
+fn millis(took: Duration) -> String {
+    format!("{:.1}", took.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackendConfig;
+
+    #[tokio::test]
+    async fn test_adds_upstream_headers_and_echoes_timings_on_request() {
+        let config: DebugHeadersConfig = serde_yaml::from_str("{ echo: on_request }").unwrap();
+        let filter = DebugHeadersFilter::new(&config);
+        let backend: BackendConfig = serde_yaml::from_str("{ url: 'http://127.0.0.1:9' }").unwrap();
+        let backend = Backend::new(&backend);
+
+        let mut req = Request::post("/").header(CLIENT_IP, "10.9.9.9").body(Body::empty()).unwrap();
+        let mut ctx = FilterContext::new("id".to_string(), &req, Some("192.0.2.7:0".parse().unwrap()));
+        filter.on_request(&mut req, &mut ctx).await;
+        assert!(!ctx.echo_timings);
+        ctx.timings.record_attempt(None);
+        filter.on_backend_selected(&mut req, &backend, &ctx).await;
+        assert_eq!(req.headers()[ATTEMPT], "2");
+        assert_eq!(req.headers()[CLIENT_IP], "192.0.2.7");
+        let received = req.headers()[RECEIVED_AT].to_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(received).is_ok(), "{}", received);
+        let mut response = Response::new(Body::empty());
+        filter.on_response(&mut response, &ctx).await;
+        assert!(!response.headers().contains_key("server-timing"));
+
+        let mut req = Request::get("/").header("x-lb-debug", "1").body(Body::empty()).unwrap();
+        let mut ctx = FilterContext::new("id".to_string(), &req, None);
+        filter.on_request(&mut req, &mut ctx).await;
+        assert!(ctx.echo_timings && !req.headers().contains_key("x-lb-debug"));
+        let mut response = Response::new(Body::empty());
+        filter.on_response(&mut response, &ctx).await;
+        let timing = response.headers()["server-timing"].to_str().unwrap();
+        assert!(timing.starts_with("queue;dur=0.0, connect;dur=0.0, upstream;dur=0.0, total;dur="), "{}", timing);
+        assert_eq!(response.headers()["x-lb-attempts"], "0");
+    }
+}
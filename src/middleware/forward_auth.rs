@@ -0,0 +1,369 @@
+// src/middleware/forward_auth.rs
+use super::auth::{AuthOutcome, AuthStrategy};
+use super::builtin::filter_error_response;
+use super::chain::{Filter, FilterContext};
+use crate::config::ForwardAuthConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, CONNECTION, CONTENT_LENGTH, HOST, LOCATION,
+    TRANSFER_ENCODING,
+};
+use hyper::{Body, Request, Response, StatusCode};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+use url::Url;
+
+/// Lets requests through when an auth service answers 2xx for them.
+/// Failing to reach the service fails closed with a 500.
+pub struct ForwardAuthFilter {
+    url: Url,
+    client: reqwest::Client,
+    /// `None` forwards every request header.
+    request_headers: Option<Vec<HeaderName>>,
+    response_headers: Vec<HeaderName>,
+    signin_url: Option<String>,
+    cache_ttl: Duration,
+    cache_key_headers: Vec<HeaderName>,
+    cache_max_entries: usize,
+    /// Headers to copy upstream, by the caller's credentials and what they
+    /// asked for.
+    decisions: DashMap<Vec<u8>, Allowed>,
+}
+
+struct Allowed {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    expires: Instant,
+}
+
+/// What the auth service said about a request.
+enum Verdict {
+    Allow(Vec<(HeaderName, HeaderValue)>),
+    Deny(Response<Body>),
+}
+
+impl ForwardAuthFilter {
+    pub fn new(config: &ForwardAuthConfig) -> Result<Self> {
+        // Validated in Config::validate
+        let names = |names: &[String]| -> Vec<HeaderName> {
+            names.iter().filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok()).collect()
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            // A redirect is the service's answer for the client, not for us
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        Ok(Self {
+            url: config.url.clone(),
+            client,
+            request_headers: config.request_headers.as_deref().map(names),
+            response_headers: names(&config.response_headers),
+            signin_url: config.signin_url.clone(),
+            cache_ttl: Duration::from_secs(config.cache_ttl_secs),
+            cache_key_headers: names(&config.cache_key_headers),
+            cache_max_entries: config.cache_max_entries,
+            decisions: DashMap::new(),
+        })
+    }
+
+    /// The caller's credentials with the method, host and URI the auth
+    /// service is shown, since it may decide per host or path. `None` when
+    /// decisions aren't cached or the request has no credentials.
+    fn cache_key(&self, req: &Request<Body>) -> Option<Vec<u8>> {
+        if self.cache_ttl.is_zero() {
+            return None;
+        }
+        let mut key = Vec::new();
+        key.extend_from_slice(req.method().as_str().as_bytes());
+        key.push(0);
+        if let Some(host) = req.headers().get(HOST) {
+            key.extend_from_slice(host.as_bytes());
+        }
+        key.push(0);
+        key.extend_from_slice(path_and_query(req).as_bytes());
+        key.push(0);
+        let mut any = false;
+        for name in &self.cache_key_headers {
+            for value in req.headers().get_all(name) {
+                any = true;
+                key.extend_from_slice(value.as_bytes());
+                key.push(b'\n');
+            }
+            // Header values can't hold a NUL or newline, so values can't
+            // run together
+            key.push(0);
+        }
+        any.then_some(key)
+    }
+
+    fn remember(&self, key: Vec<u8>, headers: Vec<(HeaderName, HeaderValue)>) {
+        if self.decisions.len() >= self.cache_max_entries {
+            self.decisions.retain(|_, allowed| allowed.expires > Instant::now());
+            if self.decisions.len() >= self.cache_max_entries {
+                return;
+            }
+        }
+        let expires = Instant::now() + self.cache_ttl;
+        self.decisions.insert(key, Allowed { headers, expires });
+    }
+
+    async fn check(&self, req: &Request<Body>) -> reqwest::Result<Verdict> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in req.headers() {
+            let forwarded = match &self.request_headers {
+                Some(names) => names.contains(name),
+                None => !matches!(*name, HOST | CONNECTION | CONTENT_LENGTH | TRANSFER_ENCODING),
+            };
+            if forwarded {
+                headers.append(name.clone(), value.clone());
+            }
+        }
+        headers.insert("x-forwarded-method", HeaderValue::from_str(req.method().as_str()).unwrap());
+        if let Ok(uri) = HeaderValue::from_str(&path_and_query(req)) {
+            headers.insert("x-forwarded-uri", uri);
+        }
+        if let Some(host) = req.headers().get(HOST) {
+            headers.insert("x-forwarded-host", host.clone());
+        }
+
+        let response = self.client.get(self.url.clone()).headers(headers).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            let copied = self
+                .response_headers
+                .iter()
+                .flat_map(|name| {
+                    response.headers().get_all(name).iter().map(|value| (name.clone(), value.clone()))
+                })
+                .collect();
+            return Ok(Verdict::Allow(copied));
+        }
+
+        if status == StatusCode::UNAUTHORIZED {
+            if let Some(signin_url) = &self.signin_url {
+                let target = signin_url.replace("{url}", &original_url(req));
+                if let Ok(location) = HeaderValue::from_str(&target) {
+                    let mut redirect = Response::new(Body::empty());
+                    *redirect.status_mut() = StatusCode::FOUND;
+                    redirect.headers_mut().insert(LOCATION, location);
+                    return Ok(Verdict::Deny(redirect));
+                }
+            }
+        }
+        let mut denied = Response::builder().status(status);
+        for (name, value) in response.headers() {
+            if !matches!(*name, CONNECTION | CONTENT_LENGTH | TRANSFER_ENCODING) {
+                denied = denied.header(name, value);
+            }
+        }
+        let body = response.bytes().await?;
+        Ok(Verdict::Deny(denied.body(Body::from(body)).unwrap()))
+    }
+}
+
+#[async_trait]
+impl AuthStrategy for ForwardAuthFilter {
+    async fn authenticate(&self, req: &Request<Body>) -> AuthOutcome {
+        let key = self.cache_key(req);
+        let cached = key.as_ref().and_then(|key| {
+            let allowed = self.decisions.get(key)?;
+            (allowed.expires > Instant::now()).then(|| allowed.headers.clone())
+        });
+        if let Some(headers) = cached {
+            return AuthOutcome::Allowed(headers);
+        }
+        match self.check(req).await {
+            Ok(Verdict::Allow(headers)) => {
+                if let Some(key) = key {
+                    self.remember(key, headers.clone());
+                }
+                AuthOutcome::Allowed(headers)
+            }
+            // The sign-in redirect only replaces a 401
+            Ok(Verdict::Deny(response)) if matches!(response.status(), StatusCode::UNAUTHORIZED | StatusCode::FOUND) => {
+                AuthOutcome::Missing(response)
+            }
+            Ok(Verdict::Deny(response)) => AuthOutcome::Denied(response),
+            Err(e) => {
+                warn!(url = %self.url, error = %e, "Forward auth request failed");
+                AuthOutcome::Denied(filter_error_response())
+            }
+        }
+    }
+
+    fn strip(&self, req: &mut Request<Body>) {
+        // Only the auth service may set these
+        for name in &self.response_headers {
+            req.headers_mut().remove(name);
+        }
+    }
+}
+
+#[async_trait]
+impl Filter for ForwardAuthFilter {
+    fn name(&self) -> &str {
+        "forward_auth"
+    }
+
+    async fn on_request(
+        &self,
+        req: &mut Request<Body>,
+        _ctx: &mut FilterContext,
+    ) -> Option<Response<Body>> {
+        self.strip(req);
+        match self.authenticate(req).await {
+            AuthOutcome::Allowed(headers) => {
+                for (name, value) in headers {
+                    req.headers_mut().append(name, value);
+                }
+                None
+            }
+            AuthOutcome::Missing(response) | AuthOutcome::Denied(response) => {
+                debug!(status = response.status().as_u16(), "Forward auth denied the request");
+                Some(response)
+            }
+        }
+    }
+}
+
+fn path_and_query(req: &Request<Body>) -> String {
+    req.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("/").to_string()
+}
+
+/// The URL the client asked for, percent-encoded for a query parameter.
+fn original_url(req: &Request<Body>) -> String {
+    let scheme = req
+        .headers()
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .or(req.uri().scheme_str())
+        .unwrap_or("http");
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|v| v.to_str().ok())
+        .or(req.uri().authority().map(|a| a.as_str()))
+        .unwrap_or_default();
+    let url = format!("{}://{}{}", scheme, host, path_and_query(req));
+    utf8_percent_encode(&url, NON_ALPHANUMERIC).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// An auth service that lets `cookie: session=ok` through as alice.
+    async fn auth_service(calls: Arc<AtomicUsize>) -> Url {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let make_service = hyper::service::make_service_fn(move |_| {
+            let calls = calls.clone();
+            async move {
+                Ok::<_, Infallible>(hyper::service::service_fn(move |req: Request<Body>| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    let allowed = req.headers().get("cookie").is_some_and(|v| v == "session=ok");
+                    let admin = req.headers()["x-forwarded-uri"].as_bytes().starts_with(b"/admin");
+                    let response = if allowed && admin {
+                        Response::builder().status(403).body(Body::from("admins only"))
+                    } else if allowed {
+                        assert_eq!(req.headers()["x-forwarded-uri"], "/app?x=1");
+                        Response::builder()
+                            .header("x-auth-request-user", "alice")
+                            .header("x-internal", "secret")
+                            .body(Body::empty())
+                    } else if req.headers().contains_key("cookie") {
+                        Response::builder().status(403).body(Body::from("forbidden"))
+                    } else {
+                        Response::builder().status(401).body(Body::empty())
+                    };
+                    async move { Ok::<_, Infallible>(response.unwrap()) }
+                }))
+            }
+        });
+        let server = hyper::Server::from_tcp(listener).unwrap().serve(make_service);
+        tokio::spawn(server);
+        format!("http://{}/auth", addr).parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_forward_auth_allows_denies_and_caches() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let url = auth_service(calls.clone()).await;
+        let config: ForwardAuthConfig = serde_yaml::from_str(&format!(
+            "{{ url: '{}', response_headers: [x-auth-request-user], \
+               signin_url: 'https://login.example.com/start?rd={{url}}', cache_ttl_secs: 60 }}",
+            url
+        ))
+        .unwrap();
+        let filter = ForwardAuthFilter::new(&config).unwrap();
+        let request = |cookie: Option<&str>| {
+            let mut req = Request::get("/app?x=1")
+                .header("host", "app.example.com")
+                .header("x-auth-request-user", "mallory");
+            if let Some(cookie) = cookie {
+                req = req.header("cookie", cookie);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        let mut req = request(Some("session=ok"));
+        let mut ctx = FilterContext::new("id".to_string(), &req, None);
+        assert!(filter.on_request(&mut req, &mut ctx).await.is_none());
+        let users: Vec<_> = req.headers().get_all("x-auth-request-user").iter().collect();
+        assert_eq!(users, ["alice"]);
+        assert!(!req.headers().contains_key("x-internal"));
+
+        // The same credentials again are answered from the cache
+        let mut req = request(Some("session=ok"));
+        assert!(filter.on_request(&mut req, &mut ctx).await.is_none());
+        assert_eq!(req.headers()["x-auth-request-user"], "alice");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let mut req = request(Some("session=bad"));
+        let response = filter.on_request(&mut req, &mut ctx).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"forbidden");
+
+        let mut req = request(None);
+        let response = filter.on_request(&mut req, &mut ctx).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers()[LOCATION],
+            "https://login.example.com/start?rd=http%3A%2F%2Fapp%2Eexample%2Ecom%2Fapp%3Fx%3D1"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_forward_auth_caches_per_path() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let url = auth_service(calls.clone()).await;
+        let config: ForwardAuthConfig =
+            serde_yaml::from_str(&format!("{{ url: '{}', cache_ttl_secs: 60 }}", url)).unwrap();
+        let filter = ForwardAuthFilter::new(&config).unwrap();
+        let request = |uri: &str, host: &str| {
+            Request::get(uri).header("host", host).header("cookie", "session=ok").body(Body::empty()).unwrap()
+        };
+
+        let mut req = request("/app?x=1", "app.example.com");
+        let mut ctx = FilterContext::new("id".to_string(), &req, None);
+        assert!(filter.on_request(&mut req, &mut ctx).await.is_none());
+
+        // Being let into /app doesn't let the same session into /admin
+        let mut req = request("/admin", "app.example.com");
+        let response = filter.on_request(&mut req, &mut ctx).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let mut req = request("/admin", "app.example.com");
+        assert!(filter.on_request(&mut req, &mut ctx).await.is_some());
+        // Nor into another host
+        let mut req = request("/app?x=1", "other.example.com");
+        assert!(filter.on_request(&mut req, &mut ctx).await.is_none());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,338 @@
+// src/middleware/idempotency.rs
+use super::chain::{Filter, FilterContext};
+use crate::config::IdempotencyConfig;
+use crate::proxy::ProxyError;
+use async_trait::async_trait;
+use dashmap::mapref::entry::Entry as MapEntry;
+use dashmap::DashMap;
+use futures::Stream;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Set on responses replayed for a repeated key.
+const REPLAYED: &str = "idempotent-replayed";
+
+/// Answers repeats of a request with the same `Idempotency-Key` from the
+/// first one's response; see `IdempotencyConfig`. Responses that are 5xx,
+/// too large, or cut short aren't kept, so the client can try again.
+pub struct IdempotencyFilter {
+    header: HeaderName,
+    methods: Vec<Method>,
+    ttl: Duration,
+    scope_headers: Vec<HeaderName>,
+    max_body_bytes: usize,
+    max_keys: usize,
+    in_flight_timeout: Duration,
+    entries: Arc<DashMap<Vec<u8>, Entry>>,
+}
+
+/// A request's key, kept in its `FilterContext` rather than by request ID,
+/// which clients can choose and reuse.
+#[derive(Debug, Clone)]
+pub struct IdempotencyKey {
+    /// Which filter holds it, when a route has one of its own.
+    filter: usize,
+    key: Vec<u8>,
+}
+
+enum Entry {
+    InFlight(Instant),
+    Done(Arc<Remembered>),
+}
+
+struct Remembered {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    expires: Instant,
+}
+
+impl Entry {
+    /// Whether a new request may take the key over.
+    fn is_stale(&self, in_flight_timeout: Duration) -> bool {
+        match self {
+            Entry::InFlight(since) => since.elapsed() >= in_flight_timeout,
+            Entry::Done(remembered) => remembered.expires <= Instant::now(),
+        }
+    }
+}
+
+impl IdempotencyFilter {
+    pub fn new(config: &IdempotencyConfig) -> Self {
+        // Validated in Config::validate
+        let names = |names: &[String]| -> Vec<HeaderName> {
+            names.iter().filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok()).collect()
+        };
+        Self {
+            header: HeaderName::from_bytes(config.header.as_bytes())
+                .expect("invalid idempotency header name"),
+            methods: config.methods.iter().filter_map(|m| Method::from_bytes(m.as_bytes()).ok()).collect(),
+            ttl: Duration::from_secs(config.ttl_secs),
+            scope_headers: names(&config.scope_headers),
+            max_body_bytes: config.max_body_bytes as usize,
+            max_keys: config.max_keys,
+            in_flight_timeout: Duration::from_secs(config.in_flight_timeout_secs),
+            entries: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// The key `ctx` holds with this filter.
+    fn held<'a>(&self, ctx: &'a FilterContext) -> Option<&'a [u8]> {
+        let held = ctx.idempotency_key.as_ref()?;
+        (held.filter == self.id()).then_some(held.key.as_slice())
+    }
+
+    fn id(&self) -> usize {
+        Arc::as_ptr(&self.entries) as usize
+    }
+
+    /// The client's key, scoped to the method, path and caller.
+    fn key(&self, req: &Request<Body>) -> Option<Vec<u8>> {
+        if !self.methods.contains(req.method()) {
+            return None;
+        }
+        let mut key = req.headers().get(&self.header)?.as_bytes().to_vec();
+        key.push(0);
+        key.extend_from_slice(req.method().as_str().as_bytes());
+        key.push(0);
+        key.extend_from_slice(req.uri().path().as_bytes());
+        for name in &self.scope_headers {
+            key.push(0);
+            if let Some(value) = req.headers().get(name) {
+                key.extend_from_slice(value.as_bytes());
+            }
+        }
+        Some(key)
+    }
+
+    /// Forget expired keys and abandoned requests once there's no room.
+    fn make_room(&self) -> bool {
+        if self.entries.len() >= self.max_keys {
+            self.entries.retain(|_, entry| !entry.is_stale(self.in_flight_timeout));
+        }
+        self.entries.len() < self.max_keys
+    }
+}
+
+#[async_trait]
+impl Filter for IdempotencyFilter {
+    fn name(&self) -> &str {
+        "idempotency"
+    }
+
+    async fn on_request(
+        &self,
+        req: &mut Request<Body>,
+        ctx: &mut FilterContext,
+    ) -> Option<Response<Body>> {
+        if ctx.idempotency_key.is_some() {
+            // A filter earlier in the chain handles this request
+            return None;
+        }
+        let key = self.key(req)?;
+        if !self.entries.contains_key(&key) && !self.make_room() {
+            debug!("Idempotency keys full, passing the request through");
+            return None;
+        }
+        match self.entries.entry(key.clone()) {
+            MapEntry::Occupied(entry) if !entry.get().is_stale(self.in_flight_timeout) => {
+                return Some(match entry.get() {
+                    Entry::Done(remembered) => replay(remembered),
+                    Entry::InFlight(_) => in_flight(),
+                });
+            }
+            MapEntry::Occupied(mut entry) => {
+                entry.insert(Entry::InFlight(Instant::now()));
+            }
+            MapEntry::Vacant(entry) => {
+                entry.insert(Entry::InFlight(Instant::now()));
+            }
+        }
+        ctx.idempotency_key = Some(IdempotencyKey { filter: self.id(), key });
+        None
+    }
+
+    async fn on_response(&self, response: &mut Response<Body>, ctx: &FilterContext) {
+        let Some(key) = self.held(ctx) else {
+            return;
+        };
+        if response.status().is_server_error() {
+            self.entries.remove(key);
+            return;
+        }
+        let mut recording = RecordingBody {
+            body: std::mem::take(response.body_mut()),
+            status: response.status(),
+            headers: response.headers().clone(),
+            key: Some(key.to_vec()),
+            entries: self.entries.clone(),
+            recorded: Vec::new(),
+            limit: self.max_body_bytes,
+            ttl: self.ttl,
+            complete: false,
+        };
+        if recording.body.is_end_stream() {
+            recording.complete = true;
+            drop(recording);
+        } else {
+            *response.body_mut() = Body::wrap_stream(recording);
+        }
+    }
+
+    async fn on_error(&self, _error: &ProxyError, ctx: &FilterContext) {
+        if let Some(key) = self.held(ctx) {
+            self.entries.remove(key);
+        }
+    }
+}
+
+fn replay(remembered: &Remembered) -> Response<Body> {
+    let mut response = Response::new(Body::from(remembered.body.clone()));
+    *response.status_mut() = remembered.status;
+    *response.headers_mut() = remembered.headers.clone();
+    response.headers_mut().insert(REPLAYED, HeaderValue::from_static("true"));
+    response
+}
+
+fn in_flight() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::CONFLICT)
+        .header(RETRY_AFTER, "1")
+        .body(Body::from("A request with this idempotency key is in progress"))
+        .unwrap()
+}
+
+/// A response body that, once it's all been sent, is remembered for its
+/// key. One that's too large or dropped part way frees the key instead.
+struct RecordingBody {
+    body: Body,
+    status: StatusCode,
+    headers: HeaderMap,
+    key: Option<Vec<u8>>,
+    entries: Arc<DashMap<Vec<u8>, Entry>>,
+    recorded: Vec<u8>,
+    limit: usize,
+    ttl: Duration,
+    complete: bool,
+}
+
+impl Stream for RecordingBody {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let polled = Pin::new(&mut self.body).poll_next(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(chunk))) if self.key.is_some() => {
+                if self.recorded.len() + chunk.len() > self.limit {
+                    // Too large to keep; later requests go to the backend
+                    if let Some(key) = self.key.take() {
+                        self.entries.remove(&key);
+                    }
+                } else {
+                    let chunk = chunk.clone();
+                    self.recorded.extend_from_slice(&chunk);
+                }
+            }
+            Poll::Ready(None) => self.complete = true,
+            _ => {}
+        }
+        polled
+    }
+}
+
+impl Drop for RecordingBody {
+    fn drop(&mut self) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        if !self.complete {
+            self.entries.remove(&key);
+            return;
+        }
+        let remembered = Remembered {
+            status: self.status,
+            headers: std::mem::take(&mut self.headers),
+            body: Bytes::from(std::mem::take(&mut self.recorded)),
+            expires: Instant::now() + self.ttl,
+        };
+        self.entries.insert(key, Entry::Done(Arc::new(remembered)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(key: &str, auth: &str) -> Request<Body> {
+        Request::post("/payments")
+            .header("idempotency-key", key)
+            .header("authorization", auth)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn send(filter: &IdempotencyFilter, mut req: Request<Body>, id: &str) -> (Option<Response<Body>>, FilterContext) {
+        let mut ctx = FilterContext::new(id.to_string(), &req, None);
+        (filter.on_request(&mut req, &mut ctx).await, ctx)
+    }
+
+    async fn respond(filter: &IdempotencyFilter, ctx: &FilterContext, status: u16, body: &'static str) -> Bytes {
+        let mut response = Response::builder().status(status).body(Body::from(body)).unwrap();
+        filter.on_response(&mut response, ctx).await;
+        hyper::body::to_bytes(response.into_body()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replays_responses_by_key_and_caller() {
+        let filter = IdempotencyFilter::new(&IdempotencyConfig::default());
+        let (passed, ctx) = send(&filter, post("k1", "alice"), "1").await;
+        assert!(passed.is_none());
+
+        // A duplicate while the first is in flight is turned away
+        let busy = send(&filter, post("k1", "alice"), "2").await.0.unwrap();
+        assert_eq!(busy.status(), StatusCode::CONFLICT);
+
+        assert_eq!(respond(&filter, &ctx, 201, "charged").await, "charged");
+
+        let replayed = send(&filter, post("k1", "alice"), "3").await.0.unwrap();
+        assert_eq!(replayed.status(), StatusCode::CREATED);
+        assert_eq!(replayed.headers()[REPLAYED], "true");
+        assert_eq!(hyper::body::to_bytes(replayed.into_body()).await.unwrap(), "charged");
+
+        // Someone else's key of the same name, and GETs, go through
+        assert!(send(&filter, post("k1", "bob"), "4").await.0.is_none());
+        let get = Request::get("/payments").header("idempotency-key", "k1").body(Body::empty()).unwrap();
+        assert!(send(&filter, get, "5").await.0.is_none());
+
+        // A failed attempt frees its key
+        let (passed, ctx) = send(&filter, post("k2", "alice"), "6").await;
+        assert!(passed.is_none());
+        respond(&filter, &ctx, 502, "").await;
+        assert!(send(&filter, post("k2", "alice"), "7").await.0.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_requests_sharing_a_request_id_keep_their_own_keys() {
+        let filter = IdempotencyFilter::new(&IdempotencyConfig::default());
+        // Both came with the same trusted x-request-id
+        let (passed, first) = send(&filter, post("k1", "alice"), "same").await;
+        assert!(passed.is_none());
+        let (passed, second) = send(&filter, post("k2", "bob"), "same").await;
+        assert!(passed.is_none());
+
+        respond(&filter, &first, 201, "alice's").await;
+        respond(&filter, &second, 201, "bob's").await;
+
+        let replayed = send(&filter, post("k1", "alice"), "a").await.0.unwrap();
+        assert_eq!(hyper::body::to_bytes(replayed.into_body()).await.unwrap(), "alice's");
+        let replayed = send(&filter, post("k2", "bob"), "b").await.0.unwrap();
+        assert_eq!(replayed.status(), StatusCode::CREATED);
+        assert_eq!(hyper::body::to_bytes(replayed.into_body()).await.unwrap(), "bob's");
+    }
+}
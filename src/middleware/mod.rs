@@ -0,0 +1,22 @@
+// src/middleware/mod.rs
+mod auth;
+mod builtin;
+mod chain;
+mod debug_headers;
+mod forward_auth;
+mod idempotency;
+mod peek;
+mod signing;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use auth::{ApiKeyAuth, AuthOutcome, AuthStrategy, RouteAuthFilter};
+pub use builtin::{HeaderFilter, MetricsFilter, RequestIdFilter, SecurityHeadersFilter};
+pub use chain::{Entered, Filter, FilterChain, FilterContext};
+pub use debug_headers::DebugHeadersFilter;
+pub use forward_auth::ForwardAuthFilter;
+pub use idempotency::{IdempotencyFilter, IdempotencyKey};
+pub use peek::BodyPeek;
+pub use signing::HmacSignFilter;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmFilter;
@@ -0,0 +1,115 @@
+// src/middleware/peek.rs
+use futures::{stream, StreamExt};
+use hyper::body::{Bytes, HttpBody};
+use hyper::{Body, Request};
+
+/// The start of a request body, read ahead so a filter can look at it
+/// (to check it matches its `Content-Type`, say) without buffering the
+/// rest. The request keeps its whole body: what was read is replayed
+/// ahead of the part that wasn't.
+///
+/// ```ignore
+/// // In a filter's on_request
+/// let peek = BodyPeek::peek(req, 512).await.ok()?;
+/// if is_json(req.headers()) && !peek.bytes().starts_with(b"{") {
+///     return Some(bad_request());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BodyPeek {
+    bytes: Bytes,
+    complete: bool,
+}
+
+impl BodyPeek {
+    /// Up to `limit` bytes from the start of `req`'s body. Later peeks of
+    /// the same request reuse what earlier ones read when it's enough.
+    pub async fn peek(req: &mut Request<Body>, limit: usize) -> hyper::Result<BodyPeek> {
+        if let Some(earlier) = req.extensions().get::<BodyPeek>() {
+            if earlier.complete || earlier.bytes.len() >= limit {
+                return Ok(earlier.truncated(limit));
+            }
+        }
+
+        let mut body = std::mem::take(req.body_mut());
+        let mut chunks = Vec::new();
+        let mut read = 0;
+        let mut complete = true;
+        // Whole chunks are kept for the replay, so this can read past `limit`
+        while read < limit {
+            match body.data().await {
+                Some(Ok(chunk)) => {
+                    read += chunk.len();
+                    chunks.push(chunk);
+                }
+                Some(Err(e)) => return Err(e),
+                None => break,
+            }
+        }
+        if read >= limit {
+            complete = body.is_end_stream();
+        }
+
+        let head: Bytes = match chunks.as_slice() {
+            [chunk] => chunk.clone(),
+            chunks => chunks.concat().into(),
+        };
+        let peek = BodyPeek { bytes: head.clone(), complete };
+        *req.body_mut() = if complete {
+            Body::from(head)
+        } else {
+            let replayed = stream::iter(chunks.into_iter().map(Ok::<_, hyper::Error>));
+            Body::wrap_stream(replayed.chain(body))
+        };
+        req.extensions_mut().insert(peek.clone());
+        Ok(peek.truncated(limit))
+    }
+
+    /// The first bytes of the body, no more than the `limit` asked for.
+    pub fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    /// Whether the whole body was read, so `bytes` is all of it.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    fn truncated(&self, limit: usize) -> BodyPeek {
+        if self.bytes.len() <= limit {
+            return self.clone();
+        }
+        BodyPeek {
+            bytes: self.bytes.slice(..limit),
+            complete: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_peeked_bytes_are_replayed_upstream() {
+        let chunks = ["{\"id\":", "1,", "\"name\":\"x\"}"];
+        let stream = stream::iter(chunks.map(|chunk| Ok::<_, std::io::Error>(Bytes::from(chunk))));
+        let mut req = Request::post("/").body(Body::wrap_stream(stream)).unwrap();
+
+        let peek = BodyPeek::peek(&mut req, 4).await.unwrap();
+        assert_eq!(&peek.bytes()[..], b"{\"id");
+        assert!(!peek.is_complete());
+        // Answered from what the first peek read
+        let again = BodyPeek::peek(&mut req, 6).await.unwrap();
+        assert_eq!(&again.bytes()[..], b"{\"id\":");
+
+        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"{\"id\":1,\"name\":\"x\"}");
+
+        let mut req = Request::post("/").body(Body::from("short")).unwrap();
+        let peek = BodyPeek::peek(&mut req, 64).await.unwrap();
+        assert!(peek.is_complete());
+        assert_eq!(&peek.bytes()[..], b"short");
+        assert_eq!(hyper::body::to_bytes(req.into_body()).await.unwrap(), "short");
+    }
+}
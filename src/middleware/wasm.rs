@@ -0,0 +1,361 @@
+// src/middleware/wasm.rs
+use super::builtin::filter_error_response;
+use super::chain::{Filter, FilterContext};
+use crate::config::WasmFilterConfig;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use std::sync::Arc;
+use tracing::{info, warn};
+use wasmtime::{Caller, Engine, InstancePre, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+/// Linear memory one call may grow to.
+const MAX_MEMORY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Host functions modules import from `lb`. Getters return the value's
+/// length and only write it when it fits in `cap` bytes at `ptr`, so a
+/// module can retry with a bigger buffer. Header functions act on the
+/// request headers in `on_request` and the response headers in
+/// `on_response`.
+///
+/// - `get_config(ptr, cap) -> len`
+/// - `get_method(ptr, cap) -> len`
+/// - `get_path(ptr, cap) -> len`
+/// - `get_header(name_ptr, name_len, ptr, cap) -> len`, or -1 if absent
+/// - `set_header(name_ptr, name_len, value_ptr, value_len) -> 0`, or -1
+///   for an invalid name or value
+/// - `remove_header(name_ptr, name_len)`
+/// - `send_response(status, body_ptr, body_len) -> 0`: answer the request
+///   instead of proxying it; -1 outside `on_request` or for a bad status
+/// - `log(ptr, len)`
+///
+/// Each call gets a fresh instance, so modules keep no state between
+/// requests.
+pub struct WasmFilter {
+    name: String,
+    engine: Engine,
+    instance: InstancePre<Call>,
+    config: Arc<[u8]>,
+    fuel: u64,
+    fail_open: bool,
+    has_on_request: bool,
+    has_on_response: bool,
+}
+
+/// A response sent by `send_response`.
+type Answer = (StatusCode, Vec<u8>);
+
+/// What one call can see and change.
+struct Call {
+    headers: HeaderMap,
+    method: String,
+    path: String,
+    config: Arc<[u8]>,
+    /// Only `on_request` may answer.
+    can_answer: bool,
+    answer: Option<Answer>,
+    limits: StoreLimits,
+}
+
+impl WasmFilter {
+    pub fn load(config: &WasmFilterConfig) -> Result<Self> {
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config).map_err(|e| anyhow!("{}", e))?;
+        let module = Module::from_file(&engine, &config.module).map_err(|e| anyhow!("{:#}", e))?;
+        let has_export = |name: &str| module.get_export(name).is_some_and(|e| e.func().is_some());
+        let (has_on_request, has_on_response) = (has_export("on_request"), has_export("on_response"));
+
+        let mut linker = Linker::new(&engine);
+        add_host_functions(&mut linker).map_err(|e| anyhow!("{}", e))?;
+        let instance = linker.instantiate_pre(&module).map_err(|e| anyhow!("{:#}", e))?;
+
+        let name = config
+            .module
+            .file_stem()
+            .map(|stem| format!("wasm:{}", stem.to_string_lossy()))
+            .unwrap_or_else(|| "wasm".to_string());
+        info!(filter = %name, has_on_request, has_on_response, "Loaded wasm filter");
+        Ok(Self {
+            name,
+            engine,
+            instance,
+            config: config.config.as_bytes().into(),
+            fuel: config.fuel,
+            fail_open: config.fail_open,
+            has_on_request,
+            has_on_response,
+        })
+    }
+
+    /// Run `export` against `headers`, which are handed back either way.
+    fn call(
+        &self,
+        export: &str,
+        args: Option<i32>,
+        headers: HeaderMap,
+        ctx: &FilterContext,
+    ) -> (HeaderMap, Result<Option<Answer>>) {
+        let call = Call {
+            headers,
+            method: ctx.method.to_string(),
+            path: ctx.path.clone(),
+            config: self.config.clone(),
+            can_answer: args.is_none(),
+            answer: None,
+            limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY_BYTES).build(),
+        };
+        let mut store = Store::new(&self.engine, call);
+        store.limiter(|call| &mut call.limits);
+        let result = self.run(&mut store, export, args);
+        let call = store.into_data();
+        (call.headers, result.map(|()| call.answer))
+    }
+
+    fn run(&self, store: &mut Store<Call>, export: &str, args: Option<i32>) -> Result<()> {
+        store.set_fuel(self.fuel).map_err(|e| anyhow!("{}", e))?;
+        let instance = self.instance.instantiate(&mut *store).map_err(|e| anyhow!("{:#}", e))?;
+        let result = match args {
+            None => instance
+                .get_typed_func::<(), ()>(&mut *store, export)
+                .and_then(|func| func.call(&mut *store, ())),
+            Some(arg) => instance
+                .get_typed_func::<i32, ()>(&mut *store, export)
+                .and_then(|func| func.call(&mut *store, arg)),
+        };
+        result.map_err(|e| anyhow!("{:#}", e))
+    }
+
+    fn failed(&self, export: &str, error: &anyhow::Error, ctx: &FilterContext) {
+        warn!(
+            request_id = %ctx.request_id,
+            filter = %self.name,
+            error = %error,
+            fail_open = self.fail_open,
+            "wasm filter {} failed",
+            export
+        );
+    }
+}
+
+#[async_trait]
+impl Filter for WasmFilter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn on_request(
+        &self,
+        req: &mut Request<Body>,
+        ctx: &mut FilterContext,
+    ) -> Option<Response<Body>> {
+        if !self.has_on_request {
+            return None;
+        }
+        let headers = std::mem::take(req.headers_mut());
+        let (headers, result) = self.call("on_request", None, headers, ctx);
+        *req.headers_mut() = headers;
+        match result {
+            Ok(answer) => answer.map(|(status, body)| {
+                let mut response = Response::new(Body::from(body));
+                *response.status_mut() = status;
+                response
+            }),
+            Err(e) => {
+                self.failed("on_request", &e, ctx);
+                (!self.fail_open).then(filter_error_response)
+            }
+        }
+    }
+
+    async fn on_response(&self, response: &mut Response<Body>, ctx: &FilterContext) {
+        if !self.has_on_response {
+            return;
+        }
+        let status = response.status().as_u16() as i32;
+        let headers = std::mem::take(response.headers_mut());
+        let (headers, result) = self.call("on_response", Some(status), headers, ctx);
+        *response.headers_mut() = headers;
+        if let Err(e) = result {
+            self.failed("on_response", &e, ctx);
+            if !self.fail_open {
+                *response = filter_error_response();
+            }
+        }
+    }
+}
+
+type HostResult<T> = wasmtime::Result<T>;
+
+fn add_host_functions(linker: &mut Linker<Call>) -> HostResult<()> {
+    linker.func_wrap("lb", "get_config", |mut caller: Caller<'_, Call>, ptr: i32, cap: i32| {
+        let value = caller.data().config.clone();
+        write_guest(&mut caller, ptr, cap, &value)
+    })?;
+    linker.func_wrap("lb", "get_method", |mut caller: Caller<'_, Call>, ptr: i32, cap: i32| {
+        let value = caller.data().method.clone();
+        write_guest(&mut caller, ptr, cap, value.as_bytes())
+    })?;
+    linker.func_wrap("lb", "get_path", |mut caller: Caller<'_, Call>, ptr: i32, cap: i32| {
+        let value = caller.data().path.clone();
+        write_guest(&mut caller, ptr, cap, value.as_bytes())
+    })?;
+    linker.func_wrap(
+        "lb",
+        "get_header",
+        |mut caller: Caller<'_, Call>, name_ptr: i32, name_len: i32, ptr: i32, cap: i32| {
+            let name = read_guest(&mut caller, name_ptr, name_len)?;
+            let value = HeaderName::from_bytes(&name)
+                .ok()
+                .and_then(|name| caller.data().headers.get(name))
+                .map(|value| value.as_bytes().to_vec());
+            match value {
+                Some(value) => write_guest(&mut caller, ptr, cap, &value),
+                None => Ok(-1),
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "lb",
+        "set_header",
+        |mut caller: Caller<'_, Call>, name_ptr: i32, name_len: i32, value_ptr: i32, value_len: i32| {
+            let name = read_guest(&mut caller, name_ptr, name_len)?;
+            let value = read_guest(&mut caller, value_ptr, value_len)?;
+            let (Ok(name), Ok(value)) = (HeaderName::from_bytes(&name), HeaderValue::from_bytes(&value)) else {
+                return Ok(-1);
+            };
+            caller.data_mut().headers.insert(name, value);
+            Ok(0)
+        },
+    )?;
+    linker.func_wrap(
+        "lb",
+        "remove_header",
+        |mut caller: Caller<'_, Call>, name_ptr: i32, name_len: i32| -> HostResult<()> {
+            let name = read_guest(&mut caller, name_ptr, name_len)?;
+            if let Ok(name) = HeaderName::from_bytes(&name) {
+                caller.data_mut().headers.remove(name);
+            }
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "lb",
+        "send_response",
+        |mut caller: Caller<'_, Call>, status: i32, body_ptr: i32, body_len: i32| {
+            let body = read_guest(&mut caller, body_ptr, body_len)?;
+            let status = u16::try_from(status).ok().and_then(|s| StatusCode::from_u16(s).ok());
+            match status {
+                Some(status) if caller.data().can_answer => {
+                    caller.data_mut().answer = Some((status, body));
+                    Ok(0)
+                }
+                _ => Ok(-1),
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "lb",
+        "log",
+        |mut caller: Caller<'_, Call>, ptr: i32, len: i32| -> HostResult<()> {
+            let message = read_guest(&mut caller, ptr, len)?;
+            info!(path = %caller.data().path, "wasm: {}", String::from_utf8_lossy(&message));
+            Ok(())
+        },
+    )?;
+    Ok(())
+}
+
+fn guest_memory(caller: &mut Caller<'_, Call>) -> HostResult<wasmtime::Memory> {
+    caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| wasmtime::Error::msg("module doesn't export its memory"))
+}
+
+fn guest_range(ptr: i32, len: i32, size: usize) -> HostResult<std::ops::Range<usize>> {
+    let (Ok(start), Ok(len)) = (usize::try_from(ptr), usize::try_from(len)) else {
+        return Err(wasmtime::Error::msg("negative pointer or length"));
+    };
+    match start.checked_add(len) {
+        Some(end) if end <= size => Ok(start..end),
+        _ => Err(wasmtime::Error::msg("pointer out of bounds")),
+    }
+}
+
+fn read_guest(caller: &mut Caller<'_, Call>, ptr: i32, len: i32) -> HostResult<Vec<u8>> {
+    let memory = guest_memory(caller)?;
+    let data = memory.data(&*caller);
+    Ok(data[guest_range(ptr, len, data.len())?].to_vec())
+}
+
+fn write_guest(caller: &mut Caller<'_, Call>, ptr: i32, cap: i32, value: &[u8]) -> HostResult<i32> {
+    let len = i32::try_from(value.len()).map_err(|_| wasmtime::Error::msg("value too large"))?;
+    if len <= cap {
+        let memory = guest_memory(caller)?;
+        let data = memory.data_mut(&mut *caller);
+        let range = guest_range(ptr, len, data.len())?;
+        data[range].copy_from_slice(value);
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, wat: &str) -> WasmFilter {
+        let path = std::env::temp_dir().join(format!("lb-test-{}-{}.wat", name, std::process::id()));
+        std::fs::write(&path, wat).unwrap();
+        let config: WasmFilterConfig =
+            serde_yaml::from_str(&format!("{{ module: {}, fuel: 100000 }}", path.display())).unwrap();
+        let filter = WasmFilter::load(&config).unwrap();
+        std::fs::remove_file(path).unwrap();
+        filter
+    }
+
+    #[tokio::test]
+    async fn test_wasm_filter_edits_headers_and_answers() {
+        let filter = load(
+            "block",
+            r#"(module
+                (import "lb" "get_header" (func $get_header (param i32 i32 i32 i32) (result i32)))
+                (import "lb" "set_header" (func $set_header (param i32 i32 i32 i32) (result i32)))
+                (import "lb" "send_response" (func $send_response (param i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "x-block")
+                (data (i32.const 16) "blocked")
+                (data (i32.const 32) "x-seen")
+                (data (i32.const 48) "yes")
+                (func (export "on_request")
+                    (if (i32.ge_s (call $get_header (i32.const 0) (i32.const 7) (i32.const 64) (i32.const 0)) (i32.const 0))
+                        (then (drop (call $send_response (i32.const 403) (i32.const 16) (i32.const 7))))
+                        (else (drop (call $set_header (i32.const 32) (i32.const 6) (i32.const 48) (i32.const 3))))))
+                (func (export "on_response") (param i32)
+                    (drop (call $set_header (i32.const 32) (i32.const 6) (i32.const 48) (i32.const 3)))))"#,
+        );
+
+        let mut req = Request::get("/").header("accept", "*/*").body(Body::empty()).unwrap();
+        let mut ctx = FilterContext::new("id".to_string(), &req, None);
+        assert!(filter.on_request(&mut req, &mut ctx).await.is_none());
+        assert_eq!(req.headers()["x-seen"], "yes");
+        assert_eq!(req.headers()["accept"], "*/*");
+        let mut response = Response::new(Body::empty());
+        filter.on_response(&mut response, &ctx).await;
+        assert_eq!(response.headers()["x-seen"], "yes");
+
+        let mut req = Request::get("/").header("x-block", "1").body(Body::empty()).unwrap();
+        let response = filter.on_request(&mut req, &mut ctx).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Running out of fuel fails closed
+        let spin = load(
+            "spin",
+            r#"(module (func (export "on_request") (loop $forever (br $forever))))"#,
+        );
+        let response = spin.on_request(&mut req, &mut ctx).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(req.headers()["x-block"], "1");
+    }
+}
@@ -0,0 +1,187 @@
+// src/proxy/admission.rs
+use crate::config::{AdmissionConfig, MAX_PRIORITY};
+use crate::metrics::MetricsSink;
+use crate::routing::Route;
+use hyper::header::{HeaderMap, HeaderName};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// How often the event loop lag and memory use are sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Weight of the newest lag sample in the smoothed value.
+const LAG_SMOOTHING: f64 = 0.3;
+
+/// Rejects requests at random, lowest priority first, while the process
+/// is past its limits; see `AdmissionConfig`.
+pub struct Admission {
+    max_event_loop_lag: Duration,
+    max_memory_bytes: Option<u64>,
+    max_in_flight: Option<usize>,
+    priority_header: Option<HeaderName>,
+    protected_priority: u8,
+    shed_all_at: f64,
+    event_loop_lag_micros: AtomicU64,
+    memory_bytes: AtomicU64,
+    in_flight: AtomicUsize,
+    metrics: Arc<dyn MetricsSink>,
+}
+
+/// Counts a request as in flight until dropped.
+pub struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Admission {
+    /// Validated in Config::validate.
+    pub fn new(config: &AdmissionConfig, metrics: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            max_event_loop_lag: Duration::from_millis(config.max_event_loop_lag_ms),
+            max_memory_bytes: config.max_memory_bytes,
+            max_in_flight: config.max_in_flight,
+            priority_header: config
+                .priority_header
+                .as_ref()
+                .map(|name| HeaderName::from_bytes(name.as_bytes()).expect("invalid priority_header")),
+            protected_priority: config.protected_priority,
+            shed_all_at: config.shed_all_at,
+            event_loop_lag_micros: AtomicU64::new(0),
+            memory_bytes: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+            metrics,
+        }
+    }
+
+    /// Sample the event loop lag and memory use in the background.
+    pub fn start(self: &Arc<Self>) {
+        let admission = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut lag = 0.0;
+            loop {
+                let started = Instant::now();
+                tokio::time::sleep(SAMPLE_INTERVAL).await;
+                let Some(admission) = admission.upgrade() else {
+                    return;
+                };
+                let late = started.elapsed().saturating_sub(SAMPLE_INTERVAL).as_secs_f64();
+                lag = LAG_SMOOTHING * late + (1.0 - LAG_SMOOTHING) * lag;
+                admission.event_loop_lag_micros.store((lag * 1e6) as u64, Ordering::Relaxed);
+                if admission.max_memory_bytes.is_some() {
+                    if let Some(rss) = resident_memory() {
+                        admission.memory_bytes.store(rss, Ordering::Relaxed);
+                    }
+                }
+                admission.metrics.update_admission_pressure(
+                    admission.pressure(),
+                    Duration::from_secs_f64(lag),
+                );
+            }
+        });
+    }
+
+    /// The highest measured value over its limit.
+    pub fn pressure(&self) -> f64 {
+        let lag = self.event_loop_lag_micros.load(Ordering::Relaxed) as f64
+            / self.max_event_loop_lag.as_micros() as f64;
+        let memory = self.max_memory_bytes.map_or(0.0, |max| {
+            self.memory_bytes.load(Ordering::Relaxed) as f64 / max as f64
+        });
+        let in_flight = self.max_in_flight.map_or(0.0, |max| {
+            self.in_flight.load(Ordering::Relaxed) as f64 / max as f64
+        });
+        lag.max(memory).max(in_flight)
+    }
+
+    /// The share of requests at `priority` to reject under `pressure`.
+    fn shed_fraction(&self, pressure: f64, priority: u8) -> f64 {
+        if pressure <= 1.0 || priority >= self.protected_priority {
+            return 0.0;
+        }
+        let overload = ((pressure - 1.0) / (self.shed_all_at - 1.0)).min(1.0);
+        let exposure = f64::from(self.protected_priority - priority) / f64::from(self.protected_priority);
+        overload * exposure
+    }
+
+    fn priority(&self, headers: &HeaderMap, route: &Route) -> u8 {
+        self.priority_header
+            .as_ref()
+            .and_then(|name| headers.get(name)?.to_str().ok()?.trim().parse().ok())
+            .map(|priority: u8| priority.min(MAX_PRIORITY))
+            .unwrap_or(route.policy().priority)
+    }
+
+    /// Let a request on `route` in, counting it in flight until the guard
+    /// drops, or `None` if it's shed.
+    pub fn admit(&self, headers: &HeaderMap, route: &Route) -> Option<InFlight<'_>> {
+        let priority = self.priority(headers, route);
+        let pressure = self.pressure();
+        let fraction = self.shed_fraction(pressure, priority);
+        if fraction > 0.0 && rand::random::<f64>() < fraction {
+            debug!(route = route.name(), priority, pressure, "Shedding request");
+            self.metrics.record_load_shed(route.name());
+            return None;
+        }
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        Some(InFlight(&self.in_flight))
+    }
+}
+
+/// Resident set size from `/proc`, where there is one.
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::metrics::NoopMetrics;
+    use crate::routing::Router;
+    use hyper::{Body, Request};
+
+    #[test]
+    fn test_admission_sheds_lowest_priority_first() {
+        let config: Config = serde_yaml::from_str(
+            "load_balancer: {}\nbackends: [{ url: 'http://127.0.0.1:8001' }]\nhealth_check: {}\n\
+             circuit_breaker: {}\nretry: {}\nmetrics: {}\n\
+             routes: [{ name: batch, path_prefix: /batch, policy: { priority: 0 } }, \
+                      { name: checkout, path_prefix: /checkout, policy: { priority: 9 } }]\n\
+             admission: { enabled: true, max_in_flight: 10, priority_header: x-priority }",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let router = Router::new(&config);
+        let admission = Admission::new(&config.admission, Arc::new(NoopMetrics));
+        let route = |path| router.route(&Request::get(path).body(Body::empty()).unwrap(), None);
+        let headers = HeaderMap::new();
+
+        // 10 in flight is at the limit, so nothing is shed yet
+        let held: Vec<_> = (0..10).map(|_| admission.admit(&headers, route("/batch")).unwrap()).collect();
+        assert_eq!(admission.pressure(), 1.0);
+        assert!(admission.admit(&headers, route("/batch")).is_some());
+
+        // At 15 of 10 (`shed_all_at` 1.5) every priority 0 request is shed,
+        // 4 in 9 of the default priority 5 ones and none of the protected
+        let more: Vec<_> = (0..5).map(|_| admission.admit(&headers, route("/checkout")).unwrap()).collect();
+        assert_eq!(admission.pressure(), 1.5);
+        assert!(admission.admit(&headers, route("/batch")).is_none());
+        assert!((admission.shed_fraction(1.5, 5) - 4.0 / 9.0).abs() < 1e-9);
+        assert!(admission.admit(&headers, route("/checkout")).is_some());
+
+        let mut urgent = HeaderMap::new();
+        urgent.insert("x-priority", "9".parse().unwrap());
+        assert!(admission.admit(&urgent, route("/batch")).is_some());
+
+        drop((held, more));
+        assert_eq!(admission.pressure(), 0.0);
+    }
+}
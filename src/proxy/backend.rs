@@ -1,62 +1,222 @@
 // src/proxy/backend.rs
-use crate::config::BackendConfig;
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use tokio::sync::RwLock;
+use crate::config::{BackendConfig, BackendQueueConfig, HostHeader, IdleValidationConfig};
+use crate::proxy::connector::UNIX_SCHEME;
+use crate::proxy::throttle::TokenBucket;
+use crate::tcp_proxy::TCP_SCHEME;
+use hyper::header::HeaderValue;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Notify, RwLock};
+use tokio::time::Instant;
 use url::Url;
 use chrono::{DateTime, Utc};
 
+/// `weight_scale` for the whole weight.
+const FULL_SCALE: u32 = 1000;
+
+/// Latencies `recent_p50` is the median of.
+const RECENT_LATENCIES: usize = 32;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
 pub enum HealthStatus {
     Healthy,
     Unhealthy,
     Unknown,
 }
 
+impl HealthStatus {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => HealthStatus::Healthy,
+            1 => HealthStatus::Unhealthy,
+            _ => HealthStatus::Unknown,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Backend {
     pub id: String,
     pub url: Url,
-    pub weight: u32,
+    /// Starts as configured; the admin API can change it live.
+    weight: AtomicU32,
+    /// Thousandths of `weight` that `dynamic_weights` leaves it.
+    weight_scale: AtomicU32,
     pub max_connections: usize,
+    pub pool: String,
+    pub host_header: Option<HostHeader>,
+    pub zone: Option<String>,
+    pub region: Option<String>,
+    pub idle_validation: Option<IdleValidationConfig>,
+    /// `id` for the `X-Backend-Id` response header, made once.
+    id_header: Option<HeaderValue>,
+    /// Shared by all of this backend's response bodies.
+    bandwidth: Option<Arc<TokenBucket>>,
+    queue: Option<ConnectionQueue>,
     
     // Runtime state
     active_connections: AtomicUsize,
     total_requests: AtomicU64,
     failed_requests: AtomicU64,
-    health_status: RwLock<HealthStatus>,
+    /// Latencies seen since `take_latency` last read them.
+    latency_micros: AtomicU64,
+    latency_samples: AtomicU64,
+    recent_latencies: Mutex<RecentLatencies>,
+    /// A `HealthStatus`, read on every request without locking.
+    health_status: AtomicU8,
     last_health_check: RwLock<Option<DateTime<Utc>>>,
     consecutive_failures: AtomicUsize,
     consecutive_successes: AtomicUsize,
+    draining: AtomicBool,
+    /// Set while a maintenance window has it drained.
+    in_maintenance: AtomicBool,
 }
 
 impl Backend {
     pub fn new(config: &BackendConfig) -> Self {
-        let id = format!(
-            "{}:{}",
-            config.url.host_str().unwrap_or("unknown"),
-            config.url.port_or_known_default().unwrap_or(80)
-        );
-        
+        let id = Self::id_for(&config.url);
         Self {
+            id_header: HeaderValue::from_str(&id).ok(),
             id,
             url: config.url.clone(),
-            weight: config.weight,
+            weight: AtomicU32::new(config.weight),
+            weight_scale: AtomicU32::new(FULL_SCALE),
             max_connections: config.max_connections,
+            pool: config.pool.clone(),
+            host_header: config.host_header.clone(),
+            zone: config.zone.clone(),
+            region: config.region.clone(),
+            idle_validation: config.idle_validation,
+            bandwidth: config.bandwidth.as_ref().map(|bandwidth| Arc::new(TokenBucket::new(bandwidth))),
+            queue: config.queue.as_ref().map(ConnectionQueue::new),
             active_connections: AtomicUsize::new(0),
             total_requests: AtomicU64::new(0),
             failed_requests: AtomicU64::new(0),
-            health_status: RwLock::new(HealthStatus::Unknown),
+            latency_micros: AtomicU64::new(0),
+            latency_samples: AtomicU64::new(0),
+            recent_latencies: Mutex::new(RecentLatencies::default()),
+            health_status: AtomicU8::new(HealthStatus::Unknown as u8),
             last_health_check: RwLock::new(None),
             consecutive_failures: AtomicUsize::new(0),
             consecutive_successes: AtomicUsize::new(0),
+            draining: AtomicBool::new(false),
+            in_maintenance: AtomicBool::new(false),
         }
     }
     
+    /// The ID a backend at `url` gets: `host:port`, or `unix:<path>`.
+    pub fn id_for(url: &Url) -> String {
+        if url.scheme() == UNIX_SCHEME {
+            format!("unix:{}", url.path())
+        } else {
+            format!(
+                "{}:{}",
+                url.host_str().unwrap_or("unknown"),
+                url.port_or_known_default().unwrap_or(80)
+            )
+        }
+    }
+    
+    /// Socket path for `unix://` backends.
+    pub fn unix_socket_path(&self) -> Option<&str> {
+        (self.url.scheme() == UNIX_SCHEME).then(|| self.url.path())
+    }
+    
+    pub fn weight(&self) -> u32 {
+        self.weight.load(Ordering::Relaxed)
+    }
+    
+    pub fn set_weight(&self, weight: u32) {
+        self.weight.store(weight, Ordering::Relaxed);
+    }
+    
+    /// `weight` as `dynamic_weights` scaled it, never below 1.
+    pub fn effective_weight(&self) -> u32 {
+        let scaled = u64::from(self.weight()) * u64::from(self.weight_scale.load(Ordering::Relaxed)) / u64::from(FULL_SCALE);
+        (scaled as u32).max(1)
+    }
+    
+    /// The share of `weight` to use, from 0.0 to 1.0.
+    pub fn set_weight_scale(&self, scale: f64) {
+        let scale = (scale.clamp(0.0, 1.0) * f64::from(FULL_SCALE)).round() as u32;
+        self.weight_scale.store(scale, Ordering::Relaxed);
+    }
+    
+    /// A response time seen in a health check or request.
+    pub fn record_latency(&self, latency: Duration) {
+        self.latency_micros.fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+        self.latency_samples.fetch_add(1, Ordering::Relaxed);
+        self.recent_latencies.lock().unwrap().push(latency);
+    }
+    
+    /// The median of the last few latencies recorded, if any were.
+    pub fn recent_p50(&self) -> Option<Duration> {
+        self.recent_latencies.lock().unwrap().median()
+    }
+    
+    /// The mean of the latencies recorded since the last call, if any.
+    pub fn take_latency(&self) -> Option<Duration> {
+        let samples = self.latency_samples.swap(0, Ordering::Relaxed);
+        let micros = self.latency_micros.swap(0, Ordering::Relaxed);
+        (samples > 0).then(|| Duration::from_micros(micros / samples))
+    }
+    
+    pub fn id_header(&self) -> Option<&HeaderValue> {
+        self.id_header.as_ref()
+    }
+    
+    pub fn bandwidth(&self) -> Option<&Arc<TokenBucket>> {
+        self.bandwidth.as_ref()
+    }
+    
+    /// `tcp://` backends only receive traffic from `protocol: tcp` listeners.
+    pub fn is_tcp(&self) -> bool {
+        self.url.scheme() == TCP_SCHEME
+    }
+    
+    /// Host and port to connect a `tcp://` backend to. IPv6 literals lose
+    /// the brackets `host_str` keeps, which resolvers don't accept.
+    pub fn tcp_address(&self) -> (String, u16) {
+        let host = match self.url.host() {
+            Some(url::Host::Ipv6(ip)) => ip.to_string(),
+            Some(host) => host.to_string(),
+            None => "localhost".to_string(),
+        };
+        // Config::validate requires a port
+        (host, self.url.port().unwrap_or(0))
+    }
+    
+    /// Draining backends get no new traffic, whatever their health.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+    
+    pub fn set_draining(&self, draining: bool) {
+        self.draining.store(draining, Ordering::Relaxed);
+    }
+    
+    /// Drained by a maintenance window, which puts it back when it closes.
+    pub fn in_maintenance(&self) -> bool {
+        self.in_maintenance.load(Ordering::Relaxed)
+    }
+    
+    pub fn set_in_maintenance(&self, in_maintenance: bool) {
+        self.in_maintenance.store(in_maintenance, Ordering::Relaxed);
+    }
+    
     pub fn active_connections(&self) -> usize {
         self.active_connections.load(Ordering::Relaxed)
     }
     
-    pub fn increment_connections(&self) -> bool {
+    /// Take one of the backend's `max_connections`, held until the guard
+    /// is dropped. `None` when they're all taken.
+    pub fn acquire(self: &Arc<Self>) -> Option<ConnectionGuard> {
+        self.try_increment().then(|| ConnectionGuard { backend: self.clone() })
+    }
+    
+    fn try_increment(&self) -> bool {
         loop {
             let current = self.active_connections.load(Ordering::Relaxed);
             if current >= self.max_connections {
@@ -74,8 +234,42 @@ impl Backend {
         }
     }
     
-    pub fn decrement_connections(&self) {
+    /// Wait in the backend's queue for a connection, which comes with how
+    /// it fared if it was `admitted`. `None` when the backend has no queue
+    /// or it's full.
+    pub async fn queue_for_connection(self: &Arc<Self>) -> Option<(Queued, Option<ConnectionGuard>)> {
+        let queue = self.queue.as_ref()?;
+        // Also leaves the queue when the request is dropped while waiting
+        let place = QueuePlace(queue);
+        let ahead = queue.waiting.fetch_add(1, Ordering::SeqCst);
+        if ahead >= queue.depth {
+            return None;
+        }
+        let started = Instant::now();
+        let deadline = started + queue.timeout;
+        let admitted = loop {
+            // Registered before checking, so a connection freed in between
+            // still wakes us
+            let freed = queue.freed.notified();
+            tokio::pin!(freed);
+            freed.as_mut().enable();
+            if self.try_increment() {
+                break true;
+            }
+            if tokio::time::timeout_at(deadline, freed).await.is_err() {
+                break self.try_increment();
+            }
+        };
+        drop(place);
+        let guard = admitted.then(|| ConnectionGuard { backend: self.clone() });
+        Some((Queued { ahead, waited: started.elapsed(), admitted }, guard))
+    }
+    
+    fn release(&self) {
         self.active_connections.fetch_sub(1, Ordering::SeqCst);
+        if let Some(queue) = &self.queue {
+            queue.freed.notify_one();
+        }
     }
     
     pub fn record_request(&self, success: bool) {
@@ -85,13 +279,16 @@ impl Backend {
         }
     }
     
-    pub async fn is_healthy(&self) -> bool {
-        *self.health_status.read().await == HealthStatus::Healthy
+    pub fn health_status(&self) -> HealthStatus {
+        HealthStatus::from_u8(self.health_status.load(Ordering::Acquire))
+    }
+    
+    pub fn is_healthy(&self) -> bool {
+        self.health_status() == HealthStatus::Healthy
     }
     
     pub async fn update_health(&self, healthy: bool) {
-        let mut status = self.health_status.write().await;
-        *status = if healthy {
+        let status = if healthy {
             self.consecutive_failures.store(0, Ordering::Relaxed);
             self.consecutive_successes.fetch_add(1, Ordering::Relaxed);
             HealthStatus::Healthy
@@ -100,6 +297,7 @@ impl Backend {
             self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
             HealthStatus::Unhealthy
         };
+        self.health_status.store(status as u8, Ordering::Release);
         
         let mut last_check = self.last_health_check.write().await;
         *last_check = Some(Utc::now());
@@ -135,9 +333,185 @@ impl Backend {
     }
 }
 
+/// A ring of the last `RECENT_LATENCIES` latencies, in microseconds.
+#[derive(Debug, Default)]
+struct RecentLatencies {
+    samples: [u32; RECENT_LATENCIES],
+    next: usize,
+    len: usize,
+}
+
+impl RecentLatencies {
+    fn push(&mut self, latency: Duration) {
+        self.samples[self.next] = u32::try_from(latency.as_micros()).unwrap_or(u32::MAX);
+        self.next = (self.next + 1) % RECENT_LATENCIES;
+        self.len = (self.len + 1).min(RECENT_LATENCIES);
+    }
+    
+    fn median(&self) -> Option<Duration> {
+        if self.len == 0 {
+            return None;
+        }
+        let mut samples = self.samples;
+        let samples = &mut samples[..self.len];
+        let middle = samples.len() / 2;
+        let (_, median, _) = samples.select_nth_unstable(middle);
+        Some(Duration::from_micros(u64::from(*median)))
+    }
+}
+
+/// One of a backend's `max_connections`, given back on drop, so early
+/// returns and panics can't leak it.
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    backend: Arc<Backend>,
+}
+
+impl ConnectionGuard {
+    pub fn backend(&self) -> &Arc<Backend> {
+        &self.backend
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.backend.release();
+    }
+}
+
+/// Requests waiting for one of a backend's `max_connections`.
+#[derive(Debug)]
+struct ConnectionQueue {
+    depth: usize,
+    timeout: Duration,
+    waiting: AtomicUsize,
+    freed: Notify,
+}
+
+impl ConnectionQueue {
+    fn new(config: &BackendQueueConfig) -> Self {
+        Self {
+            depth: config.depth,
+            timeout: Duration::from_millis(config.timeout_ms),
+            waiting: AtomicUsize::new(0),
+            freed: Notify::new(),
+        }
+    }
+}
+
+/// A request's place in a `ConnectionQueue`, given up when dropped.
+struct QueuePlace<'a>(&'a ConnectionQueue);
+
+impl Drop for QueuePlace<'_> {
+    fn drop(&mut self) {
+        self.0.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// How a request fared in a backend's queue.
+#[derive(Debug, Clone, Copy)]
+pub struct Queued {
+    /// Requests already waiting when it joined.
+    pub ahead: usize,
+    pub waited: Duration,
+    /// Whether it got a connection before `timeout_ms`.
+    pub admitted: bool,
+}
+
 #[derive(Debug)]
 pub struct BackendMetrics {
     pub active_connections: usize,
     pub total_requests: u64,
     pub failed_requests: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health_status_round_trips() {
+        let config: BackendConfig = serde_yaml::from_str("url: 'http://127.0.0.1:8001'").unwrap();
+        let backend = Backend::new(&config);
+        assert_eq!(backend.health_status(), HealthStatus::Unknown);
+        assert!(!backend.is_healthy());
+
+        backend.update_health(true).await;
+        assert_eq!(backend.health_status(), HealthStatus::Healthy);
+        assert!(backend.is_healthy());
+        backend.update_health(false).await;
+        assert_eq!(backend.health_status(), HealthStatus::Unhealthy);
+        assert_eq!(backend.consecutive_failures(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_queues_for_a_freed_connection() {
+        let config: BackendConfig = serde_yaml::from_str(
+            "{ url: 'http://127.0.0.1:8001', max_connections: 1, queue: { depth: 1, timeout_ms: 2000 } }",
+        )
+        .unwrap();
+        let backend = Arc::new(Backend::new(&config));
+        let held = backend.acquire().unwrap();
+        assert!(backend.acquire().is_none());
+
+        let waiter = tokio::spawn({
+            let backend = backend.clone();
+            async move { backend.queue_for_connection().await }
+        });
+        while backend.queue.as_ref().unwrap().waiting.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        // Past the hard limit
+        assert!(backend.queue_for_connection().await.is_none());
+
+        drop(held);
+        let (queued, guard) = waiter.await.unwrap().unwrap();
+        assert!(queued.admitted && guard.is_some());
+        assert_eq!(queued.ahead, 0);
+        assert_eq!(backend.active_connections(), 1);
+        drop(guard);
+        assert_eq!(backend.active_connections(), 0);
+
+        let config = BackendConfig { queue: Some(BackendQueueConfig { depth: 1, timeout_ms: 10 }), ..config };
+        let backend = Arc::new(Backend::new(&config));
+        let _held = backend.acquire().unwrap();
+        let (queued, guard) = backend.queue_for_connection().await.unwrap();
+        assert!(!queued.admitted && guard.is_none() && queued.waited >= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_tcp_address_unbrackets_ipv6() {
+        let address = |url: &str| {
+            let config: BackendConfig = serde_yaml::from_str(&format!("url: '{}'", url)).unwrap();
+            Backend::new(&config).tcp_address()
+        };
+        assert_eq!(address("tcp://[::1]:5432"), ("::1".to_string(), 5432));
+        assert_eq!(address("tcp://10.0.0.1:5432"), ("10.0.0.1".to_string(), 5432));
+        assert_eq!(address("tcp://db.internal:6379"), ("db.internal".to_string(), 6379));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_waiters_leave_the_queue() {
+        let config: BackendConfig = serde_yaml::from_str(
+            "{ url: 'http://127.0.0.1:8001', max_connections: 1, queue: { depth: 1, timeout_ms: 2000 } }",
+        )
+        .unwrap();
+        let backend = Arc::new(Backend::new(&config));
+        let held = backend.acquire().unwrap();
+
+        // Given up on, as when the client disconnects
+        let gave_up = tokio::time::timeout(Duration::from_millis(20), backend.queue_for_connection()).await;
+        assert!(gave_up.is_err());
+        assert_eq!(backend.queue.as_ref().unwrap().waiting.load(Ordering::SeqCst), 0);
+
+        // So the next request still gets its place
+        let waiter = tokio::spawn({
+            let backend = backend.clone();
+            async move { backend.queue_for_connection().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(held);
+        let (queued, guard) = waiter.await.unwrap().unwrap();
+        assert!(queued.admitted && guard.is_some());
+    }
+}
@@ -0,0 +1,302 @@
+// src/proxy/clients.rs
+//! One HTTP client per backend pool, each with its own idle connections and
+//! limit on requests in flight, so a pool whose backends hang can only tie
+//! up its own connections.
+use super::{Backend, Dialer, ProxyError, UpstreamConnector};
+use crate::config::{IdleValidationConfig, IdleValidationMode, UpstreamConfig, UpstreamPoolConfig};
+use crate::metrics::MetricsSink;
+use dashmap::DashMap;
+use futures::StreamExt;
+use hyper::body::HttpBody;
+use hyper::{Body, Client};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+pub struct UpstreamClients {
+    dialer: Dialer,
+    stats: Arc<ConnectionStats>,
+    defaults: UpstreamPoolConfig,
+    pools: HashMap<String, UpstreamPoolConfig>,
+    clients: DashMap<String, Arc<PoolClient>>,
+    metrics: Arc<dyn MetricsSink>,
+}
+
+pub struct PoolClient {
+    pool: String,
+    client: Client<UpstreamConnector>,
+    /// Clients of backends with `idle_validation`, made on first use and
+    /// shared by backends validating the same way.
+    validated: DashMap<IdleValidationConfig, Client<UpstreamConnector>>,
+    dialer: Dialer,
+    config: UpstreamPoolConfig,
+    /// Requests in flight, with their bodies; none when unlimited.
+    slots: Option<Arc<Semaphore>>,
+    max: Option<usize>,
+    wait: Duration,
+    stats: Arc<ConnectionStats>,
+    metrics: Arc<dyn MetricsSink>,
+}
+
+/// A request's hold on its pool, kept until its response body is done.
+pub struct PoolSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+    client: Arc<PoolClient>,
+    /// Requests using one of the backend's connections, this one included.
+    in_use: Arc<BackendConnections>,
+    backend: String,
+}
+
+/// Every backend's open upstream connections and the requests using them,
+/// by backend ID. Each HTTP/1 request has a connection to itself, so the
+/// rest of the open ones are idle.
+pub struct ConnectionStats {
+    backends: DashMap<String, Arc<BackendConnections>>,
+    metrics: Arc<dyn MetricsSink>,
+}
+
+#[derive(Default)]
+struct BackendConnections {
+    open: AtomicUsize,
+    in_use: AtomicUsize,
+}
+
+/// One upstream connection, counted as open until dropped.
+pub struct OpenConnection {
+    stats: Arc<ConnectionStats>,
+    counts: Arc<BackendConnections>,
+    backend: String,
+}
+
+impl ConnectionStats {
+    fn new(metrics: Arc<dyn MetricsSink>) -> Self {
+        Self { backends: DashMap::new(), metrics }
+    }
+
+    fn counts(&self, backend: &str) -> Arc<BackendConnections> {
+        if let Some(counts) = self.backends.get(backend) {
+            return counts.clone();
+        }
+        self.backends.entry(backend.to_string()).or_default().clone()
+    }
+
+    pub fn opened(self: &Arc<Self>, backend: String) -> OpenConnection {
+        let counts = self.counts(&backend);
+        counts.open.fetch_add(1, Ordering::Relaxed);
+        self.metrics.record_upstream_connection_opened(&backend);
+        self.report(&backend, &counts);
+        OpenConnection { stats: self.clone(), counts, backend }
+    }
+
+    fn report(&self, backend: &str, counts: &BackendConnections) {
+        let in_use = counts.in_use.load(Ordering::Relaxed);
+        let idle = counts.open.load(Ordering::Relaxed).saturating_sub(in_use);
+        self.metrics.update_upstream_connections(backend, idle, in_use);
+    }
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.counts.open.fetch_sub(1, Ordering::Relaxed);
+        self.stats.metrics.record_upstream_connection_closed(&self.backend);
+        self.stats.report(&self.backend, &self.counts);
+    }
+}
+
+impl UpstreamClients {
+    pub fn new(config: &UpstreamConfig, metrics: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            dialer: Dialer::new(config),
+            stats: Arc::new(ConnectionStats::new(metrics.clone())),
+            defaults: config.connection_pool.clone(),
+            pools: config.pools.clone(),
+            clients: DashMap::new(),
+            metrics,
+        }
+    }
+
+    /// The client for backends in `pool`, made on first use.
+    pub fn get(&self, pool: &str) -> Arc<PoolClient> {
+        if let Some(client) = self.clients.get(pool) {
+            return client.clone();
+        }
+        self.clients
+            .entry(pool.to_string())
+            .or_insert_with(|| {
+                let config = self.pools.get(pool).unwrap_or(&self.defaults);
+                Arc::new(PoolClient {
+                    pool: pool.to_string(),
+                    client: build_client(
+                        config,
+                        Duration::from_secs(config.idle_timeout_secs),
+                        UpstreamConnector::new(self.dialer.clone()).with_stats(self.stats.clone()),
+                    ),
+                    validated: DashMap::new(),
+                    dialer: self.dialer.clone(),
+                    config: config.clone(),
+                    slots: config.max_connections.map(|max| Arc::new(Semaphore::new(max))),
+                    max: config.max_connections,
+                    wait: Duration::from_millis(config.queue_timeout_ms),
+                    stats: self.stats.clone(),
+                    metrics: self.metrics.clone(),
+                })
+            })
+            .clone()
+    }
+}
+
+fn build_client(config: &UpstreamPoolConfig, idle_timeout: Duration, connector: UpstreamConnector) -> Client<UpstreamConnector> {
+    Client::builder()
+        .pool_idle_timeout(idle_timeout)
+        .pool_max_idle_per_host(config.max_idle_per_host)
+        .build::<_, Body>(connector)
+}
+
+impl PoolClient {
+    /// A slot for one request to `backend`, waiting up to
+    /// `queue_timeout_ms` for one to free up.
+    pub async fn acquire(self: &Arc<Self>, backend: &Backend) -> Result<PoolSlot, ProxyError> {
+        let started = Instant::now();
+        let permit = match &self.slots {
+            None => None,
+            Some(slots) => match tokio::time::timeout(self.wait, slots.clone().acquire_owned()).await {
+                Ok(Ok(permit)) => Some(permit),
+                _ => return Err(ProxyError::PoolExhausted(self.pool.clone())),
+            },
+        };
+        self.metrics.record_upstream_pool_wait(&backend.id, started.elapsed());
+        self.report();
+        let in_use = self.stats.counts(&backend.id);
+        in_use.in_use.fetch_add(1, Ordering::Relaxed);
+        self.stats.report(&backend.id, &in_use);
+        Ok(PoolSlot { _permit: permit, client: self.clone(), in_use, backend: backend.id.clone() })
+    }
+
+    fn in_use(&self) -> usize {
+        match (&self.slots, self.max) {
+            (Some(slots), Some(max)) => max - slots.available_permits(),
+            _ => 0,
+        }
+    }
+
+    fn report(&self) {
+        if let Some(max) = self.max {
+            self.metrics.update_upstream_pool(&self.pool, self.in_use(), max);
+        }
+    }
+}
+
+impl Drop for PoolSlot {
+    fn drop(&mut self) {
+        self.in_use.in_use.fetch_sub(1, Ordering::Relaxed);
+        self.client.stats.report(&self.backend, &self.in_use);
+        // The permit goes back after this runs, so it's still counted here
+        if let Some(max) = self.client.max {
+            let in_use = self.client.in_use().saturating_sub(1);
+            self.client.metrics.update_upstream_pool(&self.client.pool, in_use, max);
+        }
+    }
+}
+
+impl PoolSlot {
+    /// The client to send `backend` requests with.
+    pub fn client(&self, backend: &Backend) -> Client<UpstreamConnector> {
+        let pool = &self.client;
+        let Some(validation) = backend.idle_validation else {
+            return pool.client.clone();
+        };
+        pool.validated
+            .entry(validation)
+            .or_insert_with(|| {
+                let connector = UpstreamConnector::new(pool.dialer.clone()).with_stats(pool.stats.clone());
+                let idle_timeout = Duration::from_secs(pool.config.idle_timeout_secs);
+                let after = Duration::from_secs(validation.after_secs);
+                match validation.mode {
+                    // Hyper skips idle connections past the timeout on checkout
+                    IdleValidationMode::Reconnect => build_client(&pool.config, idle_timeout.min(after), connector),
+                    IdleValidationMode::Probe => build_client(&pool.config, idle_timeout, connector.with_keepalive(after)),
+                }
+            })
+            .clone()
+    }
+
+    /// `body`, holding on to the slot until it's been sent or dropped.
+    pub fn hold_through(self, body: Body) -> Body {
+        if body.is_end_stream() {
+            return body;
+        }
+        Body::wrap_stream(body.map(move |chunk| {
+            let _held = &self;
+            chunk
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::NoopMetrics;
+
+    #[tokio::test]
+    async fn test_pools_have_separate_limits() {
+        let config: UpstreamConfig = serde_yaml::from_str(
+            "{ connection_pool: { max_connections: 1, queue_timeout_ms: 10 }, pools: { search: { max_connections: 2 } } }",
+        )
+        .unwrap();
+        let clients = UpstreamClients::new(&config, Arc::new(NoopMetrics));
+        let backend = Backend::new(&serde_yaml::from_str("url: 'http://10.0.0.1'").unwrap());
+
+        let held = clients.get("default").acquire(&backend).await.unwrap();
+        assert!(matches!(clients.get("default").acquire(&backend).await, Err(ProxyError::PoolExhausted(_))));
+        let _first = clients.get("search").acquire(&backend).await.unwrap();
+        let _second = clients.get("search").acquire(&backend).await.unwrap();
+
+        drop(held.hold_through(Body::from("done")));
+        assert!(clients.get("default").acquire(&backend).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_instead_of_reusing_idle_connections() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let service = hyper::service::service_fn(|_| async { Ok::<_, hyper::Error>(hyper::Response::new(Body::empty())) });
+                tokio::spawn(hyper::server::conn::Http::new().serve_connection(stream, service));
+            }
+        });
+        let clients = UpstreamClients::new(&UpstreamConfig::default(), Arc::new(NoopMetrics));
+        let backend = |validation: &str| {
+            let config = serde_yaml::from_str(&format!("{{ url: 'http://{}', {} }}", addr, validation)).unwrap();
+            Backend::new(&config)
+        };
+        let send = |backend: Backend| {
+            let clients = &clients;
+            async move {
+                let slot = clients.get("default").acquire(&backend).await.unwrap();
+                let uri: hyper::Uri = format!("http://{}/", addr).parse().unwrap();
+                let response = slot.client(&backend).get(uri).await.unwrap();
+                hyper::body::to_bytes(response.into_body()).await.unwrap();
+            }
+        };
+
+        send(backend("weight: 1")).await;
+        send(backend("weight: 1")).await;
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+        let counts = clients.stats.counts(&addr.to_string());
+        assert_eq!((counts.open.load(Ordering::SeqCst), counts.in_use.load(Ordering::SeqCst)), (1, 0));
+
+        let validated = "idle_validation: { after_secs: 1 }";
+        send(backend(validated)).await;
+        send(backend(validated)).await;
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        send(backend(validated)).await;
+        assert_eq!(accepted.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,114 @@
+// src/proxy/compression.rs
+use async_compression::tokio::bufread::GzipEncoder;
+use futures::TryStreamExt;
+use hyper::header::{
+    HeaderMap, HeaderValue, ACCEPT_ENCODING, ACCEPT_RANGES, CONTENT_ENCODING, CONTENT_LENGTH,
+    CONTENT_TYPE, ETAG, VARY,
+};
+use hyper::{Body, Response, StatusCode};
+use std::io;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+/// Smaller bodies aren't worth the gzip overhead.
+const MIN_COMPRESS_SIZE: u64 = 256;
+
+/// Whether the client listed gzip in `Accept-Encoding` without `q=0`.
+pub(crate) fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut params = coding.split(';').map(str::trim);
+            let name = params.next().unwrap_or_default();
+            let disabled = params.any(|param| {
+                param.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0)
+            });
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !disabled
+        })
+}
+
+/// Gzip the response body on the fly if it's worth compressing.
+pub(crate) fn gzip(response: Response<Body>) -> Response<Body> {
+    if !compressible(&response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let reader = StreamReader::new(body.map_err(io::Error::other));
+    let body = Body::wrap_stream(ReaderStream::new(GzipEncoder::new(reader)));
+
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.remove(ACCEPT_RANGES);
+    // A strong ETag would now describe different bytes
+    if let Some(etag) = parts.headers.get(ETAG) {
+        if !etag.as_bytes().starts_with(b"W/") {
+            parts.headers.remove(ETAG);
+        }
+    }
+    parts
+        .headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("accept-encoding"));
+    Response::from_parts(parts, body)
+}
+
+fn compressible(response: &Response<Body>) -> bool {
+    let headers = response.headers();
+    if response.status() == StatusCode::NO_CONTENT
+        || response.status() == StatusCode::NOT_MODIFIED
+        || response.status() == StatusCode::PARTIAL_CONTENT
+        || headers.contains_key(CONTENT_ENCODING)
+    {
+        return false;
+    }
+
+    let too_small = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .is_some_and(|len| len < MIN_COMPRESS_SIZE);
+    if too_small {
+        return false;
+    }
+
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime.starts_with("text/")
+        || matches!(
+            mime,
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/wasm"
+                | "image/svg+xml"
+        )
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_gzip() {
+        let accepts = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT_ENCODING, value.parse().unwrap());
+            accepts_gzip(&headers)
+        };
+        assert!(accepts("gzip"));
+        assert!(accepts("br, GZIP;q=0.8"));
+        assert!(accepts("*"));
+        assert!(!accepts("br"));
+        assert!(!accepts("gzip;q=0"));
+        assert!(!accepts_gzip(&HeaderMap::new()));
+    }
+}
@@ -0,0 +1,234 @@
+// src/proxy/connector.rs
+use super::clients::{ConnectionStats, OpenConnection};
+use super::dial::Dialer;
+use futures::future::BoxFuture;
+use hyper::{
+    client::connect::{Connected, Connection},
+    Uri,
+};
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tower::Service;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// URI scheme for backends on a Unix domain socket.
+pub const UNIX_SCHEME: &str = "unix";
+
+/// Build a request URI for a backend on a Unix socket. Hyper's client pools
+/// connections by scheme and authority, so the socket path is hex-encoded
+/// into the authority and decoded again by [`UpstreamConnector`].
+pub fn unix_uri(socket_path: &str, path_and_query: &str) -> Result<Uri, hyper::http::Error> {
+    let authority: String = socket_path
+        .bytes()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Uri::builder()
+        .scheme(UNIX_SCHEME)
+        .authority(authority)
+        .path_and_query(path_and_query)
+        .build()
+}
+
+fn decode_socket_path(uri: &Uri) -> io::Result<String> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid unix URI: {}", uri));
+    let hex = uri.host().ok_or_else(invalid)?.as_bytes();
+    if hex.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    let bytes = hex
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect::<io::Result<Vec<u8>>>()?;
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+/// Client connector dialing TCP for `http://` URIs and the Unix socket for
+/// URIs built by [`unix_uri`].
+#[derive(Clone)]
+pub struct UpstreamConnector {
+    dialer: Dialer,
+    /// Idle time before TCP keepalive probes start, if not the dialer's.
+    keepalive: Option<Duration>,
+    stats: Option<Arc<ConnectionStats>>,
+}
+
+impl UpstreamConnector {
+    pub fn new(dialer: Dialer) -> Self {
+        Self { dialer, keepalive: None, stats: None }
+    }
+
+    /// Count the connections made in `stats`, by backend ID.
+    pub fn with_stats(self, stats: Arc<ConnectionStats>) -> Self {
+        Self { stats: Some(stats), ..self }
+    }
+
+    /// Probe idle TCP connections after `idle`, once a second, and close
+    /// them after three probes go unanswered.
+    pub fn with_keepalive(self, idle: Duration) -> Self {
+        Self { keepalive: Some(idle), ..self }
+    }
+}
+
+impl Service<Uri> for UpstreamConnector {
+    type Response = UpstreamStream;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<UpstreamStream, BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let started = Instant::now();
+        let stats = self.stats.clone();
+        if uri.scheme_str() == Some(UNIX_SCHEME) {
+            return Box::pin(async move {
+                let (stream, path) = connect_unix(uri).await?;
+                Ok(UpstreamStream { stream, dialed: Dialed::since(started), _open: stats.map(|stats| stats.opened(format!("unix:{}", path))) })
+            });
+        }
+        let (dialer, keepalive) = (self.dialer.clone(), self.keepalive);
+        Box::pin(async move {
+            if uri.scheme_str() != Some("http") {
+                return Err(format!("invalid URL, scheme is not http: {}", uri).into());
+            }
+            let host = uri.host().ok_or_else(|| format!("invalid URL, host is missing: {}", uri))?;
+            let port = uri.port_u16().unwrap_or(80);
+            let stream = dialer.connect(host.trim_start_matches('[').trim_end_matches(']'), port).await?;
+            if let Some(idle) = keepalive {
+                let keepalive = socket2::TcpKeepalive::new()
+                    .with_time(idle)
+                    .with_interval(Duration::from_secs(1))
+                    .with_retries(3);
+                socket2::SockRef::from(&stream).set_tcp_keepalive(&keepalive)?;
+            }
+            Ok(UpstreamStream {
+                stream: Stream::Tcp(stream),
+                dialed: Dialed::since(started),
+                _open: stats.map(|stats| stats.opened(format!("{}:{}", host, port))),
+            })
+        })
+    }
+}
+
+/// When a backend connection was made and how long that took. In the
+/// extensions of every response received on it.
+#[derive(Debug, Clone, Copy)]
+pub struct Dialed {
+    pub at: Instant,
+    pub took: Duration,
+}
+
+impl Dialed {
+    fn since(started: Instant) -> Self {
+        let at = Instant::now();
+        Self { at, took: at - started }
+    }
+}
+
+/// The stream, and the socket path to name it by.
+#[cfg(unix)]
+async fn connect_unix(uri: Uri) -> Result<(Stream, String), BoxError> {
+    let path = decode_socket_path(&uri)?;
+    Ok((Stream::Unix(UnixStream::connect(&path).await?), path))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(uri: Uri) -> Result<(Stream, String), BoxError> {
+    let path = decode_socket_path(&uri)?;
+    Err(format!("cannot connect to {}: Unix sockets are only supported on unix platforms", path).into())
+}
+
+/// A connection to a backend.
+pub struct UpstreamStream {
+    stream: Stream,
+    dialed: Dialed,
+    /// Counted as open until dropped.
+    _open: Option<OpenConnection>,
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Connection for UpstreamStream {
+    fn connected(&self) -> Connected {
+        match &self.stream {
+            Stream::Tcp(s) => s.connected().extra(self.dialed),
+            #[cfg(unix)]
+            Stream::Unix(_) => Connected::new().extra(self.dialed),
+        }
+    }
+}
+
+impl AsyncRead for UpstreamStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for UpstreamStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.get_mut().stream {
+            Stream::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unix_uri_round_trips_socket_path() {
+        let uri = unix_uri("/var/run/app.sock", "/health?full=1").unwrap();
+        assert_eq!(uri.scheme_str(), Some(UNIX_SCHEME));
+        assert_eq!(uri.path_and_query().unwrap().as_str(), "/health?full=1");
+        assert_eq!(decode_socket_path(&uri).unwrap(), "/var/run/app.sock");
+    }
+}
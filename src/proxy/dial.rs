@@ -0,0 +1,143 @@
+// src/proxy/dial.rs
+//! TCP connections to backends, with RFC 8305 Happy Eyeballs: a name's
+//! addresses are tried alternating between families, each attempt getting
+//! a head start of `happy_eyeballs_delay` before the next joins the race,
+//! so an unreachable IPv6 route costs a quarter second, not a timeout.
+use crate::config::{IpFamily, UpstreamConfig};
+use dashmap::DashMap;
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const KEEPALIVE: Duration = Duration::from_secs(60);
+/// How long an address that refused or timed out is tried after the
+/// rest of its name's addresses.
+const FAILED_ADDRESS_TTL: Duration = Duration::from_secs(30);
+
+/// Dials every address of a backend before giving up on it, so one dead
+/// IP behind a name doesn't count as the backend failing.
+#[derive(Debug, Clone)]
+pub struct Dialer {
+    prefer: IpFamily,
+    delay: Duration,
+    /// Addresses whose last attempt failed, and when.
+    failed: Arc<DashMap<SocketAddr, Instant>>,
+}
+
+impl Default for Dialer {
+    fn default() -> Self {
+        Self::new(&UpstreamConfig::default())
+    }
+}
+
+impl Dialer {
+    pub fn new(config: &UpstreamConfig) -> Self {
+        Self {
+            prefer: config.prefer,
+            delay: config.happy_eyeballs_delay(),
+            failed: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub async fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let connecting = async {
+            let addrs = tokio::net::lookup_host((host, port)).await?.collect();
+            self.race(self.order(addrs)).await
+        };
+        let stream = tokio::time::timeout(CONNECT_TIMEOUT, connecting)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, format!("connecting to {}:{} timed out", host, port)))??;
+        let keepalive = socket2::TcpKeepalive::new().with_time(KEEPALIVE);
+        socket2::SockRef::from(&stream).set_tcp_keepalive(&keepalive)?;
+        Ok(stream)
+    }
+
+    /// The preferred family's first address, then the other's, alternating
+    /// from there, each family keeping the resolver's order. Addresses
+    /// that recently failed go last.
+    fn order(&self, addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        let (preferred, other): (Vec<_>, Vec<_>) = addrs
+            .into_iter()
+            .partition(|addr| addr.is_ipv6() == (self.prefer == IpFamily::Ipv6));
+        let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+        let mut ordered = Vec::with_capacity(preferred.len() + other.len());
+        loop {
+            match (preferred.next(), other.next()) {
+                (None, None) => break,
+                (first, second) => ordered.extend(first.into_iter().chain(second)),
+            }
+        }
+        if !self.failed.is_empty() {
+            self.failed.retain(|_, at| at.elapsed() < FAILED_ADDRESS_TTL);
+            ordered.sort_by_key(|addr| self.failed.contains_key(addr));
+        }
+        ordered
+    }
+
+    /// Start an attempt on each address in turn, the next one starting
+    /// once the last has had `delay` or failed, until one connects.
+    async fn race(&self, addrs: Vec<SocketAddr>) -> io::Result<TcpStream> {
+        let mut pending = addrs.into_iter();
+        let mut attempts = FuturesUnordered::new();
+        let mut last_error = None;
+        loop {
+            if let Some(addr) = pending.next() {
+                attempts.push(async move { (addr, TcpStream::connect(addr).await) });
+            }
+            if attempts.is_empty() {
+                return Err(last_error
+                    .unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to")));
+            }
+            let more = !pending.as_slice().is_empty();
+            tokio::select! {
+                Some((addr, result)) = attempts.next() => match result {
+                    Ok(stream) => {
+                        self.failed.remove(&addr);
+                        return Ok(stream);
+                    }
+                    Err(e) => {
+                        debug!(address = %addr, error = %e, remaining = pending.len(), "Connect attempt failed");
+                        self.failed.insert(addr, Instant::now());
+                        last_error = Some(e);
+                    }
+                },
+                _ = tokio::time::sleep(self.delay), if more => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_orders_by_family_and_falls_through_failed_addresses() {
+        let v6: SocketAddr = "[2001:db8::1]:80".parse().unwrap();
+        let v6b: SocketAddr = "[2001:db8::2]:80".parse().unwrap();
+        let v4: SocketAddr = "192.0.2.1:80".parse().unwrap();
+        let dialer = Dialer::default();
+        assert_eq!(dialer.order(vec![v4, v6, v6b]), vec![v6, v4, v6b]);
+        let ipv4_first = Dialer::new(&UpstreamConfig { prefer: IpFamily::Ipv4, ..Default::default() });
+        assert_eq!(ipv4_first.order(vec![v6, v6b, v4]), vec![v4, v6, v6b]);
+
+        // A refused address gives way to the next without waiting out the delay
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let slow = Dialer::new(&UpstreamConfig { happy_eyeballs_delay_ms: 2000, ..Default::default() });
+        let started = std::time::Instant::now();
+        let stream = slow.race(vec![closed, listener.local_addr().unwrap()]).await.unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // ...and is tried after the others next time
+        let open = listener.local_addr().unwrap();
+        assert_eq!(slow.order(vec![closed, open]), vec![open, closed]);
+    }
+}
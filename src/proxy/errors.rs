@@ -0,0 +1,268 @@
+// src/proxy/errors.rs
+use super::{ProxyError, ERROR_CODE_HEADER};
+use crate::config::{ErrorFormat, ErrorPageConfig};
+use crate::middleware::FilterContext;
+use hyper::header::{HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use hyper::{Body, Response};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// RFC 7807 problem details, with the error code and request ID as
+/// extension members.
+const PROBLEM_JSON: &str = r#"{"type":"about:blank","title":"{reason}","status":{status},"detail":"{message}","code":"{error_code}","request_id":"{request_id}"}"#;
+const HTML: &str = "<!DOCTYPE html>\n<html><head><title>{status} {reason}</title></head>\n\
+    <body><h1>{status} {reason}</h1><p>{message}</p><p>Request ID: {request_id}</p></body></html>\n";
+
+/// The configured `error_pages` for a route, its own over the top-level
+/// ones, and the built-in pages for its `error_format`.
+#[derive(Debug, Clone)]
+pub struct ErrorPages {
+    pages: HashMap<u16, Arc<ErrorPage>>,
+    format: ErrorFormat,
+    json: Arc<ErrorPage>,
+    html: Arc<ErrorPage>,
+}
+
+#[derive(Debug)]
+struct ErrorPage {
+    content_type: HeaderValue,
+    escape: Escape,
+    body: String,
+    retry_after: Option<HeaderValue>,
+}
+
+/// How template values are made safe for the body around them.
+#[derive(Debug, Clone, Copy)]
+enum Escape {
+    None,
+    Json,
+    Html,
+}
+
+impl ErrorPages {
+    pub fn new(
+        global: &HashMap<u16, ErrorPageConfig>,
+        route: &HashMap<u16, ErrorPageConfig>,
+        format: ErrorFormat,
+    ) -> Self {
+        let pages = global
+            .iter()
+            .chain(route)
+            .map(|(status, page)| (*status, Arc::new(ErrorPage::new(page))))
+            .collect();
+        let builtin = |content_type: &str, body: &str| {
+            Arc::new(ErrorPage::new(&ErrorPageConfig {
+                content_type: content_type.to_string(),
+                body: body.to_string(),
+                retry_after_secs: None,
+            }))
+        };
+        Self {
+            pages,
+            format,
+            json: builtin("application/problem+json", PROBLEM_JSON),
+            html: builtin("text/html; charset=utf-8", HTML),
+        }
+    }
+
+    /// The page for `err` given the request's `Accept`, or `None` to answer
+    /// with plain text. A backend's own 503 is passed on as it is.
+    pub fn render(
+        &self,
+        err: &ProxyError,
+        ctx: &FilterContext,
+        accept: Option<&HeaderValue>,
+    ) -> Option<Response<Body>> {
+        if matches!(err, ProxyError::Unavailable(_)) {
+            return None;
+        }
+        let (status, message) = err.status_and_message();
+        let format = match self.format {
+            ErrorFormat::Negotiate => negotiate(accept),
+            format => format,
+        };
+        let page = match (self.pages.get(&status.as_u16()), format) {
+            (Some(page), _) => page,
+            (None, ErrorFormat::Json) => &self.json,
+            (None, ErrorFormat::Html) => &self.html,
+            (None, _) => return None,
+        };
+        let vars = [
+            ("status", status.as_str()),
+            ("reason", status.canonical_reason().unwrap_or("")),
+            ("message", message),
+            ("error_code", err.code()),
+            ("request_id", ctx.request_id.as_str()),
+            ("route", ctx.route.as_deref().unwrap_or("")),
+        ];
+        let mut response = Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, page.content_type.clone())
+            .header("x-error", err.to_string())
+            .header(ERROR_CODE_HEADER, err.code())
+            .body(Body::from(page.fill(&vars)))
+            .unwrap();
+        if let Some(retry_after) = &page.retry_after {
+            response.headers_mut().insert(RETRY_AFTER, retry_after.clone());
+        }
+        Some(response)
+    }
+}
+
+/// The format `Accept` rates highest, plain text unless JSON or HTML is
+/// asked for by name; earlier entries win ties.
+fn negotiate(accept: Option<&HeaderValue>) -> ErrorFormat {
+    let Some(accept) = accept.and_then(|value| value.to_str().ok()) else {
+        return ErrorFormat::Text;
+    };
+    let mut best = (ErrorFormat::Text, 0.0);
+    for range in accept.split(',') {
+        let mut params = range.split(';').map(str::trim);
+        let media_type = params.next().unwrap_or("").to_ascii_lowercase();
+        let quality = params
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        let format = match media_type.as_str() {
+            "application/json" | "application/problem+json" => ErrorFormat::Json,
+            media_type if media_type.ends_with("+json") => ErrorFormat::Json,
+            "text/html" | "application/xhtml+xml" => ErrorFormat::Html,
+            "text/plain" => ErrorFormat::Text,
+            _ => continue,
+        };
+        if quality > best.1 {
+            best = (format, quality);
+        }
+    }
+    best.0
+}
+
+impl ErrorPage {
+    fn new(config: &ErrorPageConfig) -> Self {
+        let content_type = config.content_type.to_ascii_lowercase();
+        let escape = if content_type.contains("json") {
+            Escape::Json
+        } else if content_type.contains("html") {
+            Escape::Html
+        } else {
+            Escape::None
+        };
+        Self {
+            // Validated in Config::validate
+            content_type: HeaderValue::from_str(&config.content_type).expect("invalid error page content_type"),
+            escape,
+            body: config.body.clone(),
+            retry_after: config.retry_after_secs.map(HeaderValue::from),
+        }
+    }
+
+    /// `body` with each `{name}` in `vars` replaced; other braces stay.
+    fn fill(&self, vars: &[(&str, &str)]) -> String {
+        let mut filled = String::with_capacity(self.body.len());
+        let mut rest = self.body.as_str();
+        while let Some(open) = rest.find('{') {
+            filled.push_str(&rest[..open]);
+            rest = &rest[open..];
+            let var = rest[1..]
+                .find('}')
+                .and_then(|close| vars.iter().find(|(name, _)| *name == &rest[1..close + 1]));
+            match var {
+                Some((name, value)) => {
+                    self.escape.push(&mut filled, value);
+                    rest = &rest[name.len() + 2..];
+                }
+                None => {
+                    filled.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        filled.push_str(rest);
+        filled
+    }
+}
+
+impl Escape {
+    fn push(self, out: &mut String, value: &str) {
+        match self {
+            Escape::None => out.push_str(value),
+            Escape::Json => {
+                // The inside of a JSON string, quotes left to the template
+                let quoted = serde_json::to_string(value).unwrap_or_default();
+                out.push_str(&quoted[1..quoted.len() - 1]);
+            }
+            Escape::Html => {
+                for c in value.chars() {
+                    match c {
+                        '&' => out.push_str("&amp;"),
+                        '<' => out.push_str("&lt;"),
+                        '>' => out.push_str("&gt;"),
+                        '"' => out.push_str("&quot;"),
+                        '\'' => out.push_str("&#39;"),
+                        c => out.push(c),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_renders_templates_with_escaped_values() {
+        let global: HashMap<u16, ErrorPageConfig> = serde_yaml::from_str(
+            r#"{ 503: { content_type: application/json, body: '{"error": "{error_code}", "id": "{request_id}", "x": {y}}', retry_after_secs: 30 } }"#,
+        )
+        .unwrap();
+        let route: HashMap<u16, ErrorPageConfig> = serde_yaml::from_str(
+            "{ 504: { content_type: text/html, body: '<p>{reason} on {route}</p>' } }",
+        )
+        .unwrap();
+        let pages = ErrorPages::new(&global, &route, ErrorFormat::Text);
+        let req = hyper::Request::get("/").body(Body::empty()).unwrap();
+        let mut ctx = FilterContext::new("a\"b".to_string(), &req, None);
+        ctx.route = Some("<api>".into());
+
+        let response = pages.render(&ProxyError::NoHealthyBackends, &ctx, None).unwrap();
+        assert_eq!(response.status(), 503);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response.headers()[RETRY_AFTER], "30");
+        assert_eq!(response.headers()[ERROR_CODE_HEADER], "LB_NO_HEALTHY_BACKENDS");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, r#"{"error": "LB_NO_HEALTHY_BACKENDS", "id": "a\"b", "x": {y}}"#);
+
+        let response = pages.render(&ProxyError::Timeout, &ctx, None).unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "<p>Gateway Timeout on &lt;api&gt;</p>");
+
+        assert!(pages.render(&ProxyError::BackendError("reset".to_string()), &ctx, None).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_negotiates_built_in_pages_from_accept() {
+        let pages = ErrorPages::new(&HashMap::new(), &HashMap::new(), ErrorFormat::Negotiate);
+        let req = hyper::Request::get("/").body(Body::empty()).unwrap();
+        let ctx = FilterContext::new("abc".to_string(), &req, None);
+        let render = |accept: &'static str| {
+            let accept = HeaderValue::from_static(accept);
+            pages.render(&ProxyError::Timeout, &ctx, Some(&accept))
+        };
+
+        let response = render("application/json").unwrap();
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["status"], 504);
+        assert_eq!(problem["code"], "LB_TIMEOUT");
+        assert_eq!(problem["request_id"], "abc");
+
+        let browser = render("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8").unwrap();
+        assert_eq!(browser.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        assert!(render("text/plain, application/json;q=0.5").is_none());
+        assert!(render("*/*").is_none());
+        assert!(pages.render(&ProxyError::Timeout, &ctx, None).is_none());
+    }
+}
@@ -0,0 +1,175 @@
+// src/proxy/fairness.rs
+use crate::config::{FairnessConfig, FairnessKey};
+use crate::metrics::MetricsSink;
+use dashmap::DashMap;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use hyper::{Body, Response, StatusCode};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+/// Caps how many requests each client has upstream at once, queueing
+/// and then rejecting the rest; see `FairnessConfig`.
+pub struct Fairness {
+    /// `None` keys by client IP.
+    header: Option<HeaderName>,
+    per_client: usize,
+    queue_timeout: Duration,
+    max_queued: usize,
+    /// Only clients with requests upstream or queued have an entry.
+    clients: DashMap<String, Arc<ClientSlots>>,
+    metrics: Arc<dyn MetricsSink>,
+}
+
+struct ClientSlots {
+    semaphore: Arc<Semaphore>,
+    queued: AtomicUsize,
+}
+
+/// One of a client's slots, held until dropped.
+pub struct FairSlot<'a> {
+    fairness: &'a Fairness,
+    key: String,
+    client: Arc<ClientSlots>,
+    permit: Option<OwnedSemaphorePermit>,
+    queued: bool,
+}
+
+impl FairSlot<'_> {
+    fn leave_queue(&mut self) {
+        if std::mem::take(&mut self.queued) {
+            self.client.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for FairSlot<'_> {
+    fn drop(&mut self) {
+        self.leave_queue();
+        self.permit.take();
+        // The map's reference and ours are the last, so nothing holds or
+        // waits for this client's slots
+        self.fairness.clients.remove_if(&self.key, |_, client| {
+            Arc::ptr_eq(client, &self.client) && Arc::strong_count(client) == 2
+        });
+    }
+}
+
+impl Fairness {
+    /// Validated in Config::validate.
+    pub fn new(config: &FairnessConfig, metrics: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            header: match &config.key {
+                FairnessKey::ClientIp => None,
+                FairnessKey::Header(name) => {
+                    Some(HeaderName::from_bytes(name.as_bytes()).expect("invalid fairness key header"))
+                }
+            },
+            per_client: config.per_client(),
+            queue_timeout: Duration::from_millis(config.queue_timeout_ms),
+            max_queued: config.max_queued_per_client,
+            clients: DashMap::new(),
+            metrics,
+        }
+    }
+
+    fn key(&self, headers: &HeaderMap, client_ip: Option<IpAddr>) -> String {
+        let header = self.header.as_ref().and_then(|name| headers.get(name));
+        match (header, client_ip) {
+            (Some(value), _) => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            (None, Some(ip)) => ip.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// A slot for the client sending `headers`, waiting for one of its own
+    /// to free up when it has them all, or the 429 to answer with.
+    pub async fn acquire(
+        &self,
+        headers: &HeaderMap,
+        client_ip: Option<IpAddr>,
+    ) -> Result<FairSlot<'_>, Response<Body>> {
+        let key = self.key(headers, client_ip);
+        let client = self
+            .clients
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Arc::new(ClientSlots {
+                    semaphore: Arc::new(Semaphore::new(self.per_client)),
+                    queued: AtomicUsize::new(0),
+                })
+            })
+            .clone();
+        let mut slot = FairSlot { fairness: self, key, client, permit: None, queued: false };
+
+        if let Ok(permit) = slot.client.semaphore.clone().try_acquire_owned() {
+            slot.permit = Some(permit);
+            return Ok(slot);
+        }
+        if !self.queue_timeout.is_zero() {
+            slot.queued = true;
+            if slot.client.queued.fetch_add(1, Ordering::Relaxed) < self.max_queued {
+                self.metrics.record_fairness("queued");
+                let semaphore = slot.client.semaphore.clone();
+                if let Ok(Ok(permit)) = tokio::time::timeout(self.queue_timeout, semaphore.acquire_owned()).await {
+                    slot.permit = Some(permit);
+                }
+            }
+            slot.leave_queue();
+        }
+        if slot.permit.is_some() {
+            return Ok(slot);
+        }
+
+        debug!(client = %slot.key, "Client is over its fair share");
+        self.metrics.record_fairness("rejected");
+        let mut response = Response::new(Body::from("Too many concurrent requests"));
+        *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+        response.headers_mut().insert(RETRY_AFTER, HeaderValue::from_static("1"));
+        Err(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::NoopMetrics;
+
+    #[tokio::test]
+    async fn test_fairness_queues_then_rejects_one_client() {
+        let config: FairnessConfig = serde_yaml::from_str(
+            "{ enabled: true, key: { header: x-api-key }, slots: 10, max_share: 0.2, \
+               queue_timeout_ms: 50, max_queued_per_client: 1 }",
+        )
+        .unwrap();
+        let fairness = Fairness::new(&config, Arc::new(NoopMetrics));
+        let mut noisy = HeaderMap::new();
+        noisy.insert("x-api-key", "noisy".parse().unwrap());
+        let ip = Some("192.0.2.1".parse().unwrap());
+
+        let first = fairness.acquire(&noisy, ip).await.unwrap();
+        let _second = fairness.acquire(&noisy, ip).await.unwrap();
+        // Queued, then out of time
+        let response = fairness.acquire(&noisy, ip).await.err().unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        // Others, keyed by IP without the header, aren't held up
+        let other = fairness.acquire(&HeaderMap::new(), ip).await.unwrap();
+
+        // A queued request gets the next slot its client frees, and a
+        // second one in the queue is over max_queued_per_client
+        let (queued, over) = tokio::join!(fairness.acquire(&noisy, ip), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            let over = fairness.acquire(&noisy, ip).await.err();
+            drop(first);
+            over
+        });
+        assert!(queued.is_ok());
+        assert_eq!(over.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+
+        drop((queued, _second, other));
+        assert!(fairness.clients.is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+// src/proxy/maintenance.rs
+use super::BackendPool;
+use crate::config::MaintenanceWindowConfig;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// How often windows are checked; they open and close on the minute.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Drains the backends of each `maintenance_windows` entry while it's open
+/// and puts them back in rotation when it closes. Backends drained some
+/// other way, e.g. from the admin API, are left as they are.
+pub struct MaintenanceScheduler {
+    windows: Vec<MaintenanceWindowConfig>,
+    /// Backends drained by a window, and which one.
+    drained: HashMap<String, String>,
+}
+
+impl MaintenanceScheduler {
+    pub fn new(windows: Vec<MaintenanceWindowConfig>) -> Self {
+        Self { windows, drained: HashMap::new() }
+    }
+
+    /// Check the windows every few seconds until the process exits.
+    pub fn spawn(mut self, pool: Arc<BackendPool>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.apply(&pool, Utc::now()).await;
+            }
+        });
+    }
+
+    async fn apply(&mut self, pool: &BackendPool, now: DateTime<Utc>) {
+        let mut due: HashMap<String, &str> = HashMap::new();
+        let backends = pool.all_backends();
+        for window in &self.windows {
+            if window.schedule.last_fired(now, window.duration()).is_none() {
+                continue;
+            }
+            for backend in &backends {
+                if window.pools.contains(&backend.pool) || window.backends.contains(&backend.id) {
+                    due.entry(backend.id.clone()).or_insert(&window.name);
+                }
+            }
+        }
+
+        let closed: Vec<String> = self.drained.keys().filter(|id| !due.contains_key(*id)).cloned().collect();
+        let mut restored = false;
+        for id in closed {
+            let window = self.drained.remove(&id).unwrap_or_default();
+            let Some(backend) = pool.get_backend(&id).filter(|b| b.in_maintenance()) else {
+                continue;
+            };
+            backend.set_in_maintenance(false);
+            backend.set_draining(false);
+            restored = true;
+            info!(window = %window, backend = %id, "Maintenance window closed, backend back in rotation");
+        }
+        if restored {
+            pool.update_healthy_backends().await;
+        }
+
+        for (id, window) in due {
+            let Some(backend) = pool.get_backend(&id) else {
+                continue;
+            };
+            if backend.in_maintenance() || (backend.is_draining() && !self.drained.contains_key(&id)) {
+                continue;
+            }
+            // A reload or discovery update replaces the backend undrained
+            if self.drained.contains_key(&id) {
+                debug!(window = %window, backend = %id, "Backend replaced during maintenance, draining again");
+            } else {
+                info!(window = %window, backend = %id, "Maintenance window open, draining backend");
+            }
+            backend.set_in_maintenance(true);
+            pool.drain_backend(&id).await;
+            self.drained.insert(id, window.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackendConfig;
+
+    #[tokio::test]
+    async fn test_drains_backends_while_a_window_is_open() {
+        let backends: Vec<BackendConfig> = serde_yaml::from_str(
+            "[{ url: 'http://10.0.0.1:80', pool: batch }, { url: 'http://10.0.0.2:80' }, { url: 'http://10.0.0.3:80' }]",
+        )
+        .unwrap();
+        let pool = BackendPool::new(backends);
+        for backend in pool.all_backends() {
+            backend.update_health(true).await;
+        }
+        // Drained by an operator, so no window touches it
+        pool.drain_backend("10.0.0.3:80").await;
+        let windows = serde_yaml::from_str(
+            "[{ name: nightly, schedule: '0 2 * * *', duration_mins: 60, pools: [batch], backends: ['10.0.0.3:80'] }]",
+        )
+        .unwrap();
+        let mut scheduler = MaintenanceScheduler::new(windows);
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let draining = |id: &str| pool.get_backend(id).unwrap().is_draining();
+
+        scheduler.apply(&pool, at("2026-10-14T02:10:00Z")).await;
+        assert!(draining("10.0.0.1:80") && !draining("10.0.0.2:80"));
+        assert!(pool.get_healthy_backends().iter().all(|b| b.id == "10.0.0.2:80"));
+
+        scheduler.apply(&pool, at("2026-10-14T03:00:00Z")).await;
+        assert!(!draining("10.0.0.1:80") && draining("10.0.0.3:80"));
+        assert_eq!(pool.get_healthy_backends().len(), 2);
+    }
+}
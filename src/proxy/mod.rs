@@ -1,10 +1,28 @@
 //
 // src/proxy/mod.rs
 //
+#[allow(clippy::module_inception)]
 mod proxy;
+mod admission;
 mod backend;
+mod clients;
+mod compression;
+mod connector;
+mod dial;
+mod errors;
+mod fairness;
+mod maintenance;
 mod pool;
+mod request_id;
+pub mod throttle;
 
-pub use proxy::{Proxy, ProxyError};
-pub use backend::{Backend, HealthStatus, BackendMetrics};
-pub use pool::BackendPool;
+pub use proxy::{Proxy, ProxyError, ERROR_CODE_HEADER};
+pub(crate) use proxy::read_body;
+pub use admission::{Admission, InFlight};
+pub use fairness::{FairSlot, Fairness};
+pub use maintenance::MaintenanceScheduler;
+pub use backend::{Backend, ConnectionGuard, HealthStatus, BackendMetrics, Queued};
+pub use pool::{BackendEvent, BackendPool, PoolStats};
+pub use connector::{unix_uri, Dialed, UpstreamConnector, UNIX_SCHEME};
+pub use dial::Dialer;
+pub use errors::ErrorPages;
@@ -1,16 +1,66 @@
 //
 // src/proxy/pool.rs
 //
-use super::backend::Backend;
+use super::backend::{Backend, HealthStatus};
 use crate::config::BackendConfig;
+use arc_swap::ArcSwap;
 use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before the oldest are dropped.
+const EVENT_CAPACITY: usize = 256;
+
+/// Something this instance found out about a backend by itself.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind", content = "backend", rename_all = "snake_case")]
+pub enum BackendEvent {
+    /// Requests failed often enough to open its circuit breaker.
+    CircuitOpened(String),
+    /// A health check failed after passing, or on the first try.
+    Unhealthy(String),
+}
+
+impl BackendEvent {
+    pub fn backend_id(&self) -> &str {
+        match self {
+            BackendEvent::CircuitOpened(id) | BackendEvent::Unhealthy(id) => id,
+        }
+    }
+    
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BackendEvent::CircuitOpened(_) => "circuit_opened",
+            BackendEvent::Unhealthy(_) => "unhealthy",
+        }
+    }
+}
+
+/// The whole pool at a glance, for the admin API and metrics.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PoolStats {
+    pub total: usize,
+    /// Getting traffic: healthy, not draining and not ejected.
+    pub in_rotation: usize,
+    pub healthy: usize,
+    pub unhealthy: usize,
+    /// Not health checked yet.
+    pub unknown: usize,
+    pub draining: usize,
+    pub active_connections: usize,
+    pub total_requests: u64,
+    pub failed_requests: u64,
+    /// `failed_requests` over `total_requests`, 0 before any requests.
+    pub error_rate: f64,
+}
 
 #[derive(Clone)]
 pub struct BackendPool {
     backends: Arc<DashMap<String, Arc<Backend>>>,
-    healthy_backends: Arc<RwLock<Vec<Arc<Backend>>>>,
+    /// Replaced whole on every change, so readers never wait.
+    healthy_backends: Arc<ArcSwap<Vec<Arc<Backend>>>>,
+    events: broadcast::Sender<BackendEvent>,
 }
 
 impl BackendPool {
@@ -26,12 +76,24 @@ impl BackendPool {
         
         Self {
             backends,
-            healthy_backends: Arc::new(RwLock::new(healthy)),
+            healthy_backends: Arc::new(ArcSwap::from_pointee(healthy)),
+            events: broadcast::channel(EVENT_CAPACITY).0,
         }
     }
     
-    pub async fn get_healthy_backends(&self) -> Vec<Arc<Backend>> {
-        self.healthy_backends.read().await.clone()
+    /// Tell subscribers what happened to a backend. Nothing is kept if
+    /// nobody is listening.
+    pub fn publish(&self, event: BackendEvent) {
+        let _ = self.events.send(event);
+    }
+    
+    pub fn subscribe(&self) -> broadcast::Receiver<BackendEvent> {
+        self.events.subscribe()
+    }
+    
+    /// The backends in rotation, as of the last change to them.
+    pub fn get_healthy_backends(&self) -> Arc<Vec<Arc<Backend>>> {
+        self.healthy_backends.load_full()
     }
     
     pub fn get_backend(&self, id: &str) -> Option<Arc<Backend>> {
@@ -42,23 +104,42 @@ impl BackendPool {
         self.backends.iter().map(|entry| entry.value().clone()).collect()
     }
     
+    pub fn pool_stats(&self) -> PoolStats {
+        let mut stats = PoolStats { in_rotation: self.healthy_backends.load().len(), ..Default::default() };
+        for backend in self.backends.iter() {
+            stats.total += 1;
+            match backend.health_status() {
+                HealthStatus::Healthy => stats.healthy += 1,
+                HealthStatus::Unhealthy => stats.unhealthy += 1,
+                HealthStatus::Unknown => stats.unknown += 1,
+            }
+            stats.draining += backend.is_draining() as usize;
+            let metrics = backend.get_metrics();
+            stats.active_connections += metrics.active_connections;
+            stats.total_requests += metrics.total_requests;
+            stats.failed_requests += metrics.failed_requests;
+        }
+        if stats.total_requests > 0 {
+            stats.error_rate = stats.failed_requests as f64 / stats.total_requests as f64;
+        }
+        stats
+    }
+    
     pub async fn update_healthy_backends(&self) {
         let mut healthy = Vec::new();
         
         for backend in self.backends.iter() {
-            if backend.is_healthy().await {
+            if backend.is_healthy() && !backend.is_draining() {
                 healthy.push(backend.value().clone());
             }
         }
         
-        let mut healthy_backends = self.healthy_backends.write().await;
-        *healthy_backends = healthy;
-        
         tracing::info!(
             "Updated healthy backends: {}/{} available",
-            healthy_backends.len(),
+            healthy.len(),
             self.backends.len()
         );
+        self.healthy_backends.store(Arc::new(healthy));
     }
     
     pub async fn add_backend(&self, config: BackendConfig) {
@@ -72,11 +153,53 @@ impl BackendPool {
             tracing::info!("Added new backend: {}", id);
         }
     
+    /// Swap in a new definition of a backend (e.g. a changed weight),
+    /// keeping its health so it stays in rotation.
+    pub async fn replace_backend(&self, config: BackendConfig) {
+        let backend = Arc::new(Backend::new(&config));
+        let Some(old) = self.backends.insert(backend.id.clone(), backend.clone()) else {
+            backend.update_health(false).await;
+            tracing::info!("Added new backend: {}", backend.id);
+            return;
+        };
+        
+        backend.update_health(old.is_healthy()).await;
+        self.healthy_backends.rcu(|healthy| {
+            let mut healthy = Vec::clone(healthy);
+            for entry in healthy.iter_mut().filter(|b| b.id == backend.id) {
+                *entry = backend.clone();
+            }
+            healthy
+        });
+        tracing::info!("Updated backend: {}", backend.id);
+    }
+    
+    /// Take a backend out of rotation without forgetting it, so requests
+    /// already sent to it can finish. Returns `false` for unknown IDs.
+    pub async fn drain_backend(&self, id: &str) -> bool {
+        let Some(backend) = self.get_backend(id) else {
+            return false;
+        };
+        backend.set_draining(true);
+        self.take_out_of_rotation(id);
+        tracing::info!("Draining backend: {}", id);
+        true
+    }
+    
+    /// Mark a backend unhealthy and take it out of rotation until a health
+    /// check passes again. Returns `false` if it wasn't in rotation.
+    pub async fn eject_backend(&self, id: &str) -> bool {
+        let Some(backend) = self.take_out_of_rotation(id) else {
+            return false;
+        };
+        backend.update_health(false).await;
+        tracing::info!("Ejected backend: {}", id);
+        true
+    }
+    
     pub async fn remove_backend(&self, id: &str) -> bool {
         if let Some((_, _backend)) = self.backends.remove(id) {
-            // Remove from healthy list
-            let mut healthy = self.healthy_backends.write().await;
-            healthy.retain(|b| b.id != id);
+            self.take_out_of_rotation(id);
             
             tracing::info!("Removed backend: {}", id);
             true
@@ -84,4 +207,47 @@ impl BackendPool {
             false
         }
     }
-}
\ No newline at end of file
+    
+    /// Drop a backend from the healthy list, returning it if it was there.
+    fn take_out_of_rotation(&self, id: &str) -> Option<Arc<Backend>> {
+        let mut removed = None;
+        self.healthy_backends.rcu(|healthy| {
+            removed = healthy.iter().find(|b| b.id == id).cloned();
+            healthy.iter().filter(|b| b.id != id).cloned().collect::<Vec<_>>()
+        });
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pool_stats_add_up_backends() {
+        let configs = ["http://10.0.0.1:80", "http://10.0.0.2:80", "http://10.0.0.3:80"]
+            .iter()
+            .map(|url| serde_yaml::from_str(&format!("url: '{}'", url)).unwrap())
+            .collect();
+        let pool = BackendPool::new(configs);
+        let backends: Vec<_> = ["10.0.0.1:80", "10.0.0.2:80", "10.0.0.3:80"]
+            .iter()
+            .map(|id| pool.get_backend(id).unwrap())
+            .collect();
+        backends[0].update_health(true).await;
+        backends[1].update_health(true).await;
+        backends[2].update_health(false).await;
+        pool.drain_backend("10.0.0.2:80").await;
+        pool.update_healthy_backends().await;
+        let _connection = backends[0].acquire().unwrap();
+        backends[0].record_request(true);
+        backends[0].record_request(true);
+        backends[0].record_request(false);
+        backends[2].record_request(false);
+
+        let stats = pool.pool_stats();
+        assert_eq!((stats.total, stats.in_rotation, stats.healthy, stats.unhealthy), (3, 1, 2, 1));
+        assert_eq!((stats.unknown, stats.draining, stats.active_connections), (0, 1, 1));
+        assert_eq!((stats.total_requests, stats.failed_requests, stats.error_rate), (4, 2, 0.5));
+    }
+}
@@ -1,49 +1,73 @@
 use crate::{
+    cache::ResponseCache,
     circuit_breaker::CircuitBreakerManager,
-    config::Config,
+    config::{BackendConfig, Config, ConfigDiff, HostHeader, RouteConfig, ValidationAction},
+    discovery::drain,
     health::HealthChecker,
-    load_balancer,
-    metrics::{MetricsCollector, Timer},
-    proxy::{Backend, BackendPool},
-    retry::{RetryStrategy, RetryDecision},
+    load_balancer::{self, RegionSpillover},
+    logging::{RequestTap, TapEvent},
+    metrics::{LatencyStats, MetricsSink, Timer, TrafficStats},
+    middleware::{Filter, FilterChain, FilterContext},
+    proxy::{
+        admission::Admission, compression, fairness::Fairness, request_id::resolve_request_id, throttle::{self, DownloadLimiter}, unix_uri, Backend, BackendEvent, BackendPool,
+        clients::{PoolSlot, UpstreamClients}, Dialed,
+    },
+    retry::RetryDecision,
+    routing::{Maintenance, Normalizer, RequestClassifier, Route, Router},
+    server::PeerAddr,
 };
 use anyhow::Result;
+use arc_swap::{ArcSwap, ArcSwapOption};
+use futures::StreamExt;
 use hyper::{
-    client::HttpConnector, Body, Client, Request, Response, StatusCode, Uri,
+    body::{Bytes, HttpBody},
+    header::{HeaderValue, ACCEPT, ALLOW, CONTENT_LENGTH, HOST, LINK},
+    Body, Method, Request, Response, StatusCode, Uri, Version,
 };
-use std::sync::Arc;
-use std::time::Duration;
-use tracing::{debug, error, info, warn};
-use uuid::Uuid;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 pub struct Proxy {
-    config: Config,
+    /// Replaced by `reload`.
+    config: ArcSwap<Config>,
+    /// What the last `reload` changed.
+    last_reload: ArcSwapOption<ConfigDiff>,
+    /// Set by `set_dynamic_routes`, checked before the configured routes.
+    dynamic_routes: Mutex<Vec<RouteConfig>>,
     pool: Arc<BackendPool>,
     load_balancer: Arc<dyn load_balancer::LoadBalancer>,
+    regions: Option<RegionSpillover>,
     health_checker: Arc<HealthChecker>,
     circuit_breakers: Arc<CircuitBreakerManager>,
-    retry_strategy: RetryStrategy,
-    client: Client<HttpConnector>,
-    metrics: Arc<MetricsCollector>,
+    clients: UpstreamClients,
+    metrics: Arc<dyn MetricsSink>,
+    traffic_stats: Arc<TrafficStats>,
+    latency_stats: Arc<LatencyStats>,
+    filters: FilterChain,
+    cache: ResponseCache,
+    normalizer: Normalizer,
+    router: ArcSwap<Router>,
+    maintenance: Arc<Maintenance>,
+    admission: Option<Arc<Admission>>,
+    fairness: Option<Fairness>,
+    download_limit: Option<DownloadLimiter>,
+    /// Finished requests for `/admin/tap`.
+    tap: RequestTap,
 }
 
 impl Proxy {
     pub fn new(
         config: Config,
         pool: Arc<BackendPool>,
-        metrics: Arc<MetricsCollector>,
+        metrics: Arc<dyn MetricsSink>,
     ) -> Self {
-        // Create HTTP client with proper settings
-        let mut http = HttpConnector::new();
-        http.set_connect_timeout(Some(Duration::from_secs(5)));
-        http.set_keepalive(Some(Duration::from_secs(60)));
-
-        let client = Client::builder()
-            .pool_idle_timeout(Duration::from_secs(90))
-            .pool_max_idle_per_host(50)
-            .build::<_, Body>(http);
+        let clients = UpstreamClients::new(&config.upstream, metrics.clone());
         
-        let load_balancer = load_balancer::create_load_balancer(config.load_balancer.algorithm);
+        let load_balancer = load_balancer::create_load_balancer(&config.load_balancer);
+        let regions = config.regions.as_ref().map(RegionSpillover::new);
         
         // Pass metrics to HealthChecker
         let health_checker = Arc::new(HealthChecker::new(
@@ -56,22 +80,222 @@ impl Proxy {
             config.circuit_breaker.clone(),
         ));
         
-        let retry_strategy = RetryStrategy::new(config.retry.clone());
+        let traffic_stats = Arc::new(TrafficStats::new(config.admin.traffic_stats_max_keys));
+        let latency_stats = Arc::new(LatencyStats::new(
+            config.admin.latency_window(),
+            config.admin.latency_stats_max_keys,
+        ));
+        
+        let filters = FilterChain::from_config(
+            &config,
+            metrics.clone(),
+            traffic_stats.clone(),
+            latency_stats.clone(),
+        );
+        debug!(filters = ?filters.names(), "Built filter chain");
+        let cache = ResponseCache::new(&config.cache, metrics.clone());
+        let normalizer = Normalizer::new(&config.normalize);
+        let router = ArcSwap::from_pointee(Router::new(&config));
+        let maintenance = Arc::new(Maintenance::new(&config.maintenance));
+        let admission = config
+            .admission
+            .enabled
+            .then(|| Arc::new(Admission::new(&config.admission, metrics.clone())));
+        let fairness = config
+            .fairness
+            .enabled
+            .then(|| Fairness::new(&config.fairness, metrics.clone()));
+        let download_limit = config.download_limit.as_ref().map(DownloadLimiter::new);
         
         // Update metrics with initial backend count
-        let backends = pool.all_backends();
-        metrics.update_backend_counts(0, backends.len());
+        metrics.update_pool_stats(&pool.pool_stats());
+        metrics.update_config(&config.content_hash());
         
         Self {
-            config,
+            config: ArcSwap::from_pointee(config),
+            last_reload: ArcSwapOption::empty(),
+            dynamic_routes: Mutex::new(Vec::new()),
             pool,
             load_balancer,
+            regions,
             health_checker,
             circuit_breakers,
-            retry_strategy,
-            client,
+            clients,
             metrics,
+            traffic_stats,
+            latency_stats,
+            filters,
+            cache,
+            normalizer,
+            router,
+            maintenance,
+            admission,
+            fairness,
+            download_limit,
+            tap: RequestTap::default(),
+        }
+    }
+    
+    /// Compute request attributes for routes that match on `attributes`.
+    pub fn with_classifier(self, classifier: Arc<dyn RequestClassifier>) -> Self {
+        let mut router = self.router.load().rebuild(&self.config.load());
+        router.add_classifier(classifier);
+        self.router.store(Arc::new(router));
+        self
+    }
+    
+    /// Run `filter` after the configured filters.
+    pub fn with_filter(mut self, filter: Arc<dyn Filter>) -> Self {
+        self.filters.push(filter);
+        self
+    }
+    
+    /// Check `routes` before the configured ones, replacing those set by an
+    /// earlier call. If the resulting config doesn't validate, the current
+    /// routes stay.
+    pub fn set_dynamic_routes(&self, routes: Vec<RouteConfig>) -> Result<()> {
+        let mut dynamic_routes = self.dynamic_routes.lock().unwrap();
+        self.apply_routes(&self.config.load(), &routes)?;
+        *dynamic_routes = routes;
+        Ok(())
+    }
+    
+    fn apply_routes(&self, config: &Config, dynamic_routes: &[RouteConfig]) -> Result<()> {
+        let mut config = config.clone();
+        config.routes = dynamic_routes.iter().chain(config.routes.iter()).cloned().collect();
+        config.validate()?;
+        let router = self.router.load().rebuild(&config);
+        self.router.store(Arc::new(router));
+        Ok(())
+    }
+    
+    /// Switch to `config`'s backends and routes. New backends are added and
+    /// changed ones replaced at once; removed ones stop getting traffic and
+    /// are dropped once their open requests finish or the drain timeout
+    /// passes. Other settings only change on restart. Each attempt is
+    /// counted in `lb_config_reload_total`.
+    pub async fn reload(self: &Arc<Self>, config: Config) -> Result<()> {
+        let hash = config.content_hash();
+        let reloaded = self.apply_reload(config).await;
+        match reloaded {
+            Ok(()) => {
+                self.metrics.record_config_reload("success");
+                self.metrics.update_config(&hash);
+            }
+            Err(_) => self.metrics.record_config_reload("failure"),
+        }
+        reloaded
+    }
+    
+    async fn apply_reload(self: &Arc<Self>, config: Config) -> Result<()> {
+        config.validate()?;
+        let current = self.config.load_full();
+        {
+            let dynamic_routes = self.dynamic_routes.lock().unwrap();
+            self.apply_routes(&config, &dynamic_routes)?;
+        }
+        
+        let wanted: HashMap<String, &BackendConfig> = config
+            .backends
+            .iter()
+            .map(|backend| (Backend::id_for(&backend.url), backend))
+            .collect();
+        for (id, backend) in &wanted {
+            match current.backends.iter().find(|old| Backend::id_for(&old.url) == *id) {
+                Some(old) if old == *backend => {}
+                Some(_) => {
+                    info!(backend = %id, "Backend changed in reloaded config");
+                    self.pool.replace_backend((*backend).clone()).await;
+                }
+                None => {
+                    info!(backend = %id, "Backend added in reloaded config");
+                    self.pool.add_backend((*backend).clone()).await;
+                }
+            }
+        }
+        for old in &current.backends {
+            let id = Backend::id_for(&old.url);
+            if wanted.contains_key(&id) {
+                continue;
+            }
+            if let Some(backend) = self.pool.get_backend(&id) {
+                if self.pool.drain_backend(&id).await {
+                    info!(backend = %id, "Backend removed in reloaded config, draining");
+                    tokio::spawn(drain(self.clone(), backend, config.runtime.drain_timeout()));
+                }
+            }
+        }
+        
+        if restart_only_changes(&current, &config) {
+            warn!("Reloaded backends and routes; other changed settings apply after a restart");
         }
+        self.last_reload.store(Some(Arc::new(ConfigDiff::between(&current, &config))));
+        self.config.store(Arc::new(config));
+        Ok(())
+    }
+    
+    /// The configuration in effect, as of the last `reload`.
+    pub fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+    
+    /// What the last `reload` changed, if there's been one.
+    pub fn last_reload(&self) -> Option<Arc<ConfigDiff>> {
+        self.last_reload.load_full()
+    }
+    
+    pub fn pool(&self) -> Arc<BackendPool> {
+        self.pool.clone()
+    }
+    
+    pub fn load_balancer(&self) -> Arc<dyn load_balancer::LoadBalancer> {
+        self.load_balancer.clone()
+    }
+    
+    pub fn circuit_breakers(&self) -> Arc<CircuitBreakerManager> {
+        self.circuit_breakers.clone()
+    }
+    
+    pub fn metrics(&self) -> Arc<dyn MetricsSink> {
+        self.metrics.clone()
+    }
+    
+    pub fn traffic_stats(&self) -> Arc<TrafficStats> {
+        self.traffic_stats.clone()
+    }
+    
+    pub fn latency_stats(&self) -> Arc<LatencyStats> {
+        self.latency_stats.clone()
+    }
+    
+    pub fn router(&self) -> Arc<Router> {
+        self.router.load_full()
+    }
+    
+    pub fn maintenance(&self) -> Arc<Maintenance> {
+        self.maintenance.clone()
+    }
+    
+    /// Remove a backend from rotation and discard all per-backend state
+    /// (circuit breaker, metric series, latency stats).
+    pub async fn remove_backend(&self, id: &str) -> bool {
+        if !self.pool.remove_backend(id).await {
+            return false;
+        }
+        
+        self.circuit_breakers.remove(id);
+        self.metrics.remove_backend(id);
+        self.latency_stats.remove_backend(id);
+        self.metrics.update_pool_stats(&self.pool.pool_stats());
+        true
+    }
+    
+    pub fn tap(&self) -> &RequestTap {
+        &self.tap
+    }
+
+    pub fn health_checker(&self) -> Arc<HealthChecker> {
+        self.health_checker.clone()
     }
     
     pub fn start_health_checker(&self) {
@@ -81,155 +305,352 @@ impl Proxy {
         });
     }
     
-    pub async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, ProxyError> {
-        let request_id = Uuid::new_v4();
-        let timer = Timer::new();
-        
-        // Record request size
-        let method = req.method().clone();
-        if let Some(content_length) = req.headers().get("content-length") {
-            if let Ok(size) = content_length.to_str().unwrap_or("0").parse::<f64>() {
-                self.metrics.request_size_bytes
-                    .with_label_values(&[method.as_str()])
-                    .observe(size);
-            }
+    /// Start measuring load for admission control, if it's enabled.
+    pub fn start_admission_control(&self) {
+        if let Some(admission) = &self.admission {
+            admission.start();
         }
+    }
+    
+    pub async fn handle(self: &Arc<Self>, req: Request<Body>) -> Result<Response<Body>, ProxyError> {
+        let request_id = resolve_request_id(req.headers(), &self.config.load().request_id);
+        let span = info_span!("request", request_id = %request_id);
+        self.handle_in_span(req, request_id)
+            .instrument(span)
+            .await
+    }
+    
+    async fn handle_in_span(
+        self: &Arc<Self>,
+        mut req: Request<Body>,
+        request_id: String,
+    ) -> Result<Response<Body>, ProxyError> {
+        // Before anything looks at the path, filters and routing included
+        self.normalizer.normalize(&mut req);
         
-        // Extract client address for IP hash algorithm
+        // Extract client address for IP hash algorithm, preferring the
+        // first X-Forwarded-For hop over the socket peer
         let client_addr = req
             .headers()
             .get("x-forwarded-for")
             .and_then(|v| v.to_str().ok())
             .and_then(|s| s.split(',').next())
-            .and_then(|s| s.trim().parse().ok());
+            .and_then(|s| s.trim().parse::<IpAddr>().ok())
+            .map(|ip| SocketAddr::new(ip, 0))
+            .or_else(|| req.extensions().get::<PeerAddr>().map(|peer| peer.0));
         
-        let uri_path = req.uri().path().to_string();
-        
-        info!(
-            request_id = %request_id,
-            method = %method,
-            path = %uri_path,
+        let mut ctx = FilterContext::new(request_id, &req, client_addr);
+        // "Request completed" repeats these, sampled as `logging.sampling` says
+        debug!(
+            request_id = %ctx.request_id,
+            method = %ctx.method,
+            path = %ctx.path,
             "Handling request"
         );
         
-        self.metrics.increment_active_connections();
-        
-        let result = self.handle_with_retry(req, client_addr, &request_id).await;
-        
-        self.metrics.decrement_active_connections();
+        let accept = req.headers().get(ACCEPT).cloned();
+        let (entered, response) = self.filters.on_request(&mut req, &mut ctx).await;
+        let mut result = match response {
+            Some(response) => Ok(response),
+            None => self.route_request(req, &mut ctx).await,
+        };
+        match &mut result {
+            Ok(response) => self.filters.on_response(entered, response, &ctx).await,
+            Err(e) => self.filters.on_error(entered, e, &ctx).await,
+        }
+        let result = result.or_else(|e| {
+            let router = self.router.load();
+            match router.error_pages(ctx.route.as_deref()).render(&e, &ctx, accept.as_ref()) {
+                Some(response) => {
+                    error!(%e, "proxy error");
+                    Ok(response)
+                }
+                None => Err(e),
+            }
+        });
+        self.tap.publish(|| TapEvent::new(&ctx, &result));
+        result
+    }
+    
+    async fn route_request(
+        self: &Arc<Self>,
+        mut req: Request<Body>,
+        ctx: &mut FilterContext,
+    ) -> Result<Response<Body>, ProxyError> {
+        let router = self.router.load_full();
+        let route = router.route(&req, ctx.client_addr.map(|addr| addr.ip()));
+        ctx.route = Some(route.shared_name());
+        if let Some(allow) = route.method_not_allowed(req.method()) {
+            debug!(route = route.name(), method = %req.method(), "Method not allowed on route");
+            ctx.answered_locally = true;
+            let page = route.error_pages().render(&ProxyError::MethodNotAllowed, ctx, req.headers().get(ACCEPT));
+            let mut response = page.unwrap_or_else(|| ProxyError::MethodNotAllowed.into());
+            response.headers_mut().insert(ALLOW, allow);
+            return Ok(response);
+        }
+        if self.maintenance.applies(route, peer_ip(&req)) {
+            debug!(route = route.name(), "Answering with the maintenance response");
+            ctx.answered_locally = true;
+            return Ok(self.maintenance.response());
+        }
+        let _in_flight = match &self.admission {
+            Some(admission) => match admission.admit(req.headers(), route) {
+                Some(in_flight) => Some(in_flight),
+                None => {
+                    // Answered here so the client gets the 503 rather than a
+                    // dropped connection
+                    ctx.answered_locally = true;
+                    let page = route.error_pages().render(&ProxyError::Overloaded, ctx, req.headers().get(ACCEPT));
+                    return Ok(page.unwrap_or_else(|| ProxyError::Overloaded.into()));
+                }
+            },
+            None => None,
+        };
+        let gzip = route.policy().compression
+            && ctx.method != Method::HEAD
+            && compression::accepts_gzip(req.headers());
         
-        // Record metrics including response size
-        match &result {
-            Ok(response) => {
-                let status = response.status().as_u16();
-                let backend_id = response
-                    .headers()
-                    .get("x-backend-id")
-                    .and_then(|v| v.to_str().ok())
-                    .unwrap_or("unknown");
-                
-                // Record response size
-                if let Some(content_length) = response.headers().get("content-length") {
-                    if let Ok(size) = content_length.to_str().unwrap_or("0").parse::<f64>() {
-                        self.metrics.response_size_bytes
-                            .with_label_values(&[method.as_str(), &status.to_string()])
-                            .observe(size);
+        // The route's own filters run inside the global ones
+        let (entered, response) = route.filters().on_request(&mut req, ctx).await;
+        let mut local_response = match response {
+            Some(response) => Some(response),
+            None => route.local_response(&req).await,
+        };
+        // Only requests on their way upstream take one of their client's slots
+        let _fair_slot = match &self.fairness {
+            Some(fairness) if local_response.is_none() => {
+                let queued = Timer::new();
+                let acquired = fairness.acquire(req.headers(), peer_ip(&req)).await;
+                ctx.timings.add_queue(queued.elapsed());
+                match acquired {
+                    Ok(slot) => Some(slot),
+                    Err(rejected) => {
+                        local_response = Some(rejected);
+                        None
                     }
                 }
-                
-                self.metrics.record_request(
-                    method.as_str(),
-                    status,
-                    backend_id,
-                    timer.elapsed(),
-                );
-                
-                info!(
-                    request_id = %request_id,
-                    status = status,
-                    backend = backend_id,
-                    duration_ms = timer.elapsed().as_millis(),
-                    "Request completed"
-                );
             }
-            Err(_e) => {
-                self.metrics.record_request(
-                    method.as_str(),
-                    503,
-                    "none",
-                    timer.elapsed(),
-                );
-                
-                error!(
-                    request_id = %request_id,
-                    error = %_e,
-                    duration_ms = timer.elapsed().as_millis(),
-                    "Request failed"
-                );
+            _ => None,
+        };
+        let download_bucket = self.download_limit.as_ref().map(|limiter| {
+            limiter.bucket(req.headers(), req.extensions().get::<PeerAddr>().map(|peer| peer.0))
+        });
+        let mut result = match local_response {
+            Some(response) => {
+                ctx.answered_locally = true;
+                Ok(response)
+            }
+            None if route.policy().cache => {
+                let ctx_ref = &*ctx;
+                let served = self
+                    .cache
+                    .serve(
+                        req,
+                        |req| self.handle_with_retry(req, route, ctx_ref),
+                        |req| self.revalidate_in_background(req, router.clone(), ctx_ref),
+                    )
+                    .await;
+                served.map(|(response, status)| {
+                    ctx.answered_locally = status.is_cached();
+                    response
+                })
             }
+            None => self.handle_with_retry(req, route, ctx).await,
+        };
+        match &mut result {
+            Ok(response) => {
+                for link in route.preload_links() {
+                    response.headers_mut().append(LINK, link.clone());
+                }
+                route.filters().on_response(entered, response, ctx).await
+            }
+            Err(e) => route.filters().on_error(entered, e, ctx).await,
+        }
+        if gzip {
+            result = result.map(compression::gzip);
+        }
+        if let Some((tier, Some(bucket))) = download_bucket {
+            let metrics = self.metrics.clone();
+            result = result.map(|response| {
+                response.map(|body| {
+                    throttle::throttle(body, bucket, move |wait| metrics.record_client_throttle(&tier, wait))
+                })
+            });
         }
-        
         result
     }
     
+    /// Refresh a stale cache entry without holding up the request that
+    /// found it.
+    fn revalidate_in_background(self: &Arc<Self>, req: Request<Body>, router: Arc<Router>, ctx: &FilterContext) {
+        let proxy = self.clone();
+        let (request_id, client_addr) = (ctx.request_id.clone(), ctx.client_addr);
+        let span = info_span!("revalidate", request_id = %request_id);
+        tokio::spawn(
+            async move {
+                let route = router.route(&req, client_addr.map(|addr| addr.ip()));
+                let ctx = FilterContext::new(request_id, &req, client_addr);
+                proxy
+                    .cache
+                    .revalidate(req, |req| proxy.handle_with_retry(req, route, &ctx))
+                    .await;
+            }
+            .instrument(span),
+        );
+    }
+    
     async fn handle_with_retry(
         &self,
         req: Request<Body>,
-        client_addr: Option<std::net::SocketAddr>,
-        request_id: &Uuid,
+        route: &Route,
+        ctx: &FilterContext,
     ) -> Result<Response<Body>, ProxyError> {
+        // Pick the pool once so every retry goes to the same one
+        let assignment = route.assign(req.headers(), ctx.client_addr.map(|addr| addr.ip()));
+        let pool = assignment.pool;
+        self.metrics.record_route(route.name(), pool);
+        
+        let mut result = self
+            .send_with_policy(req, route, pool, ctx)
+            .await;
+        if let Some((experiment, variant)) = assignment.experiment {
+            self.metrics.record_experiment(experiment.name(), &variant.name);
+            if let Ok(response) = &mut result {
+                response
+                    .headers_mut()
+                    .insert(experiment.response_header().clone(), variant.header_value.clone());
+            }
+        }
+        result
+    }
+    
+    async fn send_with_policy(
+        &self,
+        req: Request<Body>,
+        route: &Route,
+        pool: &str,
+        ctx: &FilterContext,
+    ) -> Result<Response<Body>, ProxyError> {
+        let policy = route.policy();
+        
+        if let Some(limit) = policy.max_request_body_bytes {
+            let content_length = req
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok());
+            if content_length.is_some_and(|len| len > limit) {
+                return Err(ProxyError::PayloadTooLarge(limit));
+            }
+        }
+        
+        // Unbuffered bodies can only be sent once
+        if !policy.buffer_request_body {
+            let req = match policy.max_request_body_bytes {
+                Some(limit) => req.map(|body| limit_body(body, limit)),
+                None => req,
+            };
+            return self.proxy_request(req, route, pool, ctx).await;
+        }
+        
         let (parts, body) = req.into_parts();
-        let body_bytes = hyper::body::to_bytes(body).await
-            .map_err(|e| ProxyError::RequestError(e.to_string()))?;
+        let body_bytes = read_body(body, policy.max_request_body_bytes).await?;
+        
+        // The first attempt goes out as it came in; only retries need a copy
+        let template = (policy.retry.max_attempts() > 1)
+            .then(|| (parts.method.clone(), parts.uri.clone(), parts.headers.clone()));
+        let first = std::sync::Mutex::new(Some(Request::from_parts(parts, Body::from(body_bytes.clone()))));
         
-        self.retry_strategy
+        policy.retry
             .execute_with_decision(
                 || async {
-                    // Rebuild request for each retry
-                    let mut req_builder = Request::builder()
-                        .method(parts.method.clone())
-                        .uri(parts.uri.clone());
+                    let first = first.lock().unwrap().take();
+                    let req = match (first, &template) {
+                        (Some(req), _) => req,
+                        (None, Some((method, uri, headers))) => {
+                            let mut req = Request::new(Body::from(body_bytes.clone()));
+                            *req.method_mut() = method.clone();
+                            *req.uri_mut() = uri.clone();
+                            *req.headers_mut() = headers.clone();
+                            req
+                        }
+                        (None, None) => unreachable!("retried with max_attempts of 1"),
+                    };
                     
-                    for (key, value) in &parts.headers {
-                        req_builder = req_builder.header(key, value);
+                    let response = self.proxy_request(req, route, pool, ctx).await?;
+                    // Another backend may well answer, so it counts as a failed attempt
+                    if response.status() == StatusCode::SERVICE_UNAVAILABLE {
+                        return Err(ProxyError::Unavailable(Box::new(response)));
                     }
-                    
-                    let req = req_builder
-                        .body(Body::from(body_bytes.clone()))
-                        .map_err(|e| ProxyError::RequestError(e.to_string()))?;
-                    
-                    self.proxy_request(req, client_addr, request_id).await
+                    Ok(response)
                 },
                 |error| {
                     match error {
                         ProxyError::NoHealthyBackends => RetryDecision::Retry,
                         ProxyError::BackendError(_) => RetryDecision::Retry,
                         ProxyError::Timeout => RetryDecision::Retry,
+                        ProxyError::Unavailable(_) => RetryDecision::Retry,
+                        ProxyError::InvalidResponse(_) => RetryDecision::Retry,
                         _ => RetryDecision::NoRetry,
                     }
                 },
             )
             .await
+            .or_else(|error| match error {
+                ProxyError::Unavailable(response) => Ok(*response),
+                error => Err(error),
+            })
     }
     
     async fn proxy_request(
         &self,
         req: Request<Body>,
-        client_addr: Option<std::net::SocketAddr>,
-        request_id: &Uuid,
+        route: &Route,
+        pool: &str,
+        ctx: &FilterContext,
     ) -> Result<Response<Body>, ProxyError> {
-        // Get healthy backends
-        let healthy_backends = self.pool.get_healthy_backends().await;
+        let request_id = ctx.request_id.as_str();
         
+        // Get the pool's healthy backends; tcp:// ones belong to the TCP listeners
+        let healthy = self.pool.get_healthy_backends();
+        let in_pool = |pool: &str| -> Vec<_> {
+            healthy.iter().filter(|b| b.pool == pool && !b.is_tcp()).cloned().collect()
+        };
+        let mut healthy_backends = in_pool(pool);
+        if let Some(regions) = &self.regions {
+            let local_total = self
+                .pool
+                .all_backends()
+                .iter()
+                .filter(|b| b.pool == pool && !b.is_tcp() && regions.is_local(b))
+                .count();
+            let budget_left = route.policy().latency_budget.map(|budget| budget.saturating_sub(ctx.timer.elapsed()));
+            let (backends, spilled) = regions.select(local_total, healthy_backends, budget_left);
+            if let Some(region) = spilled {
+                debug!(request_id = %request_id, pool = %pool, region = %region, "Spilling over to remote region");
+                self.metrics.record_region_spillover(route.name(), region);
+            }
+            healthy_backends = backends;
+        }
+        
+        if healthy_backends.is_empty() {
+            if let Some(fallback) = route.fallback_pool() {
+                healthy_backends = in_pool(fallback);
+                if !healthy_backends.is_empty() {
+                    debug!(request_id = %request_id, pool = %pool, fallback = %fallback, "Using fallback pool");
+                    self.metrics.record_route_fallback(route.name(), fallback);
+                }
+            }
+        }
         if healthy_backends.is_empty() {
-            warn!("No healthy backends available");
+            warn!(pool = %pool, "No healthy backends available");
             return Err(ProxyError::NoHealthyBackends);
         }
         
         // Select backend using load balancer
         let backend = self
             .load_balancer
-            .select_backend(&healthy_backends, client_addr)
+            .select_backend(&healthy_backends, ctx.client_addr)
             .await
             .ok_or(ProxyError::NoHealthyBackends)?;
         
@@ -251,15 +672,39 @@ impl Proxy {
             return Err(ProxyError::CircuitBreakerOpen(backend.id.clone()));
         }
         
-        // Check connection limit
-        if !backend.increment_connections() {
+        // Wait for room in the backend's pool
+        let waited = Timer::new();
+        let slot = self.clients.get(&backend.pool).acquire(&backend).await;
+        ctx.timings.add_queue(waited.elapsed());
+        let slot = slot.inspect_err(|_| {
+            warn!(
+                request_id = %request_id,
+                backend = %backend.id,
+                pool = %backend.pool,
+                "Upstream connection pool exhausted"
+            );
+        })?;
+        
+        // Check connection limit, queueing for one if the backend allows
+        let connection = match backend.acquire() {
+            Some(connection) => Some(connection),
+            None => match backend.queue_for_connection().await {
+                Some((queued, connection)) => {
+                    ctx.timings.add_queue(queued.waited);
+                    self.metrics.record_backend_queue(&backend.id, queued.ahead, queued.waited, queued.admitted);
+                    connection
+                }
+                None => None,
+            },
+        };
+        let Some(connection) = connection else {
             warn!(
                 request_id = %request_id,
                 backend = %backend.id,
                 "Backend connection limit reached"
             );
             return Err(ProxyError::ConnectionLimitReached(backend.id.clone()));
-        }
+        };
         
         // Update metrics
         self.metrics.update_backend_connections(
@@ -268,10 +713,28 @@ impl Proxy {
         );
         
         // Forward request
-        let result = self.forward_request(req, &backend, request_id).await;
+        let mut result = self.forward_request(req, route, &backend, slot, ctx).await;
+        let mut penalized = false;
+        if let (Ok(response), Some(validator)) = (&result, route.validator()) {
+            if let Err(violation) = validator.check(response) {
+                warn!(
+                    request_id = %request_id,
+                    backend = %backend.id,
+                    route = route.name(),
+                    violation = %violation,
+                    "Backend response failed validation"
+                );
+                self.metrics.record_response_violation(route.name(), &backend.id);
+                match validator.action {
+                    ValidationAction::Log => {}
+                    ValidationAction::Penalize => penalized = true,
+                    ValidationAction::Reject => result = Err(ProxyError::InvalidResponse(violation)),
+                }
+            }
+        }
         
-        // Decrement connections
-        backend.decrement_connections();
+        // Give the connection back
+        drop(connection);
         self.metrics.update_backend_connections(
             &backend.id,
             backend.active_connections() as i64,
@@ -279,12 +742,14 @@ impl Proxy {
         
         // Record circuit breaker result
         match &result {
-            Ok(_) => {
+            Ok(_) if !penalized => {
                 circuit_breaker.record_success().await;
                 backend.record_request(true);
             }
-            Err(_) => {
-                circuit_breaker.record_failure().await;
+            _ => {
+                if circuit_breaker.record_failure().await {
+                    self.pool.publish(BackendEvent::CircuitOpened(backend.id.clone()));
+                }
                 backend.record_request(false);
             }
         }
@@ -301,10 +766,14 @@ impl Proxy {
     async fn forward_request(
         &self,
         mut req: Request<Body>,
+        route: &Route,
         backend: &Backend,
-        request_id: &Uuid,
+        slot: PoolSlot,
+        ctx: &FilterContext,
     ) -> Result<Response<Body>, ProxyError> {
+        let request_id = ctx.request_id.as_str();
         let timer = Timer::new();
+        let sent = Instant::now();
         
         // Get the path and query from the original request
         let path_and_query = req.uri()
@@ -312,20 +781,55 @@ impl Proxy {
             .map(|pq| pq.as_str())
             .unwrap_or("/");
         
-        // Parse backend URL and replace only the path and query
-        let backend_uri = backend.url.as_str()
-            .parse::<Uri>()
-            .map_err(|e| ProxyError::InvalidUri(format!("Invalid backend URL: {}", e)))?;
+        let (new_uri, backend_host) = match backend.unix_socket_path() {
+            Some(socket_path) => {
+                let uri = unix_uri(socket_path, path_and_query)
+                    .map_err(|e| ProxyError::InvalidUri(format!("Failed to build URI: {}", e)))?;
+                (uri, HeaderValue::from_static("localhost"))
+            }
+            None => {
+                // Parse backend URL and replace only the path and query
+                let backend_uri = backend.url.as_str()
+                    .parse::<Uri>()
+                    .map_err(|e| ProxyError::InvalidUri(format!("Invalid backend URL: {}", e)))?;
+                let authority = backend_uri.authority().unwrap().clone();
+                let host = HeaderValue::from_str(authority.as_str())
+                    .map_err(|e| ProxyError::InvalidUri(format!("Invalid backend URL: {}", e)))?;
+                
+                // Build new URI with backend's scheme/authority but request's path/query
+                let uri = Uri::builder()
+                    .scheme(backend_uri.scheme().unwrap().clone())
+                    .authority(authority)
+                    .path_and_query(path_and_query)
+                    .build()
+                    .map_err(|e| ProxyError::InvalidUri(format!("Failed to build URI: {}", e)))?;
+                (uri, host)
+            }
+        };
         
-        // Build new URI with backend's scheme/authority but request's path/query
-        let new_uri = Uri::builder()
-            .scheme(backend_uri.scheme().unwrap().clone())
-            .authority(backend_uri.authority().unwrap().clone())
-            .path_and_query(path_and_query)
-            .build()
-            .map_err(|e| ProxyError::InvalidUri(format!("Failed to build URI: {}", e)))?;
+        let host_header = backend.host_header.as_ref().or(route.host_header());
+        let host = match host_header {
+            None | Some(HostHeader::Preserve) => {
+                // HTTP/2 clients send `:authority` instead of Host; without
+                // this the client would fill in the backend's authority
+                match (req.headers().contains_key(HOST), req.uri().authority()) {
+                    (false, Some(authority)) => HeaderValue::from_str(authority.as_str()).ok(),
+                    _ => None,
+                }
+            }
+            Some(HostHeader::Backend) => Some(backend_host),
+            Some(HostHeader::Set(value)) => Some(
+                HeaderValue::from_str(value)
+                    .map_err(|e| ProxyError::RequestError(format!("Invalid host_header: {}", e)))?,
+            ),
+        };
+        if let Some(host) = host {
+            req.headers_mut().insert(HOST, host);
+        }
         
         *req.uri_mut() = new_uri;
+        // Upstream connections are HTTP/1.1, whatever the client spoke
+        *req.version_mut() = Version::HTTP_11;
         
         // Add proxy headers
         let real_ip = req
@@ -334,11 +838,9 @@ impl Proxy {
             .cloned()
             .unwrap_or_else(|| "unknown".parse().unwrap());
         req.headers_mut().insert("x-forwarded-for", real_ip);
-
-        req.headers_mut().insert(
-            "x-request-id",
-            request_id.to_string().parse().unwrap(),
-        );
+        
+        self.filters.on_backend_selected(&mut req, backend, ctx).await;
+        route.filters().on_backend_selected(&mut req, backend, ctx).await;
         
         // Forward request
         debug!(
@@ -348,19 +850,56 @@ impl Proxy {
             "Forwarding request"
         );
         
-        match self.client.request(req).await {
+        let backend_error = |e: hyper::Error| ProxyError::BackendError(e.to_string());
+        let client = slot.client(backend);
+        // Whatever is left of the budget caps this attempt's timeout
+        let policy = route.policy();
+        let budget_left = policy.latency_budget.map(|budget| budget.saturating_sub(ctx.timer.elapsed()));
+        let over_budget = budget_left.is_some_and(|left| policy.upstream_timeout.is_none_or(|timeout| left <= timeout));
+        let response = match budget_left.into_iter().chain(policy.upstream_timeout).min() {
+            Some(timeout) => tokio::time::timeout(timeout, client.request(req))
+                .await
+                .map_err(|_| match over_budget {
+                    true => ProxyError::LatencyBudgetExceeded,
+                    false => ProxyError::Timeout,
+                })
+                .and_then(|response| response.map_err(backend_error)),
+            None => client.request(req).await.map_err(backend_error),
+        };
+        
+        ctx.timings.record_attempt(response.is_ok().then(|| timer.elapsed()));
+        if let Ok(response) = &response {
+            // Only a connection this attempt opened counts towards its time
+            if let Some(dialed) = response.extensions().get::<Dialed>().filter(|dialed| dialed.at >= sent) {
+                ctx.timings.add_connect(dialed.took);
+            }
+        }
+        
+        match response {
             Ok(mut response) => {
                 // Add backend identifier to response
-                response.headers_mut().insert(
-                    "x-backend-id",
-                    backend.id.parse().unwrap(),
-                );
+                if let Some(id) = backend.id_header() {
+                    response.headers_mut().insert("x-backend-id", id.clone());
+                }
+                if let Some(bucket) = backend.bandwidth() {
+                    let (metrics, id) = (self.metrics.clone(), backend.id.clone());
+                    let body = std::mem::take(response.body_mut());
+                    *response.body_mut() = throttle::throttle(body, bucket.clone(), move |wait| {
+                        metrics.record_backend_throttle(&id, wait)
+                    });
+                }
+                let body = std::mem::take(response.body_mut());
+                *response.body_mut() = slot.hold_through(body);
                 
                 self.metrics.record_backend_request(
                     &backend.id,
                     response.status().is_success(),
                     timer.elapsed(),
                 );
+                backend.record_latency(timer.elapsed());
+                if self.config.load().admin.enabled {
+                    self.latency_stats.record_backend(&backend.id, timer.elapsed());
+                }
                 
                 Ok(response)
             }
@@ -373,13 +912,57 @@ impl Proxy {
                 );
                 
                 self.metrics.record_backend_request(&backend.id, false, timer.elapsed());
+                if matches!(e, ProxyError::LatencyBudgetExceeded) {
+                    self.metrics.record_latency_budget_exceeded(route.name(), &backend.id);
+                }
                 
-                Err(ProxyError::BackendError(e.to_string()))
+                Err(e)
             }
         }
     }
 }
 
+/// Buffer a request body, failing once it's larger than `limit`.
+pub(crate) async fn read_body(mut body: Body, limit: Option<u64>) -> Result<Bytes, ProxyError> {
+    let Some(limit) = limit else {
+        return hyper::body::to_bytes(body)
+            .await
+            .map_err(|e| ProxyError::RequestError(e.to_string()));
+    };
+    
+    let mut buffered = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| ProxyError::RequestError(e.to_string()))?;
+        if (buffered.len() + chunk.len()) as u64 > limit {
+            return Err(ProxyError::PayloadTooLarge(limit));
+        }
+        buffered.extend_from_slice(&chunk);
+    }
+    Ok(buffered.into())
+}
+
+/// The socket peer's IP. Unlike `ctx.client_addr`, which prefers
+/// `X-Forwarded-For`, clients can't pick it, so it's what access and
+/// per-client limits go by.
+fn peer_ip(req: &Request<Body>) -> Option<IpAddr> {
+    req.extensions().get::<PeerAddr>().map(|peer| peer.0.ip())
+}
+
+/// Stream a request body, cutting it off once it's larger than `limit`.
+fn limit_body(body: Body, limit: u64) -> Body {
+    let mut received = 0;
+    Body::wrap_stream(body.map(move |chunk| {
+        let chunk = chunk?;
+        received += chunk.len() as u64;
+        if received > limit {
+            let error: Box<dyn std::error::Error + Send + Sync> =
+                Box::new(ProxyError::PayloadTooLarge(limit));
+            return Err(error);
+        }
+        Ok(chunk)
+    }))
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ProxyError {
     #[error("No healthy backends available")]
@@ -391,6 +974,9 @@ pub enum ProxyError {
     #[error("Request timeout")]
     Timeout,
     
+    #[error("Latency budget exceeded")]
+    LatencyBudgetExceeded,
+    
     #[error("Circuit breaker open for backend: {0}")]
     CircuitBreakerOpen(String),
     
@@ -402,23 +988,104 @@ pub enum ProxyError {
     
     #[error("Request error: {0}")]
     RequestError(String),
+    
+    #[error("Request body exceeds {0} bytes")]
+    PayloadTooLarge(u64),
+    
+    #[error("Shed by admission control")]
+    Overloaded,
+    
+    #[error("Upstream connection pool exhausted: {0}")]
+    PoolExhausted(String),
+    
+    #[error("Invalid backend response: {0}")]
+    InvalidResponse(String),
+    
+    #[error("Method not allowed")]
+    MethodNotAllowed,
+
+    /// A backend answered 503; retried like a failed attempt, and the last
+    /// such response is what the client gets.
+    #[error("Backend unavailable ({})", .0.status())]
+    Unavailable(Box<Response<Body>>),
 }
 
-impl From<ProxyError> for Response<Body> {
-    fn from(err: ProxyError) -> Self {
-        let (status, message) = match &err {
+/// Whether anything besides the backends and routes differs.
+fn restart_only_changes(current: &Config, new: &Config) -> bool {
+    let others = |config: &Config| {
+        let mut config = config.clone();
+        config.backends.clear();
+        config.routes.clear();
+        for listener in &mut config.listeners {
+            listener.routing = None;
+        }
+        serde_yaml::to_value(config).ok()
+    };
+    others(current) != others(new)
+}
+
+impl ProxyError {
+    /// The status the client gets, and a short description for it.
+    pub fn status_and_message(&self) -> (StatusCode, &'static str) {
+        match self {
             ProxyError::NoHealthyBackends => (StatusCode::SERVICE_UNAVAILABLE, "No healthy backends available"),
             ProxyError::BackendError(_) => (StatusCode::BAD_GATEWAY, "Backend error"),
             ProxyError::Timeout => (StatusCode::GATEWAY_TIMEOUT, "Gateway timeout"),
+            ProxyError::LatencyBudgetExceeded => (StatusCode::GATEWAY_TIMEOUT, "Gateway timeout"),
             ProxyError::CircuitBreakerOpen(_) => (StatusCode::SERVICE_UNAVAILABLE, "Service temporarily unavailable"),
             ProxyError::ConnectionLimitReached(_) => (StatusCode::SERVICE_UNAVAILABLE, "Backend overloaded"),
             ProxyError::InvalidUri(_) => (StatusCode::BAD_REQUEST, "Invalid request URI"),
             ProxyError::RequestError(_) => (StatusCode::BAD_REQUEST, "Invalid request"),
+            ProxyError::PayloadTooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large"),
+            ProxyError::Overloaded => (StatusCode::SERVICE_UNAVAILABLE, "Server overloaded"),
+            ProxyError::PoolExhausted(_) => (StatusCode::SERVICE_UNAVAILABLE, "Upstream pool exhausted"),
+            ProxyError::InvalidResponse(_) => (StatusCode::BAD_GATEWAY, "Invalid backend response"),
+            ProxyError::MethodNotAllowed => (StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+            ProxyError::Unavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, "Backend unavailable"),
+        }
+    }
+    
+    /// A stable name for the kind of error, for clients, dashboards and
+    /// logs. Codes of responses the load balancer made itself start with
+    /// `LB_` and are sent in [`ERROR_CODE_HEADER`]; `BACKEND_UNAVAILABLE`
+    /// is a backend's own 503, passed on as it is.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProxyError::NoHealthyBackends => "LB_NO_HEALTHY_BACKENDS",
+            ProxyError::BackendError(_) => "LB_BACKEND_ERROR",
+            ProxyError::Timeout => "LB_TIMEOUT",
+            ProxyError::LatencyBudgetExceeded => "LB_LATENCY_BUDGET_EXCEEDED",
+            ProxyError::CircuitBreakerOpen(_) => "LB_CIRCUIT_OPEN",
+            ProxyError::ConnectionLimitReached(_) => "LB_BACKEND_OVERLOADED",
+            ProxyError::InvalidUri(_) => "LB_INVALID_URI",
+            ProxyError::RequestError(_) => "LB_INVALID_REQUEST",
+            ProxyError::PayloadTooLarge(_) => "LB_PAYLOAD_TOO_LARGE",
+            ProxyError::Overloaded => "LB_OVERLOADED",
+            ProxyError::PoolExhausted(_) => "LB_POOL_EXHAUSTED",
+            ProxyError::InvalidResponse(_) => "LB_INVALID_RESPONSE",
+            ProxyError::MethodNotAllowed => "LB_METHOD_NOT_ALLOWED",
+            ProxyError::Unavailable(_) => "BACKEND_UNAVAILABLE",
+        }
+    }
+}
+
+/// Carries [`ProxyError::code`] on the error responses the load balancer
+/// makes, whatever their body.
+pub const ERROR_CODE_HEADER: &str = "x-lb-error-code";
+
+impl From<ProxyError> for Response<Body> {
+    fn from(err: ProxyError) -> Self {
+        // The backend's own answer
+        let err = match err {
+            ProxyError::Unavailable(response) => return *response,
+            err => err,
         };
+        let (status, message) = err.status_and_message();
         
         Response::builder()
             .status(status)
             .header("x-error", err.to_string())
+            .header(ERROR_CODE_HEADER, err.code())
             .body(Body::from(message))
             .unwrap()
     }
@@ -0,0 +1,85 @@
+//
+// src/proxy/request_id.rs
+//
+use crate::config::RequestIdConfig;
+use hyper::HeaderMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+/// Longest incoming request ID we are willing to propagate.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Pick the request ID for an incoming request.
+///
+/// The first trusted header carrying a usable value wins; otherwise a fresh
+/// ID is generated.
+pub fn resolve_request_id(headers: &HeaderMap, config: &RequestIdConfig) -> String {
+    config
+        .trusted_headers
+        .iter()
+        .filter_map(|name| headers.get(name.as_str()))
+        .filter_map(|value| value.to_str().ok())
+        .map(str::trim)
+        .find(|value| is_valid_request_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(generate_request_id)
+}
+
+/// A UUID-shaped ID made of a random per-process prefix and a counter, so
+/// there's no call into the OS for randomness on every request.
+fn generate_request_id() -> String {
+    static PREFIX: OnceLock<u64> = OnceLock::new();
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let prefix = *PREFIX.get_or_init(rand::random);
+    Uuid::from_u64_pair(prefix, COUNTER.fetch_add(1, Ordering::Relaxed)).to_string()
+}
+
+/// Only accept short, printable, whitespace-free IDs so a client can't inject
+/// garbage into our logs or upstream headers.
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value.bytes().all(|b| b.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RequestIdConfig {
+        RequestIdConfig {
+            trusted_headers: vec!["x-request-id".into(), "x-correlation-id".into()],
+            ..RequestIdConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_honors_first_trusted_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-correlation-id", "corr-1".parse().unwrap());
+        headers.insert("x-request-id", "req-1".parse().unwrap());
+
+        assert_eq!(resolve_request_id(&headers, &config()), "req-1");
+    }
+
+    #[test]
+    fn test_falls_back_to_next_trusted_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", "".parse().unwrap());
+        headers.insert("x-correlation-id", "corr-1".parse().unwrap());
+
+        assert_eq!(resolve_request_id(&headers, &config()), "corr-1");
+    }
+
+    #[test]
+    fn test_generates_id_when_absent_or_invalid() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", "has spaces".parse().unwrap());
+        headers.insert("x-untrusted-id", "nope".parse().unwrap());
+
+        let id = resolve_request_id(&headers, &config());
+        assert!(Uuid::parse_str(&id).is_ok());
+        assert_ne!(resolve_request_id(&headers, &config()), id);
+    }
+}
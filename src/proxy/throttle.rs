@@ -0,0 +1,218 @@
+// src/proxy/throttle.rs
+//! Bandwidth caps on response bodies.
+use crate::config::{BandwidthConfig, DownloadLimitConfig, DownloadLimitScope, FairnessKey};
+use dashmap::DashMap;
+use futures::StreamExt;
+use hyper::body::HttpBody;
+use hyper::header::HeaderMap;
+use hyper::Body;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Bytes per second, with up to `burst` saved up while idle. Takers can
+/// overdraw it, and then wait until it's paid back, so concurrent bodies
+/// queue behind each other instead of all waking at once.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    /// Tokens available as of the instant, negative when overdrawn.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    pub fn new(config: &BandwidthConfig) -> Self {
+        let burst = config.burst() as f64;
+        Self {
+            rate: config.bytes_per_sec as f64,
+            burst,
+            state: Mutex::new((burst, Instant::now())),
+        }
+    }
+
+    /// Take `bytes`, returning how long to wait before sending them.
+    pub fn take(&self, bytes: usize) -> Duration {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let tokens = self.refill(&mut state);
+        *tokens -= bytes as f64;
+        match *tokens < 0.0 {
+            true => Duration::from_secs_f64(-*tokens / self.rate),
+            false => Duration::ZERO,
+        }
+    }
+
+    /// Whether it's saved up its whole burst, so a fresh bucket would do.
+    pub fn is_full(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *self.refill(&mut state) >= self.burst
+    }
+
+    fn refill<'a>(&self, state: &'a mut (f64, Instant)) -> &'a mut f64 {
+        let (tokens, refilled) = state;
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*refilled).as_secs_f64() * self.rate).min(self.burst);
+        *refilled = now;
+        tokens
+    }
+}
+
+/// After this many new buckets, forget those nobody's using.
+const SWEEP_EVERY: u64 = 1024;
+
+/// `download_limit`'s buckets, one per client or connection and tier.
+pub struct DownloadLimiter {
+    per: DownloadLimitScope,
+    key_header: Option<String>,
+    default: BandwidthConfig,
+    tier_header: Option<String>,
+    tiers: HashMap<String, Option<BandwidthConfig>>,
+    buckets: DashMap<String, Arc<TokenBucket>>,
+    created: AtomicU64,
+}
+
+impl DownloadLimiter {
+    pub fn new(config: &DownloadLimitConfig) -> Self {
+        Self {
+            per: config.per,
+            key_header: match &config.key {
+                FairnessKey::Header(header) => Some(header.clone()),
+                FairnessKey::ClientIp => None,
+            },
+            default: config.limit.clone(),
+            tier_header: config.tier_header.clone(),
+            tiers: config.tiers.clone(),
+            buckets: DashMap::new(),
+            created: AtomicU64::new(0),
+        }
+    }
+
+    /// The tier of a request from `peer` and the bucket its response goes
+    /// through, which is none for unlimited tiers.
+    pub fn bucket(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> (String, Option<Arc<TokenBucket>>) {
+        let tier = self
+            .tier_header
+            .as_ref()
+            .and_then(|name| headers.get(name))
+            .and_then(|value| value.to_str().ok())
+            .filter(|tier| self.tiers.contains_key(*tier));
+        let limit = match tier {
+            Some(tier) => match &self.tiers[tier] {
+                Some(limit) => limit,
+                None => return (tier.to_string(), None),
+            },
+            None => &self.default,
+        };
+        let tier = tier.unwrap_or("default").to_string();
+
+        let client = match self.per {
+            DownloadLimitScope::Connection => peer.map(|peer| peer.to_string()),
+            DownloadLimitScope::Client => {
+                let header = self.key_header.as_ref().and_then(|name| headers.get(name));
+                header
+                    .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                    .or_else(|| peer.map(|peer| peer.ip().to_string()))
+            }
+        };
+        let key = format!("{}/{}", tier, client.unwrap_or_default());
+        if let Some(bucket) = self.buckets.get(&key) {
+            return (tier, Some(bucket.clone()));
+        }
+        if self.created.fetch_add(1, Ordering::Relaxed).is_multiple_of(SWEEP_EVERY) {
+            self.buckets
+                .retain(|_, bucket| Arc::strong_count(bucket) > 1 || !bucket.is_full());
+        }
+        let bucket = self
+            .buckets
+            .entry(key)
+            .or_insert_with(|| Arc::new(TokenBucket::new(limit)))
+            .clone();
+        (tier, Some(bucket))
+    }
+}
+
+/// `body` sent no faster than `bucket` allows. `on_wait` hears about each
+/// wait before it happens.
+pub fn throttle<F>(body: Body, bucket: Arc<TokenBucket>, on_wait: F) -> Body
+where
+    F: Fn(Duration) + Send + Sync + 'static,
+{
+    if body.is_end_stream() {
+        return body;
+    }
+    let on_wait = Arc::new(on_wait);
+    Body::wrap_stream(body.then(move |chunk| {
+        let (bucket, on_wait) = (bucket.clone(), on_wait.clone());
+        async move {
+            if let Ok(bytes) = &chunk {
+                let wait = bucket.take(bytes.len());
+                if !wait.is_zero() {
+                    on_wait(wait);
+                    tokio::time::sleep(wait).await;
+                }
+            }
+            chunk
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[tokio::test]
+    async fn test_throttles_past_the_burst() {
+        let bucket = Arc::new(TokenBucket::new(&BandwidthConfig { bytes_per_sec: 100_000, burst_bytes: Some(5_000) }));
+        let waited = Arc::new(AtomicU64::new(0));
+        let counted = waited.clone();
+        let chunks: Vec<Result<&'static [u8], std::io::Error>> = vec![Ok(&[0; 5_000]), Ok(&[0; 10_000]), Ok(&[0; 5_000])];
+        let body = throttle(Body::wrap_stream(futures::stream::iter(chunks)), bucket, move |wait| {
+            counted.fetch_add(wait.as_micros() as u64, Ordering::Relaxed);
+        });
+
+        let started = Instant::now();
+        let bytes = hyper::body::to_bytes(body).await.unwrap();
+        assert_eq!(bytes.len(), 20_000);
+        // The burst covers the first 5000 bytes; the other 15000 take 150ms
+        assert!(started.elapsed() >= Duration::from_millis(145), "{:?}", started.elapsed());
+        let waited = Duration::from_micros(waited.load(Ordering::Relaxed));
+        assert!(waited >= Duration::from_millis(140) && waited <= Duration::from_millis(150), "{:?}", waited);
+    }
+
+    #[test]
+    fn test_download_limits_share_buckets_by_tier_and_client() {
+        let config: DownloadLimitConfig = serde_yaml::from_str(
+            "{ bytes_per_sec: 1000, key: { header: x-api-key }, tier_header: x-tier, tiers: { paid: { bytes_per_sec: 5000 }, internal: null } }",
+        )
+        .unwrap();
+        let limiter = DownloadLimiter::new(&config);
+        let headers = |tier: &str, key: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-tier", tier.parse().unwrap());
+            headers.insert("x-api-key", key.parse().unwrap());
+            headers
+        };
+        let peer = Some("192.0.2.1:50000".parse().unwrap());
+
+        let (tier, free) = limiter.bucket(&headers("unknown", "a"), peer);
+        assert_eq!(tier, "default");
+        let (_, same) = limiter.bucket(&headers("", "a"), peer);
+        assert!(Arc::ptr_eq(free.as_ref().unwrap(), same.as_ref().unwrap()));
+        let (tier, paid) = limiter.bucket(&headers("paid", "a"), peer);
+        assert_eq!(tier, "paid");
+        assert!(!Arc::ptr_eq(free.as_ref().unwrap(), paid.as_ref().unwrap()));
+        assert_eq!(paid.unwrap().take(5000), Duration::ZERO);
+        let (tier, unlimited) = limiter.bucket(&headers("internal", "a"), peer);
+        assert_eq!(tier, "internal");
+        assert!(unlimited.is_none());
+
+        // Without the key header, a client's connections share its IP's bucket
+        let (_, by_ip) = limiter.bucket(&HeaderMap::new(), peer);
+        let (_, other_port) = limiter.bucket(&HeaderMap::new(), Some("192.0.2.1:50001".parse().unwrap()));
+        assert!(Arc::ptr_eq(by_ip.as_ref().unwrap(), other_port.as_ref().unwrap()));
+    }
+}
@@ -33,6 +33,10 @@ impl RetryStrategy {
         Self { config }
     }
     
+    pub fn max_attempts(&self) -> u32 {
+        self.config.max_attempts
+    }
+    
     /// Execute a function with retry logic
     pub async fn execute<F, Fut, T, E>(
         &self,
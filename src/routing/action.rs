@@ -0,0 +1,113 @@
+// src/routing/action.rs
+use super::StaticFiles;
+use crate::config::RouteConfig;
+use hyper::header::{HeaderValue, CONTENT_TYPE, LOCATION};
+use hyper::{Body, Request, Response, StatusCode, Uri};
+
+/// A response the router produces itself, without a backend.
+#[derive(Debug)]
+pub enum LocalAction {
+    Redirect {
+        status: StatusCode,
+        location: String,
+    },
+    Respond {
+        status: StatusCode,
+        body: hyper::body::Bytes,
+        content_type: HeaderValue,
+    },
+    Static(StaticFiles),
+}
+
+impl LocalAction {
+    /// The route's action, if it doesn't proxy. Validated in Config::validate.
+    pub fn new(config: &RouteConfig) -> Option<Self> {
+        if let Some(redirect) = &config.redirect {
+            return Some(Self::Redirect {
+                status: StatusCode::from_u16(redirect.status).expect("invalid redirect status"),
+                location: redirect.location.clone(),
+            });
+        }
+        if let Some(files) = &config.static_files {
+            return Some(Self::Static(StaticFiles::new(files, &config.path_prefix)));
+        }
+        config.direct_response.as_ref().map(|response| Self::Respond {
+            status: StatusCode::from_u16(response.status).expect("invalid direct_response status"),
+            body: response.body.clone().into(),
+            content_type: HeaderValue::from_str(&response.content_type)
+                .expect("invalid direct_response content_type"),
+        })
+    }
+
+    pub async fn respond(&self, host: Option<&str>, req: &Request<Body>) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        match self {
+            Self::Redirect { status, location } => {
+                *response.status_mut() = *status;
+                match HeaderValue::from_str(&render_location(location, host, req.uri())) {
+                    Ok(location) => {
+                        response.headers_mut().insert(LOCATION, location);
+                    }
+                    // Only possible with odd bytes in the request's Host
+                    Err(_) => *response.status_mut() = StatusCode::BAD_REQUEST,
+                }
+            }
+            Self::Respond {
+                status,
+                body,
+                content_type,
+            } => {
+                *response.status_mut() = *status;
+                *response.body_mut() = Body::from(body.clone());
+                response.headers_mut().insert(CONTENT_TYPE, content_type.clone());
+            }
+            Self::Static(files) => {
+                response = files.serve(req.method(), req.uri(), req.headers()).await;
+            }
+        }
+        response
+    }
+}
+
+/// Fill in the `{placeholder}`s of a redirect location in one pass, so
+/// request values are never expanded themselves.
+fn render_location(template: &str, host: Option<&str>, uri: &Uri) -> String {
+    let mut location = String::with_capacity(template.len() + uri.path().len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        location.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        match &rest[start + 1..start + len] {
+            "host" => location.push_str(host.unwrap_or_default()),
+            "path" => location.push_str(uri.path()),
+            "query" => location.push_str(uri.query().unwrap_or_default()),
+            "path_and_query" => {
+                location.push_str(uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/"))
+            }
+            _ => location.push_str(&rest[start..=start + len]),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    location.push_str(rest);
+    location
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_location() {
+        let uri: Uri = "/shop/cart?item=1".parse().unwrap();
+        assert_eq!(
+            render_location("https://{host}{path_and_query}", Some("example.com"), &uri),
+            "https://example.com/shop/cart?item=1"
+        );
+        assert_eq!(
+            render_location("https://new.example.com{path}?from={query}", None, &uri),
+            "https://new.example.com/shop/cart?from=item=1"
+        );
+    }
+}
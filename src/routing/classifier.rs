@@ -0,0 +1,19 @@
+// src/routing/classifier.rs
+use hyper::{Body, Request};
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// What classifiers found out about a request, e.g. `country: DE`.
+pub type RequestAttributes = HashMap<String, String>;
+
+/// Computes attributes routes can match on, such as a country from the
+/// client IP or a tenant from a token claim. Classifiers only run for
+/// requests whose host and path match a route with `attributes`.
+pub trait RequestClassifier: Send + Sync {
+    fn classify(
+        &self,
+        req: &Request<Body>,
+        client_ip: Option<IpAddr>,
+        attributes: &mut RequestAttributes,
+    );
+}
@@ -0,0 +1,148 @@
+// src/routing/experiment.rs
+use super::overrides::cookies;
+use super::split::{fnv1a, Weights};
+use crate::config::ExperimentConfig;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use std::net::IpAddr;
+
+/// An A/B experiment: clients are bucketed by a salted hash of their key,
+/// so a client keeps its variant for as long as the weights stay the same.
+#[derive(Debug)]
+pub struct Experiment {
+    name: String,
+    salt: String,
+    bucket_by: Vec<BucketKey>,
+    variants: Vec<Variant>,
+    weights: Weights,
+    response_header: HeaderName,
+}
+
+#[derive(Debug)]
+pub struct Variant {
+    pub name: String,
+    pub pool: String,
+    /// `name`, ready for the response header
+    pub header_value: HeaderValue,
+}
+
+#[derive(Debug)]
+enum BucketKey {
+    Header(HeaderName),
+    Cookie(String),
+    Ip,
+}
+
+impl Experiment {
+    /// Validated in Config::validate.
+    pub fn new(config: &ExperimentConfig) -> Self {
+        let bucket_by = config
+            .bucket_by
+            .iter()
+            .map(|source| match (source.strip_prefix("header:"), source.strip_prefix("cookie:")) {
+                (Some(header), _) => BucketKey::Header(
+                    HeaderName::from_bytes(header.as_bytes()).expect("invalid bucket_by header"),
+                ),
+                (_, Some(cookie)) => BucketKey::Cookie(cookie.to_string()),
+                _ => BucketKey::Ip,
+            })
+            .collect();
+        let variants = config
+            .variants
+            .iter()
+            .map(|variant| Variant {
+                name: variant.name.clone(),
+                pool: variant.pool.clone(),
+                header_value: HeaderValue::from_str(&variant.name).expect("invalid variant name"),
+            })
+            .collect();
+
+        Self {
+            name: config.name.clone(),
+            salt: config.salt.clone().unwrap_or_else(|| config.name.clone()),
+            bucket_by,
+            variants,
+            weights: Weights::new(config.variants.iter().map(|variant| variant.weight)),
+            response_header: HeaderName::from_bytes(config.response_header.as_bytes())
+                .expect("invalid experiment response_header"),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn response_header(&self) -> &HeaderName {
+        &self.response_header
+    }
+
+    pub fn assign(&self, headers: &HeaderMap, client_ip: Option<IpAddr>) -> &Variant {
+        let index = match self.client_key(headers, client_ip) {
+            Some(key) => {
+                let mut salted = Vec::with_capacity(self.salt.len() + 1 + key.len());
+                salted.extend_from_slice(self.salt.as_bytes());
+                salted.push(b':');
+                salted.extend_from_slice(key.as_bytes());
+                self.weights.index_at(fnv1a(&salted))
+            }
+            None => self.weights.random(),
+        };
+        &self.variants[index]
+    }
+
+    fn client_key(&self, headers: &HeaderMap, client_ip: Option<IpAddr>) -> Option<String> {
+        self.bucket_by.iter().find_map(|source| match source {
+            BucketKey::Header(name) => headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            BucketKey::Cookie(name) => cookies(headers)
+                .find(|(cookie, _)| cookie == name)
+                .map(|(_, value)| value.to_string()),
+            BucketKey::Ip => client_ip.map(|ip| ip.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VariantConfig;
+
+    #[test]
+    fn test_assignment_is_sticky_and_salted() {
+        let experiment = |salt: &str| {
+            Experiment::new(&ExperimentConfig {
+                name: "checkout".to_string(),
+                salt: Some(salt.to_string()),
+                bucket_by: vec!["cookie:uid".to_string(), "ip".to_string()],
+                variants: ["control", "treatment"]
+                    .iter()
+                    .map(|name| VariantConfig {
+                        name: name.to_string(),
+                        pool: name.to_string(),
+                        weight: 50,
+                    })
+                    .collect(),
+                response_header: "x-experiment-variant".to_string(),
+            })
+        };
+        let (first, second) = (experiment("a"), experiment("b"));
+
+        let mut reshuffled = 0;
+        for user in 0..200 {
+            let mut headers = HeaderMap::new();
+            headers.insert("cookie", format!("uid={}", user).parse().unwrap());
+            let variant = &first.assign(&headers, None).name;
+            assert_eq!(&first.assign(&headers, Some([10, 0, 0, 1].into())).name, variant);
+            if &second.assign(&headers, None).name != variant {
+                reshuffled += 1;
+            }
+        }
+        // Independent salts disagree for about half of the clients
+        assert!((60..140).contains(&reshuffled), "{} reshuffled", reshuffled);
+
+        let ip = Some([192, 0, 2, 7].into());
+        let by_ip = &first.assign(&HeaderMap::new(), ip).name;
+        assert!((0..10).all(|_| &first.assign(&HeaderMap::new(), ip).name == by_ip));
+    }
+}
@@ -0,0 +1,45 @@
+// src/routing/geoip.rs
+use super::{RequestAttributes, RequestClassifier};
+use anyhow::{Context, Result};
+use hyper::{Body, Request};
+use maxminddb::{geoip2, Reader};
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Sets `country` and `continent` (ISO codes such as `DE` and `EU`) from a
+/// MaxMind database. Addresses missing from the database, like private
+/// ranges, get neither.
+pub struct GeoIpClassifier {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIpClassifier {
+    pub fn open(database: &Path) -> Result<Self> {
+        let reader = Reader::open_readfile(database)
+            .with_context(|| format!("Failed to open GeoIP database {}", database.display()))?;
+        Ok(Self { reader })
+    }
+}
+
+impl RequestClassifier for GeoIpClassifier {
+    fn classify(
+        &self,
+        _req: &Request<Body>,
+        client_ip: Option<IpAddr>,
+        attributes: &mut RequestAttributes,
+    ) {
+        let Some(ip) = client_ip else {
+            return;
+        };
+        let Ok(record) = self.reader.lookup::<geoip2::Country>(ip) else {
+            return;
+        };
+
+        if let Some(code) = record.country.and_then(|country| country.iso_code) {
+            attributes.insert("country".to_string(), code.to_string());
+        }
+        if let Some(code) = record.continent.and_then(|continent| continent.code) {
+            attributes.insert("continent".to_string(), code.to_string());
+        }
+    }
+}
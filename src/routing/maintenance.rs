@@ -0,0 +1,130 @@
+// src/routing/maintenance.rs
+use super::Route;
+use crate::config::{IpList, MaintenanceConfig};
+use dashmap::DashMap;
+use hyper::body::Bytes;
+use hyper::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE, RETRY_AFTER};
+use hyper::{Body, Response, StatusCode};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::error;
+
+/// Whether requests are answered with the maintenance response instead of
+/// being routed. Starts out as configured; the admin API flips it.
+pub struct Maintenance {
+    enabled: AtomicBool,
+    /// Set from the admin API, overriding a route's `maintenance`.
+    routes: DashMap<String, bool>,
+    allow: IpList,
+    status: StatusCode,
+    body: Bytes,
+    content_type: HeaderValue,
+    retry_after: Option<HeaderValue>,
+}
+
+impl Maintenance {
+    /// Validated in Config::validate. A page that can't be read falls back
+    /// to `body`.
+    pub fn new(config: &MaintenanceConfig) -> Self {
+        let text = || (Bytes::from(config.body.clone()), HeaderValue::from_static("text/plain; charset=utf-8"));
+        let (body, content_type) = match &config.page {
+            Some(page) => match std::fs::read(page) {
+                Ok(contents) => {
+                    let mime = mime_guess::from_path(page).first_or_octet_stream();
+                    (Bytes::from(contents), HeaderValue::from_str(mime.as_ref()).unwrap())
+                }
+                Err(e) => {
+                    error!(page = %page.display(), error = %e, "Failed to read maintenance page");
+                    text()
+                }
+            },
+            None => text(),
+        };
+        Self {
+            enabled: AtomicBool::new(config.enabled),
+            routes: DashMap::new(),
+            allow: config.allow.clone(),
+            status: StatusCode::from_u16(config.status).expect("invalid maintenance status"),
+            body,
+            content_type,
+            retry_after: config.retry_after_secs.map(HeaderValue::from),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn route_enabled(&self, route: &Route) -> bool {
+        self.routes
+            .get(route.name())
+            .map(|enabled| *enabled)
+            .unwrap_or(route.maintenance())
+    }
+
+    pub fn set_route_enabled(&self, route: &str, enabled: bool) {
+        self.routes.insert(route.to_string(), enabled);
+    }
+
+    /// Whether a request from `client_ip` that matched `route` gets the
+    /// maintenance response.
+    pub fn applies(&self, route: &Route, client_ip: Option<IpAddr>) -> bool {
+        (self.is_enabled() || self.route_enabled(route))
+            && !client_ip.is_some_and(|ip| self.allow.contains(ip))
+    }
+
+    pub fn response(&self) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, self.content_type.clone());
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        if let Some(retry_after) = &self.retry_after {
+            headers.insert(RETRY_AFTER, retry_after.clone());
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::routing::Router;
+    use hyper::Request;
+
+    #[test]
+    fn test_maintenance_by_route_with_allowlist() {
+        let config: Config = serde_yaml::from_str(
+            "load_balancer: {}\nbackends: [{ url: 'http://127.0.0.1:8001' }]\nhealth_check: {}\n\
+             circuit_breaker: {}\nretry: {}\nmetrics: {}\n\
+             routes: [{ name: api, path_prefix: /api, maintenance: true }, { name: web }]\n\
+             maintenance: { allow: [10.0.0.0/8, '2001:db8::1'], retry_after_secs: 120 }",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let router = Router::new(&config);
+        let maintenance = Maintenance::new(&config.maintenance);
+        let route = |path| router.route(&Request::get(path).body(Body::empty()).unwrap(), None);
+        let outside = Some("192.0.2.1".parse().unwrap());
+
+        assert!(maintenance.applies(route("/api/x"), outside));
+        assert!(!maintenance.applies(route("/api/x"), Some("10.1.2.3".parse().unwrap())));
+        assert!(!maintenance.applies(route("/api/x"), Some("::ffff:10.1.2.3".parse().unwrap())));
+        assert!(!maintenance.applies(route("/api/x"), Some("2001:db8::1".parse().unwrap())));
+        assert!(!maintenance.applies(route("/"), outside));
+
+        maintenance.set_route_enabled("api", false);
+        assert!(!maintenance.applies(route("/api/x"), outside));
+        maintenance.set_enabled(true);
+        assert!(maintenance.applies(route("/"), outside));
+
+        let response = maintenance.response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[RETRY_AFTER], "120");
+    }
+}
@@ -0,0 +1,85 @@
+// src/routing/methods.rs
+use hyper::header::HeaderValue;
+use hyper::Method;
+
+/// What `Allow` lists for routes that don't restrict methods.
+const ANY: [Method; 7] = [
+    Method::GET,
+    Method::HEAD,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+    Method::OPTIONS,
+];
+
+/// The methods a route accepts, checked before any backend work.
+#[derive(Debug, Clone)]
+pub struct MethodAcl {
+    /// `None` accepts any method not in `denied`.
+    allowed: Option<Vec<Method>>,
+    denied: Vec<Method>,
+    allow: HeaderValue,
+}
+
+impl MethodAcl {
+    /// The ACL for a route's `methods`, `None` if it accepts everything.
+    /// Allowing `GET` allows `HEAD` too.
+    pub fn new(methods: &[String], reject_trace_connect: bool) -> Option<Self> {
+        if methods.is_empty() && !reject_trace_connect {
+            return None;
+        }
+        let denied = match reject_trace_connect {
+            true => vec![Method::TRACE, Method::CONNECT],
+            false => Vec::new(),
+        };
+        let allowed = (!methods.is_empty()).then(|| {
+            // Validated in Config::validate
+            let mut allowed: Vec<Method> = methods
+                .iter()
+                .map(|method| Method::from_bytes(method.as_bytes()).expect("invalid route method"))
+                .collect();
+            if allowed.contains(&Method::GET) && !allowed.contains(&Method::HEAD) {
+                allowed.push(Method::HEAD);
+            }
+            allowed.retain(|method| !denied.contains(method));
+            allowed
+        });
+        let listed = allowed.as_deref().unwrap_or(&ANY);
+        let allow = listed.iter().map(Method::as_str).collect::<Vec<_>>().join(", ");
+        Some(Self {
+            allowed,
+            denied,
+            allow: HeaderValue::from_str(&allow).expect("methods are valid header values"),
+        })
+    }
+
+    /// The `Allow` value to answer a 405 with, if `method` isn't accepted.
+    pub fn check(&self, method: &Method) -> Option<&HeaderValue> {
+        let accepted = !self.denied.contains(method)
+            && self.allowed.as_ref().is_none_or(|allowed| allowed.contains(method));
+        (!accepted).then_some(&self.allow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_route_methods() {
+        let methods = ["GET".to_string(), "POST".to_string(), "TRACE".to_string()];
+        let acl = MethodAcl::new(&methods, false).unwrap();
+        assert_eq!(acl.check(&Method::GET), None);
+        assert_eq!(acl.check(&Method::HEAD), None);
+        assert_eq!(acl.check(&Method::TRACE), None);
+        assert_eq!(acl.check(&Method::DELETE).unwrap(), "GET, POST, TRACE, HEAD");
+
+        let acl = MethodAcl::new(&methods, true).unwrap();
+        assert_eq!(acl.check(&Method::TRACE).unwrap(), "GET, POST, HEAD");
+        let acl = MethodAcl::new(&[], true).unwrap();
+        assert_eq!(acl.check(&Method::DELETE), None);
+        assert_eq!(acl.check(&Method::CONNECT).unwrap(), "GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS");
+        assert!(MethodAcl::new(&[], false).is_none());
+    }
+}
@@ -0,0 +1,30 @@
+// src/routing/mod.rs
+mod action;
+mod classifier;
+mod experiment;
+#[cfg(feature = "geoip")]
+mod geoip;
+mod maintenance;
+mod methods;
+mod normalize;
+mod overrides;
+mod policy;
+mod router;
+mod split;
+mod static_files;
+mod validation;
+
+pub use action::LocalAction;
+pub use classifier::{RequestAttributes, RequestClassifier};
+pub use experiment::{Experiment, Variant};
+#[cfg(feature = "geoip")]
+pub use geoip::GeoIpClassifier;
+pub use maintenance::Maintenance;
+pub use methods::MethodAcl;
+pub use normalize::Normalizer;
+pub use overrides::PoolOverride;
+pub use policy::RoutePolicy;
+pub use router::{Assignment, Route, Router};
+pub use split::TrafficSplit;
+pub use static_files::StaticFiles;
+pub use validation::ResponseValidator;
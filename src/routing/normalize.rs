@@ -0,0 +1,191 @@
+// src/routing/normalize.rs
+use crate::config::NormalizeConfig;
+use hyper::header::{HeaderValue, HOST};
+use hyper::http::uri::{Authority, PathAndQuery};
+use hyper::{Request, Uri};
+
+/// Rewrites request URLs into canonical form before routing.
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    config: NormalizeConfig,
+}
+
+impl Normalizer {
+    pub fn new(config: &NormalizeConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    pub fn normalize<B>(&self, req: &mut Request<B>) {
+        if self.config.lowercase_host {
+            self.lowercase_host(req);
+        }
+
+        let path = req.uri().path();
+        let normalized = self.normalize_path(path);
+        if normalized == path {
+            return;
+        }
+        tracing::debug!(from = %path, to = %normalized, "Normalized request path");
+
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("{}?{}", normalized, query),
+            None => normalized,
+        };
+        let mut parts = req.uri().clone().into_parts();
+        // Built from a valid path, and we only ever drop or decode characters
+        parts.path_and_query = PathAndQuery::try_from(path_and_query).ok();
+        if let Ok(uri) = Uri::from_parts(parts) {
+            *req.uri_mut() = uri;
+        }
+    }
+
+    fn lowercase_host<B>(&self, req: &mut Request<B>) {
+        if let Some(host) = req.headers().get(HOST) {
+            if host.as_bytes().iter().any(u8::is_ascii_uppercase) {
+                let lower = host.as_bytes().to_ascii_lowercase();
+                if let Ok(value) = HeaderValue::from_bytes(&lower) {
+                    req.headers_mut().insert(HOST, value);
+                }
+            }
+        }
+
+        let Some(authority) = req.uri().authority() else {
+            return;
+        };
+        if authority.as_str().bytes().any(|b| b.is_ascii_uppercase()) {
+            let mut parts = req.uri().clone().into_parts();
+            parts.authority = Authority::try_from(authority.as_str().to_ascii_lowercase()).ok();
+            if let Ok(uri) = Uri::from_parts(parts) {
+                *req.uri_mut() = uri;
+            }
+        }
+    }
+
+    fn normalize_path(&self, path: &str) -> String {
+        // `*` (OPTIONS) and other non-absolute forms are left alone
+        if !path.starts_with('/') {
+            return path.to_string();
+        }
+
+        let mut path = self.rewrite_bytes(path);
+        if self.config.merge_slashes {
+            let mut merged = String::with_capacity(path.len());
+            for c in path.chars() {
+                if !(c == '/' && merged.ends_with('/')) {
+                    merged.push(c);
+                }
+            }
+            path = merged;
+        }
+        if self.config.resolve_dot_segments {
+            path = remove_dot_segments(&path);
+        }
+        path
+    }
+
+    /// Percent-decoding and lowercasing, in one pass so that escapes are
+    /// never lowercased or decoded twice.
+    fn rewrite_bytes(&self, path: &str) -> String {
+        let lower = |b: u8| {
+            if self.config.lowercase_path {
+                b.to_ascii_lowercase()
+            } else {
+                b
+            }
+        };
+
+        let bytes = path.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let escaped = (bytes[i] == b'%')
+                .then(|| Some((hex(*bytes.get(i + 1)?)? << 4) | hex(*bytes.get(i + 2)?)?))
+                .flatten();
+            match escaped {
+                Some(byte) if self.config.decode_unreserved && is_unreserved(byte) => {
+                    out.push(lower(byte));
+                    i += 3;
+                }
+                Some(_) if self.config.decode_unreserved => {
+                    out.push(b'%');
+                    out.extend(bytes[i + 1..i + 3].iter().map(u8::to_ascii_uppercase));
+                    i += 3;
+                }
+                Some(_) => {
+                    out.extend_from_slice(&bytes[i..i + 3]);
+                    i += 3;
+                }
+                None => {
+                    out.push(lower(bytes[i]));
+                    i += 1;
+                }
+            }
+        }
+        // Only ASCII bytes were replaced, by ASCII bytes
+        String::from_utf8(out).unwrap_or_else(|_| path.to_string())
+    }
+}
+
+fn hex(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~')
+}
+
+/// RFC 3986 section 5.2.4, for absolute paths.
+fn remove_dot_segments(path: &str) -> String {
+    let segments: Vec<&str> = path[1..].split('/').collect();
+    let mut output: Vec<&str> = Vec::with_capacity(segments.len());
+    for (i, segment) in segments.iter().enumerate() {
+        let last = i == segments.len() - 1;
+        match *segment {
+            "." => {}
+            ".." => {
+                output.pop();
+            }
+            segment => {
+                output.push(segment);
+                continue;
+            }
+        }
+        // `/a/.` and `/a/b/..` keep their trailing slash
+        if last {
+            output.push("");
+        }
+    }
+    format!("/{}", output.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        let defaults = Normalizer::new(&NormalizeConfig::default());
+        let cases = [
+            ("/api/users", "/api/users"),
+            ("/a/./b/../c", "/a/c"),
+            ("/a/b/..", "/a/"),
+            ("/../../etc/passwd", "/etc/passwd"),
+            ("/admin/%2e%2e/public", "/public"),
+            ("/%7euser/%41%2f", "/~user/A%2F"),
+            ("/a//b", "/a//b"),
+            ("/100%", "/100%"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(defaults.normalize_path(path), expected, "{}", path);
+        }
+
+        let everything = Normalizer::new(&NormalizeConfig {
+            merge_slashes: true,
+            lowercase_path: true,
+            ..NormalizeConfig::default()
+        });
+        assert_eq!(everything.normalize_path("//API//V1/./Users/%2fX"), "/api/v1/users/%2Fx");
+    }
+}
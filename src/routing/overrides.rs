@@ -0,0 +1,93 @@
+// src/routing/overrides.rs
+use crate::config::PoolOverrideConfig;
+use hyper::header::{HeaderMap, HeaderName, COOKIE};
+
+/// Pins requests carrying a header or cookie to a pool, whatever the
+/// route's split says.
+#[derive(Debug)]
+pub struct PoolOverride {
+    source: Source,
+    value: Option<String>,
+    pool: String,
+}
+
+#[derive(Debug)]
+enum Source {
+    Header(HeaderName),
+    Cookie(String),
+}
+
+impl PoolOverride {
+    pub fn new(config: &PoolOverrideConfig) -> Self {
+        // Validated in Config::validate
+        let source = match (&config.header, &config.cookie) {
+            (Some(header), _) => Source::Header(
+                HeaderName::from_bytes(header.as_bytes()).expect("invalid override header"),
+            ),
+            (None, cookie) => Source::Cookie(cookie.clone().unwrap_or_default()),
+        };
+        Self {
+            source,
+            value: config.value.clone(),
+            pool: config.pool.clone(),
+        }
+    }
+
+    /// The override's pool if the request matches it.
+    pub fn pool(&self, headers: &HeaderMap) -> Option<&str> {
+        let matches = match &self.source {
+            Source::Header(name) => headers
+                .get_all(name)
+                .iter()
+                .any(|value| self.accepts(value.to_str().ok())),
+            Source::Cookie(name) => cookies(headers)
+                .any(|(cookie, value)| cookie == name && self.accepts(Some(value))),
+        };
+        matches.then_some(self.pool.as_str())
+    }
+
+    fn accepts(&self, value: Option<&str>) -> bool {
+        match &self.value {
+            Some(expected) => value == Some(expected.as_str()),
+            None => true,
+        }
+    }
+}
+
+/// `(name, value)` pairs from every `Cookie` header.
+pub(super) fn cookies(headers: &HeaderMap) -> impl Iterator<Item = (&str, &str)> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(header: Option<&str>, cookie: Option<&str>, value: Option<&str>) -> PoolOverride {
+        PoolOverride::new(&PoolOverrideConfig {
+            header: header.map(str::to_string),
+            cookie: cookie.map(str::to_string),
+            value: value.map(str::to_string),
+            pool: "canary".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_header_and_cookie_overrides() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-canary", "true".parse().unwrap());
+        headers.insert(COOKIE, "session=abc; canary=1".parse().unwrap());
+
+        assert_eq!(rule(Some("x-canary"), None, Some("true")).pool(&headers), Some("canary"));
+        assert_eq!(rule(Some("x-canary"), None, Some("false")).pool(&headers), None);
+        assert_eq!(rule(Some("x-other"), None, None).pool(&headers), None);
+        assert_eq!(rule(None, Some("canary"), Some("1")).pool(&headers), Some("canary"));
+        assert_eq!(rule(None, Some("session"), None).pool(&headers), Some("canary"));
+        assert_eq!(rule(None, Some("canary"), Some("0")).pool(&headers), None);
+    }
+}
@@ -0,0 +1,47 @@
+// src/routing/policy.rs
+use crate::config::{PolicyConfig, RetryConfig};
+use crate::retry::RetryStrategy;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// A route's [`PolicyConfig`] with the defaults filled in.
+#[derive(Debug)]
+pub struct RoutePolicy {
+    pub upstream_timeout: Option<Duration>,
+    pub latency_budget: Option<Duration>,
+    pub buffer_request_body: bool,
+    pub max_request_body_bytes: Option<u64>,
+    pub compression: bool,
+    pub cache: bool,
+    pub priority: u8,
+    pub retry: RetryStrategy,
+}
+
+/// Priority of routes that don't set one.
+pub const DEFAULT_PRIORITY: u8 = 5;
+
+impl RoutePolicy {
+    /// `retry` is used unless the policy names one of `retry_policies`.
+    pub fn new(
+        policy: &PolicyConfig,
+        retry: &RetryConfig,
+        retry_policies: &HashMap<String, RetryConfig>,
+    ) -> Self {
+        let retry = policy
+            .retry_policy
+            .as_ref()
+            .and_then(|name| retry_policies.get(name))
+            .unwrap_or(retry);
+
+        Self {
+            upstream_timeout: policy.upstream_timeout_secs.map(Duration::from_secs),
+            latency_budget: policy.latency_budget_ms.map(Duration::from_millis),
+            buffer_request_body: policy.buffer_request_body.unwrap_or(true),
+            max_request_body_bytes: policy.max_request_body_bytes,
+            compression: policy.compression.unwrap_or(false),
+            cache: policy.cache.unwrap_or(false),
+            priority: policy.priority.unwrap_or(DEFAULT_PRIORITY),
+            retry: RetryStrategy::new(retry.clone()),
+        }
+    }
+}
@@ -0,0 +1,423 @@
+// src/routing/router.rs
+use super::{
+    Experiment, LocalAction, MethodAcl, PoolOverride, RequestAttributes, RequestClassifier, ResponseValidator,
+    RoutePolicy, TrafficSplit, Variant,
+};
+use crate::config::{Config, HostHeader, RouteConfig, DEFAULT_POOL};
+use crate::middleware::FilterChain;
+use crate::proxy::ErrorPages;
+use crate::server::ListenerName;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, HOST};
+use hyper::http::uri::Authority;
+use hyper::{Body, Method, Request, Response};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Matches requests against the configured routes, first match wins.
+pub struct Router {
+    table: RouteTable,
+    /// Listeners with their own `routing`, and their tables.
+    listeners: Vec<(String, RouteTable)>,
+    classifiers: Vec<Arc<dyn RequestClassifier>>,
+}
+
+struct RouteTable {
+    routes: Vec<Route>,
+    fallback: Route,
+}
+
+#[derive(Debug)]
+pub struct Route {
+    name: Arc<str>,
+    host: Option<String>,
+    path_prefix: String,
+    attributes: Vec<(String, Vec<String>)>,
+    methods: Option<MethodAcl>,
+    overrides: Vec<PoolOverride>,
+    target: RouteTarget,
+    fallback_pool: Option<String>,
+    host_header: Option<HostHeader>,
+    action: Option<LocalAction>,
+    policy: RoutePolicy,
+    maintenance: bool,
+    preload_links: Vec<HeaderValue>,
+    filters: FilterChain,
+    error_pages: ErrorPages,
+    validator: Option<ResponseValidator>,
+}
+
+#[derive(Debug)]
+enum RouteTarget {
+    Pool(String),
+    Split(TrafficSplit),
+    Experiment(Experiment),
+}
+
+/// Where a request on a route goes.
+pub struct Assignment<'a> {
+    pub pool: &'a str,
+    /// For experiment routes, unless an override picked the pool.
+    pub experiment: Option<(&'a Experiment, &'a Variant)>,
+}
+
+impl Router {
+    pub fn new(config: &Config) -> Self {
+        let policy = |route: Option<&RouteConfig>| {
+            let policy = match route {
+                Some(route) => route.policy.or(&config.policy),
+                None => config.policy.clone(),
+            };
+            RoutePolicy::new(&policy, &config.retry, &config.retry_policies)
+        };
+
+        let table = |routes: &[RouteConfig], default_pool: &str| RouteTable {
+            routes: routes
+                .iter()
+                .map(|route| {
+                    let format = route.error_format.unwrap_or(config.error_format);
+                    let error_pages = ErrorPages::new(&config.error_pages, &route.error_pages, format);
+                    Route::new(route, config, policy(Some(route)), error_pages)
+                })
+                .collect(),
+            fallback: Route {
+                name: default_pool.into(),
+                host: None,
+                path_prefix: "/".to_string(),
+                attributes: Vec::new(),
+                methods: MethodAcl::new(&[], config.reject_trace_connect),
+                overrides: Vec::new(),
+                target: RouteTarget::Pool(default_pool.to_string()),
+                fallback_pool: None,
+                host_header: None,
+                action: None,
+                policy: policy(None),
+                maintenance: false,
+                preload_links: Vec::new(),
+                filters: FilterChain::new(),
+                error_pages: ErrorPages::new(&config.error_pages, &HashMap::new(), config.error_format),
+                validator: None,
+            },
+        };
+
+        Self {
+            table: table(&config.routes, DEFAULT_POOL),
+            listeners: config
+                .listeners
+                .iter()
+                .filter_map(|listener| {
+                    let routing = listener.routing.as_ref()?;
+                    Some((listener.name.clone(), table(&routing.routes, &routing.default_pool)))
+                })
+                .collect(),
+            classifiers: Vec::new(),
+        }
+    }
+
+    /// A router for `config`'s routes that keeps this one's classifiers.
+    pub fn rebuild(&self, config: &Config) -> Self {
+        Self {
+            classifiers: self.classifiers.clone(),
+            ..Self::new(config)
+        }
+    }
+
+    /// Run `classifier` for requests that reach a route with `attributes`.
+    pub fn add_classifier(&mut self, classifier: Arc<dyn RequestClassifier>) {
+        self.classifiers.push(classifier);
+    }
+
+    /// The error pages of the route named `route`, or the top-level ones.
+    pub fn error_pages(&self, route: Option<&str>) -> &ErrorPages {
+        let route = route.and_then(|name| self.routes().find(|route| route.name() == name));
+        route.unwrap_or(&self.table.fallback).error_pages()
+    }
+
+    /// Every configured route: the top-level ones in the order they're
+    /// checked, then each listener's.
+    pub fn routes(&self) -> impl Iterator<Item = &Route> {
+        self.table.routes.iter().chain(self.listeners.iter().flat_map(|(_, table)| &table.routes))
+    }
+
+    /// The first route matching the request's host, path and attributes, or
+    /// one sending everything to the `default` pool.
+    /// Requests on a listener with its own `routing` use its routes.
+    pub fn route(&self, req: &Request<Body>, client_ip: Option<IpAddr>) -> &Route {
+        let table = req
+            .extensions()
+            .get::<ListenerName>()
+            .and_then(|listener| self.listeners.iter().find(|(name, _)| **name == *listener.0))
+            .map_or(&self.table, |(_, table)| table);
+        let authority = request_authority(req);
+        let host = authority.as_ref().map(Authority::host);
+        let path = req.uri().path();
+
+        // Classify lazily, at most once
+        let mut attributes = None;
+        for route in &table.routes {
+            if !route.matches(host, path) {
+                continue;
+            }
+            if route.attributes.is_empty() {
+                return route;
+            }
+            let attributes = attributes.get_or_insert_with(|| self.classify(req, client_ip));
+            if route.matches_attributes(attributes) {
+                return route;
+            }
+        }
+        &table.fallback
+    }
+
+    fn classify(&self, req: &Request<Body>, client_ip: Option<IpAddr>) -> RequestAttributes {
+        let mut attributes = RequestAttributes::new();
+        for classifier in &self.classifiers {
+            classifier.classify(req, client_ip, &mut attributes);
+        }
+        attributes
+    }
+}
+
+/// Host the request was sent to. Absolute-form URIs (and HTTP/2
+/// `:authority`) carry it in the URI, everything else in the Host header.
+fn request_authority<B>(req: &Request<B>) -> Option<Authority> {
+    req.uri()
+        .authority()
+        .cloned()
+        .or_else(|| req.headers().get(HOST)?.to_str().ok()?.parse().ok())
+}
+
+impl Route {
+    fn new(config: &RouteConfig, global: &Config, policy: RoutePolicy, error_pages: ErrorPages) -> Self {
+        let target = if let Some(experiment) = &config.experiment {
+            RouteTarget::Experiment(Experiment::new(experiment))
+        } else if config.split.is_empty() {
+            RouteTarget::Pool(config.pool.clone().unwrap_or_else(|| DEFAULT_POOL.to_string()))
+        } else {
+            // Validated in Config::validate
+            let hash_header = config
+                .split_hash_header
+                .as_ref()
+                .map(|name| HeaderName::from_bytes(name.as_bytes()).expect("invalid split_hash_header"));
+            RouteTarget::Split(TrafficSplit::new(&config.split, hash_header))
+        };
+
+        Self {
+            name: config.name.as_str().into(),
+            host: config.host.clone(),
+            path_prefix: config.path_prefix.clone(),
+            attributes: config
+                .attributes
+                .iter()
+                .map(|(name, values)| (name.clone(), values.clone()))
+                .collect(),
+            methods: MethodAcl::new(&config.methods, global.reject_trace_connect),
+            overrides: config.overrides.iter().map(PoolOverride::new).collect(),
+            target,
+            fallback_pool: config.fallback_pool.clone(),
+            host_header: config.host_header.clone(),
+            action: LocalAction::new(config),
+            policy,
+            maintenance: config.maintenance,
+            preload_links: config
+                .preload_links
+                .iter()
+                .map(|link| HeaderValue::from_str(link).expect("invalid preload_links value"))
+                .collect(),
+            filters: FilterChain::for_route(config, &global.auth_strategies),
+            error_pages,
+            validator: config.validate_response.as_ref().map(ResponseValidator::new),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    
+    /// `name`, to keep without copying it.
+    pub fn shared_name(&self) -> Arc<str> {
+        self.name.clone()
+    }
+
+    /// The `Allow` header for a 405, if the route doesn't accept `method`.
+    pub fn method_not_allowed(&self, method: &Method) -> Option<HeaderValue> {
+        self.methods.as_ref()?.check(method).cloned()
+    }
+
+    /// The route's `validate_response` rules, if it has any.
+    pub fn validator(&self) -> Option<&ResponseValidator> {
+        self.validator.as_ref()
+    }
+
+    /// Responses for errors on this route.
+    pub fn error_pages(&self) -> &ErrorPages {
+        &self.error_pages
+    }
+
+    /// Filters for this route only, run inside the global chain.
+    pub fn filters(&self) -> &FilterChain {
+        &self.filters
+    }
+
+    /// The response for routes answered by the load balancer itself, such as
+    /// redirects; `None` for routes that proxy.
+    pub async fn local_response(&self, req: &Request<Body>) -> Option<Response<Body>> {
+        let action = self.action.as_ref()?;
+        let authority = request_authority(req);
+        Some(action.respond(authority.as_ref().map(Authority::host), req).await)
+    }
+
+    pub fn policy(&self) -> &RoutePolicy {
+        &self.policy
+    }
+
+    /// `Link` headers for the route's responses.
+    pub fn preload_links(&self) -> &[HeaderValue] {
+        &self.preload_links
+    }
+
+    /// Whether the config puts this route in maintenance.
+    pub fn maintenance(&self) -> bool {
+        self.maintenance
+    }
+
+    pub fn host_header(&self) -> Option<&HostHeader> {
+        self.host_header.as_ref()
+    }
+
+    /// Where requests go while their assigned pool has no healthy backends.
+    pub fn fallback_pool(&self) -> Option<&str> {
+        self.fallback_pool.as_deref()
+    }
+
+    /// Pool (and experiment variant) this request should be sent to.
+    pub fn assign(&self, headers: &HeaderMap, client_ip: Option<IpAddr>) -> Assignment<'_> {
+        let pool = |pool| Assignment {
+            pool,
+            experiment: None,
+        };
+        if let Some(overridden) = self.overrides.iter().find_map(|rule| rule.pool(headers)) {
+            return pool(overridden);
+        }
+        match &self.target {
+            RouteTarget::Pool(target) => pool(target),
+            RouteTarget::Split(split) => pool(split.pick(headers)),
+            RouteTarget::Experiment(experiment) => {
+                let variant = experiment.assign(headers, client_ip);
+                Assignment {
+                    pool: &variant.pool,
+                    experiment: Some((experiment, variant)),
+                }
+            }
+        }
+    }
+
+    fn matches(&self, host: Option<&str>, path: &str) -> bool {
+        let host_matches = match (&self.host, host) {
+            (None, _) => true,
+            (Some(expected), Some(host)) => expected.eq_ignore_ascii_case(host),
+            (Some(_), None) => false,
+        };
+        host_matches && path.starts_with(&self.path_prefix)
+    }
+
+    fn matches_attributes(&self, attributes: &RequestAttributes) -> bool {
+        self.attributes.iter().all(|(name, values)| {
+            attributes
+                .get(name)
+                .is_some_and(|value| values.iter().any(|expected| expected == value))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router(routes: &str) -> Router {
+        let config: Config = serde_yaml::from_str(&format!(
+            "load_balancer: {{}}\nbackends: []\nhealth_check: {{}}\ncircuit_breaker: {{}}\n\
+             retry: {{}}\nmetrics: {{}}\nroutes: {}",
+            routes
+        ))
+        .unwrap();
+        Router::new(&config)
+    }
+
+    fn routed<'a>(router: &'a Router, host: &str, uri: &str) -> &'a str {
+        let req = Request::get(uri).header(HOST, host).body(Body::empty()).unwrap();
+        router.route(&req, None).name()
+    }
+
+    #[test]
+    fn test_first_matching_route_wins() {
+        let router = router(
+            "[{ name: api-v2, path_prefix: /api/v2 }, { name: api, path_prefix: /api }, \
+              { name: admin, host: admin.example.com }]",
+        );
+
+        assert_eq!(routed(&router, "example.com", "/api/v2/users"), "api-v2");
+        assert_eq!(routed(&router, "example.com", "/api/v1/users"), "api");
+        assert_eq!(routed(&router, "Admin.Example.com:8080", "/"), "admin");
+        assert_eq!(routed(&router, "example.com", "/"), DEFAULT_POOL);
+        assert_eq!(
+            routed(&router, "example.com", "http://admin.example.com/x"),
+            "admin"
+        );
+    }
+
+    #[test]
+    fn test_listeners_with_routing_use_their_own_routes() {
+        let config: Config = serde_yaml::from_str(
+            "load_balancer: {}\nbackends: []\nhealth_check: {}\ncircuit_breaker: {}\nretry: {}\nmetrics: {}\n\
+             routes: [{ name: public, path_prefix: /api }]\n\
+             listeners: [{ name: public }, { name: internal, address: '0.0.0.0:8081', \
+               routing: { routes: [{ name: debug, path_prefix: /debug }], default_pool: internal } }]",
+        )
+        .unwrap();
+        let router = Router::new(&config);
+        let routed = |listener: Option<&str>, uri: &str| {
+            let mut req = Request::get(uri).body(Body::empty()).unwrap();
+            if let Some(listener) = listener {
+                req.extensions_mut().insert(ListenerName(listener.into()));
+            }
+            router.route(&req, None).name().to_string()
+        };
+
+        assert_eq!(routed(Some("public"), "/api/users"), "public");
+        assert_eq!(routed(Some("public"), "/debug"), DEFAULT_POOL);
+        assert_eq!(routed(None, "/api/users"), "public");
+        assert_eq!(routed(Some("internal"), "/debug/pprof"), "debug");
+        assert_eq!(routed(Some("internal"), "/api/users"), "internal");
+        assert_eq!(router.routes().map(Route::name).collect::<Vec<_>>(), ["public", "debug"]);
+    }
+
+    struct TenantFromHeader;
+
+    impl RequestClassifier for TenantFromHeader {
+        fn classify(&self, req: &Request<Body>, _: Option<IpAddr>, attributes: &mut RequestAttributes) {
+            if let Some(tenant) = req.headers().get("x-tenant").and_then(|v| v.to_str().ok()) {
+                attributes.insert("tenant".to_string(), tenant.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_routes_on_classifier_attributes() {
+        let mut router = router(
+            "[{ name: big-tenants, attributes: { tenant: [acme, globex] } }, { name: api, path_prefix: /api }]",
+        );
+        router.add_classifier(Arc::new(TenantFromHeader));
+        let route = |tenant: Option<&str>| {
+            let mut req = Request::get("/api/orders").body(Body::empty()).unwrap();
+            if let Some(tenant) = tenant {
+                req.headers_mut().insert("x-tenant", tenant.parse().unwrap());
+            }
+            router.route(&req, None).name().to_string()
+        };
+
+        assert_eq!(route(Some("globex")), "big-tenants");
+        assert_eq!(route(Some("initech")), "api");
+        assert_eq!(route(None), "api");
+    }
+}
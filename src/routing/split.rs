@@ -0,0 +1,112 @@
+// src/routing/split.rs
+use crate::config::SplitTargetConfig;
+use hyper::header::{HeaderMap, HeaderName};
+use rand::Rng;
+
+/// Weighted choice between pools, e.g. 95% stable and 5% canary.
+#[derive(Debug)]
+pub struct TrafficSplit {
+    pools: Vec<String>,
+    weights: Weights,
+    hash_header: Option<HeaderName>,
+}
+
+impl TrafficSplit {
+    pub fn new(targets: &[SplitTargetConfig], hash_header: Option<HeaderName>) -> Self {
+        Self {
+            pools: targets.iter().map(|t| t.pool.clone()).collect(),
+            weights: Weights::new(targets.iter().map(|t| t.weight)),
+            hash_header,
+        }
+    }
+
+    /// Pool for a request. With a hash header present the same value always
+    /// gets the same pool, on every load balancer instance, for as long as
+    /// the weights stay the same.
+    pub fn pick(&self, headers: &HeaderMap) -> &str {
+        let hashed = self
+            .hash_header
+            .as_ref()
+            .and_then(|name| headers.get(name))
+            .map(|value| fnv1a(value.as_bytes()));
+        let index = match hashed {
+            Some(hash) => self.weights.index_at(hash),
+            None => self.weights.random(),
+        };
+        &self.pools[index]
+    }
+}
+
+/// Relative weights; at least one must be non-zero.
+#[derive(Debug)]
+pub(super) struct Weights {
+    weights: Vec<u64>,
+    total: u64,
+}
+
+impl Weights {
+    pub(super) fn new(weights: impl Iterator<Item = u32>) -> Self {
+        let weights: Vec<u64> = weights.map(u64::from).collect();
+        let total = weights.iter().sum();
+        Self { weights, total }
+    }
+
+    /// Index chosen by a hash; the same hash always gets the same index.
+    pub(super) fn index_at(&self, hash: u64) -> usize {
+        let mut point = hash % self.total;
+        for (index, weight) in self.weights.iter().enumerate() {
+            if point < *weight {
+                return index;
+            }
+            point -= weight;
+        }
+        // `point < total`, so only a zero total gets here
+        self.weights.len() - 1
+    }
+
+    pub(super) fn random(&self) -> usize {
+        self.index_at(rand::thread_rng().gen_range(0..self.total))
+    }
+}
+
+/// Stable across processes and releases, unlike `DefaultHasher`.
+pub(super) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(hash_header: Option<&'static str>) -> TrafficSplit {
+        let targets = [
+            SplitTargetConfig { pool: "stable".into(), weight: 90 },
+            SplitTargetConfig { pool: "canary".into(), weight: 10 },
+        ];
+        TrafficSplit::new(&targets, hash_header.map(HeaderName::from_static))
+    }
+
+    #[test]
+    fn test_split_follows_weights_and_hash_is_sticky() {
+        let random = split(None);
+        let canary = (0..10_000)
+            .filter(|_| random.pick(&HeaderMap::new()) == "canary")
+            .count();
+        assert!((800..1200).contains(&canary), "canary got {}", canary);
+
+        let sticky = split(Some("x-user-id"));
+        let mut canary_users = 0;
+        for user in 0..1000 {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-user-id", user.to_string().parse().unwrap());
+            let pool = sticky.pick(&headers);
+            assert!((0..10).all(|_| sticky.pick(&headers) == pool));
+            if pool == "canary" {
+                canary_users += 1;
+            }
+        }
+        assert!((50..150).contains(&canary_users), "canary got {} users", canary_users);
+    }
+}
@@ -0,0 +1,285 @@
+// src/routing/static_files.rs
+use crate::config::StaticFilesConfig;
+use hyper::body::Bytes;
+use hyper::header::{
+    HeaderMap, HeaderValue, ACCEPT_RANGES, ALLOW, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+    LOCATION, RANGE,
+};
+use hyper::{Body, Method, Response, StatusCode, Uri};
+use percent_encoding::percent_decode_str;
+use std::fs::Metadata;
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Serves a directory for a `static_files` route. Paths can't leave the
+/// root, by `..` or by symlink, and hidden (dot) files are never served.
+#[derive(Debug)]
+pub struct StaticFiles {
+    root: PathBuf,
+    prefix: String,
+    index: Vec<String>,
+}
+
+enum Resolved {
+    File(PathBuf, Metadata),
+    /// A directory requested without its trailing slash
+    AddSlash,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+impl StaticFiles {
+    pub fn new(config: &StaticFilesConfig, prefix: &str) -> Self {
+        // Canonical, so resolved files can be checked against it
+        let root = std::fs::canonicalize(&config.root).unwrap_or_else(|_| config.root.clone());
+        Self {
+            root,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            index: config.index.clone(),
+        }
+    }
+
+    pub async fn serve(&self, method: &Method, uri: &Uri, headers: &HeaderMap) -> Response<Body> {
+        if method != Method::GET && method != Method::HEAD {
+            let mut response = status_response(StatusCode::METHOD_NOT_ALLOWED);
+            response
+                .headers_mut()
+                .insert(ALLOW, HeaderValue::from_static("GET, HEAD"));
+            return response;
+        }
+        let Some(relative) = self.relative_path(uri.path()) else {
+            return status_response(StatusCode::NOT_FOUND);
+        };
+
+        let served = match self.resolve(&relative, uri.path().ends_with('/')).await {
+            Ok(Resolved::File(path, metadata)) => {
+                file_response(method, headers, &path, &metadata).await
+            }
+            Ok(Resolved::AddSlash) => {
+                let location = match uri.query() {
+                    Some(query) => format!("{}/?{}", uri.path(), query),
+                    None => format!("{}/", uri.path()),
+                };
+                HeaderValue::from_str(&location).map(|location| {
+                    let mut response = status_response(StatusCode::MOVED_PERMANENTLY);
+                    response.headers_mut().insert(LOCATION, location);
+                    response
+                })
+                .map_err(|_| io::ErrorKind::InvalidInput.into())
+            }
+            Err(e) => Err(e),
+        };
+
+        served.unwrap_or_else(|e| match e.kind() {
+            io::ErrorKind::NotFound | io::ErrorKind::InvalidInput => {
+                status_response(StatusCode::NOT_FOUND)
+            }
+            io::ErrorKind::PermissionDenied => status_response(StatusCode::FORBIDDEN),
+            _ => {
+                tracing::warn!(root = %self.root.display(), error = %e, "Failed to serve static file");
+                status_response(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        })
+    }
+
+    /// The file path below the root for a request path, or `None` if the
+    /// path is outside the route or tries to escape the root.
+    fn relative_path(&self, request_path: &str) -> Option<PathBuf> {
+        let rest = request_path.strip_prefix(&self.prefix)?;
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+
+        let decoded = percent_decode_str(rest).decode_utf8().ok()?;
+        let mut relative = PathBuf::new();
+        for segment in decoded.split('/') {
+            match segment {
+                "" => continue,
+                _ if segment.starts_with('.') || segment.contains(['\\', '\0']) => return None,
+                _ => relative.push(segment),
+            }
+        }
+        Some(relative)
+    }
+
+    async fn resolve(&self, relative: &Path, trailing_slash: bool) -> io::Result<Resolved> {
+        let (path, metadata) = self.lookup(&self.root.join(relative)).await?;
+        if !metadata.is_dir() {
+            return Ok(Resolved::File(path, metadata));
+        }
+        if !trailing_slash {
+            return Ok(Resolved::AddSlash);
+        }
+
+        for index in &self.index {
+            match self.lookup(&path.join(index)).await {
+                Ok((path, metadata)) if metadata.is_file() => {
+                    return Ok(Resolved::File(path, metadata))
+                }
+                _ => continue,
+            }
+        }
+        Err(io::ErrorKind::NotFound.into())
+    }
+
+    /// Follow symlinks, refusing anything that ends up outside the root.
+    async fn lookup(&self, path: &Path) -> io::Result<(PathBuf, Metadata)> {
+        let path = tokio::fs::canonicalize(path).await?;
+        if !path.starts_with(&self.root) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        let metadata = tokio::fs::metadata(&path).await?;
+        Ok((path, metadata))
+    }
+}
+
+async fn file_response(
+    method: &Method,
+    headers: &HeaderMap,
+    path: &Path,
+    metadata: &Metadata,
+) -> io::Result<Response<Body>> {
+    let len = metadata.len();
+    let range = headers
+        .get(RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(ByteRange::Full, |value| parse_range(value, len));
+
+    let (status, start, end) = match range {
+        ByteRange::Full => (StatusCode::OK, 0, len),
+        ByteRange::Partial(first, last) => (StatusCode::PARTIAL_CONTENT, first, last + 1),
+        ByteRange::Unsatisfiable => {
+            let mut response = status_response(StatusCode::RANGE_NOT_SATISFIABLE);
+            response
+                .headers_mut()
+                .insert(CONTENT_RANGE, content_range(&format!("bytes */{}", len)));
+            return Ok(response);
+        }
+    };
+
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        let mut file = File::open(path).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        file_body(file, end - start)
+    };
+
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    let content_type = mime_guess::from_path(path).first_or_octet_stream();
+    let headers = response.headers_mut();
+    if let Ok(content_type) = HeaderValue::from_str(content_type.as_ref()) {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(end - start));
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if status == StatusCode::PARTIAL_CONTENT {
+        let value = format!("bytes {}-{}/{}", start, end - 1, len);
+        headers.insert(CONTENT_RANGE, content_range(&value));
+    }
+    Ok(response)
+}
+
+/// Stream `len` bytes from the file's current position.
+fn file_body(file: File, len: u64) -> Body {
+    let chunks = futures::stream::try_unfold((file, len), |(mut file, remaining)| async move {
+        if remaining == 0 {
+            return Ok(None);
+        }
+        let mut chunk = vec![0; remaining.min(CHUNK_SIZE) as usize];
+        let read = file.read(&mut chunk).await?;
+        if read == 0 {
+            // Truncated while we were sending it
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        chunk.truncate(read);
+        Ok(Some((Bytes::from(chunk), (file, remaining - read as u64))))
+    });
+    Body::wrap_stream(chunks)
+}
+
+/// Parse a single `bytes=` range. Other units, multiple ranges and
+/// malformed values are ignored, per RFC 9110, and get the whole file.
+fn parse_range(value: &str, len: u64) -> ByteRange {
+    let Some((first, last)) = value
+        .strip_prefix("bytes=")
+        .filter(|spec| !spec.contains(','))
+        .and_then(|spec| spec.trim().split_once('-'))
+    else {
+        return ByteRange::Full;
+    };
+
+    let range = match (first.parse::<u64>(), last.parse::<u64>()) {
+        // `bytes=-500`: the last 500 bytes
+        (Err(_), Ok(suffix)) if first.is_empty() => {
+            if suffix == 0 || len == 0 {
+                return ByteRange::Unsatisfiable;
+            }
+            (len.saturating_sub(suffix), len - 1)
+        }
+        (Ok(first), Err(_)) if last.is_empty() => (first, len.saturating_sub(1)),
+        (Ok(first), Ok(last)) if first <= last => (first, last.min(len.saturating_sub(1))),
+        _ => return ByteRange::Full,
+    };
+
+    if range.0 >= len {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(range.0, range.1)
+    }
+}
+
+fn content_range(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).expect("content range is ASCII")
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path_stays_inside_root() {
+        let files = StaticFiles {
+            root: PathBuf::from("/srv/www"),
+            prefix: "/assets".to_string(),
+            index: Vec::new(),
+        };
+
+        assert_eq!(files.relative_path("/assets/css/app.css"), Some("css/app.css".into()));
+        assert_eq!(files.relative_path("/assets/"), Some(PathBuf::new()));
+        assert_eq!(files.relative_path("/assets/a%20b.txt"), Some("a b.txt".into()));
+        assert_eq!(files.relative_path("/assetsfoo"), None);
+        assert_eq!(files.relative_path("/assets/../etc/passwd"), None);
+        assert_eq!(files.relative_path("/assets/%2e%2e/etc/passwd"), None);
+        assert_eq!(files.relative_path("/assets/..%2fetc"), None);
+        assert_eq!(files.relative_path("/assets/.env"), None);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), ByteRange::Partial(0, 99));
+        assert_eq!(parse_range("bytes=900-", 1000), ByteRange::Partial(900, 999));
+        assert_eq!(parse_range("bytes=-100", 1000), ByteRange::Partial(900, 999));
+        assert_eq!(parse_range("bytes=0-5000", 1000), ByteRange::Partial(0, 999));
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), ByteRange::Full);
+        assert_eq!(parse_range("items=0-1", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=9-1", 1000), ByteRange::Full);
+    }
+}
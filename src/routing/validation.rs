@@ -0,0 +1,89 @@
+// src/routing/validation.rs
+use crate::config::{ResponseValidationConfig, ValidationAction};
+use hyper::body::HttpBody;
+use hyper::header::{HeaderName, CONTENT_TYPE};
+use hyper::{Body, Response};
+
+/// A route's `validate_response` rules.
+#[derive(Debug)]
+pub struct ResponseValidator {
+    required_headers: Vec<HeaderName>,
+    max_status: Option<u16>,
+    content_types: Vec<String>,
+    pub action: ValidationAction,
+}
+
+impl ResponseValidator {
+    pub fn new(config: &ResponseValidationConfig) -> Self {
+        Self {
+            // Validated in Config::validate
+            required_headers: config
+                .required_headers
+                .iter()
+                .filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+                .collect(),
+            max_status: config.max_status,
+            content_types: config.content_types.iter().map(|t| t.to_ascii_lowercase()).collect(),
+            action: config.action,
+        }
+    }
+
+    /// What's wrong with `response`, if anything.
+    pub fn check(&self, response: &Response<Body>) -> Result<(), String> {
+        let status = response.status().as_u16();
+        if self.max_status.is_some_and(|max| status > max) {
+            return Err(format!("status {}", status));
+        }
+        if let Some(missing) = self.required_headers.iter().find(|name| !response.headers().contains_key(*name)) {
+            return Err(format!("missing header {}", missing));
+        }
+        if self.content_types.is_empty() || response.body().is_end_stream() {
+            return Ok(());
+        }
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+        let media_type = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        let allowed = self.content_types.iter().any(|allowed| match allowed.strip_suffix("/*") {
+            Some(kind) => media_type.split('/').next() == Some(kind),
+            None => *allowed == media_type,
+        });
+        match allowed {
+            true => Ok(()),
+            false => Err(format!("content type {:?}", content_type)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_status_headers_and_content_type() {
+        let config: ResponseValidationConfig = serde_yaml::from_str(
+            "{ max_status: 499, required_headers: [x-api-version], content_types: [application/json, 'text/*'], action: reject }",
+        )
+        .unwrap();
+        let validator = ResponseValidator::new(&config);
+        let response = |status: u16, content_type: &str, body: &'static str| {
+            Response::builder()
+                .status(status)
+                .header("x-api-version", "2")
+                .header(CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        assert!(validator.check(&response(200, "application/json; charset=utf-8", "{}")).is_ok());
+        assert!(validator.check(&response(404, "text/csv", "a,b")).is_ok());
+        assert_eq!(validator.check(&response(502, "application/json", "{}")), Err("status 502".to_string()));
+        assert!(validator.check(&response(200, "application/xml", "<a/>")).is_err());
+        // No body, nothing to check the type of
+        assert!(validator.check(&response(200, "application/xml", "")).is_ok());
+        let bare = Response::new(Body::from("{}"));
+        assert_eq!(validator.check(&bare), Err("missing header x-api-version".to_string()));
+    }
+}
@@ -0,0 +1,108 @@
+// src/server/access.rs
+use crate::config::{AccessConfig, IpList};
+use base64::Engine;
+use hyper::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use hyper::{Body, Request, Response, StatusCode};
+use std::net::IpAddr;
+
+/// Checks requests to the metrics or admin server against an
+/// `AccessConfig`.
+#[derive(Clone)]
+pub struct AccessControl {
+    /// Used in the `WWW-Authenticate` realm.
+    server: &'static str,
+    allow: IpList,
+    /// `Basic <base64>` as sent, so checks don't decode anything.
+    basic: Option<String>,
+    bearer: Option<String>,
+}
+
+impl AccessControl {
+    pub fn new(server: &'static str, config: &AccessConfig) -> Self {
+        let basic = config.basic_auth.as_ref().map(|basic| {
+            let credentials = format!("{}:{}", basic.username, basic.password);
+            format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials))
+        });
+        Self {
+            server,
+            allow: config.allow.clone(),
+            basic,
+            bearer: config.bearer_token.as_ref().map(|token| format!("Bearer {}", token)),
+        }
+    }
+
+    /// `None` if `req` from `client` may go ahead, or the 403 or 401 to
+    /// answer it with.
+    pub fn check(&self, client: IpAddr, req: &Request<Body>) -> Option<Response<Body>> {
+        if !self.allow.is_empty() && !self.allow.contains(client) {
+            return Some(respond(StatusCode::FORBIDDEN, "Forbidden"));
+        }
+        if self.basic.is_none() && self.bearer.is_none() {
+            return None;
+        }
+        let presented = req.headers().get(AUTHORIZATION).map(|value| value.as_bytes()).unwrap_or_default();
+        let accepted = [&self.basic, &self.bearer]
+            .into_iter()
+            .flatten()
+            .any(|expected| constant_time_eq(presented, expected.as_bytes()));
+        if accepted {
+            return None;
+        }
+        let mut response = respond(StatusCode::UNAUTHORIZED, "Unauthorized");
+        let challenge = match self.basic {
+            Some(_) => format!("Basic realm=\"{}\"", self.server),
+            None => "Bearer".to_string(),
+        };
+        if let Ok(value) = challenge.parse() {
+            response.headers_mut().insert(WWW_AUTHENTICATE, value);
+        }
+        Some(response)
+    }
+}
+
+/// Compare in constant time, so a secret can't be guessed byte by byte.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn respond(status: StatusCode, message: &'static str) -> Response<Body> {
+    Response::builder().status(status).body(Body::from(message)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(authorization: Option<&str>) -> Request<Body> {
+        let mut req = Request::get("/metrics");
+        if let Some(value) = authorization {
+            req = req.header(AUTHORIZATION, value);
+        }
+        req.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_access_control_checks_address_and_credentials() {
+        let config: AccessConfig = serde_yaml::from_str(
+            "{ allow: [10.0.0.0/8], basic_auth: { username: prom, password: hunter2 }, bearer_token: t0ken }",
+        )
+        .unwrap();
+        let access = AccessControl::new("metrics", &config);
+        let inside: IpAddr = "10.1.2.3".parse().unwrap();
+
+        let outside = access.check("192.0.2.1".parse().unwrap(), &request(Some("Bearer t0ken")));
+        assert_eq!(outside.unwrap().status(), StatusCode::FORBIDDEN);
+
+        let missing = access.check(inside, &request(None)).unwrap();
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(missing.headers()[WWW_AUTHENTICATE], "Basic realm=\"metrics\"");
+        assert!(access.check(inside, &request(Some("Bearer wrong"))).is_some());
+
+        // prom:hunter2
+        assert!(access.check(inside, &request(Some("Basic cHJvbTpodW50ZXIy"))).is_none());
+        assert!(access.check(inside, &request(Some("Bearer t0ken"))).is_none());
+
+        let open = AccessControl::new("admin", &AccessConfig::default());
+        assert!(open.check("192.0.2.1".parse().unwrap(), &request(None)).is_none());
+    }
+}
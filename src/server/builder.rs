@@ -1,10 +1,24 @@
 // ────────────────────────────────
 // src/server/builder.rs
 // ────────────────────────────────
-use crate::server::listener::bind_tcp;
+use crate::config::{HttpOptions, RequestValidation};
+use crate::metrics::MetricsSink;
+use crate::server::{
+    connection::{
+        ConnectionActivity, ConnectionGate, ConnectionLimits, ConnectionService,
+        IpConnectionTracker, MonitoredStream,
+    },
+    drain::{draining, DrainWatcher},
+    handover::SocketHandover,
+    listener::{AcceptBackoff, BoundListener, ClientStream},
+    validation,
+};
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use anyhow::Result;
-use hyper::{server::conn::Http, Body, Request, Response};
+use hyper::{header::HeaderValue, server::conn::Http, Body, Request, Response};
 use tower::Service;
 
 /// Builder pattern so `main.rs` can inject its Proxy (or any handler).
@@ -15,7 +29,19 @@ where
     H::Future: Send + 'static,
 {
     addr: SocketAddr,
+    unix_socket: Option<PathBuf>,
     handler: Option<H>,
+    name: Arc<str>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    acceptors: usize,
+    reuse_port: bool,
+    backlog: u32,
+    limits: ConnectionLimits,
+    http_options: HttpOptions,
+    handover: Arc<SocketHandover>,
+    drain: Option<DrainWatcher>,
+    shared_gate: ConnectionGate,
+    reject_when_full: bool,
 }
 
 impl<H> ServerBuilder<H>
@@ -25,7 +51,28 @@ where
     H::Future: Send + 'static,
 {
     pub fn new(addr: SocketAddr) -> Self {
-        Self { addr, handler: None }
+        Self {
+            addr,
+            unix_socket: None,
+            handler: None,
+            name: Arc::from("http"),
+            metrics: None,
+            acceptors: 1,
+            reuse_port: false,
+            backlog: 1024,
+            limits: ConnectionLimits::default(),
+            http_options: HttpOptions::default(),
+            handover: Arc::new(SocketHandover::default()),
+            drain: None,
+            shared_gate: ConnectionGate::default(),
+            reject_when_full: false,
+        }
+    }
+
+    /// Listen on this Unix socket path instead of the TCP address.
+    pub fn with_unix_socket(mut self, path: Option<PathBuf>) -> Self {
+        self.unix_socket = path;
+        self
     }
 
     /// Inject your request handler (usually wraps `proxy::Proxy`).
@@ -34,25 +81,375 @@ where
         self
     }
 
-    /// Consume the builder, boot the TCP listener, spawn Hyper tasks.
+    /// Listener name used as the `listener` label on connection metrics.
+    pub fn with_name(mut self, name: impl Into<Arc<str>>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Record connection-level metrics (lifetime, time to first byte).
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsSink>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Run `count` accept loops instead of one. With `reuse_port` each loop
+    /// gets its own `SO_REUSEPORT` socket; otherwise they share one socket.
+    pub fn with_acceptors(mut self, count: usize, reuse_port: bool, backlog: u32) -> Self {
+        self.acceptors = count.max(1);
+        self.reuse_port = reuse_port;
+        self.backlog = backlog;
+        self
+    }
+
+    /// Per-IP connection limits and connection timeouts.
+    pub fn with_limits(mut self, limits: ConnectionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// HTTP/1 and HTTP/2 protocol settings for accepted connections.
+    pub fn with_http_options(mut self, options: HttpOptions) -> Self {
+        self.http_options = options;
+        self
+    }
+
+    /// Take listening sockets from `handover` when it has a matching one, and
+    /// register the bound sockets with it for the next process.
+    pub fn with_handover(mut self, handover: Arc<SocketHandover>) -> Self {
+        self.handover = handover;
+        self
+    }
+
+    /// Also hold a slot of `gate`, shared with other listeners, for each
+    /// connection. With `reject_when_full`, connections that find it full
+    /// are accepted and closed at once rather than left in the backlog.
+    pub fn with_shared_gate(mut self, gate: ConnectionGate, reject_when_full: bool) -> Self {
+        self.shared_gate = gate;
+        self.reject_when_full = reject_when_full;
+        self
+    }
+
+    /// Stop accepting and gracefully close connections once draining starts.
+    pub fn with_drain(mut self, drain: DrainWatcher) -> Self {
+        self.drain = Some(drain);
+        self
+    }
+
+    /// Consume the builder, boot the listener, spawn Hyper tasks.
     pub async fn serve(self) -> Result<()> {
         let handler = self.handler.expect("handler must be set via with_handler()");
 
-        // 1️⃣ Bind the TCP socket (plain or TLS can be swapped later).
-        let listener = bind_tcp(self.addr).await?;
-        tracing::info!("HTTP server listening on {}", self.addr);
+        // 1️⃣ Bind the socket(s) (plain or TLS can be swapped later).
+        let listeners: Vec<Arc<BoundListener>> = if let Some(path) = &self.unix_socket {
+            let listener = Arc::new(self.handover.bind_unix(&self.name, path)?);
+            tracing::info!(
+                "HTTP server '{}' listening on unix:{} ({} acceptor(s))",
+                self.name,
+                path.display(),
+                self.acceptors
+            );
+            vec![listener; self.acceptors]
+        } else {
+            let listeners = if self.reuse_port {
+                self.handover
+                    .bind_tcp_reuseport(&self.name, self.addr, self.acceptors, self.backlog)?
+                    .into_iter()
+                    .map(|listener| Arc::new(BoundListener::Tcp(listener)))
+                    .collect()
+            } else {
+                let listener = self.handover.bind_tcp(&self.name, self.addr).await?;
+                let listener = Arc::new(BoundListener::Tcp(listener));
+                vec![listener; self.acceptors]
+            };
+            tracing::info!(
+                "HTTP server '{}' listening on {} ({} acceptor(s), reuse_port={})",
+                self.name,
+                self.addr,
+                self.acceptors,
+                self.reuse_port
+            );
+            listeners
+        };
+
+        let limits = Arc::new(self.limits);
+        let http = Arc::new(build_http(&self.http_options, &limits));
+        let alt_svc = match (&self.http_options.http3, &self.unix_socket) {
+            (Some(http3), None) => http3.alt_svc(self.addr).and_then(|value| HeaderValue::from_str(&value).ok()),
+            _ => None,
+        };
+        let ip_tracker = limits
+            .max_connections_per_ip
+            .map(|max| Arc::new(IpConnectionTracker::new(max)));
+        let gate = ConnectionGate::new(limits.max_connections);
+
+        let loops = listeners.into_iter().map(|listener| {
+            let acceptor = Acceptor {
+                handler: handler.clone(),
+                name: self.name.clone(),
+                metrics: self.metrics.clone(),
+                limits: limits.clone(),
+                ip_tracker: ip_tracker.clone(),
+                http: http.clone(),
+                alt_svc: alt_svc.clone(),
+                validation: self.http_options.request_validation,
+                drain: self.drain.clone(),
+                gate: gate.clone(),
+                shared_gate: self.shared_gate.clone(),
+                reject_when_full: self.reject_when_full,
+            };
+            tokio::spawn(acceptor.run(listener))
+        });
+
+        // Accept errors are retried, so this only fails if an acceptor panics
+        for result in futures::future::try_join_all(loops).await? {
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/// State owned by a single accept loop.
+struct Acceptor<H> {
+    handler: H,
+    name: Arc<str>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    limits: Arc<ConnectionLimits>,
+    ip_tracker: Option<Arc<IpConnectionTracker>>,
+    http: Arc<Http>,
+    alt_svc: Option<HeaderValue>,
+    validation: RequestValidation,
+    drain: Option<DrainWatcher>,
+    gate: ConnectionGate,
+    shared_gate: ConnectionGate,
+    reject_when_full: bool,
+}
 
+impl<H> Acceptor<H>
+where
+    H: Service<Request<Body>, Response = Response<Body>> + Send + Clone + 'static,
+    H::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    H::Future: Send + 'static,
+{
+    async fn run(mut self, listener: Arc<BoundListener>) -> Result<()> {
+        let mut backoff = AcceptBackoff::default();
         loop {
-            let (stream, peer) = listener.accept().await?;
-            let svc = handler.clone();
+            let accept = async {
+                let slot = self.gate.acquire().await;
+                let shared_slot = match self.reject_when_full {
+                    true => None,
+                    false => self.shared_gate.acquire().await,
+                };
+                ((slot, shared_slot), listener.accept().await)
+            };
+            let ((slot, shared_slot), accepted) = tokio::select! {
+                accepted = accept => accepted,
+                _ = draining(&mut self.drain) => {
+                    tracing::info!("HTTP server '{}' stopped accepting connections", self.name);
+                    return Ok(());
+                }
+            };
+            let (stream, peer) = match accepted {
+                Ok(accepted) => {
+                    backoff.reset();
+                    accepted
+                }
+                Err(err) => {
+                    let kind = backoff.on_error(&self.name, &err).await;
+                    if let Some(metrics) = &self.metrics {
+                        metrics.record_accept_error(&self.name, kind);
+                    }
+                    continue;
+                }
+            };
+            let accepted_at = Instant::now();
+
+            let shared_slot = match (self.reject_when_full, shared_slot) {
+                (false, shared_slot) => shared_slot,
+                (true, _) => match self.shared_gate.try_acquire() {
+                    Ok(shared_slot) => shared_slot,
+                    Err(_) => {
+                        tracing::debug!(?peer, "max connections reached");
+                        if let Some(metrics) = &self.metrics {
+                            metrics.record_connection_rejected(&self.name, "max_connections");
+                        }
+                        continue;
+                    }
+                },
+            };
+
+            // Unix socket clients have no IP, so per-IP limits don't apply
+            let ip_guard = match (&self.ip_tracker, peer) {
+                (Some(tracker), Some(peer)) => match tracker.try_acquire(peer.ip()) {
+                    Some(guard) => Some(guard),
+                    None => {
+                        tracing::debug!(%peer, "per-IP connection limit reached");
+                        if let Some(metrics) = &self.metrics {
+                            metrics.record_connection_rejected(&self.name, "per_ip_limit");
+                        }
+                        continue;
+                    }
+                },
+                _ => None,
+            };
+
+            let activity = Arc::new(ConnectionActivity::new(accepted_at));
+            let mut svc = ConnectionService::new(self.handler.clone(), peer, activity.clone())
+                .with_listener(self.name.clone())
+                .with_keep_alive_limits(self.limits.max_requests, self.limits.max_age)
+                .with_alt_svc(self.alt_svc.clone())
+                .with_validation(self.validation)
+                .with_body_read_timeout(self.limits.body_read_timeout);
+            if let Some(metrics) = &self.metrics {
+                svc = svc.with_metrics(metrics.clone(), self.name.clone());
+            }
+            let metrics = self.metrics.clone();
+            let name = self.name.clone();
+            let limits = self.limits.clone();
+            let http = self.http.clone();
+            let drain = self.drain.clone();
 
             // 2️⃣ Spawn one Tokio task per connection.
             tokio::spawn(async move {
-                let http = Http::new();
-                if let Err(err) = http.serve_connection(stream, svc).await {
-                    tracing::warn!(%peer, %err, "connection error");
+                let _slots = (slot, shared_slot);
+                let _ip_guard = ip_guard;
+                if let Some(metrics) = &metrics {
+                    metrics.record_connection_opened(&name);
+                }
+
+                let (closed_by, rejected) =
+                    serve_connection(&http, stream, svc, peer, &limits, activity, drain).await;
+
+                if let Some(metrics) = &metrics {
+                    if let Some(reason) = closed_by {
+                        metrics.record_connection_rejected(&name, reason);
+                    }
+                    if let Some(reason) = rejected {
+                        metrics.record_request_rejected(&name, reason);
+                    }
+                    metrics.record_connection_closed(&name, accepted_at.elapsed());
                 }
             });
         }
     }
-}
\ No newline at end of file
+}
+
+/// Serve one connection, enforcing idle timeout and max lifetime by
+/// gracefully shutting it down, and cutting it off `close_grace` later.
+/// Returns the limit that closed it, if any, and why the parser rejected
+/// its last request, if it did.
+async fn serve_connection<S>(
+    http: &Http,
+    stream: ClientStream,
+    svc: S,
+    peer: Option<SocketAddr>,
+    limits: &ConnectionLimits,
+    activity: Arc<ConnectionActivity>,
+    mut drain: Option<DrainWatcher>,
+) -> (Option<&'static str>, Option<&'static str>)
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Send + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    S::Future: Send + 'static,
+{
+    let stream = MonitoredStream::new(stream, activity.clone(), limits.write_timeout);
+    let conn = http.serve_connection(stream, svc);
+    tokio::pin!(conn);
+
+    let lifetime = optional_sleep(limits.max_lifetime);
+    let idle = async {
+        match limits.idle_timeout {
+            Some(timeout) => activity.idle(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    // Armed when a limit closes the connection
+    let grace = tokio::time::sleep(Duration::ZERO);
+    tokio::pin!(lifetime, idle, grace);
+
+    let mut closed_by = None;
+    let mut shutting_down = false;
+    let mut cutting_off = false;
+    let result = loop {
+        tokio::select! {
+            result = conn.as_mut() => break result,
+            _ = &mut lifetime, if !shutting_down => {
+                tracing::debug!(?peer, "max connection lifetime reached");
+                closed_by = Some("max_lifetime");
+                shutting_down = true;
+                conn.as_mut().graceful_shutdown();
+                grace.as_mut().reset(tokio::time::Instant::now() + limits.close_grace);
+                cutting_off = true;
+            }
+            _ = &mut idle, if !shutting_down => {
+                tracing::debug!(?peer, "idle timeout reached");
+                closed_by = Some("idle_timeout");
+                shutting_down = true;
+                conn.as_mut().graceful_shutdown();
+                grace.as_mut().reset(tokio::time::Instant::now() + limits.close_grace);
+                cutting_off = true;
+            }
+            _ = &mut grace, if cutting_off => {
+                tracing::debug!(?peer, "requests still running after the close grace period");
+                break Ok(());
+            }
+            _ = activity.abort_requested() => {
+                tracing::debug!(?peer, "request body read timeout");
+                closed_by = Some("body_read_timeout");
+                break Ok(());
+            }
+            _ = activity.close_requested(), if !shutting_down => {
+                tracing::debug!(?peer, "keep-alive limit reached");
+                shutting_down = true;
+                conn.as_mut().graceful_shutdown();
+            }
+            _ = draining(&mut drain), if !shutting_down => {
+                shutting_down = true;
+                conn.as_mut().graceful_shutdown();
+            }
+        }
+    };
+
+    let rejected = match result {
+        Ok(()) => None,
+        Err(err) => {
+            tracing::warn!(?peer, %err, "connection error");
+            validation::parse_rejection(&err)
+        }
+    };
+    (closed_by, rejected)
+}
+
+/// Connection settings shared by every connection of the listener.
+fn build_http(options: &HttpOptions, limits: &ConnectionLimits) -> Http {
+    let mut http = Http::new();
+    // Both setters reset the mode when passed `false`, so only call one
+    if !options.http2 {
+        http.http1_only(true);
+    } else if options.http2_only {
+        http.http2_only(true);
+    }
+    http.http1_keep_alive(options.http1_keep_alive)
+        .http1_half_close(options.http1_half_close)
+        .http2_max_concurrent_streams(options.http2_max_concurrent_streams);
+
+    if let Some(interval) = options.http2_keep_alive_interval_secs {
+        http.http2_keep_alive_interval(Duration::from_secs(interval))
+            .http2_keep_alive_timeout(Duration::from_secs(options.http2_keep_alive_timeout_secs));
+    }
+    if let Some(size) = options.max_header_size {
+        http.max_buf_size(size)
+            .http2_max_header_list_size(size.try_into().unwrap_or(u32::MAX));
+    }
+    if let Some(timeout) = limits.header_read_timeout {
+        http.http1_header_read_timeout(timeout);
+    }
+    http
+}
+
+async fn optional_sleep(duration: Option<Duration>) {
+    match duration {
+        Some(duration) => tokio::time::sleep(duration).await,
+        None => std::future::pending().await,
+    }
+}
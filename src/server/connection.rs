@@ -0,0 +1,518 @@
+// ────────────────────────────────
+// src/server/connection.rs
+// Per-connection context, limits, and timeouts.
+// ────────────────────────────────
+use crate::config::{ListenerConfig, RequestValidation};
+use crate::metrics::MetricsSink;
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use futures::Stream;
+use hyper::body::{Bytes, HttpBody};
+use hyper::{header::{self, HeaderValue}, Body, Request, Response, StatusCode, Version};
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore, TryAcquireError};
+use tokio::time::Sleep;
+use tower::Service;
+
+/// Address of the client socket, stored in every request's extensions.
+/// Absent for connections accepted on a Unix socket.
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddr(pub SocketAddr);
+
+/// Name of the listener a request arrived on, stored in its extensions.
+#[derive(Debug, Clone)]
+pub struct ListenerName(pub Arc<str>);
+
+/// Limits and timeouts applied to every client connection of a listener.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionLimits {
+    pub max_connections_per_ip: Option<usize>,
+    pub header_read_timeout: Option<Duration>,
+    pub idle_timeout: Option<Duration>,
+    pub write_timeout: Option<Duration>,
+    pub body_read_timeout: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
+    /// How long requests get to finish once a limit closes the connection.
+    pub close_grace: Duration,
+    pub max_requests: Option<u64>,
+    pub max_age: Option<Duration>,
+    pub max_connections: Option<usize>,
+}
+
+impl From<&ListenerConfig> for ConnectionLimits {
+    fn from(config: &ListenerConfig) -> Self {
+        let secs = |v: Option<u64>| v.map(Duration::from_secs);
+        Self {
+            max_connections_per_ip: config.max_connections_per_ip,
+            header_read_timeout: secs(config.header_read_timeout_secs),
+            idle_timeout: secs(config.idle_timeout_secs),
+            write_timeout: secs(config.write_timeout_secs),
+            body_read_timeout: secs(config.body_read_timeout_secs),
+            max_lifetime: secs(config.max_connection_lifetime_secs),
+            close_grace: Duration::from_secs(config.close_grace_secs),
+            max_requests: config.max_requests_per_connection,
+            max_age: secs(config.max_connection_age_secs),
+            max_connections: config.max_connections,
+        }
+    }
+}
+
+/// Caps the open connections of a listener. Acceptors wait for a slot
+/// before accepting, so excess connections queue in the kernel backlog
+/// instead of being accepted and starved.
+#[derive(Clone, Default)]
+pub struct ConnectionGate {
+    slots: Option<Arc<Semaphore>>,
+}
+
+impl ConnectionGate {
+    pub fn new(max: Option<usize>) -> Self {
+        Self {
+            slots: max.map(|max| Arc::new(Semaphore::new(max))),
+        }
+    }
+
+    /// Wait for a free slot; it's released when the permit is dropped.
+    pub async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let slots = self.slots.clone()?;
+        // The semaphore is never closed
+        slots.acquire_owned().await.ok()
+    }
+
+    /// A free slot without waiting, `Ok(None)` if there's no limit, or
+    /// `Err` if they're all taken.
+    pub fn try_acquire(&self) -> Result<Option<OwnedSemaphorePermit>, TryAcquireError> {
+        match &self.slots {
+            Some(slots) => slots.clone().try_acquire_owned().map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Open connection counts per client IP, shared by all acceptors of a listener.
+pub struct IpConnectionTracker {
+    counts: DashMap<IpAddr, usize>,
+    max: usize,
+}
+
+impl IpConnectionTracker {
+    pub fn new(max: usize) -> Self {
+        Self {
+            counts: DashMap::new(),
+            max,
+        }
+    }
+
+    /// Reserve a slot for `ip`, or `None` if it's already at the limit.
+    pub fn try_acquire(self: &Arc<Self>, ip: IpAddr) -> Option<IpConnectionGuard> {
+        let mut count = self.counts.entry(ip).or_insert(0);
+        if *count >= self.max {
+            return None;
+        }
+        *count += 1;
+        Some(IpConnectionGuard {
+            tracker: self.clone(),
+            ip,
+        })
+    }
+}
+
+/// Releases the IP slot when the connection ends.
+pub struct IpConnectionGuard {
+    tracker: Arc<IpConnectionTracker>,
+    ip: IpAddr,
+}
+
+impl Drop for IpConnectionGuard {
+    fn drop(&mut self) {
+        self.tracker
+            .counts
+            .remove_if_mut(&self.ip, |_, count| {
+                *count -= 1;
+                *count == 0
+            });
+    }
+}
+
+/// Tracks when a connection last moved bytes and how many requests it has in
+/// flight, so idle connections can be told apart from slow requests.
+pub struct ConnectionActivity {
+    accepted_at: Instant,
+    last_io_ms: AtomicU64,
+    in_flight: AtomicUsize,
+    close: Notify,
+    abort: Notify,
+}
+
+impl ConnectionActivity {
+    pub fn new(accepted_at: Instant) -> Self {
+        Self {
+            accepted_at,
+            last_io_ms: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+            close: Notify::new(),
+            abort: Notify::new(),
+        }
+    }
+
+    pub fn touch(&self) {
+        let now = self.accepted_at.elapsed().as_millis() as u64;
+        self.last_io_ms.store(now, Ordering::Relaxed);
+    }
+
+    fn idle_for(&self) -> Duration {
+        let now = self.accepted_at.elapsed().as_millis() as u64;
+        Duration::from_millis(now.saturating_sub(self.last_io_ms.load(Ordering::Relaxed)))
+    }
+
+    fn is_busy(&self) -> bool {
+        self.in_flight.load(Ordering::Relaxed) > 0
+    }
+
+    /// Resolves once a request handler asked for the connection to be
+    /// gracefully closed.
+    pub async fn close_requested(&self) {
+        self.close.notified().await
+    }
+
+    /// Resolves once a request body ran out of time, and the connection
+    /// must close without finishing its requests.
+    pub async fn abort_requested(&self) {
+        self.abort.notified().await
+    }
+
+    /// Resolves once the connection has had no requests in flight and no I/O
+    /// for `timeout`.
+    pub async fn idle(&self, timeout: Duration) {
+        loop {
+            let idle_for = self.idle_for();
+            if !self.is_busy() && idle_for >= timeout {
+                return;
+            }
+            tokio::time::sleep(timeout.saturating_sub(idle_for).max(Duration::from_millis(10)))
+                .await;
+        }
+    }
+}
+
+/// A request in flight on a connection, until its response is ready or
+/// hyper drops it (a client cancelling or resetting the stream).
+struct InFlight(Arc<ConnectionActivity>);
+
+impl InFlight {
+    fn new(activity: Arc<ConnectionActivity>) -> Self {
+        activity.in_flight.fetch_add(1, Ordering::Relaxed);
+        Self(activity)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A request body that fails, and has its connection aborted, once it
+/// hasn't arrived in full by its deadline.
+struct TimedBody {
+    inner: Body,
+    deadline: Pin<Box<Sleep>>,
+    activity: Arc<ConnectionActivity>,
+}
+
+impl Stream for TimedBody {
+    type Item = Result<Bytes, Box<dyn std::error::Error + Send + Sync>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Poll::Ready(chunk) = Pin::new(&mut self.inner).poll_data(cx) {
+            return Poll::Ready(chunk.map(|chunk| chunk.map_err(Into::into)));
+        }
+        match self.deadline.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.activity.abort.notify_one();
+                let error = io::Error::new(io::ErrorKind::TimedOut, "request body read timeout");
+                Poll::Ready(Some(Err(error.into())))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Stream wrapper recording I/O activity and failing writes the client
+/// hasn't drained within the write timeout.
+pub struct MonitoredStream<S> {
+    inner: S,
+    activity: Arc<ConnectionActivity>,
+    write_timeout: Option<Duration>,
+    write_deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> MonitoredStream<S> {
+    pub fn new(inner: S, activity: Arc<ConnectionActivity>, write_timeout: Option<Duration>) -> Self {
+        Self {
+            inner,
+            activity,
+            write_timeout,
+            write_deadline: None,
+        }
+    }
+
+    /// Called when a write is pending; errors once the deadline passes.
+    fn poll_write_deadline(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let Some(timeout) = self.write_timeout else {
+            return Poll::Pending;
+        };
+        let deadline = self
+            .write_deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        match deadline.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "client write timeout",
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for MonitoredStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if matches!(result, Poll::Ready(Ok(()))) && buf.filled().len() > before {
+            self.activity.touch();
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for MonitoredStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match Pin::new(&mut self.inner).poll_write(cx, buf) {
+            Poll::Ready(result) => {
+                self.write_deadline = None;
+                if matches!(result, Ok(n) if n > 0) {
+                    self.activity.touch();
+                }
+                Poll::Ready(result)
+            }
+            Poll::Pending => self.poll_write_deadline(cx).map(|r| r.map(|_| 0)),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match Pin::new(&mut self.inner).poll_flush(cx) {
+            Poll::Ready(result) => {
+                self.write_deadline = None;
+                Poll::Ready(result)
+            }
+            Poll::Pending => self.poll_write_deadline(cx),
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Per-connection service wrapper: attaches [`PeerAddr`] and
+/// [`ListenerName`], rejects requests
+/// failing the listener's request validation, counts in-flight requests,
+/// asks clients to reconnect once keep-alive limits are reached,
+/// advertises HTTP/3 when it's served, and optionally records time from
+/// accept to the first response headers (which separates slow
+/// clients/networks from slow backends).
+#[derive(Clone)]
+pub struct ConnectionService<H> {
+    inner: H,
+    peer: Option<SocketAddr>,
+    listener: Option<Arc<str>>,
+    activity: Arc<ConnectionActivity>,
+    metrics: Option<(Arc<dyn MetricsSink>, Arc<str>)>,
+    observed: Arc<AtomicBool>,
+    max_requests: Option<u64>,
+    max_age: Option<Duration>,
+    requests: Arc<AtomicU64>,
+    alt_svc: Option<HeaderValue>,
+    validation: RequestValidation,
+    body_read_timeout: Option<Duration>,
+}
+
+impl<H> ConnectionService<H> {
+    pub fn new(inner: H, peer: Option<SocketAddr>, activity: Arc<ConnectionActivity>) -> Self {
+        Self {
+            inner,
+            peer,
+            listener: None,
+            activity,
+            metrics: None,
+            observed: Arc::new(AtomicBool::new(false)),
+            max_requests: None,
+            max_age: None,
+            requests: Arc::new(AtomicU64::new(0)),
+            alt_svc: None,
+            validation: RequestValidation::default(),
+            body_read_timeout: None,
+        }
+    }
+
+    /// Close the connection after the response to the `max_requests`th
+    /// request, or to the first request once it is older than `max_age`.
+    pub fn with_keep_alive_limits(mut self, max_requests: Option<u64>, max_age: Option<Duration>) -> Self {
+        self.max_requests = max_requests;
+        self.max_age = max_age;
+        self
+    }
+
+    fn keep_alive_exhausted(&self) -> bool {
+        let served = self.requests.fetch_add(1, Ordering::Relaxed) + 1;
+        self.max_requests.is_some_and(|max| served >= max)
+            || self
+                .max_age
+                .is_some_and(|age| self.activity.accepted_at.elapsed() >= age)
+    }
+
+    /// Tag requests as having arrived on `listener`.
+    pub fn with_listener(mut self, listener: Arc<str>) -> Self {
+        self.listener = Some(listener);
+        self
+    }
+
+    /// Record time to the first response headers, and rejected requests,
+    /// for `listener`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsSink>, listener: Arc<str>) -> Self {
+        self.metrics = Some((metrics, listener));
+        self
+    }
+
+    pub fn with_validation(mut self, validation: RequestValidation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Close the connection when a request body hasn't arrived in full
+    /// within `timeout`.
+    pub fn with_body_read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.body_read_timeout = timeout;
+        self
+    }
+
+    /// Add `Alt-Svc: alt_svc` to responses that don't have one.
+    pub fn with_alt_svc(mut self, alt_svc: Option<HeaderValue>) -> Self {
+        self.alt_svc = alt_svc;
+        self
+    }
+}
+
+impl<H> Service<Request<Body>> for ConnectionService<H>
+where
+    H: Service<Request<Body>, Response = Response<Body>>,
+    H::Future: Send + 'static,
+{
+    type Response = H::Response;
+    type Error = H::Error;
+    type Future = BoxFuture<'static, Result<H::Response, H::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        if let Some(peer) = self.peer {
+            req.extensions_mut().insert(PeerAddr(peer));
+        }
+        if let Some(listener) = &self.listener {
+            req.extensions_mut().insert(ListenerName(listener.clone()));
+        }
+        if let Err(reason) = super::validation::validate(self.validation, &mut req) {
+            tracing::warn!(peer = ?self.peer, reason, "Rejected request with ambiguous framing");
+            if let Some((metrics, listener)) = &self.metrics {
+                metrics.record_request_rejected(listener, reason);
+            }
+            // What follows on the connection may be framed differently than we'd read it
+            let response = Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header(header::CONNECTION, "close")
+                .body(Body::from("Bad Request"))
+                .unwrap();
+            return Box::pin(async move { Ok(response) });
+        }
+        if let Some(timeout) = self.body_read_timeout.filter(|_| !req.body().is_end_stream()) {
+            let activity = self.activity.clone();
+            req = req.map(|body| {
+                let timed = TimedBody { inner: body, deadline: Box::pin(tokio::time::sleep(timeout)), activity };
+                Body::wrap_stream(timed)
+            });
+        }
+        let in_flight = InFlight::new(self.activity.clone());
+        let close = self.keep_alive_exhausted();
+        let http2 = req.version() == Version::HTTP_2;
+
+        let future = self.inner.call(req);
+        let activity = self.activity.clone();
+        let first_byte = self.metrics.clone();
+        let observed = self.observed.clone();
+        let alt_svc = self.alt_svc.clone();
+
+        Box::pin(async move {
+            let mut result = future.await;
+            drop(in_flight);
+            if close {
+                // `Connection` is illegal in HTTP/2; send GOAWAY instead
+                if http2 {
+                    activity.close.notify_one();
+                } else if let Ok(response) = &mut result {
+                    response
+                        .headers_mut()
+                        .insert(header::CONNECTION, HeaderValue::from_static("close"));
+                }
+            }
+            if let (Some(alt_svc), Ok(response)) = (alt_svc, &mut result) {
+                response.headers_mut().entry(header::ALT_SVC).or_insert(alt_svc);
+            }
+            if let Some((metrics, listener)) = first_byte {
+                if !observed.swap(true, Ordering::Relaxed) {
+                    metrics.record_connection_first_byte(&listener, activity.accepted_at.elapsed());
+                }
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn test_dropped_requests_leave_the_connection_idle() {
+        let activity = Arc::new(ConnectionActivity::new(Instant::now()));
+        let handler = tower::service_fn(|_req: Request<Body>| async {
+            std::future::pending::<Result<Response<Body>, Infallible>>().await
+        });
+        let mut service = ConnectionService::new(handler, None, activity.clone());
+
+        let call = service.call(Request::new(Body::empty()));
+        assert!(activity.is_busy());
+        // As hyper does when the client resets the stream
+        drop(call);
+        assert!(!activity.is_busy());
+    }
+}
@@ -0,0 +1,79 @@
+// ────────────────────────────────
+// src/server/drain.rs
+// Graceful shutdown: stop accepting, then wait for open connections.
+// ────────────────────────────────
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+/// Owned by `main`; tells every listener to stop accepting and waits until
+/// all connections holding a [`DrainWatcher`] have finished.
+pub struct Drain {
+    signal: watch::Sender<bool>,
+    watcher: DrainWatcher,
+    done: mpsc::Receiver<()>,
+}
+
+impl Drain {
+    pub fn new() -> Self {
+        let (signal, signal_rx) = watch::channel(false);
+        let (done_tx, done) = mpsc::channel(1);
+        Self {
+            signal,
+            watcher: DrainWatcher {
+                signal: signal_rx,
+                _done: done_tx,
+            },
+            done,
+        }
+    }
+
+    /// Handle for accept loops and connections. Drain waits for every clone
+    /// to be dropped.
+    pub fn watcher(&self) -> DrainWatcher {
+        self.watcher.clone()
+    }
+
+    /// Signal draining and wait up to `timeout` for open connections to
+    /// close. Returns `false` if some were still open at the deadline.
+    pub async fn drain(self, timeout: Duration) -> bool {
+        let Self {
+            signal,
+            watcher,
+            mut done,
+        } = self;
+        let _ = signal.send(true);
+        drop(watcher);
+        // `recv` yields `None` once the last watcher is gone
+        tokio::time::timeout(timeout, done.recv()).await.is_ok()
+    }
+}
+
+impl Default for Drain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone)]
+pub struct DrainWatcher {
+    signal: watch::Receiver<bool>,
+    _done: mpsc::Sender<()>,
+}
+
+impl DrainWatcher {
+    /// Resolves once draining has started.
+    pub async fn draining(&mut self) {
+        if self.signal.wait_for(|draining| *draining).await.is_err() {
+            // Drain dropped without draining: never resolve
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// [`DrainWatcher::draining`] for optional watchers; never resolves for `None`.
+pub(crate) async fn draining(drain: &mut Option<DrainWatcher>) {
+    match drain {
+        Some(drain) => drain.draining().await,
+        None => std::future::pending().await,
+    }
+}
@@ -31,11 +31,12 @@ impl Service<Request<Body>> for RequestHandler {
     fn call(&mut self, req: Request<Body>) -> Self::Future {
         let proxy = self.proxy.clone();
         Box::pin(async move {
-            proxy.handle(req).await.map_err(|e| {
+            // An error here would make hyper drop the connection, so the
+            // client gets the error response instead
+            Ok(proxy.handle(req).await.unwrap_or_else(|e| {
                 tracing::error!(%e, "proxy error");
-                // Fixed: Use a public error constructor
-                Box::new(e) as Box<dyn std::error::Error + Send + Sync>
-            })
+                e.into()
+            }))
         })
     }
 }
\ No newline at end of file
@@ -0,0 +1,363 @@
+// ────────────────────────────────
+// src/server/handover.rs
+// Listening sockets inherited from systemd (`LISTEN_FDS`) or from the
+// previous LB process, and handed to the next one on upgrade.
+// ────────────────────────────────
+use crate::server::listener::{bind_tcp, bind_tcp_reuseport, bind_unix, BoundListener};
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::path::Path;
+use tokio::net::TcpListener;
+
+#[cfg(unix)]
+use {
+    crate::server::listener::remove_stale_socket,
+    nix::sys::socket::{self, ControlMessage, ControlMessageOwned, MsgFlags, SockaddrStorage},
+    std::io::{IoSlice, IoSliceMut},
+    std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd},
+    std::sync::Mutex,
+    std::time::Duration,
+    tokio::net::UnixListener,
+};
+
+/// First inherited descriptor under systemd's socket activation protocol.
+#[cfg(unix)]
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Upper bound on descriptors accepted in one handover.
+#[cfg(unix)]
+const MAX_HANDOVER_FDS: usize = 256;
+
+#[cfg(unix)]
+struct InheritedSocket {
+    name: Option<String>,
+    fd: OwnedFd,
+    from_systemd: bool,
+}
+
+/// Binds listeners, preferring inherited sockets, and remembers what was
+/// bound so it can be passed on.
+///
+/// Inherited sockets are matched to listeners by local address, preferring
+/// the one with the listener's name (`LISTEN_FDNAMES`, or the name sent by
+/// the previous process). A systemd socket named after the listener is also
+/// taken when its address is spelled differently (e.g. `[::]:8080`).
+#[derive(Default)]
+pub struct SocketHandover {
+    #[cfg(unix)]
+    inherited: Mutex<Vec<InheritedSocket>>,
+    #[cfg(unix)]
+    bound: Mutex<Vec<(String, OwnedFd)>>,
+}
+
+#[cfg(unix)]
+impl SocketHandover {
+    /// Collect sockets passed by systemd, then ask the process listening on
+    /// `control_socket` (if any) for its listeners.
+    pub fn inherit(control_socket: Option<&Path>) -> Result<Self> {
+        let mut inherited = from_systemd_env();
+        if let Some(path) = control_socket {
+            inherited.extend(request_handover(path)?);
+        }
+        if !inherited.is_empty() {
+            tracing::info!("Inherited {} listening socket(s)", inherited.len());
+        }
+        Ok(Self {
+            inherited: Mutex::new(inherited),
+            bound: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub async fn bind_tcp(&self, name: &str, addr: SocketAddr) -> Result<TcpListener> {
+        let listener = match self.take_tcp(name, addr) {
+            Some(listener) => listener,
+            None => bind_tcp(addr).await?,
+        };
+        self.register(name, &listener)?;
+        Ok(listener)
+    }
+
+    pub fn bind_tcp_reuseport(
+        &self,
+        name: &str,
+        addr: SocketAddr,
+        count: usize,
+        backlog: u32,
+    ) -> Result<Vec<TcpListener>> {
+        let mut listeners: Vec<TcpListener> =
+            std::iter::from_fn(|| self.take_tcp(name, addr)).take(count).collect();
+        let missing = count - listeners.len();
+        if missing > 0 {
+            listeners.extend(bind_tcp_reuseport(addr, missing, backlog)?);
+        }
+        for listener in &listeners {
+            self.register(name, listener)?;
+        }
+        Ok(listeners)
+    }
+
+    pub fn bind_unix(&self, name: &str, path: &Path) -> Result<BoundListener> {
+        let inherited = self.take(name, |addr| {
+            addr.as_unix_addr().and_then(|unix| unix.path()) == Some(path)
+        });
+        let listener = match inherited {
+            Some(fd) => {
+                let listener = std::os::unix::net::UnixListener::from(fd);
+                listener.set_nonblocking(true)?;
+                BoundListener::Unix(UnixListener::from_std(listener)?)
+            }
+            None => bind_unix(path)?,
+        };
+        if let BoundListener::Unix(unix) = &listener {
+            self.register(name, unix)?;
+        }
+        Ok(listener)
+    }
+
+    /// Serve handover requests on `path`. Resolves once a new process has
+    /// received this process's listeners, at which point it should drain.
+    pub async fn serve_handover(&self, path: &Path) -> Result<()> {
+        remove_stale_socket(path)?;
+        let control = UnixListener::bind(path)?;
+        tracing::info!("Accepting socket handover requests on {}", path.display());
+
+        loop {
+            let (stream, _) = control.accept().await?;
+            let stream = stream.into_std()?;
+            stream.set_nonblocking(false)?;
+            let bound = self.duplicate_bound()?;
+
+            let sent = tokio::task::spawn_blocking(move || send_sockets(&stream, &bound)).await?;
+            match sent {
+                Ok(count) => {
+                    tracing::info!("Handed {} listening socket(s) to a new process", count);
+                    return Ok(());
+                }
+                Err(e) => tracing::warn!("Socket handover failed: {}", e),
+            }
+        }
+    }
+
+    fn take_tcp(&self, name: &str, addr: SocketAddr) -> Option<TcpListener> {
+        let fd = self.take(name, |local| sockaddr_to_std(local) == Some(addr))?;
+        let listener = std::net::TcpListener::from(fd);
+        listener.set_nonblocking(true).ok()?;
+        TcpListener::from_std(listener).ok()
+    }
+
+    fn take(&self, name: &str, matches_addr: impl Fn(&SockaddrStorage) -> bool) -> Option<OwnedFd> {
+        let mut inherited = self.inherited.lock().unwrap();
+        let named = |s: &InheritedSocket| s.name.as_deref() == Some(name);
+        let local = |s: &InheritedSocket| {
+            socket::getsockname::<SockaddrStorage>(s.fd.as_raw_fd())
+                .map(|addr| matches_addr(&addr))
+                .unwrap_or(false)
+        };
+        let index = inherited
+            .iter()
+            .position(|s| named(s) && local(s))
+            .or_else(|| inherited.iter().position(local))
+            .or_else(|| inherited.iter().position(|s| s.from_systemd && named(s)))?;
+        Some(inherited.remove(index).fd)
+    }
+
+    fn register(&self, name: &str, listener: &impl AsFd) -> Result<()> {
+        let fd = listener.as_fd().try_clone_to_owned()?;
+        self.bound.lock().unwrap().push((name.to_string(), fd));
+        Ok(())
+    }
+
+    fn duplicate_bound(&self) -> Result<Vec<(String, OwnedFd)>> {
+        self.bound
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, fd)| Ok((name.clone(), fd.try_clone()?)))
+            .collect()
+    }
+}
+
+#[cfg(not(unix))]
+impl SocketHandover {
+    pub fn inherit(control_socket: Option<&Path>) -> Result<Self> {
+        if control_socket.is_some() {
+            anyhow::bail!("socket handover is only supported on unix platforms");
+        }
+        Ok(Self::default())
+    }
+
+    pub async fn bind_tcp(&self, _name: &str, addr: SocketAddr) -> Result<TcpListener> {
+        bind_tcp(addr).await
+    }
+
+    pub fn bind_tcp_reuseport(
+        &self,
+        _name: &str,
+        addr: SocketAddr,
+        count: usize,
+        backlog: u32,
+    ) -> Result<Vec<TcpListener>> {
+        bind_tcp_reuseport(addr, count, backlog)
+    }
+
+    pub fn bind_unix(&self, _name: &str, path: &Path) -> Result<BoundListener> {
+        bind_unix(path)
+    }
+
+    pub async fn serve_handover(&self, _path: &Path) -> Result<()> {
+        anyhow::bail!("socket handover is only supported on unix platforms")
+    }
+}
+
+/// Sockets passed under systemd's socket activation protocol.
+#[cfg(unix)]
+fn from_systemd_env() -> Vec<InheritedSocket> {
+    let for_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        == Some(std::process::id());
+    let count = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<RawFd>().ok())
+        .unwrap_or(0);
+    let names = std::env::var("LISTEN_FDNAMES").unwrap_or_default();
+
+    // Not meant to be inherited by anything we might spawn
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+    if !for_us {
+        return Vec::new();
+    }
+
+    let mut names = names.split(':');
+    (0..count)
+        .map(|i| InheritedSocket {
+            name: names.next().filter(|n| !n.is_empty()).map(str::to_string),
+            // Safety: systemd hands these descriptors to this process
+            fd: unsafe { OwnedFd::from_raw_fd(LISTEN_FDS_START + i) },
+            from_systemd: true,
+        })
+        .collect()
+}
+
+/// Ask the process serving `path` for its listening sockets. Returns nothing
+/// if no process is listening there.
+#[cfg(unix)]
+fn request_handover(path: &Path) -> Result<Vec<InheritedSocket>> {
+    use std::io::ErrorKind;
+
+    let stream = match std::os::unix::net::UnixStream::connect(path) {
+        Ok(stream) => stream,
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+            return Ok(Vec::new());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    tracing::info!("Requesting listening sockets from {}", path.display());
+    receive_sockets(&stream)
+}
+
+/// Counterpart of [`send_sockets`].
+#[cfg(unix)]
+fn receive_sockets(stream: &std::os::unix::net::UnixStream) -> Result<Vec<InheritedSocket>> {
+    let mut payload = vec![0u8; 64 * 1024];
+    let mut cmsg = nix::cmsg_space!([RawFd; MAX_HANDOVER_FDS]);
+    let mut iov = [IoSliceMut::new(&mut payload)];
+    let msg = socket::recvmsg::<()>(
+        stream.as_raw_fd(),
+        &mut iov,
+        Some(&mut cmsg),
+        MsgFlags::MSG_CMSG_CLOEXEC,
+    )?;
+    let len = msg.bytes;
+
+    let mut fds = Vec::new();
+    for cmsg in msg.cmsgs()? {
+        if let ControlMessageOwned::ScmRights(received) = cmsg {
+            // Safety: the kernel just installed these descriptors for us
+            fds.extend(received.into_iter().map(|fd| unsafe { OwnedFd::from_raw_fd(fd) }));
+        }
+    }
+
+    let names = String::from_utf8_lossy(&payload[..len]).into_owned();
+    let mut names = names.split('\n');
+    Ok(fds
+        .into_iter()
+        .map(|fd| InheritedSocket {
+            name: names.next().filter(|n| !n.is_empty()).map(str::to_string),
+            fd,
+            from_systemd: false,
+        })
+        .collect())
+}
+
+/// Send `sockets` in a single message: newline-separated names as the
+/// payload, the descriptors as `SCM_RIGHTS`.
+#[cfg(unix)]
+fn send_sockets(stream: &std::os::unix::net::UnixStream, sockets: &[(String, OwnedFd)]) -> Result<usize> {
+    if sockets.len() > MAX_HANDOVER_FDS {
+        anyhow::bail!("too many listening sockets to hand over: {}", sockets.len());
+    }
+    let names = sockets
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let fds: Vec<RawFd> = sockets.iter().map(|(_, fd)| fd.as_raw_fd()).collect();
+
+    // An empty payload would be indistinguishable from EOF on the other side
+    let payload = if names.is_empty() { "\n" } else { names.as_str() };
+    let cmsgs = [ControlMessage::ScmRights(&fds)];
+    let cmsgs = if fds.is_empty() { &[][..] } else { &cmsgs[..] };
+    socket::sendmsg::<()>(
+        stream.as_raw_fd(),
+        &[IoSlice::new(payload.as_bytes())],
+        cmsgs,
+        MsgFlags::empty(),
+        None,
+    )?;
+    Ok(sockets.len())
+}
+
+#[cfg(unix)]
+fn sockaddr_to_std(addr: &SockaddrStorage) -> Option<SocketAddr> {
+    if let Some(v4) = addr.as_sockaddr_in() {
+        return Some(SocketAddr::V4((*v4).into()));
+    }
+    addr.as_sockaddr_in6().map(|v6| SocketAddr::V6((*v6).into()))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sockets_survive_handover_with_names() {
+        let http = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let admin = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let sockets = vec![
+            ("http".to_string(), OwnedFd::from(http.try_clone().unwrap())),
+            ("@admin".to_string(), OwnedFd::from(admin.try_clone().unwrap())),
+        ];
+
+        let (old, new) = std::os::unix::net::UnixStream::pair().unwrap();
+        assert_eq!(send_sockets(&old, &sockets).unwrap(), 2);
+        let received = receive_sockets(&new).unwrap();
+
+        let handover = SocketHandover {
+            inherited: Mutex::new(received),
+            bound: Mutex::new(Vec::new()),
+        };
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let taken = handover.bind_tcp("@admin", admin.local_addr().unwrap()).await.unwrap();
+            assert_eq!(taken.local_addr().unwrap(), admin.local_addr().unwrap());
+            let taken = handover.bind_tcp("http", http.local_addr().unwrap()).await.unwrap();
+            assert_eq!(taken.local_addr().unwrap(), http.local_addr().unwrap());
+        });
+        assert!(handover.inherited.lock().unwrap().is_empty());
+        assert_eq!(handover.bound.lock().unwrap().len(), 2);
+    }
+}
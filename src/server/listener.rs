@@ -1,12 +1,195 @@
 // ────────────────────────────────
 // src/server/listener.rs
-// Encapsulates low‑level TCP bind/accept so we can swap TLS later.
+// Encapsulates low‑level TCP/Unix bind/accept so we can swap TLS later.
 // ────────────────────────────────
 use anyhow::Result;
+use std::io;
 use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{TcpSocket, UnixListener, UnixStream};
+
+/// A bound listening socket.
+pub enum BoundListener {
+    Tcp(TcpListener),
+    /// Unix domain socket, for sidecar deployments behind another proxy.
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl BoundListener {
+    /// Accept one connection. Unix socket clients have no peer address.
+    pub async fn accept(&self) -> io::Result<(ClientStream, Option<SocketAddr>)> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                Ok((ClientStream::Tcp(stream), Some(peer)))
+            }
+            #[cfg(unix)]
+            Self::Unix(listener) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((ClientStream::Unix(stream), None))
+            }
+        }
+    }
+}
+
+const ACCEPT_BACKOFF_MIN: Duration = Duration::from_millis(10);
+const ACCEPT_BACKOFF_MAX: Duration = Duration::from_secs(1);
+
+/// Keeps accept loops alive through failed `accept()` calls. Running out of
+/// file descriptors or memory leaves the listener itself fine, so the loop
+/// pauses (doubling up to a second) instead of spinning; other errors only
+/// affect the one connection.
+#[derive(Default)]
+pub struct AcceptBackoff {
+    delay: Option<Duration>,
+}
+
+impl AcceptBackoff {
+    pub fn reset(&mut self) {
+        self.delay = None;
+    }
+
+    /// Log a failed accept and pause if needed. Returns the error kind for
+    /// the `lb_accept_errors_total` metric.
+    pub async fn on_error(&mut self, listener: &str, err: &io::Error) -> &'static str {
+        if !is_resource_exhaustion(err) {
+            tracing::warn!(listener, %err, "accept failed");
+            return "other";
+        }
+        let delay = self
+            .delay
+            .map_or(ACCEPT_BACKOFF_MIN, |d| (d * 2).min(ACCEPT_BACKOFF_MAX));
+        self.delay = Some(delay);
+        tracing::error!(listener, %err, "accept failed, pausing for {:?}", delay);
+        tokio::time::sleep(delay).await;
+        "resource_exhausted"
+    }
+}
+
+#[cfg(unix)]
+fn is_resource_exhaustion(err: &io::Error) -> bool {
+    use nix::errno::Errno;
+
+    matches!(
+        err.raw_os_error().map(Errno::from_raw),
+        Some(Errno::EMFILE | Errno::ENFILE | Errno::ENOBUFS | Errno::ENOMEM)
+    )
+}
+
+#[cfg(not(unix))]
+fn is_resource_exhaustion(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::OutOfMemory
+}
+
+/// An accepted client connection.
+pub enum ClientStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl AsyncRead for ClientStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ClientStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(unix)]
+            Self::Unix(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(unix)]
+            Self::Unix(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
 
 pub async fn bind_tcp(addr: SocketAddr) -> Result<TcpListener> {
     let listener = TcpListener::bind(addr).await?;
     Ok(listener)
 }
+
+/// Bind `count` listeners on the same address with `SO_REUSEPORT`, letting
+/// the kernel balance new connections across them.
+#[cfg(unix)]
+pub fn bind_tcp_reuseport(addr: SocketAddr, count: usize, backlog: u32) -> Result<Vec<TcpListener>> {
+    (0..count)
+        .map(|_| {
+            let socket = if addr.is_ipv4() {
+                TcpSocket::new_v4()?
+            } else {
+                TcpSocket::new_v6()?
+            };
+            socket.set_reuseaddr(true)?;
+            socket.set_reuseport(true)?;
+            socket.bind(addr)?;
+            Ok(socket.listen(backlog)?)
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+pub fn bind_tcp_reuseport(_addr: SocketAddr, _count: usize, _backlog: u32) -> Result<Vec<TcpListener>> {
+    anyhow::bail!("SO_REUSEPORT is only supported on unix platforms")
+}
+
+/// Bind a Unix domain socket at `path`, replacing a stale socket file left
+/// behind by a previous run.
+#[cfg(unix)]
+pub fn bind_unix(path: &Path) -> Result<BoundListener> {
+    remove_stale_socket(path)?;
+    Ok(BoundListener::Unix(UnixListener::bind(path)?))
+}
+
+#[cfg(unix)]
+pub(crate) fn remove_stale_socket(path: &Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", path.display());
+        }
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn bind_unix(path: &Path) -> Result<BoundListener> {
+    anyhow::bail!("cannot listen on {}: Unix sockets are only supported on unix platforms", path.display())
+}
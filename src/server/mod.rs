@@ -1,7 +1,21 @@
 // src/server/mod.rs
+pub mod access;
 pub mod builder;
+pub mod connection;
+pub mod drain;
 pub mod handler;
+pub mod handover;
+#[cfg(feature = "http3")]
+pub mod http3;
 pub mod listener;
+pub mod metrics_server;
+pub mod tls;
+pub mod validation;
 
+pub use access::AccessControl;
 pub use builder::ServerBuilder;
-pub use handler::RequestHandler;
\ No newline at end of file
+pub use connection::{ConnectionGate, ConnectionLimits, ListenerName, PeerAddr};
+pub use drain::{Drain, DrainWatcher};
+pub use handler::RequestHandler;
+pub use handover::SocketHandover;
+pub use metrics_server::MetricsServer;
\ No newline at end of file
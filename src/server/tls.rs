@@ -0,0 +1,105 @@
+// src/server/tls.rs
+use crate::config::TlsConfig;
+use crate::metrics::MetricsSink;
+use anyhow::{Context as _, Result};
+use futures::Stream;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_native_tls::{TlsAcceptor, TlsStream};
+use tracing::{debug, warn};
+
+/// How long a client gets to finish its handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handshakes waiting for the server to take them.
+const BACKLOG: usize = 64;
+
+/// An acceptor for the certificate `config` names.
+pub fn acceptor(config: &TlsConfig) -> Result<TlsAcceptor> {
+    let cert = std::fs::read(&config.cert).with_context(|| format!("Reading {}", config.cert.display()))?;
+    let key = std::fs::read(&config.key).with_context(|| format!("Reading {}", config.key.display()))?;
+    let identity = native_tls::Identity::from_pkcs8(&cert, &key)?;
+    Ok(TlsAcceptor::from(native_tls::TlsAcceptor::new(identity)?))
+}
+
+/// A client connection past its TLS handshake.
+pub struct TlsConnection {
+    stream: TlsStream<TcpStream>,
+    peer: SocketAddr,
+}
+
+impl TlsConnection {
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+}
+
+/// The connections accepted on `listener` that complete a handshake, each
+/// on a task of its own so a slow client can't hold up the rest. Handshake
+/// durations are recorded for the listener `name`. Accepting stops once the
+/// stream is dropped.
+pub fn incoming(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    name: Arc<str>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+) -> impl Stream<Item = io::Result<TlsConnection>> {
+    let (tx, rx) = mpsc::channel(BACKLOG);
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!(error = %e, "Failed to accept a TLS connection");
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        continue;
+                    }
+                },
+                _ = tx.closed() => return,
+            };
+            let accepted_at = Instant::now();
+            let (acceptor, tx, name, metrics) = (acceptor.clone(), tx.clone(), name.clone(), metrics.clone());
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => {
+                        if let Some(metrics) = &metrics {
+                            metrics.record_tls_handshake(&name, accepted_at.elapsed());
+                        }
+                        let _ = tx.send(Ok(TlsConnection { stream, peer })).await;
+                    }
+                    Ok(Err(e)) => debug!(%peer, error = %e, "TLS handshake failed"),
+                    Err(_) => debug!(%peer, "TLS handshake timed out"),
+                }
+            });
+        }
+    });
+    futures::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|connection| (connection, rx)) })
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
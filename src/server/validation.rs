@@ -0,0 +1,121 @@
+// src/server/validation.rs
+//! Request smuggling protections: checks on how HTTP/1 requests are framed,
+//! for backends that might read them differently than hyper does.
+use crate::config::RequestValidation;
+use hyper::header::{CONTENT_LENGTH, HOST, TRANSFER_ENCODING};
+use hyper::{Body, Request, Version};
+
+/// Apply `level` to `req`, dropping a `Content-Length` that
+/// `Transfer-Encoding` overrides. `Err` is why it's rejected, for
+/// `lb_requests_rejected_total`.
+pub fn validate(level: RequestValidation, req: &mut Request<Body>) -> Result<(), &'static str> {
+    // HTTP/2 frames bodies itself, and its parser refuses these headers
+    if !matches!(req.version(), Version::HTTP_09 | Version::HTTP_10 | Version::HTTP_11) {
+        return Ok(());
+    }
+    let headers = req.headers_mut();
+    let chunked = headers.contains_key(TRANSFER_ENCODING);
+    if level == RequestValidation::Strict {
+        // hyper drops a `Content-Length` sent after `Transfer-Encoding`, so
+        // only one sent before it is seen here
+        if chunked && headers.contains_key(CONTENT_LENGTH) {
+            return Err("conflicting_length");
+        }
+        let mut encodings = headers.get_all(TRANSFER_ENCODING).iter();
+        let only_chunked = encodings.next().is_some_and(|te| te.as_bytes().eq_ignore_ascii_case(b"chunked"));
+        if chunked && (!only_chunked || encodings.next().is_some()) {
+            return Err("transfer_encoding");
+        }
+        if headers.get_all(HOST).iter().nth(1).is_some() {
+            return Err("duplicate_host");
+        }
+        let printable = |byte: &u8| matches!(byte, b'\t' | b' '..=b'~');
+        if headers.iter().any(|(name, value)| name.as_str().contains('_') || !value.as_bytes().iter().all(printable)) {
+            return Err("invalid_header");
+        }
+    }
+    if chunked {
+        headers.remove(CONTENT_LENGTH);
+    }
+    Ok(())
+}
+
+/// Why the parser refused a request, if `err` is a parse error; hyper has
+/// already answered it with a 400 (or 414/431) and closed the connection.
+pub fn parse_rejection(err: &hyper::Error) -> Option<&'static str> {
+    if !err.is_parse() {
+        return None;
+    }
+    if err.is_parse_too_large() {
+        return Some("too_large");
+    }
+    // hyper's parse errors are only told apart by their messages
+    let message = err.to_string();
+    Some(match message.as_str() {
+        "invalid HTTP header parsed" => "invalid_header",
+        "invalid content-length parsed" => "content_length",
+        "invalid transfer-encoding parsed" | "unexpected transfer-encoding parsed" => "transfer_encoding",
+        "URI too long" => "too_large",
+        _ => "malformed",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn request(headers: &[(&str, &[u8])]) -> Request<Body> {
+        let mut req = Request::post("/upload");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        req.body(Body::empty()).unwrap()
+    }
+
+    /// What hyper's server makes of `raw`, and the start of its answer.
+    async fn parse(raw: &[u8]) -> (Option<&'static str>, String) {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let service = hyper::service::service_fn(|_| async { Ok::<_, hyper::Error>(hyper::Response::new(Body::empty())) });
+        let conn = tokio::spawn(hyper::server::conn::Http::new().serve_connection(server, service));
+        client.write_all(raw).await.unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        let result = conn.await.unwrap();
+        let status = String::from_utf8_lossy(&response[..12]).into_owned();
+        (result.err().as_ref().and_then(parse_rejection), status)
+    }
+
+    #[tokio::test]
+    async fn test_rejects_ambiguous_requests() {
+        let both: &[(&str, &[u8])] = &[("content-length", b"5"), ("transfer-encoding", b"chunked")];
+        let mut req = request(both);
+        assert_eq!(validate(RequestValidation::Standard, &mut req), Ok(()));
+        assert!(!req.headers().contains_key(CONTENT_LENGTH));
+        assert_eq!(validate(RequestValidation::Strict, &mut request(both)), Err("conflicting_length"));
+
+        for (headers, reason) in [
+            (&[("transfer-encoding", b"gzip, chunked" as &[u8])][..], "transfer_encoding"),
+            (&[("transfer-encoding", b"chunked"), ("transfer-encoding", b"chunked")], "transfer_encoding"),
+            (&[("host", b"a.example"), ("host", b"b.example")], "duplicate_host"),
+            (&[("x_forwarded_for", b"10.0.0.1")], "invalid_header"),
+            (&[("x-name", b"caf\xc3\xa9")], "invalid_header"),
+        ] {
+            assert_eq!(validate(RequestValidation::Strict, &mut request(headers)), Err(reason));
+            assert_eq!(validate(RequestValidation::Standard, &mut request(headers)), Ok(()));
+        }
+        let mut http2 = request(both);
+        *http2.version_mut() = Version::HTTP_2;
+        assert_eq!(validate(RequestValidation::Strict, &mut http2), Ok(()));
+
+        // The parser's own rejections, whatever the level
+        let obs_fold = b"GET / HTTP/1.1\r\nHost: a\r\nX-Long: one\r\n two\r\n\r\n";
+        assert_eq!(parse(obs_fold).await, (Some("invalid_header"), "HTTP/1.1 400".to_string()));
+        let bad_name = b"GET / HTTP/1.1\r\nHost: a\r\nBad Name: x\r\n\r\n";
+        assert_eq!(parse(bad_name).await.0, Some("invalid_header"));
+        let lengths = b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab";
+        assert_eq!(parse(lengths).await.0, Some("content_length"));
+        let encoding = b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: gzip\r\n\r\n";
+        assert_eq!(parse(encoding).await.0, Some("transfer_encoding"));
+    }
+}
@@ -0,0 +1,212 @@
+// src/startup.rs
+//! Checks run before the load balancer starts, so a deployment that can't
+//! work fails at once, with an exit code saying why, instead of after it's
+//! taken traffic.
+use crate::config::Config;
+use crate::proxy::Backend;
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::path::Path;
+use tracing::{error, info, warn};
+
+/// Why the process exited, for orchestrators telling a bad config from a
+/// crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitReason {
+    Ok,
+    /// The load balancer failed after starting.
+    Runtime,
+    /// The config file couldn't be read, parsed or validated.
+    Config,
+    /// A listener's address or socket path can't be bound.
+    Bind,
+    /// None of the configured backends can be resolved.
+    Backends,
+}
+
+impl ExitReason {
+    pub fn code(self) -> i32 {
+        match self {
+            ExitReason::Ok => 0,
+            ExitReason::Runtime => 1,
+            ExitReason::Config => 2,
+            ExitReason::Bind => 3,
+            ExitReason::Backends => 4,
+        }
+    }
+}
+
+/// What the checks found, written as JSON with `--startup-report`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupReport {
+    /// RFC 3339.
+    pub checked_at: String,
+    pub ok: bool,
+    pub exit_reason: ExitReason,
+    pub exit_code: i32,
+    pub checks: Vec<CheckResult>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    /// e.g. `config`, `listener:http` or `backends`.
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl StartupReport {
+    fn new(checks: Vec<CheckResult>, failed: ExitReason) -> Self {
+        let ok = checks.iter().all(|check| check.ok);
+        let exit_reason = if ok { ExitReason::Ok } else { failed };
+        Self {
+            checked_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            ok,
+            exit_reason,
+            exit_code: exit_reason.code(),
+            checks,
+        }
+    }
+
+    /// A report for a config that failed to load.
+    pub fn config_error(error: &anyhow::Error) -> Self {
+        let check = CheckResult { name: "config".to_string(), ok: false, detail: format!("{:#}", error) };
+        Self::new(vec![check], ExitReason::Config)
+    }
+
+    /// Log each check.
+    pub fn log(&self) {
+        for check in &self.checks {
+            match check.ok {
+                true => info!(check = %check.name, detail = %check.detail, "Startup check passed"),
+                false => error!(check = %check.name, detail = %check.detail, "Startup check failed"),
+            }
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Check that every listener can be bound and at least one static backend
+/// resolved. Listeners are skipped when their sockets may be inherited from
+/// systemd or a previous process, which still holds them.
+pub async fn self_check(config: &Config) -> StartupReport {
+    let mut checks = vec![CheckResult { name: "config".to_string(), ok: true, detail: "valid".to_string() }];
+
+    let inheriting = config.runtime.handover_socket.is_some() || std::env::var_os("LISTEN_FDS").is_some();
+    let mut addresses: Vec<(String, SocketAddr)> = Vec::new();
+    for listener in &config.listeners {
+        let name = format!("listener:{}", listener.name);
+        match &listener.unix_socket {
+            Some(path) => checks.push(check_socket_dir(name, path)),
+            None => addresses.push((name, listener.address)),
+        }
+    }
+    if config.metrics.enabled {
+        addresses.push(("listener:@metrics".to_string(), SocketAddr::new(config.metrics.bind, config.metrics.port)));
+    }
+    if config.admin.enabled {
+        addresses.push(("listener:@admin".to_string(), SocketAddr::new(config.admin.bind, config.admin.port)));
+    }
+    if let Some(cluster) = &config.cluster {
+        addresses.push(("listener:@cluster".to_string(), cluster.listen));
+    }
+    for (name, addr) in addresses {
+        checks.push(match inheriting {
+            true => CheckResult { name, ok: true, detail: format!("{} may be inherited, not checked", addr) },
+            false => check_bind(name, addr),
+        });
+    }
+    let bound = checks.iter().all(|check| check.ok);
+
+    checks.push(check_backends(config).await);
+    let failed = if bound { ExitReason::Backends } else { ExitReason::Bind };
+    StartupReport::new(checks, failed)
+}
+
+fn check_bind(name: String, addr: SocketAddr) -> CheckResult {
+    match std::net::TcpListener::bind(addr) {
+        Ok(_) => CheckResult { name, ok: true, detail: format!("{} can be bound", addr) },
+        Err(e) => CheckResult { name, ok: false, detail: format!("{}: {}", addr, e) },
+    }
+}
+
+fn check_socket_dir(name: String, path: &Path) -> CheckResult {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    match dir.is_dir() {
+        true => CheckResult { name, ok: true, detail: format!("{} exists", dir.display()) },
+        false => CheckResult { name, ok: false, detail: format!("no directory {} for {}", dir.display(), path.display()) },
+    }
+}
+
+/// Passes if any static backend resolves; the rest are only warned about,
+/// since health checks take care of backends that are down.
+async fn check_backends(config: &Config) -> CheckResult {
+    let name = "backends".to_string();
+    if config.backends.is_empty() {
+        return CheckResult { name, ok: true, detail: "none configured, discovery provides them".to_string() };
+    }
+    let mut resolved = 0;
+    for backend in &config.backends {
+        let id = Backend::id_for(&backend.url);
+        let found = match (backend.url.scheme(), backend.url.host_str()) {
+            ("unix", _) => match Path::new(backend.url.path()).exists() {
+                true => Ok(()),
+                false => Err("socket not found".to_string()),
+            },
+            (_, Some(host)) => {
+                let port = backend.url.port_or_known_default().unwrap_or(80);
+                match tokio::net::lookup_host((host.trim_matches(['[', ']']), port)).await {
+                    Ok(mut addrs) => addrs.next().map(|_| ()).ok_or_else(|| "no addresses".to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            (_, None) => Err("no host".to_string()),
+        };
+        match found {
+            Ok(()) => resolved += 1,
+            Err(e) => warn!(backend = %id, error = %e, "Backend can't be resolved"),
+        }
+    }
+    let detail = format!("{} of {} resolved", resolved, config.backends.len());
+    CheckResult { name, ok: resolved > 0, detail }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(listener: SocketAddr, backends: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            "listeners: [{{ name: http, address: '{}' }}]\n\
+             load_balancer: {{}}\nbackends: {}\nhealth_check: {{}}\n\
+             circuit_breaker: {{}}\nretry: {{}}\nmetrics: {{ enabled: false }}",
+            listener, backends
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reports_bind_and_backend_failures() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let free = "127.0.0.1:0".parse().unwrap();
+
+        let report = self_check(&config(free, "[{ url: 'http://127.0.0.1:9' }, { url: 'unix:///nonexistent/app.sock' }]")).await;
+        assert!(report.ok, "{:?}", report);
+        assert_eq!(report.checks.last().unwrap().detail, "1 of 2 resolved");
+
+        let report = self_check(&config(taken.local_addr().unwrap(), "[{ url: 'http://127.0.0.1:9' }]")).await;
+        assert_eq!((report.exit_reason, report.exit_code), (ExitReason::Bind, 3));
+        assert!(!report.checks.iter().find(|c| c.name == "listener:http").unwrap().ok);
+
+        let report = self_check(&config(free, "[{ url: 'unix:///nonexistent/app.sock' }]")).await;
+        assert_eq!(report.exit_code, 4);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["exit_reason"], "backends");
+    }
+}
@@ -0,0 +1,243 @@
+// src/tcp_proxy/client_hello.rs
+use md5::{Digest, Md5};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// How long a client gets to send its hello before the connection is
+/// forwarded without one.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Largest TLS record, plus its header.
+const MAX_RECORD: usize = 5 + 16 * 1024;
+
+const HANDSHAKE: u8 = 0x16;
+const CLIENT_HELLO: u8 = 0x01;
+
+const SERVER_NAME: u16 = 0x0000;
+const SUPPORTED_GROUPS: u16 = 0x000a;
+const EC_POINT_FORMATS: u16 = 0x000b;
+
+/// The parts of a TLS ClientHello that tell clients apart, for
+/// fingerprinting connections passed through without terminating TLS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientHello {
+    pub version: u16,
+    pub cipher_suites: Vec<u16>,
+    /// Extension types, in the order sent.
+    pub extensions: Vec<u16>,
+    pub supported_groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+    /// The SNI host name, if sent.
+    pub server_name: Option<String>,
+}
+
+impl ClientHello {
+    /// Parse the ClientHello in the first TLS record of `record`. `None`
+    /// if it isn't one, or is cut short.
+    pub fn parse(record: &[u8]) -> Option<Self> {
+        let mut record = Reader(record);
+        if record.u8()? != HANDSHAKE {
+            return None;
+        }
+        record.take(2)?;
+        let mut handshake = Reader(record.block16()?);
+        if handshake.u8()? != CLIENT_HELLO {
+            return None;
+        }
+        let length = handshake.u24()?;
+        let mut hello = Reader(handshake.take(length)?);
+
+        let version = hello.u16()?;
+        hello.take(32)?;
+        hello.block8()?;
+        let cipher_suites = Reader(hello.block16()?).u16s();
+        hello.block8()?;
+
+        let mut parsed = Self {
+            version,
+            cipher_suites,
+            extensions: Vec::new(),
+            supported_groups: Vec::new(),
+            ec_point_formats: Vec::new(),
+            server_name: None,
+        };
+        // Extensions are optional
+        let mut extensions = Reader(hello.block16().unwrap_or_default());
+        while let (Some(kind), Some(data)) = (extensions.u16(), extensions.block16()) {
+            parsed.extensions.push(kind);
+            let mut data = Reader(data);
+            match kind {
+                SERVER_NAME => parsed.server_name = server_name(&mut data),
+                SUPPORTED_GROUPS => parsed.supported_groups = Reader(data.block16()?).u16s(),
+                EC_POINT_FORMATS => parsed.ec_point_formats = data.block8()?.to_vec(),
+                _ => {}
+            }
+        }
+        Some(parsed)
+    }
+
+    /// The JA3 string: version, ciphers, extensions, groups and point
+    /// formats in decimal, leaving out GREASE values.
+    pub fn ja3(&self) -> String {
+        let list = |values: Vec<u16>| values.iter().map(u16::to_string).collect::<Vec<_>>().join("-");
+        let real = |values: &[u16]| -> Vec<u16> { values.iter().copied().filter(|value| !is_grease(*value)).collect() };
+        format!(
+            "{},{},{},{},{}",
+            self.version,
+            list(real(&self.cipher_suites)),
+            list(real(&self.extensions)),
+            list(real(&self.supported_groups)),
+            list(self.ec_point_formats.iter().map(|format| *format as u16).collect()),
+        )
+    }
+
+    /// The MD5 of `ja3`, in hex, as JA3 fingerprints are usually shared.
+    pub fn ja3_hash(&self) -> String {
+        Md5::digest(self.ja3().as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// What was read from a client before connecting upstream.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Preread {
+    /// Still to be sent on, ahead of the rest.
+    pub bytes: Vec<u8>,
+    pub hello: Option<ClientHello>,
+}
+
+/// Read the client's first TLS record from `client`, waiting up to
+/// `HELLO_TIMEOUT`.
+pub async fn read_client_hello<S: AsyncRead + Unpin>(client: &mut S) -> Preread {
+    let mut buf = Vec::with_capacity(1024);
+    let read = async {
+        loop {
+            let wanted = match buf.get(..5) {
+                Some(header) if header[0] != HANDSHAKE => return,
+                Some(header) => 5 + u16::from_be_bytes([header[3], header[4]]) as usize,
+                None => 5,
+            };
+            if buf.len() >= wanted.min(MAX_RECORD) {
+                return;
+            }
+            match client.read_buf(&mut buf).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+        }
+    };
+    let _ = tokio::time::timeout(HELLO_TIMEOUT, read).await;
+    let hello = ClientHello::parse(&buf);
+    Preread { bytes: buf, hello }
+}
+
+/// GREASE values (RFC 8701) are random per connection, so they're left
+/// out of fingerprints.
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn server_name(data: &mut Reader) -> Option<String> {
+    let mut names = Reader(data.block16()?);
+    while let Some(kind) = names.u8() {
+        let name = names.block16()?;
+        if kind == 0 {
+            return String::from_utf8(name.to_vec()).ok();
+        }
+    }
+    None
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.take(3).map(|b| (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize)
+    }
+
+    fn block8(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()? as usize;
+        self.take(len)
+    }
+
+    fn block16(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+
+    fn u16s(&mut self) -> Vec<u16> {
+        std::iter::from_fn(|| self.u16()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ClientHello record for `example.com` with a GREASE cipher.
+    fn client_hello() -> Vec<u8> {
+        let mut extensions = Vec::new();
+        // server_name
+        extensions.extend([0x00, 0x00, 0x00, 0x10, 0x00, 0x0e, 0x00, 0x00, 0x0b]);
+        extensions.extend(b"example.com");
+        // supported_groups: x25519, secp256r1
+        extensions.extend([0x00, 0x0a, 0x00, 0x06, 0x00, 0x04, 0x00, 0x1d, 0x00, 0x17]);
+        // ec_point_formats: uncompressed
+        extensions.extend([0x00, 0x0b, 0x00, 0x02, 0x01, 0x00]);
+        // A GREASE extension, empty
+        extensions.extend([0x3a, 0x3a, 0x00, 0x00]);
+
+        let mut hello = vec![0x03, 0x03];
+        hello.extend([0u8; 32]);
+        hello.push(0);
+        // GREASE, TLS_AES_128_GCM_SHA256, TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
+        hello.extend([0x00, 0x06, 0x0a, 0x0a, 0x13, 0x01, 0xc0, 0x2f]);
+        hello.extend([0x01, 0x00]);
+        hello.extend((extensions.len() as u16).to_be_bytes());
+        hello.extend(extensions);
+
+        let mut handshake = vec![CLIENT_HELLO, 0];
+        handshake.extend((hello.len() as u16).to_be_bytes());
+        handshake.extend(hello);
+        let mut record = vec![HANDSHAKE, 0x03, 0x01];
+        record.extend((handshake.len() as u16).to_be_bytes());
+        record.extend(handshake);
+        record
+    }
+
+    #[tokio::test]
+    async fn test_fingerprints_client_hellos() {
+        let record = client_hello();
+        let hello = ClientHello::parse(&record).unwrap();
+        assert_eq!(hello.server_name.as_deref(), Some("example.com"));
+        assert_eq!(hello.ja3(), "771,4865-49199,0-10-11,29-23,0");
+        assert_eq!(hello.ja3_hash(), "bca193bf3b6d2156cfbe0e6b4b306d3e");
+        assert!(ClientHello::parse(&record[..record.len() - 1]).is_none());
+        assert!(ClientHello::parse(b"GET / HTTP/1.1\r\n").is_none());
+
+        // What's read is handed back along with the hello, so it can be sent on
+        let mut stream = [record.as_slice(), b"application data"].concat();
+        let read = read_client_hello(&mut stream.as_slice()).await;
+        assert_eq!((read.bytes.as_slice(), read.hello), (&stream[..read.bytes.len()], Some(hello)));
+        assert!(read.bytes.len() >= record.len());
+        stream.truncate(3);
+        let cut_short = Preread { bytes: b"\x16\x03\x01".to_vec(), hello: None };
+        assert_eq!(read_client_hello(&mut stream.as_slice()).await, cut_short);
+    }
+}
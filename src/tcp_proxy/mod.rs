@@ -0,0 +1,14 @@
+//
+// src/tcp_proxy/mod.rs
+// Layer-4 passthrough: forwards raw byte streams to `tcp://` backends.
+//
+mod client_hello;
+mod server;
+mod sni;
+mod stream;
+
+pub use client_hello::ClientHello;
+pub use server::TcpProxy;
+
+/// URL scheme of backends served by `protocol: tcp` listeners.
+pub const TCP_SCHEME: &str = "tcp";
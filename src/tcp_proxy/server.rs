@@ -0,0 +1,326 @@
+// src/tcp_proxy/server.rs
+use crate::{
+    circuit_breaker::CircuitBreakerManager,
+    config::ListenerConfig,
+    load_balancer::LoadBalancer,
+    metrics::MetricsSink,
+    proxy::{Backend, BackendEvent, BackendPool, ConnectionGuard, Proxy},
+    server::{
+        connection::{ConnectionActivity, ConnectionGate, ConnectionLimits, IpConnectionTracker},
+        drain::{draining, DrainWatcher},
+        handover::SocketHandover,
+        listener::{AcceptBackoff, BoundListener, ClientStream},
+    },
+    tcp_proxy::{
+        client_hello::{read_client_hello, ClientHello, Preread},
+        sni::SniRouter,
+        stream::{ByteCounters, MeteredStream},
+    },
+};
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tracing::{debug, info, warn};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often byte counts of long-lived connections are flushed to metrics.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A `protocol: tcp` listener. Backends are picked from the healthy `tcp://`
+/// backends, of the pool `sni_routes` picks if any, by the same balancer
+/// and circuit breakers as HTTP traffic.
+pub struct TcpProxy {
+    name: Arc<str>,
+    address: SocketAddr,
+    unix_socket: Option<PathBuf>,
+    limits: ConnectionLimits,
+    tls_fingerprint: bool,
+    sni: Option<SniRouter>,
+    pool: Arc<BackendPool>,
+    load_balancer: Arc<dyn LoadBalancer>,
+    circuit_breakers: Arc<CircuitBreakerManager>,
+    metrics: Arc<dyn MetricsSink>,
+    handover: Arc<SocketHandover>,
+    drain: Option<DrainWatcher>,
+}
+
+impl TcpProxy {
+    pub fn new(listener: &ListenerConfig, proxy: &Proxy) -> Self {
+        Self {
+            name: Arc::from(listener.name.as_str()),
+            address: listener.address,
+            unix_socket: listener.unix_socket.clone(),
+            limits: ConnectionLimits::from(listener),
+            tls_fingerprint: listener.tls_fingerprint,
+            sni: SniRouter::new(listener),
+            pool: proxy.pool(),
+            load_balancer: proxy.load_balancer(),
+            circuit_breakers: proxy.circuit_breakers(),
+            metrics: proxy.metrics(),
+            handover: Arc::new(SocketHandover::default()),
+            drain: None,
+        }
+    }
+
+    /// Take the listening socket from `handover` when it has a matching one.
+    pub fn with_handover(mut self, handover: Arc<SocketHandover>) -> Self {
+        self.handover = handover;
+        self
+    }
+
+    /// Stop accepting once draining starts. Open connections keep the drain
+    /// waiting until they close.
+    pub fn with_drain(mut self, drain: DrainWatcher) -> Self {
+        self.drain = Some(drain);
+        self
+    }
+
+    /// Bind the listener and forward connections until draining starts.
+    pub async fn serve(mut self) -> Result<()> {
+        let listener = match &self.unix_socket {
+            Some(path) => self.handover.bind_unix(&self.name, path)?,
+            None => BoundListener::Tcp(self.handover.bind_tcp(&self.name, self.address).await?),
+        };
+        match &self.unix_socket {
+            Some(path) => info!("TCP proxy '{}' listening on unix:{}", self.name, path.display()),
+            None => info!("TCP proxy '{}' listening on {}", self.name, self.address),
+        }
+
+        let ip_tracker = self
+            .limits
+            .max_connections_per_ip
+            .map(|max| Arc::new(IpConnectionTracker::new(max)));
+        let gate = ConnectionGate::new(self.limits.max_connections);
+        let mut drain = self.drain.take();
+        let mut backoff = AcceptBackoff::default();
+        let proxy = Arc::new(self);
+
+        loop {
+            let accept = async { (gate.acquire().await, listener.accept().await) };
+            let (slot, accepted) = tokio::select! {
+                accepted = accept => accepted,
+                _ = draining(&mut drain) => {
+                    info!("TCP proxy '{}' stopped accepting connections", proxy.name);
+                    return Ok(());
+                }
+            };
+            let (stream, peer) = match accepted {
+                Ok(accepted) => {
+                    backoff.reset();
+                    accepted
+                }
+                Err(err) => {
+                    let kind = backoff.on_error(&proxy.name, &err).await;
+                    proxy.metrics.record_accept_error(&proxy.name, kind);
+                    continue;
+                }
+            };
+
+            let ip_guard = match (&ip_tracker, peer) {
+                (Some(tracker), Some(peer)) => match tracker.try_acquire(peer.ip()) {
+                    Some(guard) => Some(guard),
+                    None => {
+                        debug!(%peer, "per-IP connection limit reached");
+                        proxy.metrics.record_connection_rejected(&proxy.name, "per_ip_limit");
+                        continue;
+                    }
+                },
+                _ => None,
+            };
+
+            let proxy = proxy.clone();
+            let drain = drain.clone();
+            tokio::spawn(async move {
+                let _slot = slot;
+                let _ip_guard = ip_guard;
+                let _drain = drain;
+                proxy.handle(stream, peer).await;
+            });
+        }
+    }
+
+    async fn handle(&self, mut client: ClientStream, peer: Option<SocketAddr>) {
+        let accepted_at = Instant::now();
+        self.metrics.record_connection_opened(&self.name);
+
+        // Read before connecting; it's sent on ahead of the rest
+        let preread = match self.tls_fingerprint || self.sni.is_some() {
+            true => read_client_hello(&mut client).await,
+            false => Preread::default(),
+        };
+        let server_name = preread.hello.as_ref().and_then(|hello| hello.server_name.as_deref());
+        let pool = match &self.sni {
+            Some(sni) => match sni.pool(server_name) {
+                Some(pool) => Some(pool),
+                None => {
+                    debug!(?peer, server_name, "No SNI route for the connection");
+                    self.metrics.record_connection_rejected(&self.name, "no_sni_route");
+                    self.metrics.record_connection_closed(&self.name, accepted_at.elapsed());
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        match self.connect(peer, pool).await {
+            Some((connection, upstream)) => {
+                let backend = connection.backend().clone();
+                let closed_by = self.pipe(client, upstream, &backend, peer, accepted_at, &preread).await;
+                if let Some(reason) = closed_by {
+                    self.metrics.record_connection_rejected(&self.name, reason);
+                }
+                drop(connection);
+                self.metrics
+                    .update_backend_connections(&backend.id, backend.active_connections() as i64);
+            }
+            None => self.metrics.record_connection_rejected(&self.name, "no_backend"),
+        }
+
+        self.metrics
+            .record_connection_closed(&self.name, accepted_at.elapsed());
+    }
+
+    /// Pick a backend, from `pool` if given, and open the upstream
+    /// connection, holding one of the backend's connections until the guard
+    /// is dropped.
+    async fn connect(&self, peer: Option<SocketAddr>, pool: Option<&str>) -> Option<(ConnectionGuard, TcpStream)> {
+        let backends: Vec<_> = self
+            .pool
+            .get_healthy_backends()
+            .iter()
+            .filter(|b| b.is_tcp() && pool.is_none_or(|pool| b.pool == pool))
+            .cloned()
+            .collect();
+        if backends.is_empty() {
+            warn!(listener = %self.name, pool, "No healthy tcp backends available");
+            return None;
+        }
+
+        let backend = self.load_balancer.select_backend(&backends, peer).await?;
+        let circuit_breaker = self.circuit_breakers.get_or_create(&backend.id);
+        if !circuit_breaker.call_permitted().await {
+            warn!(backend = %backend.id, "Circuit breaker is open");
+            return None;
+        }
+        let Some(connection) = backend.acquire() else {
+            warn!(backend = %backend.id, "Backend connection limit reached");
+            return None;
+        };
+        self.metrics
+            .update_backend_connections(&backend.id, backend.active_connections() as i64);
+
+        let result = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(backend.tcp_address())).await;
+
+        let upstream = match result {
+            Ok(Ok(stream)) => {
+                circuit_breaker.record_success().await;
+                backend.record_request(true);
+                Some(stream)
+            }
+            Ok(Err(e)) => {
+                warn!(backend = %backend.id, error = %e, "Backend connect failed");
+                None
+            }
+            Err(_) => {
+                warn!(backend = %backend.id, "Backend connect timed out");
+                None
+            }
+        };
+        if upstream.is_none() {
+            if circuit_breaker.record_failure().await {
+                self.pool.publish(BackendEvent::CircuitOpened(backend.id.clone()));
+            }
+            backend.record_request(false);
+        }
+        self.metrics
+            .update_circuit_breaker_state(&backend.id, circuit_breaker.get_state().await);
+
+        let Some(upstream) = upstream else {
+            drop(connection);
+            self.metrics
+                .update_backend_connections(&backend.id, backend.active_connections() as i64);
+            return None;
+        };
+        Some((connection, upstream))
+    }
+
+    /// Send what was already read from the client, then copy bytes both
+    /// ways until either side closes or a connection limit is hit. Returns
+    /// the limit that closed the connection, if any.
+    async fn pipe(
+        &self,
+        client: ClientStream,
+        mut upstream: TcpStream,
+        backend: &Backend,
+        peer: Option<SocketAddr>,
+        accepted_at: Instant,
+        preread: &Preread,
+    ) -> Option<&'static str> {
+        let counters = Arc::new(ByteCounters::default());
+        counters.add_received(preread.bytes.len() as u64);
+        if let Err(e) = upstream.write_all(&preread.bytes).await {
+            debug!(?peer, backend = %backend.id, error = %e, "TCP connection error");
+            return None;
+        }
+        let activity = Arc::new(ConnectionActivity::new(accepted_at));
+        let mut client = MeteredStream::new(client, counters.clone(), activity.clone());
+
+        let copy = tokio::io::copy_bidirectional(&mut client, &mut upstream);
+        let idle = async {
+            match self.limits.idle_timeout {
+                Some(timeout) => activity.idle(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        let lifetime = async {
+            match self.limits.max_lifetime {
+                Some(lifetime) => tokio::time::sleep(lifetime).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(copy, idle, lifetime);
+        let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+        flush.tick().await;
+
+        let (mut received, mut sent) = (0, 0);
+        let mut record = || {
+            let (r, s) = counters.take();
+            received += r;
+            sent += s;
+            self.metrics.record_tcp_bytes(&self.name, &backend.id, r, s);
+        };
+
+        let closed_by = loop {
+            tokio::select! {
+                result = &mut copy => {
+                    if let Err(e) = result {
+                        debug!(?peer, backend = %backend.id, error = %e, "TCP connection error");
+                    }
+                    break None;
+                }
+                _ = &mut idle => break Some("idle_timeout"),
+                _ = &mut lifetime => break Some("max_lifetime"),
+                _ = flush.tick() => record(),
+            }
+        };
+        record();
+
+        let ja3 = preread.hello.as_ref().map(ClientHello::ja3_hash);
+        debug!(
+            ?peer,
+            backend = %backend.id,
+            ja3 = ja3.as_deref(),
+            server_name = preread.hello.as_ref().and_then(|hello| hello.server_name.as_deref()),
+            bytes_received = received,
+            bytes_sent = sent,
+            closed_by = closed_by.unwrap_or("peer"),
+            "TCP connection closed"
+        );
+        closed_by
+    }
+}
@@ -0,0 +1,81 @@
+// src/tcp_proxy/sni.rs
+use crate::config::ListenerConfig;
+
+/// Picks a pool for a TLS connection by the server name it asked for.
+pub struct SniRouter {
+    routes: Vec<(Vec<String>, String)>,
+    default_pool: Option<String>,
+}
+
+impl SniRouter {
+    /// `None` when the listener has no SNI routing.
+    pub fn new(listener: &ListenerConfig) -> Option<Self> {
+        if listener.sni_routes.is_empty() && listener.sni_default_pool.is_none() {
+            return None;
+        }
+        let routes = listener
+            .sni_routes
+            .iter()
+            .map(|route| {
+                let names = route.server_names.iter().map(|name| name.to_ascii_lowercase()).collect();
+                (names, route.pool.clone())
+            })
+            .collect();
+        Some(Self { routes, default_pool: listener.sni_default_pool.clone() })
+    }
+
+    /// The pool to connect `server_name` to, or `None` when it has none
+    /// and the connection is closed.
+    pub fn pool(&self, server_name: Option<&str>) -> Option<&str> {
+        let server_name = server_name.map(str::to_ascii_lowercase);
+        server_name
+            .and_then(|server_name| {
+                self.routes
+                    .iter()
+                    .find(|(names, _)| names.iter().any(|name| matches(name, &server_name)))
+            })
+            .map(|(_, pool)| pool.as_str())
+            .or(self.default_pool.as_deref())
+    }
+}
+
+/// `*.example.com` matches names under `example.com` but not itself.
+fn matches(pattern: &str, server_name: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => server_name
+            .strip_suffix(domain)
+            .and_then(|sub| sub.strip_suffix('.'))
+            .is_some_and(|sub| !sub.is_empty()),
+        None => pattern == server_name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routes_server_names_to_pools() {
+        let listener: ListenerConfig = serde_yaml::from_str(
+            "{ protocol: tcp, sni_routes: [{ server_names: [api.example.com], pool: api }, \
+              { server_names: ['*.example.com', example.org], pool: web }] }",
+        )
+        .unwrap();
+        let router = SniRouter::new(&listener).unwrap();
+        assert_eq!(router.pool(Some("api.example.com")), Some("api"));
+        assert_eq!(router.pool(Some("API.Example.com")), Some("api"));
+        assert_eq!(router.pool(Some("www.example.com")), Some("web"));
+        assert_eq!(router.pool(Some("a.b.example.com")), Some("web"));
+        assert_eq!(router.pool(Some("example.org")), Some("web"));
+        // The wildcard doesn't cover the domain itself, or lookalikes
+        assert_eq!(router.pool(Some("example.com")), None);
+        assert_eq!(router.pool(Some("badexample.com")), None);
+        assert_eq!(router.pool(None), None);
+
+        let listener = ListenerConfig { sni_default_pool: Some("rest".to_string()), ..listener };
+        let router = SniRouter::new(&listener).unwrap();
+        assert_eq!(router.pool(Some("example.com")), Some("rest"));
+        assert_eq!(router.pool(None), Some("rest"));
+        assert!(SniRouter::new(&ListenerConfig::default()).is_none());
+    }
+}
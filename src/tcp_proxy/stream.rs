@@ -0,0 +1,90 @@
+// src/tcp_proxy/stream.rs
+use crate::server::connection::ConnectionActivity;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Bytes moved through a connection since the last [`ByteCounters::take`].
+#[derive(Default)]
+pub struct ByteCounters {
+    received: AtomicU64,
+    sent: AtomicU64,
+}
+
+impl ByteCounters {
+    /// Reset the counters, returning `(received, sent)` since the last call.
+    pub fn take(&self) -> (u64, u64) {
+        (
+            self.received.swap(0, Ordering::Relaxed),
+            self.sent.swap(0, Ordering::Relaxed),
+        )
+    }
+
+    /// Count bytes received from the client before it was wrapped.
+    pub fn add_received(&self, bytes: u64) {
+        self.received.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+/// Client-side stream wrapper counting bytes received from and sent to the
+/// client, and recording activity for the idle timeout.
+pub struct MeteredStream<S> {
+    inner: S,
+    counters: Arc<ByteCounters>,
+    activity: Arc<ConnectionActivity>,
+}
+
+impl<S> MeteredStream<S> {
+    pub fn new(inner: S, counters: Arc<ByteCounters>, activity: Arc<ConnectionActivity>) -> Self {
+        Self {
+            inner,
+            counters,
+            activity,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for MeteredStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = buf.filled().len() - before;
+        if read > 0 {
+            self.counters.received.fetch_add(read as u64, Ordering::Relaxed);
+            self.activity.touch();
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for MeteredStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            if n > 0 {
+                self.counters.sent.fetch_add(n as u64, Ordering::Relaxed);
+                self.activity.touch();
+            }
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
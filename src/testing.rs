@@ -0,0 +1,347 @@
+// src/testing.rs
+//! Scaffolding for integration tests: the whole load balancer in process,
+//! in front of mock backends whose behaviour can change mid-test. Everything
+//! listens on ephemeral loopback ports.
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! use rust_load_balancer::testing::TestHarness;
+//!
+//! let harness = TestHarness::start(2).await?;
+//! harness.backend(1).set_latency(std::time::Duration::from_millis(50));
+//! let response = harness.get("/").await?;
+//! assert_eq!(response.status(), 200);
+//! harness.shutdown().await;
+//! # Ok(())
+//! # }
+//! ```
+use anyhow::{bail, Context, Result};
+use hyper::body::Bytes;
+use hyper::client::HttpConnector;
+use hyper::server::conn::Http;
+use hyper::{Body, Client, Request, Response, StatusCode};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::config::Config;
+use crate::lifecycle::LoadBalancerHandle;
+use crate::{Builder, Instance};
+
+/// Path the mock backends answer health checks on.
+pub const HEALTH_PATH: &str = "/health";
+
+/// How mock backends behave, changed through `MockBackend`'s setters.
+struct Behaviour {
+    latency_micros: AtomicU64,
+    /// An `f64`'s bits.
+    failure_rate: AtomicU64,
+    failure_status: AtomicU16,
+    healthy: AtomicBool,
+    requests: AtomicUsize,
+}
+
+/// An HTTP server answering 200 with its name in the body and the
+/// `X-Mock-Backend` header, and `HEALTH_PATH` with 200 or 503.
+pub struct MockBackend {
+    name: String,
+    addr: SocketAddr,
+    behaviour: Arc<Behaviour>,
+    accept: Mutex<JoinHandle<()>>,
+    connections: Arc<Mutex<JoinSet<()>>>,
+}
+
+impl MockBackend {
+    pub async fn start(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let behaviour = Arc::new(Behaviour {
+            latency_micros: AtomicU64::new(0),
+            failure_rate: AtomicU64::new(0f64.to_bits()),
+            failure_status: AtomicU16::new(503),
+            healthy: AtomicBool::new(true),
+            requests: AtomicUsize::new(0),
+        });
+        let connections = Arc::new(Mutex::new(JoinSet::new()));
+        let accept = spawn_accept(listener, name.clone(), behaviour.clone(), connections.clone());
+        Ok(Self { name, addr, behaviour, accept: Mutex::new(accept), connections })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The backend's ID in the load balancer's pool.
+    pub fn id(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// Wait this long before answering each request.
+    pub fn set_latency(&self, latency: Duration) {
+        self.behaviour.latency_micros.store(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Answer this share of requests (0.0 to 1.0) with the failure status.
+    pub fn set_failure_rate(&self, rate: f64) {
+        self.behaviour.failure_rate.store(rate.to_bits(), Ordering::Relaxed);
+    }
+
+    /// 503 unless set.
+    pub fn set_failure_status(&self, status: u16) {
+        self.behaviour.failure_status.store(status, Ordering::Relaxed);
+    }
+
+    /// Whether health checks pass. Other requests are answered as before.
+    pub fn set_healthy(&self, healthy: bool) {
+        self.behaviour.healthy.store(healthy, Ordering::Relaxed);
+    }
+
+    /// Requests served so far, health checks aside.
+    pub fn requests(&self) -> usize {
+        self.behaviour.requests.load(Ordering::Relaxed)
+    }
+
+    /// Stop listening and drop every open connection, including any with a
+    /// request in progress, as if the process died.
+    pub fn kill(&self) {
+        self.accept.lock().unwrap().abort();
+        self.connections.lock().unwrap().abort_all();
+    }
+
+    /// Listen again on the same address after `kill`.
+    pub async fn restart(&self) -> Result<()> {
+        self.kill();
+        // The killed listener closes once its task is dropped
+        let mut bound = TcpListener::bind(self.addr).await;
+        for _ in 0..100 {
+            if bound.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            bound = TcpListener::bind(self.addr).await;
+        }
+        let listener = bound.with_context(|| format!("Failed to listen on {} again", self.addr))?;
+        let accept = spawn_accept(listener, self.name.clone(), self.behaviour.clone(), self.connections.clone());
+        *self.accept.lock().unwrap() = accept;
+        Ok(())
+    }
+}
+
+fn spawn_accept(
+    listener: TcpListener,
+    name: String,
+    behaviour: Arc<Behaviour>,
+    connections: Arc<Mutex<JoinSet<()>>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (name, behaviour) = (name.clone(), behaviour.clone());
+            let service = hyper::service::service_fn(move |req| {
+                respond(name.clone(), behaviour.clone(), req)
+            });
+            connections.lock().unwrap().spawn(async move {
+                let _ = Http::new().serve_connection(stream, service).await;
+            });
+        }
+    })
+}
+
+impl Drop for MockBackend {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+async fn respond(
+    name: String,
+    behaviour: Arc<Behaviour>,
+    req: Request<Body>,
+) -> Result<Response<Body>, hyper::Error> {
+    if req.uri().path() == HEALTH_PATH {
+        let status = match behaviour.healthy.load(Ordering::Relaxed) {
+            true => StatusCode::OK,
+            false => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        return Ok(Response::builder().status(status).body(Body::empty()).unwrap());
+    }
+
+    behaviour.requests.fetch_add(1, Ordering::Relaxed);
+    let latency = Duration::from_micros(behaviour.latency_micros.load(Ordering::Relaxed));
+    if !latency.is_zero() {
+        tokio::time::sleep(latency).await;
+    }
+    let failure_rate = f64::from_bits(behaviour.failure_rate.load(Ordering::Relaxed));
+    let status = match rand::random::<f64>() < failure_rate {
+        true => StatusCode::from_u16(behaviour.failure_status.load(Ordering::Relaxed)).unwrap(),
+        false => StatusCode::OK,
+    };
+    // Drain the body, so requests with one are read in full
+    hyper::body::to_bytes(req.into_body()).await?;
+    Ok(Response::builder()
+        .status(status)
+        .header("x-mock-backend", name.as_str())
+        .body(Body::from(name))
+        .unwrap())
+}
+
+/// The load balancer built by `Builder`, serving on a loopback port in front
+/// of `MockBackend`s named `backend-0`, `backend-1` and so on.
+pub struct TestHarness {
+    instance: Arc<Instance>,
+    backends: Vec<MockBackend>,
+    addr: SocketAddr,
+    client: Client<HttpConnector>,
+    serving: JoinHandle<Result<()>>,
+}
+
+impl TestHarness {
+    /// See `config` for the settings.
+    pub async fn start(backends: usize) -> Result<Self> {
+        Self::start_with(backends, |_| {}).await
+    }
+
+    /// Start with `customize` applied to the config first.
+    pub async fn start_with(backends: usize, customize: impl FnOnce(&mut Config)) -> Result<Self> {
+        let mut mocks = Vec::with_capacity(backends);
+        for i in 0..backends {
+            mocks.push(MockBackend::start(format!("backend-{}", i)).await?);
+        }
+        let addr = free_port()?;
+        let mut config = config(addr, &mocks)?;
+        customize(&mut config);
+
+        let instance = Arc::new(Builder::new(config).build().await?);
+        let serving = tokio::spawn({
+            let instance = instance.clone();
+            async move { instance.serve().await }
+        });
+        wait_for_listener(addr, &serving).await?;
+        Ok(Self {
+            instance,
+            backends: mocks,
+            addr,
+            client: Client::new(),
+            serving,
+        })
+    }
+
+    pub fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    pub fn handle(&self) -> LoadBalancerHandle {
+        self.instance.handle()
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn backend(&self, i: usize) -> &MockBackend {
+        &self.backends[i]
+    }
+
+    pub fn backends(&self) -> &[MockBackend] {
+        &self.backends
+    }
+
+    /// `GET` `path` through the load balancer.
+    pub async fn get(&self, path: &str) -> Result<Response<Bytes>> {
+        self.request(Request::get(path).body(Body::empty())?).await
+    }
+
+    /// Send `req`, whose URI is only a path, through the load balancer.
+    pub async fn request(&self, mut req: Request<Body>) -> Result<Response<Bytes>> {
+        let uri = format!("http://{}{}", self.addr, req.uri());
+        *req.uri_mut() = uri.parse()?;
+        let response = self.client.request(req).await?;
+        let (parts, body) = response.into_parts();
+        Ok(Response::from_parts(parts, hyper::body::to_bytes(body).await?))
+    }
+
+    /// Drain for up to a second and wait for `serve` to return.
+    pub async fn shutdown(self) {
+        self.instance.handle().shutdown(Duration::from_secs(1)).await;
+        let _ = self.serving.await;
+    }
+}
+
+/// The harness's starting config: round robin over the mock backends, health
+/// checks every second, no retry backoff, and no metrics or admin servers.
+fn config(addr: SocketAddr, backends: &[MockBackend]) -> Result<Config> {
+    let backends: Vec<String> = backends.iter().map(|b| format!("{{ url: '{}' }}", b.url())).collect();
+    let yaml = format!(
+        "listeners: [{{ name: http, address: '{}' }}]\n\
+         load_balancer: {{ algorithm: round_robin }}\n\
+         backends: [{}]\n\
+         health_check: {{ interval_secs: 1, timeout_secs: 1, unhealthy_threshold: 1, healthy_threshold: 1, path: '{}' }}\n\
+         circuit_breaker: {{ failure_threshold: 5, success_threshold: 1, timeout_secs: 1 }}\n\
+         retry: {{ max_attempts: 3, backoff_base_ms: 1, backoff_max_ms: 1 }}\n\
+         metrics: {{ enabled: false }}\n\
+         runtime: {{ drain_timeout_secs: 1 }}",
+        addr,
+        backends.join(", "),
+        HEALTH_PATH
+    );
+    serde_yaml::from_str(&yaml).context("Invalid harness config")
+}
+
+/// A loopback port nothing is listening on, for now.
+fn free_port() -> Result<SocketAddr> {
+    Ok(std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?)
+}
+
+async fn wait_for_listener(addr: SocketAddr, serving: &JoinHandle<Result<()>>) -> Result<()> {
+    for _ in 0..200 {
+        if tokio::net::TcpStream::connect(addr).await.is_ok() {
+            return Ok(());
+        }
+        if serving.is_finished() {
+            bail!("Load balancer stopped before listening on {}", addr);
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    bail!("Load balancer isn't listening on {} after 2s", addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_harness_proxies_to_mock_backends() {
+        let harness = TestHarness::start(1).await.unwrap();
+        let backend = harness.backend(0);
+
+        let response = harness.get("/hello").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(&response.body()[..], b"backend-0");
+        assert_eq!(backend.requests(), 1);
+
+        backend.set_failure_rate(1.0);
+        backend.set_failure_status(500);
+        let response = harness.get("/hello").await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        backend.set_healthy(false);
+        let health = Client::new()
+            .get(format!("{}{}", backend.url(), HEALTH_PATH).parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(health.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        harness.shutdown().await;
+    }
+}
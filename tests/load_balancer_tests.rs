@@ -1,20 +1,303 @@
 // tests/load_balancer_tests.rs
 #[cfg(test)]
 mod tests {
-    use super::*;
-    
+    use hyper::{Body, Request, StatusCode};
+    use rust_load_balancer::circuit_breaker::CircuitBreakerState;
+    use rust_load_balancer::testing::TestHarness;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    /// Poll `check` every 50ms for up to 5s.
+    async fn eventually(mut check: impl AsyncFnMut() -> bool) -> bool {
+        for _ in 0..100 {
+            if check().await {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        false
+    }
+
     #[tokio::test]
     async fn test_round_robin_distribution() {
-        // Test that requests are distributed evenly
+        let harness = TestHarness::start(3).await.unwrap();
+        for _ in 0..30 {
+            let response = harness.get("/").await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        for backend in harness.backends() {
+            assert_eq!(
+                backend.requests(),
+                10,
+                "{} got an uneven share",
+                backend.name()
+            );
+        }
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_failover_when_backend_dies_mid_request() {
+        let harness = TestHarness::start(2).await.unwrap();
+        harness.backend(0).set_latency(Duration::from_millis(300));
+
+        let request = harness.get("/");
+        let kill = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            harness.backend(0).kill();
+        };
+        let (response, ()) = tokio::join!(request, kill);
+        let response = response.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-mock-backend"], "backend-1");
+
+        // And it stays out of the way afterwards
+        for _ in 0..4 {
+            let response = harness.get("/").await.unwrap();
+            assert_eq!(response.headers()["x-mock-backend"], "backend-1");
+        }
+        harness.shutdown().await;
     }
-    
+
     #[tokio::test]
     async fn test_circuit_breaker_opens_on_failures() {
-        // Test circuit breaker state transitions
+        // One backend, so its breaker alone decides, and no retries to hide it
+        let harness = TestHarness::start_with(1, |config| config.retry.max_attempts = 1)
+            .await
+            .unwrap();
+        let backend = harness.backend(0);
+        let breaker = harness
+            .instance()
+            .proxy()
+            .circuit_breakers()
+            .get_or_create(&backend.id());
+        // Refused connections are what count against the breaker
+        backend.kill();
+
+        for _ in 0..5 {
+            let response = harness.get("/").await.unwrap();
+            assert!(response.status().is_server_error());
+        }
+        assert_eq!(breaker.get_state().await, CircuitBreakerState::Open);
+        // While open, the backend isn't called even once it's back
+        backend.restart().await.unwrap();
+        let response = harness.get("/").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(backend.requests(), 0);
+
+        // After `timeout_secs` one trial request goes through and closes it
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let response = harness.get("/").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(breaker.get_state().await, CircuitBreakerState::Closed);
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_retries_503_on_another_backend() {
+        let harness = TestHarness::start(2).await.unwrap();
+        harness.backend(0).set_failure_rate(1.0);
+
+        for _ in 0..4 {
+            let response = harness.get("/").await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["x-mock-backend"], "backend-1");
+        }
+        assert!(
+            harness.backend(0).requests() > 0,
+            "backend-0 was never tried"
+        );
+        harness.shutdown().await;
     }
-    
+
+    #[tokio::test]
+    async fn test_latency_budget_cuts_off_slow_backends() {
+        let harness = TestHarness::start_with(2, |config| config.policy.latency_budget_ms = Some(200))
+            .await
+            .unwrap();
+        for backend in harness.backends() {
+            backend.set_latency(Duration::from_secs(2));
+        }
+
+        // The budget covers every attempt, so retries don't stretch it
+        let started = std::time::Instant::now();
+        let response = harness.get("/").await.unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(response.headers()["x-lb-error-code"], "LB_LATENCY_BUDGET_EXCEEDED");
+        assert!(started.elapsed() < Duration::from_secs(1), "took {:?}", started.elapsed());
+
+        harness.backend(0).set_latency(Duration::ZERO);
+        harness.backend(1).set_latency(Duration::ZERO);
+        let response = harness.get("/").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        harness.shutdown().await;
+    }
+
     #[tokio::test]
     async fn test_health_check_removes_unhealthy_backends() {
-        // Test health check behavior
+        let harness = TestHarness::start(2).await.unwrap();
+        let pool = harness.instance().pool();
+        let healthy = async || pool.get_healthy_backends().len();
+
+        harness.backend(0).set_healthy(false);
+        assert!(eventually(async || healthy().await == 1).await);
+        let before = harness.backend(0).requests();
+        for _ in 0..4 {
+            let response = harness.get("/").await.unwrap();
+            assert_eq!(response.headers()["x-mock-backend"], "backend-1");
+        }
+        assert_eq!(harness.backend(0).requests(), before);
+
+        harness.backend(0).set_healthy(true);
+        assert!(eventually(async || healthy().await == 2).await);
+        let mut served_by = std::collections::HashSet::new();
+        for _ in 0..4 {
+            let response = harness.get("/").await.unwrap();
+            served_by.insert(response.headers()["x-mock-backend"].clone());
+        }
+        assert_eq!(served_by.len(), 2);
+        harness.shutdown().await;
+    }
+
+    /// Send one keep-alive request on `stream`; `None` if it was closed.
+    async fn request_on(stream: &mut TcpStream) -> Option<String> {
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: lb\r\n\r\n").await.ok()?;
+        let mut buf = [0; 1024];
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => None,
+            Ok(n) => Some(String::from_utf8_lossy(&buf[..n]).into_owned()),
+        }
+    }
+
+    /// Read from `stream` until it closes, or `None` if it's still open
+    /// after `wait`.
+    async fn read_until_closed(stream: &mut (impl AsyncReadExt + Unpin), wait: Duration) -> Option<String> {
+        let mut read = Vec::new();
+        let closed = tokio::time::timeout(wait, async {
+            let mut buf = [0; 1024];
+            while let Ok(n @ 1..) = stream.read(&mut buf).await {
+                read.extend_from_slice(&buf[..n]);
+            }
+        })
+        .await;
+        closed.ok().map(|()| String::from_utf8_lossy(&read).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_cuts_off_trickled_request_bodies() {
+        let harness = TestHarness::start_with(1, |config| {
+            config.listeners[0].idle_timeout_secs = Some(1);
+            config.listeners[0].body_read_timeout_secs = Some(1);
+        })
+        .await
+        .unwrap();
+        let stream = TcpStream::connect(harness.addr()).await.unwrap();
+        let (mut read, mut write) = stream.into_split();
+        write
+            .write_all(b"POST / HTTP/1.1\r\nHost: lb\r\nContent-Length: 100\r\n\r\n")
+            .await
+            .unwrap();
+        // One byte at a time, well within the idle timeout
+        let trickle = tokio::spawn(async move {
+            for _ in 0..100 {
+                if write.write_all(b"x").await.is_err() {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        });
+        let read = read_until_closed(&mut read, Duration::from_secs(5)).await;
+        assert!(read.is_some_and(|read| !read.starts_with("HTTP/1.1 200")));
+        trickle.abort();
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_cuts_off_requests_after_the_close_grace_period() {
+        let harness = TestHarness::start_with(1, |config| {
+            config.listeners[0].max_connection_lifetime_secs = Some(1);
+            config.listeners[0].close_grace_secs = 1;
+        })
+        .await
+        .unwrap();
+        harness.backend(0).set_latency(Duration::from_secs(10));
+        let mut stream = TcpStream::connect(harness.addr()).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: lb\r\n\r\n").await.unwrap();
+        // Closed at 2s, long before the backend answers
+        assert_eq!(read_until_closed(&mut stream, Duration::from_secs(5)).await.as_deref(), Some(""));
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_allowlist_ignores_forwarded_for() {
+        let harness = TestHarness::start_with(1, |config| {
+            config.maintenance = serde_yaml::from_str("{ enabled: true, allow: [10.0.0.0/8] }").unwrap();
+        })
+        .await
+        .unwrap();
+        let forwarded = Request::get("/").header("x-forwarded-for", "10.1.2.3").body(Body::empty()).unwrap();
+        let response = harness.request(forwarded).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(harness.backend(0).requests(), 0);
+        harness.shutdown().await;
+
+        let harness = TestHarness::start_with(1, |config| {
+            config.maintenance = serde_yaml::from_str("{ enabled: true, allow: [127.0.0.1] }").unwrap();
+        })
+        .await
+        .unwrap();
+        assert_eq!(harness.get("/").await.unwrap().status(), StatusCode::OK);
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_fairness_ignores_forwarded_for() {
+        let harness = TestHarness::start_with(1, |config| {
+            config.fairness =
+                serde_yaml::from_str("{ enabled: true, slots: 10, max_share: 0.1, queue_timeout_ms: 0 }").unwrap();
+        })
+        .await
+        .unwrap();
+        harness.backend(0).set_latency(Duration::from_millis(300));
+        let forwarded = |ip: &str| Request::get("/").header("x-forwarded-for", ip).body(Body::empty()).unwrap();
+
+        // Both come from the same socket IP, whatever they claim
+        let first = harness.request(forwarded("192.0.2.1"));
+        let second = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            harness.request(forwarded("192.0.2.2")).await
+        };
+        let (first, second) = tokio::join!(first, second);
+        assert_eq!(first.unwrap().status(), StatusCode::OK);
+        assert_eq!(second.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_rejects_connections_over_the_global_limit() {
+        let harness = TestHarness::start_with(1, |config| {
+            config.runtime.max_connections = Some(1);
+            config.runtime.reject_when_full = true;
+        })
+        .await
+        .unwrap();
+
+        // The harness's own readiness probe may still hold the slot
+        let mut held = None;
+        assert!(eventually(async || {
+            let mut stream = TcpStream::connect(harness.addr()).await.unwrap();
+            let served = request_on(&mut stream).await.is_some_and(|r| r.starts_with("HTTP/1.1 200"));
+            held = served.then_some(stream);
+            served
+        })
+        .await);
+
+        let mut over = TcpStream::connect(harness.addr()).await.unwrap();
+        assert_eq!(request_on(&mut over).await, None);
+
+        drop(held);
+        assert!(eventually(async || harness.get("/").await.is_ok()).await);
+        harness.shutdown().await;
     }
 }
\ No newline at end of file